@@ -0,0 +1,40 @@
+// Compares the future event lists on a model with many pending events.
+//
+// Run with `cargo bench --bench event_list`, optionally followed by the number of entities.
+
+use std::collections::BinaryHeap;
+use std::time::{Duration, Instant};
+
+use rustsim::{process, Action, CalendarQueue, EventEntry, FutureEventList, Simulation};
+
+// Every entity holds for a pseudo-random time, so the scheduler always has `entities` pending events.
+fn run(events: impl FutureEventList + 'static, entities: u64, steps: usize) -> Duration {
+    let mut simulation: Simulation<()> = Simulation::with_event_list(events);
+    let keys: Vec<_> = (0..entities)
+        .map(|id| {
+            let mut state = id;
+            simulation.add_generator(process(move |_| {
+                state = state.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+                Some(Action::Hold(Duration::from_micros(state >> 44)))
+            }))
+        })
+        .collect();
+    simulation.schedule_now_all(keys);
+
+    let start = Instant::now();
+    simulation.step_n(steps);
+    start.elapsed()
+}
+
+fn main() {
+    let entities = std::env::args()
+        .skip(1)
+        .find_map(|argument| argument.parse().ok())
+        .unwrap_or(100_000);
+    let steps = 1_000_000;
+    let heap = run(BinaryHeap::<EventEntry>::new(), entities, steps);
+    let calendar = run(CalendarQueue::new(), entities, steps);
+    println!("{} steps with {} pending events", steps, entities);
+    println!("BinaryHeap:    {:?} ({:?} per step)", heap, heap / steps as u32);
+    println!("CalendarQueue: {:?} ({:?} per step)", calendar, calendar / steps as u32);
+}
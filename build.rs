@@ -0,0 +1,13 @@
+// Generates the gRPC code of the `cosim` feature from `proto/rustsim.proto`, compiled with `protox` so `protoc`
+// isn't needed.
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    #[cfg(feature = "cosim")]
+    {
+        println!("cargo:rerun-if-changed=proto/rustsim.proto");
+        let descriptors = protox::compile(["proto/rustsim.proto"], ["proto"]).expect("the protocol is invalid");
+        tonic_build::configure()
+            .compile_fds(descriptors)
+            .expect("the gRPC code can't be generated");
+    }
+}
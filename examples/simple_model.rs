@@ -1,10 +1,10 @@
 #![feature(generators)] 
 
 // Structs from the standard library
-use std::{rc::Rc, cell::Cell, time::Duration};
+use std::time::Duration;
 
 // Import from the library the necessary structs to execute a simulation    
-use rustsim::{Key, Simulation, GenBoxed, Action, State, StateKey};
+use rustsim::{Key, Simulation, GenBoxed, Action, SharedState, StateKey};
 
 // A simple model of entities A and B
 // 1.- Entity B will start the simulation by doing a Passivate
@@ -36,8 +36,8 @@ fn main() {
     let entity_states = state.insert(Passivated { entity_a: false, entity_b: false });
 
     // Instantiate and insert the generators to the simulation.
-    let a_key = simulation.add_generator(entity_a(Rc::clone(&shared_state), entity_b_key, entity_states));
-    let b_key = simulation.add_generator(entity_b(Rc::clone(&shared_state), a_key, entity_states));
+    let a_key = simulation.add_generator(entity_a(shared_state.clone(), entity_b_key, entity_states));
+    let b_key = simulation.add_generator(entity_b(shared_state.clone(), a_key, entity_states));
     
     // Replace the null value with Entity B Key's
     *state.get_mut(entity_b_key).unwrap() = Some(b_key);
@@ -48,9 +48,10 @@ fn main() {
     // Or the generator will end up extracting an empty state
     shared_state.set(state);
 
-    // Schedule the entities using their associated Keys at the current simulated time (0 seconds).
-    simulation.schedule_now(b_key);
-    simulation.schedule_now(a_key);
+    // Declare the entities for the initialization phase using their associated Keys.
+    // They will be resumed in this exact order before any other event, so Entity B is guaranteed to do its Passivate first.
+    simulation.schedule_init(b_key);
+    simulation.schedule_init(a_key);
     
     // Advance the simulation until a maximum of 60 simulated seconds or no more events are in the scheduler (not possible with this model)
     simulation.run_with_limit(Duration::from_secs(60));
@@ -67,7 +68,7 @@ fn main() {
 //                        Each entity will indicate the other it's current state using this struct as a medium.
 // 
 // A short explanation of both entities are explained above main but a line by line explanation is also included in the body of this function.
-fn entity_a(shared_state: Rc<Cell<State>>, entity_b_key: StateKey<Option<Key>>, entity_states_key: StateKey<Passivated>) -> GenBoxed<()> {
+fn entity_a(shared_state: SharedState, entity_b_key: StateKey<Option<Key>>, entity_states_key: StateKey<Passivated>) -> GenBoxed<()> {
     Box::new(move |_|{
         // Temporarily extract the state leaving a default one in place
         let mut state = shared_state.take();
@@ -138,7 +139,7 @@ fn entity_a(shared_state: Rc<Cell<State>>, entity_b_key: StateKey<Option<Key>>,
 // A function that will create an instance of Entity B
 // It's almost the same as Entity A with the difference that it can take Entity A Key directly without using the simulation state
 // It's body it's almost identical with the exception that it will first do a Passivate then it's normal execution
-fn entity_b(shared_state: Rc<Cell<State>>, entity_a_key: Key, entity_states_key: StateKey<Passivated>) -> GenBoxed<()> {
+fn entity_b(shared_state: SharedState, entity_a_key: Key, entity_states_key: StateKey<Passivated>) -> GenBoxed<()> {
     Box::new(move |_| {
 
         let mut state = shared_state.take();
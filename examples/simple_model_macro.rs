@@ -0,0 +1,57 @@
+#![feature(generators)]
+
+// The model of simple_model.rs written with the process! macro, run with:
+// cargo run --example simple_model_macro --features macros
+use std::time::Duration;
+
+use rustsim::{process, Key, Simulation, State};
+
+// Helper struct to determine if entities are in passivate
+pub struct Passivated {
+    entity_a: bool,
+    entity_b: bool,
+}
+
+fn main() {
+    let mut simulation: Simulation<()> = Simulation::default();
+    let shared_state = simulation.state();
+
+    let mut state = State::default();
+    let entity_b_key = state.insert(None::<Key>);
+    let passivated = state.insert(Passivated { entity_a: false, entity_b: false });
+    shared_state.set(state);
+
+    // The macros lock the state for each statement using state!, so it's never missing when an entity yields.
+    let a_key = simulation.add_generator(process!(shared_state.clone() => {
+        loop {
+            hold!(5s);
+            if state!(passivated).entity_b {
+                println!("[ENTITY A] -> ACTIVATE [ENTITY B]");
+                activate!(state!(entity_b_key).unwrap());
+            }
+            state!(passivated).entity_a = true;
+            passivate!();
+            state!(passivated).entity_a = false;
+        }
+    }));
+    let b_key = simulation.add_generator(process!(shared_state.clone() => {
+        state!(passivated).entity_b = true;
+        passivate!();
+        state!(passivated).entity_b = false;
+        loop {
+            hold!(5s);
+            if state!(passivated).entity_a {
+                println!("[ENTITY B] -> ACTIVATE [ENTITY A]");
+                activate!(a_key);
+            }
+            state!(passivated).entity_b = true;
+            passivate!();
+            state!(passivated).entity_b = false;
+        }
+    }));
+    shared_state.with_mut(|state| *state.get_mut(entity_b_key).unwrap() = Some(b_key));
+
+    simulation.schedule_init(b_key);
+    simulation.schedule_init(a_key);
+    simulation.run_with_limit(Duration::from_secs(60));
+}
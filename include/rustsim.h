@@ -0,0 +1,87 @@
+/* The C API of rustsim, built with the `ffi` feature. See the documentation of the `ffi` module. */
+
+#ifndef RUSTSIM_H
+#define RUSTSIM_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Returned by the functions taking a simulation. */
+
+/* The step was executed. */
+#define RUSTSIM_OK 0
+/* No more events are left. */
+#define RUSTSIM_EXHAUSTED 1
+/* An entity yielded an invalid action, or its callback returned one, the simulation can still be stepped. */
+#define RUSTSIM_INVALID_ACTION -1
+/* A null pointer, a stale key or an invalid time was passed. */
+#define RUSTSIM_INVALID_ARGUMENT -2
+/* The engine panicked, the simulation shouldn't be used anymore. */
+#define RUSTSIM_PANICKED -3
+
+/* The kinds of actions returned by entities. */
+
+#define RUSTSIM_COMPLETE 0u
+/* Hold for `duration` seconds, which must be a non-negative number. */
+#define RUSTSIM_HOLD 1u
+#define RUSTSIM_PASSIVATE 2u
+/* Activate the passive entity `other`. */
+#define RUSTSIM_ACTIVATE 3u
+/* Cancel the pending event of `other`. */
+#define RUSTSIM_CANCEL 4u
+
+/* The opaque simulation. */
+typedef struct RustsimSimulation RustsimSimulation;
+
+/*
+ * The action returned by an entity callback.
+ *
+ * An unknown kind, an invalid duration or a zero key makes the step return RUSTSIM_INVALID_ACTION, and the entity
+ * is left passive.
+ */
+typedef struct RustsimAction {
+    uint32_t kind;
+    double duration;
+    uint64_t other;
+} RustsimAction;
+
+/* An entity: called with its user data and the simulation time in seconds every time it's resumed. */
+typedef RustsimAction (*RustsimEntity)(void *user_data, double time);
+
+/* Create a simulation recording its trace, to be released with rustsim_simulation_free. */
+RustsimSimulation *rustsim_simulation_new(void);
+
+/* Release a simulation created with rustsim_simulation_new, null is ignored. */
+void rustsim_simulation_free(RustsimSimulation *simulation);
+
+/* Add an entity resumed by calling `entity` with `user_data`, returning its key, or 0 if `simulation` is null. */
+uint64_t rustsim_add_entity(RustsimSimulation *simulation, RustsimEntity entity, void *user_data);
+
+/* Schedule the entity `key` `delay` seconds from now. */
+int32_t rustsim_schedule(RustsimSimulation *simulation, uint64_t key, double delay);
+
+/* Execute the next event. */
+int32_t rustsim_step(RustsimSimulation *simulation);
+
+/* Returns the simulation time in seconds, NaN if `simulation` is null. */
+double rustsim_time(const RustsimSimulation *simulation);
+
+/* Returns the trace recorded so far as CSV, to be released with rustsim_string_free, or null if `simulation` is
+ * null. */
+char *rustsim_trace_csv(const RustsimSimulation *simulation);
+
+/* Release a string returned by this API, null is ignored. */
+void rustsim_string_free(char *string);
+
+/* Returns the message of the last failure, or null if there was none. The string belongs to the simulation and is
+ * valid until the next call taking it. */
+const char *rustsim_last_error(const RustsimSimulation *simulation);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
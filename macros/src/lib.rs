@@ -0,0 +1,354 @@
+//! Procedural macros of `rustsim`, re-exported by its `macros` feature.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::visit_mut::{self, VisitMut};
+use syn::{parse_macro_input, parse_quote, Block, Data, DeriveInput, Error, Expr, Lit, LitStr, Macro, Stmt, Token};
+
+/// Write an entity as a sequence of statements, without the generator and state boilerplate.
+///
+/// Expands to a boxed generator, `GenBoxed<R>` for any resume type `R`, where the actions are written as macros:
+///
+/// - `hold!(5s)` holds for a literal time with a unit among `ns`, `us`, `ms`, `s`, `min` and `h`, and
+///   `hold!(time)` for any `SimTime` expression.
+/// - `passivate!()`.
+/// - `activate!(key)`, or `activate!(a, b, ...)` to activate several entities at once.
+/// - `cancel!(key)` and `preempt!(key)`.
+///
+/// Given a `SharedState` before `=>`, `state!(key)` is the value of `key` in the state, read or assigned in
+/// place. The state is locked only for the statement using it, so it's always back in place when the entity
+/// yields. A statement can use `state!` once, and a `match` on `state!` must bind the value first since
+/// the state stays locked through its arms.
+///
+/// ```ignore
+/// let a = simulation.add_generator(process!(shared_state => {
+///     loop {
+///         hold!(5s);
+///         if state!(passivated).b {
+///             activate!(state!(b_key));
+///         }
+///         state!(passivated).a = true;
+///         passivate!();
+///     }
+/// }));
+/// ```
+///
+/// The macros are rewritten in the body itself, not inside the arguments of other macros such as `println!`.
+#[proc_macro]
+pub fn process(input: TokenStream) -> TokenStream {
+    expand_process(input.into()).unwrap_or_else(Error::into_compile_error).into()
+}
+
+fn expand_process(input: proc_macro2::TokenStream) -> syn::Result<proc_macro2::TokenStream> {
+    let Process { state, mut body } = syn::parse2(input)?;
+    let mut expander = Expander {
+        state: state.is_some(),
+        error: None,
+    };
+    expander.visit_block_mut(&mut body);
+    if let Some(error) = expander.error {
+        return Err(error);
+    }
+    let state = state.map(|state| quote! { let __rustsim_state: ::rustsim::SharedState = #state; });
+    Ok(quote! {
+        {
+            #state
+            ::std::boxed::Box::new(move |_| #body)
+        }
+    })
+}
+
+/// Derive `rustsim::Entity` for a struct holding the parameters of an entity.
+///
+/// The class of the entity is the name of the struct, or the one given with `#[entity(class = "...")]`. The
+/// fields marked with `#[entity(attribute)]` are cloned into its attributes, so their types must be `Clone` and
+/// distinct from each other.
+///
+/// ```ignore
+/// #[derive(Entity)]
+/// #[entity(class = "vehicle")]
+/// struct Truck {
+///     #[entity(attribute)]
+///     capacity: Capacity,
+///     trip: Duration,
+/// }
+/// ```
+#[proc_macro_derive(Entity, attributes(entity))]
+pub fn derive_entity(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    derive(input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+fn derive(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let Data::Struct(data) = &input.data else {
+        return Err(Error::new_spanned(&input.ident, "`Entity` can only be derived for structs"));
+    };
+    let mut class = LitStr::new(&input.ident.to_string(), input.ident.span());
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("entity")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("class") {
+                class = meta.value()?.parse()?;
+                Ok(())
+            } else {
+                Err(meta.error("expected `class = \"...\"`"))
+            }
+        })?;
+    }
+    let mut attributes = Vec::new();
+    for (index, field) in data.fields.iter().enumerate() {
+        let mut attribute = false;
+        for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("entity")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("attribute") {
+                    attribute = true;
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `attribute`"))
+                }
+            })?;
+        }
+        if attribute {
+            attributes.push(match &field.ident {
+                Some(name) => quote!(#name),
+                None => {
+                    let index = syn::Index::from(index);
+                    quote!(#index)
+                }
+            });
+        }
+    }
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::rustsim::Entity for #name #type_generics #where_clause {
+            const CLASS: &'static str = #class;
+
+            fn attributes(&self) -> ::rustsim::Attributes {
+                ::rustsim::Attributes::new()
+                    .class(Self::CLASS)
+                    #(.with(::std::clone::Clone::clone(&self.#attributes)))*
+            }
+        }
+    })
+}
+
+struct Process {
+    state: Option<Expr>,
+    body: Block,
+}
+
+impl Parse for Process {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.peek(syn::token::Brace) {
+            return Ok(Self {
+                state: None,
+                body: input.parse()?,
+            });
+        }
+        let state = input.parse()?;
+        input.parse::<Token![=>]>()?;
+        Ok(Self {
+            state: Some(state),
+            body: input.parse()?,
+        })
+    }
+}
+
+struct Expander {
+    state: bool,
+    error: Option<Error>,
+}
+
+impl Expander {
+    // Returns the expansion of `mac` if it's one of the action or state macros.
+    fn expand(&mut self, mac: &Macro) -> Option<Expr> {
+        let name = mac.path.get_ident()?.to_string();
+        let expanded = match name.as_str() {
+            "hold" => mac.parse_body_with(hold),
+            "passivate" => Ok(parse_quote!(yield ::rustsim::Action::Passivate)),
+            "activate" => mac
+                .parse_body_with(Punctuated::<Expr, Token![,]>::parse_terminated)
+                .map(|keys| match keys.len() {
+                    1 => action(quote!(::rustsim::Action::ActivateOne(#keys))),
+                    _ => action(quote!(::rustsim::Action::ActivateMany(::std::vec![#keys]))),
+                }),
+            "cancel" => mac
+                .parse_body::<Expr>()
+                .map(|key| action(quote!(::rustsim::Action::Cancel(#key)))),
+            "preempt" => mac
+                .parse_body::<Expr>()
+                .map(|key| action(quote!(::rustsim::Action::Preempt(#key)))),
+            "state" if !self.state => Err(Error::new_spanned(
+                mac,
+                "`state!` needs the shared state, as in `process!(shared_state => { ... })`",
+            )),
+            "state" => mac.parse_body::<Expr>().map(|key| {
+                parse_quote! {
+                    (*__rustsim_state.lock().get_mut(#key).expect("`state!` used a key missing from the state"))
+                }
+            }),
+            _ => return None,
+        };
+        match expanded {
+            Ok(mut expr) => {
+                // The arguments may use the macros too, e.g. `activate!(state!(key))`.
+                self.visit_expr_mut(&mut expr);
+                Some(expr)
+            }
+            Err(error) => {
+                self.error.get_or_insert(error);
+                Some(parse_quote!(()))
+            }
+        }
+    }
+}
+
+impl VisitMut for Expander {
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        if let Expr::Macro(mac) = expr {
+            if let Some(expanded) = self.expand(&mac.mac) {
+                *expr = expanded;
+                return;
+            }
+        }
+        visit_mut::visit_expr_mut(self, expr);
+    }
+
+    fn visit_stmt_mut(&mut self, stmt: &mut Stmt) {
+        if let Stmt::Macro(mac) = stmt {
+            if let Some(expanded) = self.expand(&mac.mac) {
+                *stmt = Stmt::Expr(expanded, mac.semi_token.or(Some(Default::default())));
+                return;
+            }
+        }
+        visit_mut::visit_stmt_mut(self, stmt);
+    }
+}
+
+// Yield `action`, built before so the state locked by its arguments is put back first.
+fn action(action: proc_macro2::TokenStream) -> Expr {
+    parse_quote! {
+        {
+            let __rustsim_action = #action;
+            yield __rustsim_action
+        }
+    }
+}
+
+// The argument of `hold!`: a literal with a time unit or any `SimTime` expression.
+fn hold(input: ParseStream) -> syn::Result<Expr> {
+    let time: Expr = input.parse()?;
+    let Expr::Lit(literal) = &time else {
+        return Ok(action(quote!(::rustsim::Action::hold(#time))));
+    };
+    let (value, unit, float) = match &literal.lit {
+        Lit::Int(int) if !int.suffix().is_empty() => (int.base10_digits().to_owned(), int.suffix(), false),
+        Lit::Float(float) if !float.suffix().is_empty() => (float.base10_digits().to_owned(), float.suffix(), true),
+        _ => return Ok(action(quote!(::rustsim::Action::hold(#time)))),
+    };
+    let seconds: f64 = match unit {
+        "ns" => 1e-9,
+        "us" => 1e-6,
+        "ms" => 1e-3,
+        "s" => 1.0,
+        "min" => 60.0,
+        "h" => 3600.0,
+        _ => {
+            return Err(Error::new_spanned(
+                literal,
+                format!("unknown time unit `{}`, expected ns, us, ms, s, min or h", unit),
+            ))
+        }
+    };
+    let duration: Expr = if float {
+        let value: f64 = value.parse().map_err(|_| Error::new(Span::call_site(), "invalid time"))?;
+        let value = value * seconds;
+        parse_quote!(::std::time::Duration::from_secs_f64(#value))
+    } else {
+        let value: u64 = value
+            .parse()
+            .map_err(|_| Error::new_spanned(literal, "the time doesn't fit in a u64"))?;
+        let nanos = (seconds * 1e9).round() as u64;
+        let nanos = value
+            .checked_mul(nanos)
+            .ok_or_else(|| Error::new_spanned(literal, "the time doesn't fit in a Duration"))?;
+        parse_quote!(::std::time::Duration::from_nanos(#nanos))
+    };
+    Ok(parse_quote!(yield ::rustsim::Action::Hold(#duration)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // The expansion of `process!` for `input`, as a string to compare with the expected tokens.
+    fn expand(input: proc_macro2::TokenStream) -> String {
+        expand_process(input).unwrap().to_string()
+    }
+
+    #[test]
+    fn actions_expand_to_yields() {
+        let expanded = expand(quote! {{
+            hold!(1.5s);
+            hold!(500ms);
+            hold!(time);
+            passivate!();
+            activate!(a, b);
+            preempt!(a);
+        }});
+        let expected = quote! {{
+            ::std::boxed::Box::new(move |_| {
+                yield ::rustsim::Action::Hold(::std::time::Duration::from_secs_f64(1.5f64));
+                yield ::rustsim::Action::Hold(::std::time::Duration::from_nanos(500000000u64));
+                {
+                    let __rustsim_action = ::rustsim::Action::hold(time);
+                    yield __rustsim_action
+                };
+                yield ::rustsim::Action::Passivate;
+                {
+                    let __rustsim_action = ::rustsim::Action::ActivateMany(::std::vec![a, b]);
+                    yield __rustsim_action
+                };
+                {
+                    let __rustsim_action = ::rustsim::Action::Preempt(a);
+                    yield __rustsim_action
+                };
+            })
+        }};
+        assert_eq!(expected.to_string(), expanded);
+    }
+
+    #[test]
+    fn state_expands_to_the_locked_value() {
+        let expanded = expand(quote!(shared.clone() => {
+            state!(count) += 1;
+            activate!(state!(other));
+        }));
+        let expected = quote! {{
+            let __rustsim_state: ::rustsim::SharedState = shared.clone();
+            ::std::boxed::Box::new(move |_| {
+                (*__rustsim_state.lock().get_mut(count).expect("`state!` used a key missing from the state")) += 1;
+                {
+                    let __rustsim_action = ::rustsim::Action::ActivateOne(
+                        (*__rustsim_state.lock().get_mut(other).expect("`state!` used a key missing from the state"))
+                    );
+                    yield __rustsim_action
+                };
+            })
+        }};
+        assert_eq!(expected.to_string(), expanded);
+    }
+
+    #[test]
+    fn invalid_macros_are_rejected() {
+        let error = |input| expand_process(input).unwrap_err().to_string();
+        assert_eq!("unknown time unit `d`, expected ns, us, ms, s, min or h", error(quote!({ hold!(2d); })));
+        assert_eq!(
+            "`state!` needs the shared state, as in `process!(shared_state => { ... })`",
+            error(quote!({ state!(count) += 1; }))
+        );
+    }
+}
@@ -0,0 +1,515 @@
+//! Spatial structures for agent-based models.
+//!
+//! Agents are entities, identified by their [`Key`]. A [`Grid`] places them in discrete cells and a [`Space`] at
+//! continuous positions, both answering neighbor queries. They are plain values meant to be stored in the shared
+//! [`State`](crate::State), so agents move with ordinary processes holding between moves:
+//!
+//! ```ignore
+//! let space = state.insert(Space::new(100.0, 100.0, 5.0).torus());
+//!
+//! // An agent
+//! let key = own_key.get().unwrap();
+//! shared_state.lock().get_mut(space).unwrap().place(key, Point::new(50.0, 50.0));
+//! loop {
+//!     yield Action::Hold(Duration::from_secs(1));
+//!     let mut state = shared_state.lock();
+//!     let space = state.get_mut(space).unwrap();
+//!     space.move_by(key, rng.uniform(-1.0, 1.0), rng.uniform(-1.0, 1.0));
+//!     let contacts = space.neighbors(key, 2.0);
+//!     ...
+//! }
+//! ```
+//!
+//! Positions are kept after their agent completes, remove them with an `on_complete` hook if needed. Queries return
+//! agents in a deterministic order, so runs stay reproducible.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::Key;
+
+/// A cell of a [`Grid`], as `(column, row)`.
+pub type Cell = (usize, usize);
+
+/// Error returned when an agent can't be placed in a [`Grid`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GridError {
+    OutOfBounds(Cell),
+    /// The cell already holds as many agents as the capacity of the grid.
+    Full(Cell),
+}
+
+impl fmt::Display for GridError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OutOfBounds(cell) => write!(f, "cell {:?} is out of the grid", cell),
+            Self::Full(cell) => write!(f, "cell {:?} is full", cell),
+        }
+    }
+}
+
+impl std::error::Error for GridError {}
+
+/// Which cells around a cell are its neighbors, up to a radius.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Neighborhood {
+    /// Cells within the radius in both directions, the 8 surrounding cells for radius 1.
+    Moore,
+    /// Cells within the radius in Manhattan distance, the 4 adjacent cells for radius 1.
+    VonNeumann,
+}
+
+/// A grid of `width` by `height` cells, each holding agents in the order they arrived.
+#[derive(Debug, Clone)]
+pub struct Grid {
+    width: usize,
+    height: usize,
+    torus: bool,
+    capacity: Option<usize>,
+    cells: Vec<Vec<Key>>,
+    positions: HashMap<Key, Cell>,
+}
+
+impl Grid {
+    #[must_use]
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            torus: false,
+            capacity: None,
+            cells: vec![Vec::new(); width * height],
+            positions: HashMap::new(),
+        }
+    }
+
+    /// Wrap the edges around, so cells on opposite borders are neighbors.
+    #[must_use]
+    pub fn torus(mut self) -> Self {
+        self.torus = true;
+        self
+    }
+
+    /// Allow at most `capacity` agents per cell, e.g. one for models where agents exclude each other.
+    #[must_use]
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = Some(capacity);
+        self
+    }
+
+    #[must_use]
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    #[must_use]
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Put `agent` in `cell`, moving it if it was already in the grid.
+    pub fn place(&mut self, agent: Key, cell: Cell) -> Result<(), GridError> {
+        let index = self.index(cell).ok_or(GridError::OutOfBounds(cell))?;
+        if self.positions.get(&agent) == Some(&cell) {
+            return Ok(());
+        }
+        if matches!(self.capacity, Some(capacity) if self.cells[index].len() >= capacity) {
+            return Err(GridError::Full(cell));
+        }
+        self.remove(agent);
+        self.cells[index].push(agent);
+        self.positions.insert(agent, cell);
+        Ok(())
+    }
+
+    /// Take `agent` out of the grid, returning the cell it was in.
+    pub fn remove(&mut self, agent: Key) -> Option<Cell> {
+        let cell = self.positions.remove(&agent)?;
+        let index = self.index(cell).expect("positions are inside the grid");
+        self.cells[index].retain(|&other| other != agent);
+        Some(cell)
+    }
+
+    #[must_use]
+    pub fn position(&self, agent: Key) -> Option<Cell> {
+        self.positions.get(&agent).copied()
+    }
+
+    /// Returns the agents in `cell`, empty if it's out of the grid.
+    #[must_use]
+    pub fn agents_at(&self, cell: Cell) -> &[Key] {
+        self.index(cell).map_or(&[], |index| &self.cells[index])
+    }
+
+    /// Returns the number of agents in the grid.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Returns the cells around `cell` within `radius`, without `cell` itself, row by row.
+    #[must_use]
+    pub fn neighbor_cells(&self, cell: Cell, neighborhood: Neighborhood, radius: usize) -> Vec<Cell> {
+        let radius = radius as isize;
+        let mut cells = Vec::new();
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                let within = match neighborhood {
+                    Neighborhood::Moore => true,
+                    Neighborhood::VonNeumann => dx.abs() + dy.abs() <= radius,
+                };
+                if (dx, dy) == (0, 0) || !within {
+                    continue;
+                }
+                if let Some(neighbor) = self.offset(cell, dx, dy) {
+                    // Small tori reach the same cell from several sides.
+                    if neighbor != cell && !cells.contains(&neighbor) {
+                        cells.push(neighbor);
+                    }
+                }
+            }
+        }
+        cells
+    }
+
+    /// Returns the cells around `cell` within `radius` that can take one more agent.
+    #[must_use]
+    pub fn free_neighbor_cells(&self, cell: Cell, neighborhood: Neighborhood, radius: usize) -> Vec<Cell> {
+        let mut cells = self.neighbor_cells(cell, neighborhood, radius);
+        cells.retain(|&cell| !matches!(self.capacity, Some(capacity) if self.agents_at(cell).len() >= capacity));
+        cells
+    }
+
+    /// Returns the other agents in the cell of `agent` and in the cells around it within `radius`.
+    #[must_use]
+    pub fn neighbors(&self, agent: Key, neighborhood: Neighborhood, radius: usize) -> Vec<Key> {
+        let Some(cell) = self.position(agent) else {
+            return Vec::new();
+        };
+        let mut agents: Vec<Key> = self
+            .agents_at(cell)
+            .iter()
+            .copied()
+            .filter(|&other| other != agent)
+            .collect();
+        for neighbor in self.neighbor_cells(cell, neighborhood, radius) {
+            agents.extend_from_slice(self.agents_at(neighbor));
+        }
+        agents
+    }
+
+    fn index(&self, (x, y): Cell) -> Option<usize> {
+        (x < self.width && y < self.height).then_some(y * self.width + x)
+    }
+
+    fn offset(&self, (x, y): Cell, dx: isize, dy: isize) -> Option<Cell> {
+        let wrap = |value: usize, delta: isize, size: usize| -> Option<usize> {
+            let moved = value as isize + delta;
+            if self.torus {
+                Some(moved.rem_euclid(size as isize) as usize)
+            } else {
+                (0..size as isize).contains(&moved).then_some(moved as usize)
+            }
+        };
+        Some((wrap(x, dx, self.width)?, wrap(y, dy, self.height)?))
+    }
+}
+
+/// A position in a [`Space`].
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Point {
+    #[must_use]
+    pub fn new(x: f64, y: f64) -> Self {
+        Self { x, y }
+    }
+}
+
+/// A continuous rectangle of `width` by `height` where agents are at any position.
+///
+/// Agents are indexed in buckets of `bucket_size`, so neighbor queries within a radius close to the bucket size only
+/// look at the agents nearby.
+#[derive(Debug, Clone)]
+pub struct Space {
+    width: f64,
+    height: f64,
+    torus: bool,
+    bucket_size: f64,
+    positions: HashMap<Key, Point>,
+    buckets: HashMap<(i64, i64), Vec<Key>>,
+}
+
+impl Space {
+    /// # Panics
+    ///
+    /// Panics if any of the sizes isn't positive.
+    #[must_use]
+    pub fn new(width: f64, height: f64, bucket_size: f64) -> Self {
+        assert!(
+            width > 0.0 && height > 0.0 && bucket_size > 0.0,
+            "The sizes of a space must be positive"
+        );
+        Self {
+            width,
+            height,
+            torus: false,
+            bucket_size,
+            positions: HashMap::new(),
+            buckets: HashMap::new(),
+        }
+    }
+
+    /// Wrap the edges around: positions and distances are taken modulo the size of the space.
+    #[must_use]
+    pub fn torus(mut self) -> Self {
+        self.torus = true;
+        self
+    }
+
+    /// Put `agent` at `point`, moving it if it was already in the space.
+    ///
+    /// Points outside of the space are wrapped around in a torus and clamped to its borders otherwise.
+    pub fn place(&mut self, agent: Key, point: Point) {
+        let point = self.normalize(point);
+        self.remove(agent);
+        self.buckets.entry(self.bucket(point)).or_default().push(agent);
+        self.positions.insert(agent, point);
+    }
+
+    /// Move `agent` by `(dx, dy)`, returning its new position, `None` if it isn't in the space.
+    pub fn move_by(&mut self, agent: Key, dx: f64, dy: f64) -> Option<Point> {
+        let point = self.position(agent)?;
+        self.place(agent, Point::new(point.x + dx, point.y + dy));
+        self.position(agent)
+    }
+
+    /// Take `agent` out of the space, returning its position.
+    pub fn remove(&mut self, agent: Key) -> Option<Point> {
+        let point = self.positions.remove(&agent)?;
+        let bucket = self.bucket(point);
+        if let Some(agents) = self.buckets.get_mut(&bucket) {
+            agents.retain(|&other| other != agent);
+            if agents.is_empty() {
+                self.buckets.remove(&bucket);
+            }
+        }
+        Some(point)
+    }
+
+    #[must_use]
+    pub fn position(&self, agent: Key) -> Option<Point> {
+        self.positions.get(&agent).copied()
+    }
+
+    /// Returns the number of agents in the space.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Returns the distance between two points, the shortest one around the edges in a torus.
+    #[must_use]
+    pub fn distance(&self, a: Point, b: Point) -> f64 {
+        let delta = |a: f64, b: f64, size: f64| {
+            let delta = (a - b).abs();
+            if self.torus {
+                delta.min(size - delta)
+            } else {
+                delta
+            }
+        };
+        delta(a.x, b.x, self.width).hypot(delta(a.y, b.y, self.height))
+    }
+
+    /// Returns the agents within `radius` of `point`, closest first and by key on ties.
+    #[must_use]
+    pub fn within(&self, point: Point, radius: f64) -> Vec<Key> {
+        let point = self.normalize(point);
+        let reach = (radius / self.bucket_size).ceil() as i64;
+        let (column, row) = self.bucket(point);
+        let mut found: Vec<(f64, Key)> = Vec::new();
+        let mut visited = Vec::new();
+        for dy in -reach..=reach {
+            for dx in -reach..=reach {
+                let bucket = self.wrap_bucket((column + dx, row + dy));
+                if visited.contains(&bucket) {
+                    continue;
+                }
+                visited.push(bucket);
+                for &agent in self.buckets.get(&bucket).into_iter().flatten() {
+                    let distance = self.distance(point, self.positions[&agent]);
+                    if distance <= radius {
+                        found.push((distance, agent));
+                    }
+                }
+            }
+        }
+        found.sort_by(|(a_distance, a), (b_distance, b)| {
+            a_distance
+                .total_cmp(b_distance)
+                .then((a.id, a.generation).cmp(&(b.id, b.generation)))
+        });
+        found.into_iter().map(|(_, agent)| agent).collect()
+    }
+
+    /// Returns the other agents within `radius` of `agent`, closest first.
+    #[must_use]
+    pub fn neighbors(&self, agent: Key, radius: f64) -> Vec<Key> {
+        let Some(point) = self.position(agent) else {
+            return Vec::new();
+        };
+        let mut agents = self.within(point, radius);
+        agents.retain(|&other| other != agent);
+        agents
+    }
+
+    fn normalize(&self, point: Point) -> Point {
+        let fit = |value: f64, size: f64| {
+            if self.torus {
+                value.rem_euclid(size)
+            } else {
+                value.clamp(0.0, size)
+            }
+        };
+        Point::new(fit(point.x, self.width), fit(point.y, self.height))
+    }
+
+    fn bucket(&self, point: Point) -> (i64, i64) {
+        (
+            (point.x / self.bucket_size).floor() as i64,
+            (point.y / self.bucket_size).floor() as i64,
+        )
+    }
+
+    // The bucket holding the points of `bucket` once wrapped around a torus.
+    fn wrap_bucket(&self, (column, row): (i64, i64)) -> (i64, i64) {
+        if !self.torus {
+            return (column, row);
+        }
+        let columns = (self.width / self.bucket_size).ceil() as i64;
+        let rows = (self.height / self.bucket_size).ceil() as i64;
+        (column.rem_euclid(columns), row.rem_euclid(rows))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{process, Action, Simulation, State};
+
+    #[test]
+    fn grid_neighborhoods() {
+        let agents: Vec<Key> = (0..4).map(Key::new).collect();
+        let mut grid = Grid::new(5, 5).capacity(1);
+        grid.place(agents[0], (2, 2)).unwrap();
+        grid.place(agents[1], (3, 2)).unwrap();
+        grid.place(agents[2], (3, 3)).unwrap();
+        grid.place(agents[3], (4, 4)).unwrap();
+
+        assert_eq!(vec![agents[1], agents[2]], grid.neighbors(agents[0], Neighborhood::Moore, 1));
+        assert_eq!(vec![agents[1]], grid.neighbors(agents[0], Neighborhood::VonNeumann, 1));
+        assert_eq!(3, grid.neighbors(agents[0], Neighborhood::Moore, 2).len());
+        assert_eq!(Err(GridError::Full((3, 3))), grid.place(agents[0], (3, 3)));
+        assert_eq!(Err(GridError::OutOfBounds((5, 0))), grid.place(agents[0], (5, 0)));
+        assert_eq!(3, grid.neighbor_cells((0, 0), Neighborhood::Moore, 1).len());
+        assert_eq!(5, grid.free_neighbor_cells((3, 3), Neighborhood::Moore, 1).len());
+
+        grid.place(agents[0], (0, 0)).unwrap();
+        assert_eq!(Some((0, 0)), grid.position(agents[0]));
+        assert!(grid.agents_at((2, 2)).is_empty());
+        assert!(grid.neighbors(agents[0], Neighborhood::Moore, 1).is_empty());
+        let torus = {
+            let mut torus = Grid::new(5, 5).torus();
+            torus.place(agents[0], (0, 0)).unwrap();
+            torus.place(agents[3], (4, 4)).unwrap();
+            torus
+        };
+        assert_eq!(vec![agents[3]], torus.neighbors(agents[0], Neighborhood::Moore, 1));
+        assert_eq!(8, torus.neighbor_cells((0, 0), Neighborhood::Moore, 1).len());
+        assert_eq!(Some((4, 4)), grid.remove(agents[3]));
+        assert_eq!(3, grid.len());
+    }
+
+    #[test]
+    fn space_queries_sort_by_distance() {
+        let agents: Vec<Key> = (0..4).map(Key::new).collect();
+        let mut space = Space::new(10.0, 10.0, 1.0).torus();
+        space.place(agents[0], Point::new(5.0, 5.0));
+        space.place(agents[1], Point::new(6.5, 5.0));
+        space.place(agents[2], Point::new(5.0, 6.0));
+        space.place(agents[3], Point::new(9.5, 5.0));
+
+        assert_eq!(vec![agents[2], agents[1]], space.neighbors(agents[0], 2.0));
+        // Around the edge, 0.5 to the right of 9.5 and 1 to the left of 0.5.
+        assert_eq!(vec![agents[3]], space.within(Point::new(0.5, 5.0), 1.0));
+        assert_eq!(Some(Point::new(0.5, 5.0)), space.move_by(agents[3], 1.0, 0.0));
+        assert_eq!(vec![agents[3]], space.within(Point::new(9.8, 5.0), 1.0));
+
+        let mut bounded = Space::new(10.0, 10.0, 2.0);
+        bounded.place(agents[0], Point::new(-3.0, 12.0));
+        assert_eq!(Some(Point::new(0.0, 10.0)), bounded.position(agents[0]));
+        assert_eq!(Some(Point::new(0.0, 10.0)), bounded.remove(agents[0]));
+        assert!(bounded.is_empty());
+    }
+
+    #[test]
+    fn agents_move_with_hold_driven_processes() {
+        let mut simulation = Simulation::default();
+        let shared_state = simulation.state();
+        let mut state = State::default();
+        let space = state.insert(Space::new(20.0, 20.0, 2.0));
+        shared_state.set(state);
+
+        // Two walkers heading towards each other one unit per second, until they are a unit apart.
+        let mut walker = |start: f64, step: f64| {
+            let shared_state = shared_state.clone();
+            let own_key: Rc<Cell<Option<Key>>> = Rc::default();
+            let key = Rc::clone(&own_key);
+            let mut placed = false;
+            let walker = simulation.add_generator(process(move |_| {
+                let key = key.get().expect("set right after adding");
+                let mut state = shared_state.lock();
+                let space = state.get_mut(space).unwrap();
+                if !placed {
+                    space.place(key, Point::new(start, 10.0));
+                    placed = true;
+                } else if !space.neighbors(key, 1.0).is_empty() {
+                    return None;
+                } else {
+                    space.move_by(key, step, 0.0);
+                }
+                Some(Action::Hold(Duration::from_secs(1)))
+            }));
+            own_key.set(Some(walker));
+            simulation.schedule_now(walker);
+            walker
+        };
+        let left = walker(2.0, 1.0);
+        let right = walker(18.0, -1.0);
+        simulation.run_until_empty();
+
+        // At 8 the left walker moves to 10 and the right one, at 11, stops; the left one stops at 9.
+        assert_eq!(Duration::from_secs(9), simulation.time());
+        let space = shared_state.with(|state| state.get(space).unwrap().clone());
+        assert_eq!(Some(Point::new(10.0, 10.0)), space.position(left));
+        assert_eq!(Some(Point::new(11.0, 10.0)), space.position(right));
+    }
+}
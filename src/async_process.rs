@@ -0,0 +1,170 @@
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::ptr;
+use std::rc::Rc;
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+use crate::process::{Generator, GeneratorState};
+use crate::{Action, GenBoxed};
+
+// Processes are only ever polled by the simulation, so nothing needs to be woken.
+fn noop_waker() -> Waker {
+    fn clone(_: *const ()) -> RawWaker {
+        RawWaker::new(ptr::null(), &VTABLE)
+    }
+    fn noop(_: *const ()) {}
+    static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+    // SAFETY: the vtable functions ignore the data pointer.
+    unsafe { Waker::from_raw(RawWaker::new(ptr::null(), &VTABLE)) }
+}
+
+struct Slot<R> {
+    action: Option<Action>,
+    resume: Option<R>,
+}
+
+/// Handle used by an [`async_process`] to yield actions.
+pub struct Co<R> {
+    slot: Rc<RefCell<Slot<R>>>,
+}
+
+impl<R> Clone for Co<R> {
+    fn clone(&self) -> Self {
+        Self {
+            slot: Rc::clone(&self.slot),
+        }
+    }
+}
+
+impl<R> Co<R> {
+    /// Yield `action` to the simulation, resolving to the value the entity is resumed with.
+    pub fn yield_(&self, action: Action) -> YieldFuture<'_, R> {
+        YieldFuture {
+            co: self,
+            action: Some(action),
+        }
+    }
+}
+
+/// Future returned by [`Co::yield_`].
+pub struct YieldFuture<'a, R> {
+    co: &'a Co<R>,
+    action: Option<Action>,
+}
+
+impl<R> Future for YieldFuture<'_, R> {
+    type Output = R;
+
+    fn poll(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<R> {
+        let this = self.get_mut();
+        let mut slot = this.co.slot.borrow_mut();
+        match this.action.take() {
+            // Suspend the process, the simulation picks the action up.
+            Some(action) => {
+                slot.action = Some(action);
+                Poll::Pending
+            }
+            None => Poll::Ready(slot.resume.take().expect("the process is polled on resume")),
+        }
+    }
+}
+
+struct AsyncProcess<R> {
+    future: Pin<Box<dyn Future<Output = ()>>>,
+    slot: Rc<RefCell<Slot<R>>>,
+}
+
+impl<R> Generator<R> for AsyncProcess<R> {
+    type Yield = Action;
+    type Return = ();
+
+    fn resume(self: Pin<&mut Self>, arg: R) -> GeneratorState<Action, ()> {
+        let this = self.get_mut();
+        this.slot.borrow_mut().resume = Some(arg);
+        let waker = noop_waker();
+        match this.future.as_mut().poll(&mut Context::from_waker(&waker)) {
+            Poll::Ready(()) => GeneratorState::Complete(()),
+            Poll::Pending => {
+                let action = this.slot.borrow_mut().action.take();
+                GeneratorState::Yielded(action.expect("async processes can only await `Co::yield_`"))
+            }
+        }
+    }
+}
+
+/// Create an entity from an `async` block, which yields actions by awaiting [`Co::yield_`].
+///
+/// The result is a regular [`GenBoxed`], so it can be mixed with generators and [`process`](crate::process) closures.
+/// The value of the first resume is discarded, the following ones are returned by `yield_`:
+///
+/// ```ignore
+/// let customer = async_process(|co| async move {
+///     co.yield_(Action::Hold(Duration::from_secs(5))).await;
+///     co.yield_(Action::Passivate).await;
+/// });
+/// ```
+///
+/// Awaiting anything but `yield_` (a timer from an async runtime, for example) panics.
+pub fn async_process<R, F, Fut>(producer: F) -> GenBoxed<R>
+where
+    R: 'static,
+    F: FnOnce(Co<R>) -> Fut,
+    Fut: Future<Output = ()> + 'static,
+{
+    let slot = Rc::new(RefCell::new(Slot {
+        action: None,
+        resume: None,
+    }));
+    let co = Co {
+        slot: Rc::clone(&slot),
+    };
+    Box::new(AsyncProcess {
+        future: Box::pin(producer(co)),
+        slot,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{process, Key, Simulation, StepContext};
+
+    fn sleeper(log: Rc<RefCell<Vec<(u64, usize)>>>) -> GenBoxed<usize> {
+        async_process(|co| async move {
+            let resumed_with = co.yield_(Action::Hold(Duration::from_secs(2))).await;
+            log.borrow_mut().push((2, resumed_with));
+            let resumed_with = co.yield_(Action::Passivate).await;
+            log.borrow_mut().push((5, resumed_with));
+        })
+    }
+
+    fn waker(sleeper: Key) -> GenBoxed<usize> {
+        let mut step = 0;
+        process(move |_| {
+            step += 1;
+            match step {
+                1 => Some(Action::Hold(Duration::from_secs(5))),
+                2 => Some(Action::ActivateOne(sleeper)),
+                _ => None,
+            }
+        })
+    }
+
+    #[test]
+    fn async_processes_mix_with_closures() {
+        let mut simulation = Simulation::default();
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let sleeper = simulation.add_generator(sleeper(Rc::clone(&log)));
+        let waker = simulation.add_generator(waker(sleeper));
+        simulation.schedule_now(sleeper);
+        simulation.schedule_now(waker);
+        let provider = |context: &StepContext| context.time().as_secs() as usize * 10;
+        simulation.run_until_empty_with(provider);
+
+        assert_eq!(vec![(2, 20), (5, 50)], *log.borrow());
+        assert_eq!(Duration::from_secs(5), simulation.time());
+    }
+}
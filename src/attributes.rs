@@ -0,0 +1,192 @@
+use std::any::{Any, TypeId};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::Key;
+
+/// Metadata attached to an entity with [`Simulation::add_generator_with`](crate::Simulation::add_generator_with):
+/// a class, tags and typed parameters, at most one value per type.
+///
+/// ```ignore
+/// let truck = simulation.add_generator_with(
+///     truck(),
+///     Attributes::new().class("vehicle").tag("truck").with(Capacity(20)),
+/// );
+/// ```
+#[derive(Default)]
+pub struct Attributes {
+    class: Option<String>,
+    tags: Vec<String>,
+    values: HashMap<TypeId, Box<dyn Any>>,
+}
+
+impl Attributes {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn class(mut self, class: impl Into<String>) -> Self {
+        self.class = Some(class.into());
+        self
+    }
+
+    #[must_use]
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        let tag = tag.into();
+        if !self.tags.contains(&tag) {
+            self.tags.push(tag);
+        }
+        self
+    }
+
+    /// Attach `value`, replacing any previous value of the same type.
+    #[must_use]
+    pub fn with<T: 'static>(mut self, value: T) -> Self {
+        self.values.insert(TypeId::of::<T>(), Box::new(value));
+        self
+    }
+
+    #[must_use]
+    pub fn class_name(&self) -> Option<&str> {
+        self.class.as_deref()
+    }
+
+    /// Returns the tags in the order they were added.
+    #[must_use]
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    #[must_use]
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|other| other == tag)
+    }
+
+    /// Returns the value of type `T`, if one was attached.
+    #[must_use]
+    pub fn get<T: 'static>(&self) -> Option<&T> {
+        self.values
+            .get(&TypeId::of::<T>())
+            .map(|value| value.downcast_ref::<T>().expect("Ensured by the type id."))
+    }
+}
+
+impl fmt::Debug for Attributes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Attributes")
+            .field("class", &self.class)
+            .field("tags", &self.tags)
+            .field("values", &self.values.len())
+            .finish()
+    }
+}
+
+/// The [`Attributes`] of every entity, queryable by [`Key`] while the simulation runs.
+///
+/// Obtained from [`Simulation::attributes`](crate::Simulation::attributes), clones share the registry so it can
+/// be moved into generators and hooks, e.g. to group statistics by tag or to filter a trace. Attributes are kept
+/// after their entity completes, use [`Simulation::entity_state`](crate::Simulation::entity_state) to tell
+/// running entities apart.
+#[derive(Debug, Clone, Default)]
+pub struct EntityAttributes {
+    inner: Rc<RefCell<HashMap<Key, Attributes>>>,
+}
+
+impl EntityAttributes {
+    pub(crate) fn insert(&self, key: Key, attributes: Attributes) {
+        self.inner.borrow_mut().insert(key, attributes);
+    }
+
+    #[must_use]
+    pub fn class_of(&self, key: Key) -> Option<String> {
+        self.inner
+            .borrow()
+            .get(&key)
+            .and_then(|attributes| attributes.class.clone())
+    }
+
+    #[must_use]
+    pub fn has_tag(&self, key: Key, tag: &str) -> bool {
+        matches!(self.inner.borrow().get(&key), Some(attributes) if attributes.has_tag(tag))
+    }
+
+    /// Returns a copy of the value of type `T` attached to the entity.
+    #[must_use]
+    pub fn get<T: Clone + 'static>(&self, key: Key) -> Option<T> {
+        self.inner
+            .borrow()
+            .get(&key)
+            .and_then(|attributes| attributes.get::<T>().cloned())
+    }
+
+    /// Returns the keys of the entities tagged `tag`, sorted by id.
+    #[must_use]
+    pub fn tagged(&self, tag: &str) -> Vec<Key> {
+        self.filter(|attributes| attributes.has_tag(tag))
+    }
+
+    /// Returns the keys of the entities of class `class`, sorted by id.
+    #[must_use]
+    pub fn of_class(&self, class: &str) -> Vec<Key> {
+        self.filter(|attributes| attributes.class_name() == Some(class))
+    }
+
+    fn filter(&self, predicate: impl Fn(&Attributes) -> bool) -> Vec<Key> {
+        let mut keys: Vec<Key> = self
+            .inner
+            .borrow()
+            .iter()
+            .filter(|(_, attributes)| predicate(attributes))
+            .map(|(&key, _)| key)
+            .collect();
+        keys.sort_by_key(|key| key.id());
+        keys
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{process, Action, Simulation, TraceEventKind};
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Capacity(u32);
+
+    #[test]
+    fn attributes_are_queryable_by_key() {
+        let mut simulation = Simulation::default();
+        let trace = simulation.record_trace();
+        let hold = || process(|_| Some(Action::Hold(Duration::from_secs(1))));
+        let truck = simulation.add_generator_with(
+            hold(),
+            Attributes::new().class("vehicle").tag("truck").with(Capacity(20)),
+        );
+        let car = simulation.add_generator_with(hold(), Attributes::new().class("vehicle").tag("car"));
+        let plain = simulation.add_generator(hold());
+        for key in [truck, car, plain] {
+            simulation.schedule_now(key);
+        }
+        simulation.step_n(6);
+
+        let attributes = simulation.attributes();
+        assert_eq!(vec![truck], attributes.tagged("truck"));
+        assert_eq!(vec![truck, car], attributes.of_class("vehicle"));
+        assert_eq!(Some(Capacity(20)), attributes.get::<Capacity>(truck));
+        assert_eq!(None, attributes.get::<Capacity>(car));
+        assert_eq!(None, attributes.class_of(plain));
+
+        let truck_holds = trace
+            .events()
+            .into_iter()
+            .filter(|event| attributes.has_tag(event.key, "truck"))
+            .filter(|event| matches!(event.kind, TraceEventKind::Yielded(Action::Hold(_))))
+            .count();
+        assert_eq!(2, truck_holds);
+    }
+}
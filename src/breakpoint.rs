@@ -0,0 +1,104 @@
+use std::mem::{discriminant, Discriminant};
+use std::time::Duration;
+
+use crate::time::SimTime;
+use crate::{Action, Key};
+
+/// Where a run stops with [`RunStatus::Breakpoint`](crate::RunStatus::Breakpoint), set with
+/// [`Simulation::set_breakpoint`](crate::Simulation::set_breakpoint).
+///
+/// The simulation is left ready to resume, calling the run method again continues past the breakpoint:
+///
+/// ```ignore
+/// simulation.set_breakpoint(Breakpoint::entity(server));
+/// while let RunStatus::Breakpoint(_) = simulation.run_until_empty() {
+///     println!("{:?} {:?}", simulation.time(), simulation.entity_state(server));
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Breakpoint {
+    /// Stop before executing the first event at or after this time. It's hit once and then removed.
+    Time(Duration),
+    /// Stop every time this entity is about to be resumed.
+    Entity(Key),
+    /// Stop every time an entity yields an action of this variant, after it's carried out.
+    Action(Discriminant<Action>),
+}
+
+impl Breakpoint {
+    /// Stop once simulated time reaches `time`.
+    #[must_use]
+    pub fn time(time: impl SimTime) -> Self {
+        Self::Time(time.to_duration())
+    }
+
+    /// Stop every time `key` is about to be resumed.
+    #[must_use]
+    pub fn entity(key: Key) -> Self {
+        Self::Entity(key)
+    }
+
+    /// Stop every time an action of the same variant as `action` is yielded, whatever its arguments, e.g.
+    /// `Breakpoint::action(&Action::Passivate)`.
+    #[must_use]
+    pub fn action(action: &Action) -> Self {
+        Self::Action(discriminant(action))
+    }
+
+    // Returns `true` if the breakpoint stops the run before the event of `key` at `time`.
+    pub(crate) fn hit_before(&self, time: Duration, key: Key) -> bool {
+        match *self {
+            Self::Time(at) => time >= at,
+            Self::Entity(entity) => entity == key,
+            Self::Action(_) => false,
+        }
+    }
+
+    // Returns `true` if the breakpoint stops the run after `action` was yielded.
+    pub(crate) fn hit_after(&self, action: &Action) -> bool {
+        matches!(*self, Self::Action(variant) if variant == discriminant(action))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{process, RunStatus, Simulation};
+
+    #[test]
+    fn runs_stop_at_breakpoints() {
+        let mut simulation = Simulation::default();
+        let mut holds = 0;
+        let worker = simulation.add_generator(process(move |_| {
+            holds += 1;
+            match holds {
+                1..=3 => Some(Action::Hold(Duration::from_secs(2))),
+                4 => Some(Action::Passivate),
+                _ => None,
+            }
+        }));
+        let waker = simulation.add_generator(process(move |_| None));
+        simulation.schedule_now(worker);
+        simulation.schedule(Duration::from_secs(10), waker);
+        simulation.set_breakpoint(Breakpoint::time(Duration::from_secs(3)));
+        simulation.set_breakpoint(Breakpoint::entity(waker));
+        simulation.set_breakpoint(Breakpoint::action(&Action::Passivate));
+
+        // Before the event at 4s, the first at or after 3s.
+        let time = Breakpoint::Time(Duration::from_secs(3));
+        assert_eq!(RunStatus::Breakpoint(time), simulation.run_until_empty());
+        assert_eq!(Duration::from_secs(2), simulation.time());
+        assert!(!simulation.breakpoints().contains(&time));
+
+        assert_eq!(RunStatus::Breakpoint(Breakpoint::action(&Action::Passivate)), simulation.run_until_empty());
+        assert_eq!(Duration::from_secs(6), simulation.time());
+
+        assert_eq!(RunStatus::Breakpoint(Breakpoint::Entity(waker)), simulation.run_until_empty());
+        assert_eq!(Duration::from_secs(6), simulation.time());
+        assert_eq!(Some(Duration::from_secs(10)), simulation.next_event_time());
+
+        // Resuming executes the event the run stopped before.
+        assert_eq!(RunStatus::Deadlocked, simulation.run_until_empty());
+        assert_eq!(Duration::from_secs(10), simulation.time());
+    }
+}
@@ -0,0 +1,285 @@
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::{process, Action, GenBoxed, Key};
+
+/// Configuration of a server that processes entities in batches, like an oven or an autoclave.
+///
+/// A batch starts as soon as `min` entities are waiting, taking at most `max` of them.
+/// With a timeout, a batch also starts with fewer than `min` entities once the timeout expires
+/// after the server started collecting.
+/// Installed with [`Simulation::add_bulk_server`](crate::Simulation::add_bulk_server).
+pub struct BulkService {
+    min: usize,
+    max: usize,
+    timeout: Option<Duration>,
+    service_time: Box<dyn FnMut(usize) -> Duration>,
+}
+
+impl BulkService {
+    /// `service_time` receives the size of the batch and must return a positive duration.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `min` is zero or greater than `max`.
+    pub fn new(min: usize, max: usize, service_time: impl FnMut(usize) -> Duration + 'static) -> Self {
+        assert!(
+            min > 0 && min <= max,
+            "Invalid batch sizes: min = {}, max = {}",
+            min,
+            max
+        );
+        Self {
+            min,
+            max,
+            timeout: None,
+            service_time: Box::new(service_time),
+        }
+    }
+
+    /// Start a batch with fewer than `min` entities if they waited `timeout`.
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    // Passive, waiting for the first entity.
+    Idle,
+    // Activated by the first entity, will decide whether to start a batch or collect when resumed.
+    Waking,
+    // Waiting for `min` entities, holding for the timeout if there is one, passive otherwise.
+    Collecting,
+    // About to start a batch.
+    Starting,
+    Busy,
+}
+
+// What the server generator has to do when resumed, before looking at its mode.
+enum Resume {
+    Loop,
+    TimeoutOver,
+    ServiceOver(Vec<Key>),
+    BatchReleased,
+}
+
+struct Inner {
+    min: usize,
+    has_timeout: bool,
+    mode: Mode,
+    waiting: VecDeque<Key>,
+    batch_sizes: Vec<usize>,
+}
+
+/// Handle to a bulk server installed in a simulation.
+///
+/// Entities join the server and are activated once the batch they were served in completes:
+///
+/// ```ignore
+/// for action in oven.join() {
+///     yield action;
+/// }
+/// // The batch containing this entity has been processed.
+/// ```
+#[derive(Clone)]
+pub struct BulkServer {
+    key: Key,
+    inner: Rc<RefCell<Inner>>,
+    current: Rc<Cell<Option<Key>>>,
+}
+
+impl BulkServer {
+    pub(crate) fn new<R: 'static>(
+        service: BulkService,
+        key: Key,
+        current: Rc<Cell<Option<Key>>>,
+    ) -> (Self, GenBoxed<R>) {
+        let inner = Rc::new(RefCell::new(Inner {
+            min: service.min,
+            has_timeout: service.timeout.is_some(),
+            mode: Mode::Idle,
+            waiting: VecDeque::new(),
+            batch_sizes: Vec::new(),
+        }));
+        let server = Self { key, inner, current };
+        let gen = server.generator(service);
+        (server, gen)
+    }
+
+    /// Enqueue the entity currently being executed.
+    ///
+    /// Returns the actions the entity has to yield, in order. The last one is always `Action::Passivate`,
+    /// the entity is activated again once its batch has been served.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of an entity.
+    #[must_use]
+    pub fn join(&self) -> Vec<Action> {
+        let key = self
+            .current
+            .get()
+            .expect("bulk servers can only be joined from inside an entity");
+        let mut inner = self.inner.borrow_mut();
+        inner.waiting.push_back(key);
+        let enough = inner.waiting.len() >= inner.min;
+        match inner.mode {
+            Mode::Idle => {
+                inner.mode = Mode::Waking;
+                vec![Action::ActivateOne(self.key), Action::Passivate]
+            }
+            Mode::Collecting if enough => {
+                inner.mode = Mode::Starting;
+                if inner.has_timeout {
+                    // The server is holding for the timeout, interrupt it before activating it.
+                    vec![Action::Cancel(self.key), Action::ActivateOne(self.key), Action::Passivate]
+                } else {
+                    vec![Action::ActivateOne(self.key), Action::Passivate]
+                }
+            }
+            _ => vec![Action::Passivate],
+        }
+    }
+
+    /// Returns the key of the server entity.
+    #[must_use]
+    pub fn key(&self) -> Key {
+        self.key
+    }
+
+    /// Returns the number of entities waiting for a batch.
+    #[must_use]
+    pub fn queue_len(&self) -> usize {
+        self.inner.borrow().waiting.len()
+    }
+
+    /// Returns the size of every batch started so far.
+    #[must_use]
+    pub fn batch_sizes(&self) -> Vec<usize> {
+        self.inner.borrow().batch_sizes.clone()
+    }
+
+    /// Returns the number of entities served or being served.
+    #[must_use]
+    pub fn served(&self) -> usize {
+        self.inner.borrow().batch_sizes.iter().sum()
+    }
+
+    fn generator<R: 'static>(&self, mut service: BulkService) -> GenBoxed<R> {
+        let shared = Rc::clone(&self.inner);
+        let mut resume = Resume::Loop;
+        // The mode is always updated in the same resume as the action that follows it,
+        // so joining entities never see a mode the server isn't actually in.
+        process(move |_| loop {
+            match std::mem::replace(&mut resume, Resume::Loop) {
+                Resume::Loop => {}
+                Resume::TimeoutOver => {
+                    // If the hold wasn't cancelled the timeout expired, serve whoever is waiting.
+                    let mut inner = shared.borrow_mut();
+                    if inner.mode == Mode::Collecting {
+                        inner.mode = Mode::Starting;
+                    }
+                }
+                Resume::ServiceOver(batch) => {
+                    resume = Resume::BatchReleased;
+                    return Some(Action::ActivateMany(batch));
+                }
+                Resume::BatchReleased => {
+                    let mut inner = shared.borrow_mut();
+                    inner.mode = if inner.waiting.is_empty() {
+                        Mode::Idle
+                    } else if inner.waiting.len() >= service.min {
+                        Mode::Starting
+                    } else {
+                        Mode::Waking
+                    };
+                }
+            }
+
+            let mode = shared.borrow().mode;
+            match mode {
+                Mode::Idle => return Some(Action::Passivate),
+                Mode::Waking => {
+                    let enough = shared.borrow().waiting.len() >= service.min;
+                    if enough {
+                        shared.borrow_mut().mode = Mode::Starting;
+                        continue;
+                    }
+                    shared.borrow_mut().mode = Mode::Collecting;
+                    if let Some(timeout) = service.timeout {
+                        resume = Resume::TimeoutOver;
+                        return Some(Action::Hold(timeout));
+                    }
+                    // Activated by the entity that completes the batch.
+                    return Some(Action::Passivate);
+                }
+                Mode::Starting => {
+                    let batch: Vec<Key> = {
+                        let mut inner = shared.borrow_mut();
+                        inner.mode = Mode::Busy;
+                        let size = inner.waiting.len().min(service.max);
+                        inner.batch_sizes.push(size);
+                        inner.waiting.drain(..size).collect()
+                    };
+                    let service_time = (service.service_time)(batch.len());
+                    resume = Resume::ServiceOver(batch);
+                    return Some(Action::Hold(service_time));
+                }
+                Mode::Collecting | Mode::Busy => {
+                    unreachable!("the server leaves {:?} before the next iteration", mode)
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{process, Simulation};
+
+    fn job(oven: BulkServer, arrival: u64, done: Rc<RefCell<Vec<(u64, Duration)>>>, clock: crate::scheduler::ClockRef) -> GenBoxed<()> {
+        let mut step = 0;
+        let mut joining = Vec::new().into_iter();
+        process(move |_| {
+            step += 1;
+            if step == 1 {
+                return Some(Action::Hold(Duration::from_secs(arrival)));
+            }
+            if step == 2 {
+                joining = oven.join().into_iter();
+            }
+            if let Some(action) = joining.next() {
+                return Some(action);
+            }
+            done.borrow_mut().push((arrival, clock.time()));
+            None
+        })
+    }
+
+    #[test]
+    fn batches_start_when_full_or_on_timeout() {
+        let mut simulation = Simulation::default();
+        let service = BulkService::new(3, 4, |_| Duration::from_secs(10)).timeout(Duration::from_secs(5));
+        let oven = simulation.add_bulk_server("oven", service);
+        let done = Rc::new(RefCell::new(Vec::new()));
+        // Three jobs fill a batch at t = 2, the next two only get served when the timeout expires.
+        for arrival in [0, 1, 2, 3, 4] {
+            let key = simulation.add_generator(job(oven.clone(), arrival, Rc::clone(&done), simulation.clock()));
+            simulation.schedule_now(key);
+        }
+        simulation.run_until_empty();
+
+        assert_eq!(vec![3, 2], oven.batch_sizes());
+        assert_eq!(5, oven.served());
+        let mut done = done.borrow().clone();
+        done.sort();
+        let secs = |secs| Duration::from_secs(secs);
+        assert_eq!(vec![(0, secs(12)), (1, secs(12)), (2, secs(12)), (3, secs(27)), (4, secs(27))], done);
+    }
+}
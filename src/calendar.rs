@@ -0,0 +1,202 @@
+//! Calendar time on top of the simulation clock.
+//!
+//! A [`Calendar`] anchors time zero of the clock to an epoch, so a model specified in real dates can be
+//! scheduled with [`Simulation::schedule_at_datetime`](crate::Simulation::schedule_at_datetime) and its
+//! trace exported with dates through [`TraceRecorder::to_csv_with_calendar`](crate::TraceRecorder::to_csv_with_calendar).
+//! Working days and shifts, Monday to Friday all day long by default, drive the business-time helpers:
+//!
+//! ```ignore
+//! let epoch = FixedOffset::east_opt(3600).unwrap().with_ymd_and_hms(2026, 1, 5, 0, 0, 0).unwrap();
+//! let calendar = Calendar::new(epoch).with_shift(NaiveTime::from_hms_opt(8, 0, 0).unwrap(), NaiveTime::from_hms_opt(16, 0, 0).unwrap());
+//! simulation.set_calendar(calendar);
+//! simulation.schedule_at_datetime(epoch + TimeDelta::days(2), truck);
+//! ```
+
+use std::time::Duration;
+
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, TimeZone, Weekday};
+
+/// Maps the simulation clock to dates, see the [module documentation](self).
+///
+/// Dates are returned in the offset of the epoch, which is also the offset working days and shifts are
+/// evaluated in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Calendar {
+    epoch: DateTime<FixedOffset>,
+    // Indexed by the number of days from Monday.
+    working_days: [bool; 7],
+    // `None` if the whole day is worked.
+    shift: Option<(NaiveTime, NaiveTime)>,
+}
+
+impl Calendar {
+    /// Create a calendar where time zero of the clock is `epoch`.
+    #[must_use]
+    pub fn new<Tz: TimeZone>(epoch: DateTime<Tz>) -> Self {
+        Self {
+            epoch: epoch.fixed_offset(),
+            working_days: [true, true, true, true, true, false, false],
+            shift: None,
+        }
+    }
+
+    /// Set the working days, replacing the default Monday to Friday.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `days` is empty.
+    #[must_use]
+    pub fn with_working_days(mut self, days: impl IntoIterator<Item = Weekday>) -> Self {
+        self.working_days = [false; 7];
+        for day in days {
+            self.working_days[day.num_days_from_monday() as usize] = true;
+        }
+        assert!(self.working_days.contains(&true), "a calendar needs at least one working day");
+        self
+    }
+
+    /// Restrict working time to the shift from `start` to `end` of every working day.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `start` isn't before `end`, shifts can't span midnight.
+    #[must_use]
+    pub fn with_shift(mut self, start: NaiveTime, end: NaiveTime) -> Self {
+        assert!(start < end, "a shift must start before it ends");
+        self.shift = Some((start, end));
+        self
+    }
+
+    #[must_use]
+    pub fn epoch(&self) -> DateTime<FixedOffset> {
+        self.epoch
+    }
+
+    /// Returns the date at simulation time `time`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the date is out of the range of [`DateTime`].
+    #[must_use]
+    pub fn datetime(&self, time: Duration) -> DateTime<FixedOffset> {
+        TimeDelta::from_std(time)
+            .ok()
+            .and_then(|delta| self.epoch.checked_add_signed(delta))
+            .expect("simulation time out of the range of the calendar")
+    }
+
+    /// Returns the simulation time at `datetime`, `None` if it's before the epoch.
+    #[must_use]
+    pub fn time_of<Tz: TimeZone>(&self, datetime: &DateTime<Tz>) -> Option<Duration> {
+        datetime.fixed_offset().signed_duration_since(self.epoch).to_std().ok()
+    }
+
+    /// Format the date at simulation time `time`, with the syntax of [`chrono::format::strftime`].
+    #[must_use]
+    pub fn format(&self, time: Duration, format: &str) -> String {
+        self.datetime(time).format(format).to_string()
+    }
+
+    #[must_use]
+    pub fn is_working_day(&self, date: NaiveDate) -> bool {
+        self.working_days[date.weekday().num_days_from_monday() as usize]
+    }
+
+    /// Returns whether simulation time `time` falls in a shift of a working day.
+    #[must_use]
+    pub fn is_working_time(&self, time: Duration) -> bool {
+        let datetime = self.datetime(time).naive_local();
+        let (start, end) = self.shift_of(datetime.date());
+        self.is_working_day(datetime.date()) && start <= datetime && datetime < end
+    }
+
+    /// Returns the first working simulation time at or after `time`.
+    #[must_use]
+    pub fn next_working_time(&self, time: Duration) -> Duration {
+        let datetime = self.datetime(time).naive_local();
+        let mut date = datetime.date();
+        loop {
+            let (start, end) = self.shift_of(date);
+            if self.is_working_day(date) && datetime < end {
+                return self.time_of_local(start.max(datetime));
+            }
+            date = date.succ_opt().expect("simulation time out of the range of the calendar");
+        }
+    }
+
+    /// Returns the simulation time `days` working days after `time`, at the same time of the day.
+    #[must_use]
+    pub fn add_business_days(&self, time: Duration, days: u32) -> Duration {
+        let mut datetime = self.datetime(time).naive_local();
+        let mut remaining = days;
+        while remaining > 0 {
+            datetime += TimeDelta::days(1);
+            if self.is_working_day(datetime.date()) {
+                remaining -= 1;
+            }
+        }
+        self.time_of_local(datetime)
+    }
+
+    // Start and end of the shift of `date`, in local time.
+    fn shift_of(&self, date: NaiveDate) -> (NaiveDateTime, NaiveDateTime) {
+        match self.shift {
+            Some((start, end)) => (date.and_time(start), date.and_time(end)),
+            None => (
+                date.and_time(NaiveTime::MIN),
+                (date + TimeDelta::days(1)).and_time(NaiveTime::MIN),
+            ),
+        }
+    }
+
+    fn time_of_local(&self, datetime: NaiveDateTime) -> Duration {
+        let datetime = datetime
+            .and_local_timezone(self.epoch.timezone())
+            .single()
+            .expect("A fixed offset maps every local time once.");
+        self.time_of(&datetime).expect("Never before the epoch.")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{process, Simulation};
+
+    fn hours(hours: u64) -> Duration {
+        Duration::from_secs(hours * 3600)
+    }
+
+    #[test]
+    fn clock_maps_to_dates_and_shifts() {
+        // Friday 2026-01-02 at 08:00, with shifts from 09:00 to 17:00.
+        let epoch = FixedOffset::east_opt(3600)
+            .unwrap()
+            .with_ymd_and_hms(2026, 1, 2, 8, 0, 0)
+            .unwrap();
+        let calendar = Calendar::new(epoch).with_shift(
+            NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+            NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+        );
+
+        assert!(!calendar.is_working_time(Duration::ZERO));
+        assert_eq!(hours(1), calendar.next_working_time(Duration::ZERO));
+        assert_eq!(hours(2), calendar.next_working_time(hours(2)));
+        // Friday at 17:00 moves to Monday at 09:00.
+        assert_eq!(hours(73), calendar.next_working_time(hours(9)));
+        assert_eq!(hours(74), calendar.add_business_days(hours(2), 1));
+        assert_eq!(None, calendar.time_of(&(epoch - TimeDelta::seconds(1))));
+        assert_eq!("2026-01-05 09:00", calendar.format(hours(73), "%Y-%m-%d %H:%M"));
+
+        let mut simulation = Simulation::default();
+        let trace = simulation.record_trace();
+        simulation.set_calendar(calendar);
+        let key = simulation.add_generator(process(|_| None));
+        simulation.schedule_at_datetime(epoch + TimeDelta::hours(1), key);
+        simulation.run_until_empty();
+
+        assert_eq!(Some(epoch + TimeDelta::hours(1)), simulation.datetime());
+        let csv = trace.to_csv_with_calendar(simulation.calendar().unwrap());
+        assert!(csv.contains("\n2026-01-02T09:00:00+01:00,0,Completed,"));
+    }
+}
@@ -0,0 +1,114 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::Key;
+
+/// What an [`Action::Cancel`](crate::Action::Cancel) did to its target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CancelOutcome {
+    /// The event of the target was removed, it's now passive.
+    Cancelled,
+    /// The target had no pending event: it was passive, already completed or failed.
+    NotScheduled,
+}
+
+/// The outcome of the last [`Action::Cancel`](crate::Action::Cancel) of each entity.
+///
+/// Obtained from [`Simulation::cancellations`](crate::Simulation::cancellations), clones share the record.
+/// Cancelling an entity that isn't scheduled isn't an error, as it's the normal outcome of races like a
+/// timeout firing just as the work completes. The canceller is resumed right away either way and can look up
+/// what happened, the record is dropped when it yields again:
+///
+/// ```ignore
+/// let cancellations = simulation.cancellations();
+/// simulation.add_generator(Box::new(move |_| {
+///     yield Action::Hold(timeout);
+///     yield Action::Cancel(worker);
+///     if cancellations.outcome() == Some(CancelOutcome::NotScheduled) {
+///         // The work finished in time.
+///     }
+/// }));
+/// ```
+#[derive(Clone)]
+pub struct Cancellations {
+    outcomes: Rc<RefCell<HashMap<Key, CancelOutcome>>>,
+    current: Rc<Cell<Option<Key>>>,
+}
+
+impl Cancellations {
+    pub(crate) fn new(current: Rc<Cell<Option<Key>>>) -> Self {
+        Self {
+            outcomes: Rc::default(),
+            current,
+        }
+    }
+
+    pub(crate) fn insert(&self, key: Key, outcome: CancelOutcome) {
+        self.outcomes.borrow_mut().insert(key, outcome);
+    }
+
+    pub(crate) fn clear(&self, key: Key) {
+        self.outcomes.borrow_mut().remove(&key);
+    }
+
+    /// Returns the outcome of the cancel the entity currently being executed just yielded, `None` if it
+    /// didn't yield one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of an entity.
+    #[must_use]
+    pub fn outcome(&self) -> Option<CancelOutcome> {
+        let key = self.current.get().expect("Cancellations::outcome called outside of an entity");
+        self.outcome_of(key)
+    }
+
+    /// Returns the outcome of the last cancel yielded by `key`, until it yields again.
+    #[must_use]
+    pub fn outcome_of(&self, key: Key) -> Option<CancelOutcome> {
+        self.outcomes.borrow().get(&key).copied()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    use super::CancelOutcome;
+    use crate::{process, Action, RunStatus, Simulation};
+
+    #[test]
+    fn cancellers_learn_the_outcome() {
+        let mut simulation = Simulation::default();
+        let cancellations = simulation.cancellations();
+        let worker = simulation.add_generator(process(|_| Some(Action::Hold(Duration::from_secs(5)))));
+        let mut holds = 0;
+        let quick = simulation.add_generator(process(move |_| {
+            holds += 1;
+            (holds == 1).then_some(Action::Hold(Duration::from_secs(1)))
+        }));
+        let outcomes = Rc::new(RefCell::new(Vec::new()));
+        let record = Rc::clone(&outcomes);
+        let mut targets = vec![quick, worker, worker];
+        let timeout = simulation.add_generator(process(move |_| {
+            if let Some(outcome) = cancellations.outcome() {
+                record.borrow_mut().push(outcome);
+            }
+            targets.pop().map(Action::Cancel)
+        }));
+        simulation.schedule_now(worker);
+        simulation.schedule_now(quick);
+        simulation.schedule(Duration::from_secs(2), timeout);
+
+        // The worker is cancelled, then passive, and the quick entity already completed.
+        assert_eq!(RunStatus::Deadlocked, simulation.run_until_empty());
+        assert_eq!(
+            vec![CancelOutcome::Cancelled, CancelOutcome::NotScheduled, CancelOutcome::NotScheduled],
+            *outcomes.borrow()
+        );
+        assert!(simulation.cancellations().outcome_of(timeout).is_none());
+    }
+}
@@ -0,0 +1,294 @@
+//! Checkpoints of a run, restored by deterministic re-execution.
+//!
+//! Generators can't be serialized, so a [`Checkpoint`] holds the [`RunMetadata`] the model was built from
+//! and every decision taken so far: the time, the entity and the action of each step. Restoring rebuilds the
+//! model from the metadata and replays as many steps, checking that every decision matches:
+//!
+//! ```ignore
+//! let checkpoints = simulation.record_checkpoints();
+//! simulation.run_for(Duration::from_secs(3600));
+//! checkpoints.checkpoint().write("run.checkpoint")?;
+//!
+//! // Later, in another process.
+//! let checkpoint = Checkpoint::read("run.checkpoint")?;
+//! let mut simulation = build_model(checkpoint.metadata().seed().unwrap());
+//! let checkpoints = simulation.restore(&checkpoint)?;
+//! simulation.run_until_empty();
+//! ```
+
+use std::cell::RefCell;
+use std::fmt::{self, Write as _};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::csv;
+use crate::metadata::RunMetadata;
+use crate::{Action, Key};
+
+/// What an entity did in a step: the action it yielded or its completion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decision {
+    pub time: Duration,
+    /// The id of the entity.
+    pub entity: usize,
+    /// The action, e.g. `hold 5000000` (in nanoseconds), `activate 3 4` or `complete`.
+    pub action: String,
+}
+
+impl Decision {
+    pub(crate) fn yielded(time: Duration, key: Key, action: &Action) -> Self {
+        let action = match action {
+            Action::Hold(duration) => format!("hold {}", duration.as_nanos()),
+            Action::HoldWithPriority(duration, priority) => {
+                format!("hold {} priority {}", duration.as_nanos(), priority)
+            }
+            Action::Passivate => "passivate".to_owned(),
+            Action::ActivateOne(other) => format!("activate {}", other.id()),
+            Action::ActivateMany(others) => {
+                let others: Vec<String> = others.iter().map(|other| other.id().to_string()).collect();
+                format!("activate {}", others.join(" "))
+            }
+            Action::Cancel(other) => format!("cancel {}", other.id()),
+            Action::Preempt(other) => format!("preempt {}", other.id()),
+            Action::Broadcast(topic, payload) => format!("broadcast {:?} {:?}", topic, payload),
+            Action::Terminate(other) => format!("terminate {}", other.id()),
+        };
+        Self {
+            time,
+            entity: key.id(),
+            action,
+        }
+    }
+
+    pub(crate) fn completed(time: Duration, key: Key) -> Self {
+        Self {
+            time,
+            entity: key.id(),
+            action: "complete".to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} entity {}: {}", self.time, self.entity, self.action)
+    }
+}
+
+/// Error produced while reading or restoring a [`Checkpoint`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckpointError {
+    /// The checkpoint text is malformed at `line`, starting at 1.
+    Parse { line: usize, message: String },
+    /// The model took another decision at `step`, so it isn't the model that produced the checkpoint.
+    Diverged {
+        step: usize,
+        expected: Decision,
+        found: Decision,
+    },
+    /// The model ran out of events at `step`, before every decision was replayed.
+    Ended { step: usize },
+}
+
+impl fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            Self::Diverged { step, expected, found } => {
+                write!(f, "step {} diverged, expected {} but found {}", step, expected, found)
+            }
+            Self::Ended { step } => write!(f, "the model ran out of events at step {}", step),
+        }
+    }
+}
+
+impl std::error::Error for CheckpointError {}
+
+/// The configuration and decisions of a run up to some point, see the [module documentation](self).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Checkpoint {
+    metadata: RunMetadata,
+    decisions: Vec<Decision>,
+}
+
+impl Checkpoint {
+    /// Returns the metadata of the run, used to build the model again.
+    #[must_use]
+    pub fn metadata(&self) -> &RunMetadata {
+        &self.metadata
+    }
+
+    /// Returns every decision, in execution order.
+    #[must_use]
+    pub fn decisions(&self) -> &[Decision] {
+        &self.decisions
+    }
+
+    /// Returns the simulation time of the last decision.
+    #[must_use]
+    pub fn time(&self) -> Duration {
+        self.decisions.last().map_or(Duration::ZERO, |decision| decision.time)
+    }
+
+    /// Returns an earlier checkpoint, keeping the decisions taken up to `time`.
+    #[must_use]
+    pub fn until(&self, time: Duration) -> Self {
+        Self {
+            metadata: self.metadata.clone(),
+            decisions: self
+                .decisions
+                .iter()
+                .take_while(|decision| decision.time <= time)
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Returns the checkpoint as text: the metadata as `# name: value` lines followed by one line per decision.
+    #[must_use]
+    pub fn to_text(&self) -> String {
+        let mut output = csv::metadata_comments(&self.metadata);
+        output.push_str("time_ns,entity,action\n");
+        for decision in &self.decisions {
+            let _ = writeln!(output, "{},{},{}", decision.time.as_nanos(), decision.entity, decision.action);
+        }
+        output
+    }
+
+    /// Parse a checkpoint written by [`Checkpoint::to_text`].
+    pub fn parse(text: &str) -> Result<Self, CheckpointError> {
+        let mut checkpoint = Self::default();
+        for (index, line) in text.lines().enumerate() {
+            let error = |message: String| CheckpointError::Parse {
+                line: index + 1,
+                message,
+            };
+            if let Some(comment) = line.strip_prefix("# ") {
+                let (name, value) = comment
+                    .split_once(": ")
+                    .ok_or_else(|| error(format!("invalid metadata `{}`", comment)))?;
+                checkpoint.metadata.set_entry(name, value).map_err(error)?;
+                continue;
+            }
+            if line.is_empty() || line == "time_ns,entity,action" {
+                continue;
+            }
+            let mut fields = line.splitn(3, ',');
+            let (Some(time), Some(entity), Some(action)) = (fields.next(), fields.next(), fields.next()) else {
+                return Err(error(format!("invalid decision `{}`", line)));
+            };
+            let time: u64 = time.parse().map_err(|_| error(format!("invalid time `{}`", time)))?;
+            checkpoint.decisions.push(Decision {
+                time: Duration::from_nanos(time),
+                entity: entity.parse().map_err(|_| error(format!("invalid entity `{}`", entity)))?,
+                action: action.to_owned(),
+            });
+        }
+        Ok(checkpoint)
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_text())
+    }
+
+    /// Read a checkpoint written by [`Checkpoint::write`].
+    pub fn read(path: impl AsRef<Path>) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        Self::parse(&text).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+}
+
+/// Records the decisions of a run so [checkpoints](Checkpoint) can be taken at any time.
+///
+/// Created with [`Simulation::record_checkpoints`](crate::Simulation::record_checkpoints).
+#[derive(Clone)]
+pub struct CheckpointRecorder {
+    decisions: Rc<RefCell<Vec<Decision>>>,
+    metadata: Rc<RefCell<RunMetadata>>,
+}
+
+impl CheckpointRecorder {
+    pub(crate) fn new(metadata: Rc<RefCell<RunMetadata>>) -> Self {
+        Self {
+            decisions: Rc::default(),
+            metadata,
+        }
+    }
+
+    pub(crate) fn record(&self, decision: Decision) {
+        self.decisions.borrow_mut().push(decision);
+    }
+
+    pub(crate) fn last(&self) -> Option<Decision> {
+        self.decisions.borrow().last().cloned()
+    }
+
+    /// Returns a checkpoint of the run up to the last executed step.
+    #[must_use]
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            metadata: self.metadata.borrow().clone(),
+            decisions: self.decisions.borrow().clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::distributions::{Distribution, Exponential};
+    use crate::{process, GenBoxed, Simulation};
+
+    fn customer(rng: crate::SimRng) -> GenBoxed<()> {
+        let service = Exponential::new(Duration::from_secs(2));
+        let mut services = 0;
+        process(move |_| {
+            if services == 5 {
+                return None;
+            }
+            services += 1;
+            Some(Action::Hold(service.sample(&rng)))
+        })
+    }
+
+    fn build(seed: u64) -> Simulation<()> {
+        let mut simulation = Simulation::default();
+        simulation.set_seed(seed);
+        for _ in 0..2 {
+            let key = simulation.add_generator(customer(simulation.rng()));
+            simulation.schedule_now(key);
+        }
+        simulation
+    }
+
+    #[test]
+    fn restored_runs_continue_identically() {
+        let mut original = build(11);
+        let checkpoints = original.record_checkpoints();
+        original.step_n(5);
+        let text = checkpoints.checkpoint().to_text();
+        original.run_until_empty();
+        let full = checkpoints.checkpoint();
+
+        let checkpoint = Checkpoint::parse(&text).unwrap();
+        assert_eq!(Some(11), checkpoint.metadata().seed());
+        assert_eq!(5, checkpoint.decisions().len());
+        let mut restored = build(checkpoint.metadata().seed().unwrap());
+        let recorder = restored.restore(&checkpoint).unwrap();
+        assert_eq!(checkpoint.time(), restored.time());
+        restored.run_until_empty();
+        assert_eq!(full, recorder.checkpoint());
+
+        let earlier = full.until(Duration::ZERO);
+        assert_eq!(2, earlier.decisions().len());
+        assert!(build(11).restore(&earlier).is_ok());
+
+        match build(12).restore(&checkpoint) {
+            Err(CheckpointError::Diverged { step, .. }) => assert_eq!(0, step),
+            other => panic!("unexpected restore result {:?}", other.err()),
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use crate::Key;
+
+/// The role a [`Component`] plays in a model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ComponentKind {
+    Entity,
+    Resource,
+    Channel,
+    Source,
+    Collector,
+}
+
+/// Description of a part of a model registered in the simulation.
+///
+/// Returned by [`Simulation::components`](crate::Simulation::components) so generic tooling
+/// can inspect any model without model-specific code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Component {
+    name: String,
+    kind: ComponentKind,
+    type_name: &'static str,
+    key: Option<Key>,
+}
+
+impl Component {
+    pub(crate) fn new(name: String, kind: ComponentKind, type_name: &'static str, key: Option<Key>) -> Self {
+        Self {
+            name,
+            kind,
+            type_name,
+            key,
+        }
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[must_use]
+    pub fn kind(&self) -> ComponentKind {
+        self.kind
+    }
+
+    /// Returns the name of the Rust type backing this component.
+    #[must_use]
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    /// Returns the [`Key`] of the component if it is an entity.
+    #[must_use]
+    pub fn key(&self) -> Option<Key> {
+        self.key
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{process, Action, Resource, Simulation, Tally};
+
+    #[test]
+    fn components_list_the_registered_parts_then_the_entities() {
+        let mut simulation = Simulation::default();
+        simulation.add_resource("teller", 2);
+        simulation.add_statistic("service time", Tally::new("service time"));
+        let customer = simulation.add_generator_named("customer", process(|_| None));
+        let mut held = false;
+        let clerk = simulation.add_generator(process(move |_| {
+            held = !held;
+            held.then_some(Action::Hold(Duration::from_secs(1)))
+        }));
+
+        let components = simulation.components();
+        let listed: Vec<(&str, ComponentKind, Option<Key>)> = components
+            .iter()
+            .map(|component| (component.name(), component.kind(), component.key()))
+            .collect();
+        let clerk_name = format!("entity {}", clerk.id());
+        assert_eq!(
+            vec![
+                ("teller", ComponentKind::Resource, None),
+                ("service time", ComponentKind::Collector, None),
+                ("customer", ComponentKind::Entity, Some(customer)),
+                (clerk_name.as_str(), ComponentKind::Entity, Some(clerk)),
+            ],
+            listed
+        );
+        assert_eq!(std::any::type_name::<Resource>(), components[0].type_name());
+
+        // Entities are only listed while they are in the simulation.
+        simulation.schedule_now(customer);
+        simulation.schedule_now(clerk);
+        simulation.step_n(2);
+        let entities: Vec<Option<Key>> = simulation.components().iter().map(Component::key).skip(2).collect();
+        assert_eq!(vec![Some(clerk)], entities);
+    }
+}
@@ -1,15 +1,63 @@
-use crate::{keys::Key, Action, GenBoxed};
-use std::ops::GeneratorState;
+use crate::{keys::{Key, WeakKey}, Action, GenBoxed};
+use crate::process::GeneratorState;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::pin::Pin;
+use std::rc::Rc;
 
+/// What an entity is doing, returned by [`Simulation::entity_state`](crate::Simulation::entity_state).
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum EntityState {
+    /// Added but never scheduled.
+    Created,
+    /// Runnable: scheduled by the model, activated, preempted or currently being resumed.
+    Scheduled,
+    /// Waiting for a hold to elapse.
+    Holding,
+    /// Waiting to be activated.
     Passive,
-    Active,
+    /// Finished, or terminated with its parent.
+    Completed,
+    /// Yielded an invalid action and won't be resumed again.
+    Failed,
+}
+
+impl EntityState {
+    /// Returns `true` for the states an entity never leaves.
+    #[must_use]
+    pub fn is_terminal(self) -> bool {
+        matches!(self, Self::Completed | Self::Failed)
+    }
+}
+
+/// Hands out the slots of new entities, shared with the `Spawner` so keys can be reserved while the simulation
+/// is running.
+#[derive(Debug, Default)]
+pub(crate) struct Slots {
+    next_id: usize,
+    // Keys of the emptied slots, with their generation already bumped, reused before growing the container.
+    free: Vec<Key>,
+}
+
+impl Slots {
+    pub(crate) fn reserve(&mut self) -> Key {
+        self.free.pop().unwrap_or_else(|| {
+            let id = self.next_id;
+            self.next_id += 1;
+            Key::new(id)
+        })
+    }
 }
 
 pub struct Container<R> {
     pub(crate) inner: Vec<Option<(GenBoxed<R>, EntityState)>>,
+    // Generation of each slot, bumped when its entity is removed so old keys become stale.
+    generations: Vec<u32>,
+    // Terminal state of the last entity removed from each slot, with its generation.
+    exits: Vec<Option<(u32, EntityState)>>,
+    slots: Rc<RefCell<Slots>>,
+    parents: HashMap<Key, Key>,
+    children: HashMap<Key, Vec<Key>>,
 }
 
 impl<R> Default for Container<R>
@@ -19,6 +67,11 @@ where
     fn default() -> Self {
         Self {
             inner: Default::default(),
+            generations: Vec::default(),
+            exits: Vec::default(),
+            slots: Rc::default(),
+            parents: HashMap::default(),
+            children: HashMap::default(),
         }
     }
 }
@@ -28,11 +81,74 @@ where
     R: 'static,
 {
     pub fn add_generator(&mut self, gen: GenBoxed<R>) -> Key {
-        let key = Key::new(self.inner.len());
-        self.inner.push(Some((gen, EntityState::Active)));
+        let key = self.reserve();
+        self.insert(key, gen);
         key
     }
 
+    /// Reserve a key for a generator that will be inserted later with [`Container::insert`].
+    ///
+    /// Slots emptied by [`Container::remove`] are reused, with a new generation, before the container grows.
+    pub(crate) fn reserve(&mut self) -> Key {
+        self.slots.borrow_mut().reserve()
+    }
+
+    /// Insert `gen` in the slot of an already reserved `key`.
+    pub(crate) fn insert(&mut self, key: Key, gen: GenBoxed<R>) {
+        if key.id >= self.inner.len() {
+            self.inner.resize_with(key.id + 1, || None);
+            self.generations.resize(key.id + 1, 0);
+        }
+        self.inner[key.id] = Some((gen, EntityState::Created));
+        self.generations[key.id] = key.generation;
+    }
+
+    /// Returns the slots used to reserve new keys.
+    pub(crate) fn slots(&self) -> Rc<RefCell<Slots>> {
+        Rc::clone(&self.slots)
+    }
+
+    /// Link `child` to `parent` so it's removed alongside it by [`Container::remove_tree`].
+    pub fn set_parent(&mut self, child: Key, parent: Key) {
+        if let Some(old_parent) = self.parents.insert(child, parent) {
+            self.unlink_child(old_parent, child);
+        }
+        self.children.entry(parent).or_default().push(child);
+    }
+
+    #[must_use]
+    pub fn parent(&self, key: Key) -> Option<Key> {
+        self.parents.get(&key).copied()
+    }
+
+    #[must_use]
+    pub fn children(&self, key: Key) -> &[Key] {
+        self.children.get(&key).map_or(&[], Vec::as_slice)
+    }
+
+    /// Remove the entity defined by `key` and all of its descendants.
+    ///
+    /// Returns the keys of every entity that was removed, `key` included if it was present.
+    pub fn remove_tree(&mut self, key: Key) -> Vec<Key> {
+        let mut removed = Vec::new();
+        let mut pending = vec![key];
+        while let Some(key) = pending.pop() {
+            if let Some(children) = self.children.remove(&key) {
+                pending.extend(children);
+            }
+            if self.remove(key).is_some() {
+                removed.push(key);
+            }
+        }
+        removed
+    }
+
+    fn unlink_child(&mut self, parent: Key, child: Key) {
+        if let Some(children) = self.children.get_mut(&parent) {
+            children.retain(|&other| other != child);
+        }
+    }
+
     #[allow(dead_code)]
     pub fn remove(&mut self, key: Key) -> Option<(GenBoxed<R>, EntityState)> {
         // if self.inner.get(key.id).is_some() {
@@ -44,7 +160,64 @@ where
         // Another way of doing the above added in rust 1.62
         // self.inner.get(key.id).is_some().then_some(self.inner[key.id].take()).flatten()
 
-        self.inner.get_mut(key.id).and_then(Option::take)
+        if let Some(parent) = self.parents.remove(&key) {
+            self.unlink_child(parent, key);
+        }
+        for child in self.children.remove(&key).unwrap_or_default() {
+            self.parents.remove(&child);
+        }
+        if self.is_stale(key) {
+            return None;
+        }
+        let removed = self.inner.get_mut(key.id).and_then(Option::take);
+        if removed.is_some() {
+            // A slot whose generation would wrap around is retired, so old keys can never match again.
+            if let Some(generation) = key.generation.checked_add(1) {
+                self.generations[key.id] = generation;
+                self.slots.borrow_mut().free.push(Key::with_generation(key.id, generation));
+            } else {
+                self.generations[key.id] = key.generation.wrapping_add(1);
+            }
+        }
+        removed
+    }
+
+    /// Returns `true` if `key` refers to a slot that has been reused or emptied since the key was handed out.
+    ///
+    /// Keys reserved but not inserted yet aren't stale.
+    #[must_use]
+    pub fn is_stale(&self, key: Key) -> bool {
+        matches!(self.generations.get(key.id), Some(&generation) if generation != key.generation)
+    }
+
+    fn slot(&self, key: Key) -> Option<&(GenBoxed<R>, EntityState)> {
+        if self.is_stale(key) {
+            return None;
+        }
+        self.inner.get(key.id).and_then(Option::as_ref)
+    }
+
+    fn slot_mut(&mut self, key: Key) -> Option<&mut (GenBoxed<R>, EntityState)> {
+        if self.is_stale(key) {
+            return None;
+        }
+        self.inner.get_mut(key.id).and_then(Option::as_mut)
+    }
+
+    /// Returns a [`Key`] to the entity referenced by `weak` if it still exists in the container.
+    #[must_use]
+    pub fn upgrade(&self, weak: WeakKey) -> Option<Key> {
+        let key = Key::with_generation(weak.id, weak.generation);
+        self.slot(key).map(|_| key)
+    }
+
+    /// Returns an iterator over the keys of every entity still in the container.
+    pub fn keys(&self) -> impl Iterator<Item = Key> + '_ {
+        self.inner
+            .iter()
+            .enumerate()
+            .filter(|(_, slot)| slot.is_some())
+            .map(|(id, _)| Key::with_generation(id, self.generations[id]))
     }
 
     /// Returns the number of elements in the container.
@@ -63,16 +236,14 @@ where
     ///
     /// # Panics
     ///
-    /// Panics when the key used was for an already extracted generator
+    /// Panics when the key used was for an already extracted generator, if the key is stale
     /// or if the generator has already completed its execution.
     pub fn step_with(&mut self, key: Key, resume_with: R) -> GeneratorState<Action, ()> {
         // Esto asume que los eventos nunca son borrados.
         // TODO: Confirmar esta asumpción.
 
         let &mut (ref mut gen, _) = self
-            .inner
-            .get_mut(key.id)
-            .and_then(Option::as_mut)
+            .slot_mut(key)
             .expect("entities shouldn't be removed from the container");
 
         // gen.step(resume_with)
@@ -89,10 +260,27 @@ where
         //     None
         // }
 
-        self.inner
-            .get(key.id)
-            .and_then(Option::as_ref)
-            .map(|(_, state)| state)
+        self.slot(key).map(|(_, state)| state)
+    }
+
+    /// Returns the state of the entity, its terminal state if it was removed with [`Container::record_exit`].
+    #[must_use]
+    pub fn state_of(&self, key: Key) -> Option<EntityState> {
+        if let Some(&state) = self.get_state(key) {
+            return Some(state);
+        }
+        match self.exits.get(key.id) {
+            Some(&Some((generation, state))) if generation == key.generation => Some(state),
+            _ => None,
+        }
+    }
+
+    /// Remember that the removed entity `key` ended in `state`, until another entity of its slot ends.
+    pub(crate) fn record_exit(&mut self, key: Key, state: EntityState) {
+        if key.id >= self.exits.len() {
+            self.exits.resize(key.id + 1, None);
+        }
+        self.exits[key.id] = Some((key.generation, state));
     }
 
     #[must_use]
@@ -103,10 +291,7 @@ where
         //     None
         // }
 
-        self.inner
-            .get_mut(key.id)
-            .and_then(Option::as_mut)
-            .map(|&mut (_, ref mut state)| state)
+        self.slot_mut(key).map(|&mut (_, ref mut state)| state)
     }
 }
 
@@ -122,7 +307,174 @@ mod test {
     use std::time::Duration;
 
     use super::*;
+    use crate::process;
+    use crate::process::script;
+
+    // Completes the first time it's resumed.
+    fn idle() -> GenBoxed<()> {
+        process(|_| None)
+    }
+
+    // Never completes.
+    fn forever() -> GenBoxed<()> {
+        process(|_| Some(Action::Hold(Duration::ZERO)))
+    }
+
+    #[test]
+    fn removing_a_parent_removes_its_descendants() {
+        let mut container = Container::default();
+        let parent = container.add_generator(idle());
+        let child = container.add_generator(idle());
+        let grandchild = container.add_generator(idle());
+        let unrelated = container.add_generator(idle());
+        container.set_parent(child, parent);
+        container.set_parent(grandchild, child);
+
+        assert_eq!(Some(parent), container.parent(child));
+        assert_eq!(&[child], container.children(parent));
+
+        let mut removed = container.remove_tree(parent);
+        removed.sort_by_key(|key| key.id());
+        assert_eq!(vec![parent, child, grandchild], removed);
+        assert!(container.get_state(unrelated).is_some());
+        assert_eq!(None, container.parent(child));
+        assert_eq!(None, container.upgrade(child.downgrade()));
+    }
 
+    #[test]
+    fn weak_keys_expire_after_removal() {
+        let mut container = Container::default();
+        let key = container.add_generator(idle());
+        let weak = key.downgrade();
+        assert_eq!(Some(key), container.upgrade(weak));
+
+        container.remove(key);
+        assert_eq!(None, container.upgrade(weak));
+    }
+
+    #[test]
+    fn stale_keys_are_rejected() {
+        let mut container = Container::default();
+        let key = container.add_generator(idle());
+        let weak = key.downgrade();
+        assert_eq!(0, key.generation());
+        assert!(container.remove(key).is_some());
+
+        assert!(container.is_stale(key));
+        assert!(container.get_state(key).is_none());
+        assert!(container.upgrade(weak).is_none());
+        assert!(container.remove(key).is_none());
+
+        // A new entity in the same slot isn't reachable through the old key.
+        let reused = Key::with_generation(key.id(), 1);
+        container.insert(reused, idle());
+        assert!(container.get_state(key).is_none());
+        assert_eq!(Some(&EntityState::Created), container.get_state(reused));
+        assert_eq!(Some(reused), container.upgrade(reused.downgrade()));
+        assert_eq!(vec![reused], container.keys().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn simulation_tracks_entity_states() {
+        use crate::{Simulation, SimulationError};
+
+        let mut simulation = Simulation::default();
+        let key = simulation.add_generator(script(vec![Action::Hold(Duration::from_secs(2)), Action::Passivate]));
+        let activator = simulation.add_generator(script(vec![
+            Action::ActivateOne(key),
+            Action::Hold(Duration::from_secs(1)),
+            Action::ActivateOne(key),
+        ]));
+        assert_eq!(Some(EntityState::Created), simulation.entity_state(key));
+
+        simulation.schedule_now(key);
+        assert_eq!(Some(EntityState::Scheduled), simulation.entity_state(key));
+        let step = simulation.step().unwrap();
+        assert_eq!(Some(key), step.key());
+        assert_eq!(Some(&Action::Hold(Duration::from_secs(2))), step.action());
+        assert!(step.should_continue() && !step.entity_finished());
+        assert_eq!(Some(EntityState::Holding), simulation.entity_state(key));
+        simulation.step().unwrap();
+        assert_eq!(Some(EntityState::Passive), simulation.entity_state(key));
+
+        simulation.schedule_now(activator);
+        simulation.step().unwrap();
+        assert_eq!(Some(EntityState::Scheduled), simulation.entity_state(key));
+        let finished: Vec<bool> = (0..2).map(|_| simulation.step().unwrap().entity_finished()).collect();
+        assert!(finished.contains(&true));
+        assert_eq!(Some(EntityState::Completed), simulation.entity_state(key));
+
+        assert_eq!(
+            Err(SimulationError::StaleKey { key: activator, other: key }),
+            simulation.step()
+        );
+        assert_eq!(Some(EntityState::Failed), simulation.entity_state(activator));
+        assert!(simulation.entity_state(activator).unwrap().is_terminal());
+        assert_eq!(None, simulation.schedule_now(activator));
+        assert!(!simulation.step().unwrap().should_continue());
+    }
+
+    #[test]
+    fn removed_slots_are_reused() {
+        let mut container = Container::default();
+        let first = container.add_generator(idle());
+        let second = container.add_generator(idle());
+        container.remove(first);
+
+        let third = container.add_generator(idle());
+        assert_eq!(first.id(), third.id());
+        assert_eq!(1, third.generation());
+        assert!(container.is_stale(first));
+        assert_eq!(2, container.len());
+
+        // Creating and destroying entities doesn't grow the container.
+        for _ in 0..100 {
+            let key = container.add_generator(idle());
+            container.remove(key);
+        }
+        assert_eq!(3, container.len());
+        assert!(container.get_state(second).is_some());
+        assert!(container.get_state(third).is_some());
+    }
+
+    #[test]
+    fn killed_entities_leave_no_events_behind() {
+        use crate::{RunStatus, Simulation};
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let mut simulation = Simulation::default();
+        let completed = Rc::new(RefCell::new(Vec::new()));
+        let record = Rc::clone(&completed);
+        simulation.on_complete(move |key| record.borrow_mut().push(key));
+
+        let parent = simulation.add_generator(forever());
+        let child = simulation.add_child(parent, forever());
+        let victim = simulation.add_generator(forever());
+        simulation.schedule_now(parent);
+        simulation.schedule_now(child);
+        simulation.schedule(Duration::from_secs(5), victim);
+
+        let mut removed = simulation.kill(parent);
+        removed.sort_by_key(|key| key.id());
+        assert_eq!(vec![parent, child], removed);
+        assert_eq!(Some(EntityState::Completed), simulation.entity_state(child));
+        assert!(simulation.kill(parent).is_empty());
+
+        let killer = simulation.add_generator(script(vec![
+            Action::Hold(Duration::from_secs(1)),
+            Action::Terminate(victim),
+        ]));
+        simulation.schedule_now(killer);
+
+        // The victim never runs, and neither do the killed entities.
+        assert_eq!(RunStatus::Exhausted, simulation.run_until_empty());
+        assert_eq!(Duration::from_secs(1), simulation.time());
+        assert_eq!(Some(EntityState::Completed), simulation.entity_state(victim));
+        assert_eq!(vec![parent, victim, killer], *completed.borrow());
+    }
+
+    generator_tests! {
     fn producer(kind: &'static str) -> GenBoxed<()> {
         let gen = move |_| {
             println!("Iniciando {}", kind);
@@ -208,4 +560,5 @@ mod test {
         // This is because when a generator completes, to say, the original function end its excecution
         // The generator cannot be resumed again and it's an error to do so.
     }   
+    }
 }
@@ -0,0 +1,399 @@
+use std::cell::{Cell, RefCell};
+use std::mem;
+use std::ops::Index;
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::scheduler::ClockRef;
+use crate::{process, Action, GenBoxed, Key};
+
+/// A continuous state variable of a [`Continuous`] system, returned by [`Continuous::add_variable`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Var(usize);
+
+/// The values of the variables of a [`Continuous`] system, indexed by [`Var`], given to derivatives and
+/// crossing functions.
+#[derive(Debug, Clone, Copy)]
+pub struct Values<'a> {
+    values: &'a [f64],
+}
+
+impl Index<Var> for Values<'_> {
+    type Output = f64;
+
+    fn index(&self, var: Var) -> &f64 {
+        &self.values[var.0]
+    }
+}
+
+/// Which sign changes of a crossing function trigger a [`Crossing`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrossingDirection {
+    /// From negative to zero or positive.
+    Rising,
+    /// From positive to zero or negative.
+    Falling,
+    Either,
+}
+
+impl CrossingDirection {
+    fn crossed(self, before: f64, after: f64) -> bool {
+        let rising = before < 0.0 && after >= 0.0;
+        let falling = before > 0.0 && after <= 0.0;
+        match self {
+            CrossingDirection::Rising => rising,
+            CrossingDirection::Falling => falling,
+            CrossingDirection::Either => rising || falling,
+        }
+    }
+}
+
+type Function = Box<dyn Fn(&Values) -> f64>;
+
+struct CrossingInner {
+    function: Function,
+    direction: CrossingDirection,
+    // Value of the function at the last check, `None` before the first one.
+    last: Option<f64>,
+    waiting: Vec<Key>,
+    occurrences: u64,
+    last_time: Option<Duration>,
+}
+
+struct Inner {
+    names: Vec<String>,
+    values: Vec<f64>,
+    derivatives: Vec<Option<Function>>,
+    // Time up to which `values` are integrated.
+    time: Duration,
+    max_step: Duration,
+    crossings: Vec<Rc<RefCell<CrossingInner>>>,
+}
+
+impl Inner {
+    fn derivatives(&self, values: &[f64]) -> Vec<f64> {
+        let values = Values { values };
+        self.derivatives
+            .iter()
+            .map(|derivative| derivative.as_ref().map_or(0.0, |derivative| derivative(&values)))
+            .collect()
+    }
+
+    // One step of the classic fourth order Runge-Kutta method.
+    fn step(&self, values: &[f64], dt: f64) -> Vec<f64> {
+        let shifted = |k: &[f64], factor: f64| -> Vec<f64> {
+            values.iter().zip(k).map(|(value, k)| value + factor * k).collect()
+        };
+        let k1 = self.derivatives(values);
+        let k2 = self.derivatives(&shifted(&k1, dt / 2.0));
+        let k3 = self.derivatives(&shifted(&k2, dt / 2.0));
+        let k4 = self.derivatives(&shifted(&k3, dt));
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| value + dt / 6.0 * (k1[i] + 2.0 * k2[i] + 2.0 * k3[i] + k4[i]))
+            .collect()
+    }
+
+    // Integrate `values` over `span` in steps of at most `max_step`.
+    fn integrate(&self, mut values: Vec<f64>, span: Duration) -> Vec<f64> {
+        let mut left = span;
+        while !left.is_zero() {
+            let dt = left.min(self.max_step);
+            values = self.step(&values, dt.as_secs_f64());
+            left -= dt;
+        }
+        values
+    }
+
+    fn advance(&mut self, time: Duration) {
+        if time > self.time {
+            let values = mem::take(&mut self.values);
+            self.values = self.integrate(values, time - self.time);
+            self.time = time;
+        }
+    }
+
+    // Time until the first crossing in the next `max_step`, or `max_step` if there's none.
+    fn next_check(&self) -> Duration {
+        let end = self.integrate(self.values.clone(), self.max_step);
+        let mut hold = self.max_step;
+        for crossing in &self.crossings {
+            let crossing = crossing.borrow();
+            let Some(before) = crossing.last else { continue };
+            let crosses = |values: &[f64]| crossing.direction.crossed(before, (crossing.function)(&Values { values }));
+            if !crosses(&end) {
+                continue;
+            }
+            // Bisection down to a nanosecond, `high` always past the crossing.
+            let (mut low, mut high) = (Duration::ZERO, self.max_step);
+            while high - low > Duration::from_nanos(1) {
+                let middle = low + (high - low) / 2;
+                if crosses(&self.integrate(self.values.clone(), middle)) {
+                    high = middle;
+                } else {
+                    low = middle;
+                }
+            }
+            hold = hold.min(high);
+        }
+        hold
+    }
+
+    // Check every crossing at the current time, returning the entities waiting for those that happened.
+    fn check(&mut self) -> Vec<Key> {
+        let values = Values { values: &self.values };
+        let mut woken = Vec::new();
+        for crossing in &self.crossings {
+            let mut crossing = crossing.borrow_mut();
+            let value = (crossing.function)(&values);
+            if matches!(crossing.last, Some(before) if crossing.direction.crossed(before, value)) {
+                crossing.occurrences += 1;
+                crossing.last_time = Some(self.time);
+                woken.append(&mut crossing.waiting);
+            }
+            crossing.last = Some(value);
+        }
+        woken
+    }
+}
+
+/// Continuous state variables integrated between the discrete events of the simulation.
+///
+/// Created with [`Simulation::add_continuous`](crate::Simulation::add_continuous). Each variable has a derivative
+/// function of the values of all of them, integrated with the fourth order Runge-Kutta method in steps of at most
+/// the maximum step of the system. A variable without a derivative is constant, a parameter that discrete events
+/// change with [`Continuous::set`]. Values are integrated up to the current time whenever they are read or set.
+///
+/// [`Crossing`]s turn the zero crossings of a function of the values into discrete events: the system checks
+/// them at least every maximum step, locates the crossing time and activates the entities waiting for it.
+///
+/// ```ignore
+/// let tank = simulation.add_continuous("tank", Duration::from_secs(1));
+/// let inflow = tank.add_variable("inflow", 2.0);
+/// let level = tank.add_variable("level", 0.0);
+/// tank.set_derivative(level, move |x| x[inflow] - 0.1 * x[level]);
+/// let full = tank.add_crossing(move |x| x[level] - 15.0, CrossingDirection::Rising);
+///
+/// simulation.add_generator(Box::new(move |_| loop {
+///     full.wait();
+///     yield Action::Passivate;
+///     tank.set(inflow, 0.0);
+///     ...
+/// }));
+/// ```
+///
+/// The system holds an entity stepping through time, so runs have to be stopped with a time limit or a condition.
+/// Crossings are found exactly when the derivatives only change through the values, if a discrete event changes them
+/// in between they are found at the next check.
+#[derive(Clone)]
+pub struct Continuous {
+    inner: Rc<RefCell<Inner>>,
+    clock: ClockRef,
+    current: Rc<Cell<Option<Key>>>,
+}
+
+impl Continuous {
+    pub(crate) fn new(max_step: Duration, clock: ClockRef, current: Rc<Cell<Option<Key>>>) -> Self {
+        assert!(!max_step.is_zero(), "The maximum step of a continuous system must be positive");
+        let inner = Inner {
+            names: Vec::new(),
+            values: Vec::new(),
+            derivatives: Vec::new(),
+            time: clock.time(),
+            max_step,
+            crossings: Vec::new(),
+        };
+        Self {
+            inner: Rc::new(RefCell::new(inner)),
+            clock,
+            current,
+        }
+    }
+
+    /// Add a variable starting at `initial`, constant until a derivative is given with [`Continuous::set_derivative`].
+    pub fn add_variable(&self, name: impl Into<String>, initial: f64) -> Var {
+        let mut inner = self.inner.borrow_mut();
+        inner.advance(self.clock.time());
+        inner.names.push(name.into());
+        inner.values.push(initial);
+        inner.derivatives.push(None);
+        Var(inner.values.len() - 1)
+    }
+
+    /// Set the derivative of `var` as a function of the values of every variable.
+    pub fn set_derivative(&self, var: Var, derivative: impl Fn(&Values) -> f64 + 'static) {
+        let mut inner = self.inner.borrow_mut();
+        inner.advance(self.clock.time());
+        inner.derivatives[var.0] = Some(Box::new(derivative));
+    }
+
+    /// Returns the value of `var` at the current time.
+    #[must_use]
+    pub fn value(&self, var: Var) -> f64 {
+        let mut inner = self.inner.borrow_mut();
+        inner.advance(self.clock.time());
+        inner.values[var.0]
+    }
+
+    /// Returns the value of the variable named `name` at the current time.
+    #[must_use]
+    pub fn value_of(&self, name: &str) -> Option<f64> {
+        let position = self.inner.borrow().names.iter().position(|other| other == name)?;
+        Some(self.value(Var(position)))
+    }
+
+    /// Change the value of `var` at the current time, e.g. when a discrete event opens a valve.
+    pub fn set(&self, var: Var, value: f64) {
+        let mut inner = self.inner.borrow_mut();
+        inner.advance(self.clock.time());
+        inner.values[var.0] = value;
+    }
+
+    /// Add a crossing of zero by `function` in `direction`, checked from the next step of the system.
+    pub fn add_crossing(
+        &self,
+        function: impl Fn(&Values) -> f64 + 'static,
+        direction: CrossingDirection,
+    ) -> Crossing {
+        let inner = Rc::new(RefCell::new(CrossingInner {
+            function: Box::new(function),
+            direction,
+            last: None,
+            waiting: Vec::new(),
+            occurrences: 0,
+            last_time: None,
+        }));
+        self.inner.borrow_mut().crossings.push(Rc::clone(&inner));
+        Crossing {
+            inner,
+            current: Rc::clone(&self.current),
+        }
+    }
+
+    pub(crate) fn into_generator<R: 'static>(self) -> GenBoxed<R> {
+        let mut woken = Vec::new();
+        process(move |_| {
+            if !woken.is_empty() {
+                return Some(Action::ActivateMany(mem::take(&mut woken)));
+            }
+            let mut inner = self.inner.borrow_mut();
+            inner.advance(self.clock.time());
+            woken = inner.check();
+            if !woken.is_empty() {
+                return Some(Action::ActivateMany(mem::take(&mut woken)));
+            }
+            Some(Action::Hold(inner.next_check()))
+        })
+    }
+}
+
+/// A zero crossing of a function of the values of a [`Continuous`] system, added with [`Continuous::add_crossing`].
+#[derive(Clone)]
+pub struct Crossing {
+    inner: Rc<RefCell<CrossingInner>>,
+    current: Rc<Cell<Option<Key>>>,
+}
+
+impl Crossing {
+    /// Wait for the next crossing with the entity currently being executed, which must then yield
+    /// `Action::Passivate` and is activated by the crossing.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of an entity.
+    pub fn wait(&self) {
+        let key = self
+            .current
+            .get()
+            .expect("crossings can only be waited on from inside an entity");
+        let mut inner = self.inner.borrow_mut();
+        if !inner.waiting.contains(&key) {
+            inner.waiting.push(key);
+        }
+    }
+
+    /// Returns how many times the crossing happened.
+    #[must_use]
+    pub fn occurrences(&self) -> u64 {
+        self.inner.borrow().occurrences
+    }
+
+    /// Returns the time of the last crossing.
+    #[must_use]
+    pub fn last_time(&self) -> Option<Duration> {
+        self.inner.borrow().last_time
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Simulation;
+
+    #[test]
+    fn exponential_decay_is_integrated_between_events() {
+        let mut simulation: Simulation<()> = Simulation::default();
+        let system = simulation.add_continuous("decay", Duration::from_millis(100));
+        let x = system.add_variable("x", 1.0);
+        system.set_derivative(x, move |values| -values[x]);
+        simulation.run_with_limit(Duration::from_secs(2));
+
+        assert!((system.value(x) - (-2.0f64).exp()).abs() < 1e-6);
+        assert_eq!(Some(system.value(x)), system.value_of("x"));
+        assert_eq!(None, system.value_of("y"));
+    }
+
+    // Fills the tank until it reaches 10, then drains it until it's down to 4.
+    fn operator(tank: Continuous, inflow: Var, drain: Var, full: Crossing, low: Crossing) -> GenBoxed<()> {
+        let mut step = 0;
+        process(move |_| {
+            step += 1;
+            match step {
+                1 => {
+                    full.wait();
+                    Some(Action::Passivate)
+                }
+                2 => {
+                    tank.set(inflow, 0.0);
+                    Some(Action::Hold(Duration::from_secs(2)))
+                }
+                3 => {
+                    tank.set(drain, 1.0);
+                    low.wait();
+                    Some(Action::Passivate)
+                }
+                _ => {
+                    tank.set(drain, 0.0);
+                    None
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn crossings_activate_waiting_entities() {
+        let mut simulation = Simulation::default();
+        let tank = simulation.add_continuous("tank", Duration::from_secs(1));
+        let inflow = tank.add_variable("inflow", 2.0);
+        let drain = tank.add_variable("drain", 0.0);
+        let level = tank.add_variable("level", 0.0);
+        tank.set_derivative(level, move |x| x[inflow] - x[drain]);
+        let full = tank.add_crossing(move |x| x[level] - 10.0, CrossingDirection::Rising);
+        let low = tank.add_crossing(move |x| x[level] - 4.0, CrossingDirection::Falling);
+        let operator = simulation.add_generator(operator(tank.clone(), inflow, drain, full.clone(), low.clone()));
+        simulation.schedule_now(operator);
+        simulation.run_with_limit(Duration::from_secs(20));
+
+        // Full at 5, the drain opens at 7 and the level falls to 4 at 13.
+        let close = |time: Option<Duration>, expected: u64| {
+            let time = time.unwrap().as_secs_f64();
+            assert!((time - expected as f64).abs() < 1e-6, "{} instead of {}", time, expected);
+        };
+        close(full.last_time(), 5);
+        close(low.last_time(), 13);
+        assert_eq!((1, 1), (full.occurrences(), low.occurrences()));
+        assert!((tank.value(level) - 4.0).abs() < 1e-6);
+        assert!(simulation.upgrade(operator.downgrade()).is_none());
+    }
+}
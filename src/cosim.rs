@@ -0,0 +1,257 @@
+//! Co-simulation with other tools, enabled by the `cosim` feature.
+//!
+//! [`CoSimServer`] serves the `CoSimulation` service of `proto/rustsim.proto` over gRPC, so a model can take part
+//! in a distributed co-simulation: `AdvanceTo` maps onto [`Simulation::step`], `Inject` onto
+//! [`Simulation::schedule`], and the streamed events are collected through the trace hooks.
+//!
+//! ```ignore
+//! let server = CoSimServer::new(|| {
+//!     let mut simulation = Simulation::default();
+//!     simulation.add_generator_named("arrival", arrival());
+//!     simulation
+//! });
+//! server.serve("127.0.0.1:50051".parse()?, shutdown).await?;
+//! ```
+//!
+//! The calls are forwarded to a [`CoSimService`], which doesn't depend on a transport and can be driven directly
+//! or behind another protocol. The messages and the client generated from the protocol are in [`proto`].
+
+use std::fmt;
+use std::time::Duration;
+
+use crate::trace::action_fields;
+use crate::{EntityState, EventId, RunStatus, Simulation, TraceRecorder};
+
+mod server;
+
+pub use server::CoSimServer;
+
+/// The code generated from `proto/rustsim.proto`: the messages, the client and the server trait.
+#[allow(clippy::all, clippy::pedantic)]
+pub mod proto {
+    tonic::include_proto!("rustsim.cosim.v1");
+}
+
+/// An event executed while advancing, the `ExecutedEvent` message of the protocol.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExecutedEvent {
+    pub time: Duration,
+    /// The entity name, or its id if it has none.
+    pub entity: String,
+    /// The yielded action, e.g. `"Hold"`, or `"Completed"`.
+    pub action: &'static str,
+    pub argument: String,
+}
+
+/// The result of [`CoSimService::advance_to`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Advance {
+    /// The time granted to the caller, external events can be injected from then on.
+    pub granted: Duration,
+    /// [`RunStatus::LimitReached`] if events are left after the granted time.
+    pub status: RunStatus,
+    pub events: Vec<ExecutedEvent>,
+}
+
+/// Error produced by [`CoSimService::inject`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CoSimError {
+    /// No entity was added with this name.
+    UnknownEntity(String),
+    /// The event would happen before the time already granted to the caller.
+    InThePast { time: Duration, granted: Duration },
+    /// The entity is already scheduled.
+    AlreadyScheduled(String),
+    /// The entity completed or failed, it can't be scheduled anymore.
+    Finished(String),
+}
+
+impl fmt::Display for CoSimError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownEntity(name) => write!(f, "no entity named `{}`", name),
+            Self::InThePast { time, granted } => {
+                write!(f, "cannot inject an event at {:?}, {:?} was already granted", time, granted)
+            }
+            Self::AlreadyScheduled(name) => write!(f, "`{}` is already scheduled", name),
+            Self::Finished(name) => write!(f, "`{}` completed or failed, it can't be scheduled", name),
+        }
+    }
+}
+
+impl std::error::Error for CoSimError {}
+
+/// Serves a [`Simulation`] to a co-simulation master, see the [module documentation](self).
+pub struct CoSimService {
+    simulation: Simulation<()>,
+    trace: TraceRecorder,
+    streamed: usize,
+    granted: Duration,
+}
+
+impl CoSimService {
+    /// Serve a model whose entities are already added, with names for those receiving external events.
+    pub fn new(mut simulation: Simulation<()>) -> Self {
+        let trace = simulation.trace().unwrap_or_else(|| simulation.record_trace());
+        let streamed = trace.len();
+        let granted = simulation.time();
+        Self {
+            simulation,
+            trace,
+            streamed,
+            granted,
+        }
+    }
+
+    /// Execute every event up to and including `time` and grant it, returning the events executed since the last
+    /// call. Stops early with [`RunStatus::Failed`] if an entity yields an invalid action, calling it again resumes.
+    pub fn advance_to(&mut self, time: Duration) -> Advance {
+        let status = loop {
+            match self.simulation.next_event_time() {
+                Some(next) if next <= time => {}
+                Some(_) => break RunStatus::LimitReached,
+                None if self.simulation.deadlock().is_some() => break RunStatus::Deadlocked,
+                None => break RunStatus::Exhausted,
+            }
+            if let Err(error) = self.simulation.step() {
+                break RunStatus::Failed(error);
+            }
+        };
+        if !matches!(status, RunStatus::Failed(_)) {
+            self.granted = self.granted.max(time);
+        }
+        Advance {
+            granted: self.granted,
+            status,
+            events: self.stream(),
+        }
+    }
+
+    /// Schedule the entity named `entity` at `time`, which can't be before the granted time.
+    pub fn inject(&mut self, entity: &str, time: Duration) -> Result<EventId, CoSimError> {
+        if time < self.granted {
+            return Err(CoSimError::InThePast {
+                time,
+                granted: self.granted,
+            });
+        }
+        let key = self
+            .simulation
+            .names()
+            .key_of(entity)
+            .ok_or_else(|| CoSimError::UnknownEntity(entity.to_owned()))?;
+        let finished = matches!(
+            self.simulation.entity_state(key),
+            None | Some(EntityState::Completed | EntityState::Failed)
+        );
+        if finished || self.simulation.upgrade(key.downgrade()).is_none() {
+            return Err(CoSimError::Finished(entity.to_owned()));
+        }
+        let delay = time.saturating_sub(self.simulation.time());
+        self.simulation
+            .schedule(delay, key)
+            .ok_or_else(|| CoSimError::AlreadyScheduled(entity.to_owned()))
+    }
+
+    /// Returns the time granted to the caller.
+    #[must_use]
+    pub fn granted(&self) -> Duration {
+        self.granted
+    }
+
+    /// Returns the time of the next pending event.
+    #[must_use]
+    pub fn next_event_time(&self) -> Option<Duration> {
+        self.simulation.next_event_time()
+    }
+
+    /// Returns the served simulation, e.g. to read its state between calls.
+    pub fn simulation_mut(&mut self) -> &mut Simulation<()> {
+        &mut self.simulation
+    }
+
+    // Returns the trace events recorded since the last call.
+    fn stream(&mut self) -> Vec<ExecutedEvent> {
+        let names = self.simulation.names();
+        let events = self.trace.events();
+        let executed = events
+            .get(self.streamed..)
+            .unwrap_or_default()
+            .iter()
+            .map(|event| {
+                let (action, argument) = action_fields(&event.kind);
+                ExecutedEvent {
+                    time: event.time,
+                    entity: names.label(event.key),
+                    action,
+                    argument,
+                }
+            })
+            .collect();
+        self.streamed = events.len();
+        executed
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{process, Action};
+
+    #[test]
+    fn external_events_are_injected_and_streamed() {
+        let mut simulation = Simulation::default();
+        let mut ticks = 0;
+        let clock = simulation.add_generator_named(
+            "clock",
+            process(move |_| {
+                ticks += 1;
+                (ticks <= 3).then_some(Action::Hold(Duration::from_secs(1)))
+            }),
+        );
+        let mut arrivals = 0;
+        simulation.add_generator_named(
+            "arrival",
+            process(move |_| {
+                arrivals += 1;
+                (arrivals < 2).then_some(Action::Passivate)
+            }),
+        );
+        simulation.schedule_now(clock);
+        let mut service = CoSimService::new(simulation);
+
+        let advance = service.advance_to(Duration::from_secs(1));
+        assert_eq!(RunStatus::LimitReached, advance.status);
+        let actions: Vec<_> = advance.events.iter().map(|event| (event.time.as_secs(), event.action)).collect();
+        assert_eq!(vec![(0, "Hold"), (1, "Hold")], actions);
+        assert_eq!("clock", advance.events[0].entity);
+        assert_eq!("1", advance.events[0].argument);
+
+        let past = service.inject("arrival", Duration::from_millis(500));
+        assert_eq!(
+            Err(CoSimError::InThePast {
+                time: Duration::from_millis(500),
+                granted: Duration::from_secs(1)
+            }),
+            past
+        );
+        assert_eq!(Err(CoSimError::UnknownEntity("nobody".to_owned())), service.inject("nobody", Duration::from_secs(2)));
+        assert_eq!(Err(CoSimError::AlreadyScheduled("clock".to_owned())), service.inject("clock", Duration::from_secs(2)));
+        service.inject("arrival", Duration::from_millis(1500)).unwrap();
+
+        let advance = service.advance_to(Duration::from_secs(10));
+        assert_eq!(Duration::from_secs(10), advance.granted);
+        assert_eq!(RunStatus::Deadlocked, advance.status);
+        let entities: Vec<_> = advance.events.iter().map(|event| (event.entity.as_str(), event.action)).collect();
+        assert_eq!(
+            vec![("arrival", "Passivate"), ("clock", "Hold"), ("clock", "Completed")],
+            entities
+        );
+        assert!(service.advance_to(Duration::from_secs(11)).events.is_empty());
+
+        assert_eq!(Err(CoSimError::Finished("clock".to_owned())), service.inject("clock", Duration::from_secs(12)));
+        service.inject("arrival", Duration::from_secs(12)).unwrap();
+        assert_eq!(RunStatus::Exhausted, service.advance_to(Duration::from_secs(12)).status);
+        assert_eq!(Err(CoSimError::Finished("arrival".to_owned())), service.inject("arrival", Duration::from_secs(13)));
+    }
+}
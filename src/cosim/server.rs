@@ -0,0 +1,248 @@
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use tokio::sync::oneshot;
+use tokio_stream::Stream;
+use tonic::transport::Server;
+use tonic::{Request, Response, Status};
+
+use super::proto::co_simulation_server::{CoSimulation, CoSimulationServer};
+use super::proto::{AdvanceRequest, ExecutedEvent, InjectReply, InjectRequest, StatusReply, StatusRequest};
+use super::{Advance, CoSimError, CoSimService};
+use crate::{RunStatus, Simulation};
+
+type Reply<T> = oneshot::Sender<T>;
+type EventStream = Pin<Box<dyn Stream<Item = Result<ExecutedEvent, Status>> + Send>>;
+
+// A call forwarded to the thread running the simulation.
+enum Command {
+    AdvanceTo(Duration, Reply<Advance>),
+    Inject(String, Duration, Reply<Result<(), CoSimError>>),
+    // The granted time and the time of the next event.
+    Status(Reply<(Duration, Option<Duration>)>),
+}
+
+/// Serves the `CoSimulation` service of `proto/rustsim.proto` over gRPC, forwarding the calls to a
+/// [`CoSimService`].
+///
+/// Simulations aren't `Send`, so the model is built and run on a thread of its own, which stops once the server
+/// is dropped. [`CoSimServer::serve`] runs a server on its own, [`CoSimServer::into_service`] adds the service to
+/// a [`tonic`] server built by the caller:
+///
+/// ```ignore
+/// let server = CoSimServer::new(|| build_model());
+/// tokio::runtime::Runtime::new()?.block_on(server.serve("127.0.0.1:50051".parse()?, std::future::pending()))?;
+/// ```
+pub struct CoSimServer {
+    commands: mpsc::Sender<Command>,
+}
+
+impl CoSimServer {
+    /// Build the model with `build` on the simulation thread, the entities receiving external events named.
+    pub fn new(build: impl FnOnce() -> Simulation<()> + Send + 'static) -> Self {
+        let (commands, received) = mpsc::channel();
+        thread::spawn(move || {
+            let mut service = CoSimService::new(build());
+            // Replies are dropped if the caller went away.
+            for command in received {
+                match command {
+                    Command::AdvanceTo(time, reply) => {
+                        let _ = reply.send(service.advance_to(time));
+                    }
+                    Command::Inject(entity, time, reply) => {
+                        let _ = reply.send(service.inject(&entity, time).map(|_| ()));
+                    }
+                    Command::Status(reply) => {
+                        let _ = reply.send((service.granted(), service.next_event_time()));
+                    }
+                }
+            }
+        });
+        Self { commands }
+    }
+
+    /// Returns the service, to be added to a [`tonic`] server.
+    #[must_use]
+    pub fn into_service(self) -> CoSimulationServer<Self> {
+        CoSimulationServer::new(self)
+    }
+
+    /// Serve on `address` until `shutdown` completes. Must be called from a Tokio runtime.
+    pub async fn serve(
+        self,
+        address: SocketAddr,
+        shutdown: impl Future<Output = ()> + Send,
+    ) -> Result<(), tonic::transport::Error> {
+        Server::builder()
+            .add_service(self.into_service())
+            .serve_with_shutdown(address, shutdown)
+            .await
+    }
+
+    async fn call<T>(&self, command: impl FnOnce(Reply<T>) -> Command) -> Result<T, Status> {
+        let (reply, replied) = oneshot::channel();
+        let stopped = || Status::internal("the simulation thread stopped");
+        self.commands.send(command(reply)).map_err(|_| stopped())?;
+        replied.await.map_err(|_| stopped())
+    }
+}
+
+fn to_time(seconds: f64) -> Result<Duration, String> {
+    Duration::try_from_secs_f64(seconds).map_err(|_| format!("invalid time {}", seconds))
+}
+
+impl From<super::ExecutedEvent> for ExecutedEvent {
+    fn from(event: super::ExecutedEvent) -> Self {
+        Self {
+            time: event.time.as_secs_f64(),
+            entity: event.entity,
+            action: event.action.to_owned(),
+            argument: event.argument,
+        }
+    }
+}
+
+impl From<CoSimError> for Status {
+    fn from(error: CoSimError) -> Self {
+        let message = error.to_string();
+        match error {
+            CoSimError::UnknownEntity(_) => Self::not_found(message),
+            CoSimError::InThePast { .. } => Self::out_of_range(message),
+            CoSimError::AlreadyScheduled(_) | CoSimError::Finished(_) => Self::failed_precondition(message),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl CoSimulation for CoSimServer {
+    type AdvanceToStream = EventStream;
+
+    // Streams the executed events, ending with an `ABORTED` status if an entity yielded an invalid action.
+    async fn advance_to(&self, request: Request<AdvanceRequest>) -> Result<Response<EventStream>, Status> {
+        let time = to_time(request.into_inner().time).map_err(Status::invalid_argument)?;
+        let advance = self.call(|reply| Command::AdvanceTo(time, reply)).await?;
+        let failure = match advance.status {
+            RunStatus::Failed(error) => Some(Err(Status::aborted(error.to_string()))),
+            _ => None,
+        };
+        let events = advance
+            .events
+            .into_iter()
+            .map(ExecutedEvent::from)
+            .map(Ok)
+            .chain(failure);
+        Ok(Response::new(Box::pin(tokio_stream::iter(events))))
+    }
+
+    async fn inject(&self, request: Request<InjectRequest>) -> Result<Response<InjectReply>, Status> {
+        let InjectRequest { entity, time } = request.into_inner();
+        let time = to_time(time).map_err(Status::invalid_argument)?;
+        self.call(|reply| Command::Inject(entity, time, reply)).await??;
+        Ok(Response::new(InjectReply {}))
+    }
+
+    async fn status(&self, _request: Request<StatusRequest>) -> Result<Response<StatusReply>, Status> {
+        let (granted, next_event) = self.call(Command::Status).await?;
+        Ok(Response::new(StatusReply {
+            granted: granted.as_secs_f64(),
+            next_event: next_event.map(|time| time.as_secs_f64()),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tokio_stream::StreamExt;
+    use tonic::Code;
+
+    use super::*;
+    use crate::cosim::proto::co_simulation_client::CoSimulationClient;
+    use crate::{process, Action};
+
+    fn model() -> Simulation<()> {
+        let mut simulation = Simulation::default();
+        let mut ticks = 0;
+        let clock = simulation.add_generator_named(
+            "clock",
+            process(move |_| {
+                ticks += 1;
+                (ticks <= 3).then_some(Action::Hold(Duration::from_secs(1)))
+            }),
+        );
+        simulation.add_generator_named("arrival", process(|_| None));
+        simulation.schedule_now(clock);
+        simulation
+    }
+
+    #[test]
+    fn masters_drive_the_model_over_grpc() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let address = listener.local_addr().unwrap();
+            let (stop, stopped) = oneshot::channel::<()>();
+            let server = Server::builder()
+                .add_service(CoSimServer::new(model).into_service())
+                .serve_with_incoming_shutdown(TcpListenerStream::new(listener), async {
+                    let _ = stopped.await;
+                });
+            let server = tokio::spawn(server);
+
+            let mut client = CoSimulationClient::connect(format!("http://{}", address))
+                .await
+                .unwrap();
+            let inject = |entity: &str, time| InjectRequest {
+                entity: entity.to_owned(),
+                time,
+            };
+            client.inject(inject("arrival", 1.5)).await.unwrap();
+            let events: Vec<ExecutedEvent> = client
+                .advance_to(AdvanceRequest { time: 2.0 })
+                .await
+                .unwrap()
+                .into_inner()
+                .collect::<Result<_, _>>()
+                .await
+                .unwrap();
+            let events: Vec<_> = events
+                .into_iter()
+                .map(|event| (event.time, event.entity, event.action))
+                .collect();
+            let expected = [
+                (0.0, "clock", "Hold"),
+                (1.0, "clock", "Hold"),
+                (1.5, "arrival", "Completed"),
+                (2.0, "clock", "Hold"),
+            ];
+            let expected: Vec<_> = expected
+                .iter()
+                .map(|&(time, entity, action)| (time, entity.to_owned(), action.to_owned()))
+                .collect();
+            assert_eq!(expected, events);
+
+            let status = client.status(StatusRequest {}).await.unwrap().into_inner();
+            assert_eq!((2.0, Some(3.0)), (status.granted, status.next_event));
+            let code = |result: Result<_, Status>| result.unwrap_err().code();
+            assert_eq!(Code::OutOfRange, code(client.inject(inject("arrival", 1.0)).await));
+            assert_eq!(Code::NotFound, code(client.inject(inject("nobody", 3.0)).await));
+            assert_eq!(
+                Code::FailedPrecondition,
+                code(client.inject(inject("arrival", 3.0)).await)
+            );
+            let negative = client.advance_to(AdvanceRequest { time: -1.0 }).await;
+            assert_eq!(Code::InvalidArgument, negative.unwrap_err().code());
+
+            stop.send(()).unwrap();
+            server.await.unwrap().unwrap();
+        });
+    }
+}
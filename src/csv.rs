@@ -0,0 +1,23 @@
+// Helpers shared by the CSV exporters.
+use std::borrow::Cow;
+use std::fmt::Write as _;
+
+use crate::metadata::RunMetadata;
+
+// Quote `value` if it contains a separator, a quote or a line break.
+pub(crate) fn field(value: &str) -> Cow<'_, str> {
+    if value.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", value.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+
+// The metadata as `# name: value` lines, skipped by readers configured with `#` as the comment character.
+pub(crate) fn metadata_comments(metadata: &RunMetadata) -> String {
+    let mut comments = String::new();
+    for (name, value) in metadata.entries() {
+        let _ = writeln!(comments, "# {}: {}", name, value.replace(['\n', '\r'], " "));
+    }
+    comments
+}
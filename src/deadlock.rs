@@ -0,0 +1,102 @@
+use std::fmt;
+use std::time::Duration;
+
+use crate::Key;
+
+/// An entity left passive when no more events are left, see [`Deadlock`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PassiveEntity {
+    pub key: Key,
+    /// Simulation time at which the entity became passive.
+    pub since: Duration,
+    /// The entity that cancelled it, `None` if it passivated itself.
+    pub cancelled_by: Option<Key>,
+}
+
+/// Report of the entities waiting forever because nothing is left to activate them.
+///
+/// Returned by [`Simulation::deadlock`](crate::Simulation::deadlock) once the scheduler is empty,
+/// runs that end this way return [`RunStatus::Deadlocked`](crate::RunStatus::Deadlocked).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Deadlock {
+    time: Duration,
+    entities: Vec<PassiveEntity>,
+}
+
+impl Deadlock {
+    pub(crate) fn new(time: Duration, mut entities: Vec<PassiveEntity>) -> Self {
+        entities.sort_by_key(|entity| entity.key.id());
+        Self { time, entities }
+    }
+
+    /// Returns the simulation time at which the last event was executed.
+    #[must_use]
+    pub fn time(&self) -> Duration {
+        self.time
+    }
+
+    /// Returns the passive entities, sorted by id.
+    #[must_use]
+    pub fn entities(&self) -> &[PassiveEntity] {
+        &self.entities
+    }
+}
+
+impl fmt::Display for Deadlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} entities passive with no events left at {:?}:", self.entities.len(), self.time)?;
+        for entity in &self.entities {
+            write!(f, "\n  entity {} passive since {:?}", entity.key.id(), entity.since)?;
+            match entity.cancelled_by {
+                Some(other) => write!(f, ", cancelled by entity {}", other.id())?,
+                None => write!(f, ", passivated itself")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{process, Action, RunStatus, Simulation};
+
+    #[test]
+    fn passive_entities_are_reported() {
+        let mut simulation = Simulation::default();
+        let waiter = simulation.add_generator(process(|_| Some(Action::Passivate)));
+        let holder = simulation.add_generator(process(|_| Some(Action::Hold(Duration::from_secs(10)))));
+        let mut cancelled = false;
+        let canceller = simulation.add_generator(process(move |_| {
+            if cancelled {
+                return None;
+            }
+            cancelled = true;
+            Some(Action::Cancel(holder))
+        }));
+        simulation.schedule_now(waiter);
+        simulation.schedule_now(holder);
+        simulation.schedule(Duration::from_secs(2), canceller);
+
+        assert!(simulation.deadlock().is_none());
+        assert_eq!(RunStatus::Deadlocked, simulation.run_until_empty());
+        let deadlock = simulation.deadlock().unwrap();
+        assert_eq!(Duration::from_secs(2), deadlock.time());
+        assert_eq!(
+            &[
+                PassiveEntity {
+                    key: waiter,
+                    since: Duration::ZERO,
+                    cancelled_by: None,
+                },
+                PassiveEntity {
+                    key: holder,
+                    since: Duration::from_secs(2),
+                    cancelled_by: Some(canceller),
+                },
+            ],
+            deadlock.entities()
+        );
+        assert!(deadlock.to_string().contains("entity 1 passive since 2s, cancelled by entity 2"));
+    }
+}
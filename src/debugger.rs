@@ -0,0 +1,192 @@
+//! An interactive command loop to step through a simulation, enabled by the `debugger` feature.
+//!
+//! ```ignore
+//! let mut debugger = Debugger::new(&mut simulation);
+//! debugger.watch("queue length", queue_length);
+//! debugger.run(std::io::stdin().lock(), std::io::stdout())?;
+//! ```
+//!
+//! Commands, one per line:
+//!
+//! - `step [n]` (`s`): execute the next `n` events, one by default.
+//! - `continue [time]` (`c`): run until `time`, in seconds, or until a breakpoint or the end of the run.
+//! - `events` (`e`): print the future event list.
+//! - `entities` (`ls`): print the state of every entity.
+//! - `print <name>` (`p`): print a watched [`State`] value.
+//! - `time` (`t`): print the simulation time.
+//! - `help` (`h`) and `quit` (`q`).
+
+use std::fmt::{Debug, Write as _};
+use std::io::{self, BufRead, Write};
+use std::time::Duration;
+
+use crate::state::{State, StateKey};
+use crate::{Breakpoint, RunStatus, Simulation};
+
+type Printer = Box<dyn Fn(&State) -> String>;
+
+const HELP: &str = "\
+step [n]          execute the next n events
+continue [time]   run until time (seconds), a breakpoint or the end
+events            print the future event list
+entities          print the state of every entity
+print <name>      print a watched state value
+time              print the simulation time
+quit              leave the debugger";
+
+/// Drives a [`Simulation`] with textual commands, see the [module documentation](self).
+pub struct Debugger<'a> {
+    simulation: &'a mut Simulation<()>,
+    watches: Vec<(String, Printer)>,
+}
+
+impl<'a> Debugger<'a> {
+    pub fn new(simulation: &'a mut Simulation<()>) -> Self {
+        Self {
+            simulation,
+            watches: Vec::new(),
+        }
+    }
+
+    /// Make the value of `key` in the shared [`State`] printable as `name`.
+    pub fn watch<V: Debug + 'static>(&mut self, name: impl Into<String>, key: StateKey<V>) -> &mut Self {
+        let printer = move |state: &State| match state.get(key) {
+            Some(value) => format!("{:?}", value),
+            None => "<removed>".to_owned(),
+        };
+        self.watches.push((name.into(), Box::new(printer)));
+        self
+    }
+
+    /// Read commands from `input` until `quit` or the end of the input, writing a prompt and the answers to
+    /// `output`.
+    pub fn run(&mut self, input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+        write!(output, "(rustsim) ")?;
+        output.flush()?;
+        for line in input.lines() {
+            let line = line?;
+            let command = line.trim();
+            if matches!(command, "quit" | "q") {
+                break;
+            }
+            if !command.is_empty() {
+                writeln!(output, "{}", self.execute(command))?;
+            }
+            write!(output, "(rustsim) ")?;
+            output.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Execute a single command, returning its answer.
+    pub fn execute(&mut self, command: &str) -> String {
+        let command = command.trim();
+        let mut words = command.split_whitespace();
+        let name = words.next().unwrap_or_default();
+        let argument = words.next();
+        match (name, argument) {
+            ("step" | "s", count) => match count.map_or(Ok(1), str::parse) {
+                Ok(count) => {
+                    let executed = self.simulation.step_n(count);
+                    format!("executed {} events, time {:?}", executed, self.simulation.time())
+                }
+                Err(_) => format!("invalid event count `{}`", count.unwrap_or_default()),
+            },
+            ("continue" | "c", None) => self.answer(|simulation| simulation.run_until_empty()),
+            ("continue" | "c", Some(time)) => match time.parse::<f64>().map(Duration::try_from_secs_f64) {
+                Ok(Ok(time)) => {
+                    let breakpoint = Breakpoint::time(time);
+                    self.simulation.set_breakpoint(breakpoint);
+                    let answer = self.answer(|simulation| simulation.run_until_empty());
+                    self.simulation.remove_breakpoint(breakpoint);
+                    answer
+                }
+                _ => format!("invalid time `{}`", time),
+            },
+            ("events" | "e", _) => self.events(),
+            ("entities" | "ls", _) => self.entities(),
+            ("print" | "p", Some(watch)) => self.print(command[name.len()..].trim()).unwrap_or_else(|| {
+                format!("no watch named `{}`", watch)
+            }),
+            ("time" | "t", _) => format!("{:?}", self.simulation.time()),
+            ("help" | "h", _) => HELP.to_owned(),
+            _ => format!("unknown command `{}`, try `help`", command),
+        }
+    }
+
+    fn answer(&mut self, run: impl FnOnce(&mut Simulation<()>) -> RunStatus) -> String {
+        let status = run(self.simulation);
+        format!("{:?}, time {:?}", status, self.simulation.time())
+    }
+
+    fn events(&self) -> String {
+        let events = self.simulation.pending_events();
+        if events.is_empty() {
+            return "no pending events".to_owned();
+        }
+        let names = self.simulation.names();
+        let mut answer = String::new();
+        for event in events {
+            let _ = writeln!(answer, "{:?}\t{}", event.time(), names.label(event.key()));
+        }
+        answer.pop();
+        answer
+    }
+
+    fn entities(&self) -> String {
+        let names = self.simulation.names();
+        let lines: Vec<String> = self
+            .simulation
+            .entity_keys()
+            .into_iter()
+            .filter_map(|key| {
+                let state = self.simulation.entity_state(key)?;
+                Some(format!("{}\t{:?}", names.label(key), state))
+            })
+            .collect();
+        if lines.is_empty() {
+            "no entities".to_owned()
+        } else {
+            lines.join("\n")
+        }
+    }
+
+    fn print(&self, name: &str) -> Option<String> {
+        let (_, printer) = self.watches.iter().find(|(watch, _)| watch == name)?;
+        Some(self.simulation.state().with(|state| printer(state)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{process, Action};
+
+    #[test]
+    fn commands_drive_the_simulation() {
+        let mut simulation = Simulation::default();
+        let served = simulation.state().with_mut(|state| state.insert(0u32));
+        let state = simulation.state();
+        let teller = simulation.add_generator_named(
+            "teller",
+            process(move |_| {
+                state.with_mut(|state| *state.get_mut(served).unwrap() += 1);
+                Some(Action::Hold(Duration::from_secs(2)))
+            }),
+        );
+        simulation.schedule_now(teller);
+        let mut debugger = Debugger::new(&mut simulation);
+        debugger.watch("served", served);
+
+        assert_eq!("executed 2 events, time 2s", debugger.execute("step 2"));
+        assert_eq!("4s\tteller", debugger.execute("events"));
+        assert_eq!("teller\tHolding", debugger.execute("ls"));
+        assert_eq!("Breakpoint(Time(5s)), time 4s", debugger.execute("continue 5"));
+        assert_eq!("3", debugger.execute("p served"));
+
+        let mut output = Vec::new();
+        debugger.run("t\nbogus\nq\nstep\n".as_bytes(), &mut output).unwrap();
+        let expected = "(rustsim) 4s\n(rustsim) unknown command `bogus`, try `help`\n(rustsim) ";
+        assert_eq!(expected, String::from_utf8(output).unwrap());
+    }
+}
@@ -0,0 +1,482 @@
+//! Models in the DEVS formalism, built into a [`Simulation`].
+//!
+//! An atomic model is a state with a time advance and transition functions, see [`Atomic`]. Atomic models are
+//! composed into [`Coupled`] models by connecting their output ports to input ports, and coupled models can
+//! themselves be components of larger coupled models:
+//!
+//! ```ignore
+//! let mut server = Coupled::new();
+//! server.add_atomic("processor", Processor::new(Duration::from_secs(3)));
+//! server.couple_input("in", ("processor", "in"));
+//! server.couple_output(("processor", "done"), "done");
+//!
+//! let mut model = Coupled::new();
+//! model.add_atomic("generator", Generator::new(Duration::from_secs(2)));
+//! model.add_coupled("server", server);
+//! model.couple(("generator", "out"), ("server", "in"));
+//! model.couple_output(("server", "done"), "done");
+//!
+//! let devs = model.build(&mut simulation);
+//! simulation.run_with_limit(Duration::from_secs(100));
+//! println!("{:?}", devs.outputs());
+//! ```
+//!
+//! Every atomic model becomes an entity holding until its next internal transition, woken up by preemption
+//! when an input changes its schedule. Inputs are delivered one at a time. A model receiving an input when its
+//! internal transition is due goes through the internal transition first, routing its output, then through
+//! the external transition with no elapsed time.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::{process, Action, ComponentKind, Key, Simulation};
+
+/// The name of an input or output port.
+pub type Port = &'static str;
+
+/// The time advance of a model that waits for inputs without any internal transition scheduled.
+pub const PASSIVE: Duration = Duration::MAX;
+
+/// An atomic DEVS model, whose inputs and outputs are values of type `V` on named ports.
+pub trait Atomic<V> {
+    /// Returns how long until the next internal transition in the current state, [`PASSIVE`] if none.
+    fn time_advance(&self) -> Duration;
+
+    /// The output of the model, computed right before each internal transition.
+    fn output(&self) -> Vec<(Port, V)>;
+
+    /// The internal transition, when the time advance elapsed.
+    fn internal(&mut self);
+
+    /// The external transition, when `value` arrives on `port`, `elapsed` after the previous transition.
+    fn external(&mut self, elapsed: Duration, port: Port, value: V);
+}
+
+enum Model<V> {
+    Atomic(Box<dyn Atomic<V>>),
+    Coupled(Coupled<V>),
+}
+
+/// A DEVS model made of named components and the couplings between their ports.
+pub struct Coupled<V> {
+    components: Vec<(String, Model<V>)>,
+    // Between components, `(component, port)` to `(component, port)`.
+    couplings: Vec<((String, Port), (String, Port))>,
+    // From the input ports of the model to the components.
+    inputs: Vec<(Port, (String, Port))>,
+    // From the components to the output ports of the model.
+    outputs: Vec<((String, Port), Port)>,
+}
+
+impl<V> Default for Coupled<V> {
+    fn default() -> Self {
+        Self {
+            components: Vec::new(),
+            couplings: Vec::new(),
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+        }
+    }
+}
+
+// Where a value sent on an output port of an atomic model goes.
+#[derive(Debug, Clone, Copy)]
+enum Target {
+    Atomic(usize, Port),
+    // An output port of the outermost model.
+    Output(Port),
+}
+
+// The atomic models of a coupled model, flattened, and how its ports connect to them.
+struct Flat<V> {
+    names: Vec<String>,
+    models: Vec<Box<dyn Atomic<V>>>,
+    routes: HashMap<(usize, Port), Vec<Target>>,
+    ports: Ports,
+}
+
+// The atomic ports behind the ports of a component.
+enum Ports {
+    Atomic(usize),
+    Coupled {
+        // The atomic input ports each input port leads to.
+        inputs: HashMap<Port, Vec<(usize, Port)>>,
+        // The atomic output ports leading to each output port.
+        outputs: HashMap<Port, Vec<(usize, Port)>>,
+    },
+}
+
+impl Ports {
+    fn resolve(&self, port: Port, input: bool) -> Vec<(usize, Port)> {
+        match self {
+            Self::Atomic(index) => vec![(*index, port)],
+            Self::Coupled { inputs, .. } if input => inputs.get(port).cloned().unwrap_or_default(),
+            Self::Coupled { outputs, .. } => outputs.get(port).cloned().unwrap_or_default(),
+        }
+    }
+
+    fn shift(self, offset: usize) -> Self {
+        let shift = |ports: HashMap<Port, Vec<(usize, Port)>>| {
+            ports
+                .into_iter()
+                .map(|(port, atomic)| (port, atomic.into_iter().map(|(index, port)| (index + offset, port)).collect()))
+                .collect()
+        };
+        match self {
+            Self::Atomic(index) => Self::Atomic(index + offset),
+            Self::Coupled { inputs, outputs } => Self::Coupled {
+                inputs: shift(inputs),
+                outputs: shift(outputs),
+            },
+        }
+    }
+}
+
+impl<V> Coupled<V>
+where
+    V: Clone + 'static,
+{
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_atomic(&mut self, name: impl Into<String>, model: impl Atomic<V> + 'static) {
+        self.components.push((name.into(), Model::Atomic(Box::new(model))));
+    }
+
+    pub fn add_coupled(&mut self, name: impl Into<String>, model: Coupled<V>) {
+        self.components.push((name.into(), Model::Coupled(model)));
+    }
+
+    /// Send the values of output port `from` of a component to input port `to` of another component.
+    pub fn couple(&mut self, from: (&str, Port), to: (&str, Port)) {
+        self.couplings.push(((from.0.to_owned(), from.1), (to.0.to_owned(), to.1)));
+    }
+
+    /// Send the values received on input port `port` of this model to input port `to` of a component.
+    pub fn couple_input(&mut self, port: Port, to: (&str, Port)) {
+        self.inputs.push((port, (to.0.to_owned(), to.1)));
+    }
+
+    /// Send the values of output port `from` of a component out of this model on port `port`.
+    pub fn couple_output(&mut self, from: (&str, Port), port: Port) {
+        self.outputs.push(((from.0.to_owned(), from.1), port));
+    }
+
+    /// Add an entity per atomic model to `simulation`, named after the path of the model, e.g. `server/processor`.
+    ///
+    /// The values sent on the output ports of this model are recorded in the returned [`DevsModel`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if a coupling names a component that doesn't exist.
+    pub fn build<R: 'static>(self, simulation: &mut Simulation<R>) -> DevsModel<V> {
+        let flat = self.flatten("");
+        let mut routes = flat.routes;
+        let Ports::Coupled { outputs, .. } = flat.ports else {
+            unreachable!("A coupled model has coupled ports.")
+        };
+        for (port, sources) in outputs {
+            for source in sources {
+                routes.entry(source).or_default().push(Target::Output(port));
+            }
+        }
+        let now = simulation.time();
+        let network = Rc::new(RefCell::new(Network {
+            next: flat
+                .models
+                .iter()
+                .map(|model| now.saturating_add(model.time_advance()))
+                .collect(),
+            last: vec![now; flat.models.len()],
+            waiting: vec![Waiting::Ready; flat.models.len()],
+            keys: Vec::new(),
+            models: flat.models,
+            routes,
+            outputs: Vec::new(),
+        }));
+        let clock = simulation.clock();
+        for (index, name) in flat.names.iter().enumerate() {
+            simulation.register_component::<dyn Atomic<V>>(name.clone(), ComponentKind::Entity);
+            let shared = Rc::clone(&network);
+            let clock = clock.clone();
+            // Activations and preemptions of the models whose schedule changed, yielded one at a time.
+            let mut wake_ups = VecDeque::new();
+            let key = simulation.add_generator_named(
+                name.clone(),
+                process(move |_| {
+                    let mut network = shared.borrow_mut();
+                    if let Some(action) = wake_ups.pop_front() {
+                        return Some(action);
+                    }
+                    let now = clock.time();
+                    if network.next[index] == now {
+                        let mut changed = Vec::new();
+                        network.fire(index, now, &mut changed);
+                        for other in changed.into_iter().filter(|&other| other != index) {
+                            let key = network.keys[other];
+                            match network.waiting[other] {
+                                Waiting::Holding => wake_ups.push_back(Action::Preempt(key)),
+                                Waiting::Passive => wake_ups.push_back(Action::ActivateOne(key)),
+                                Waiting::Ready => continue,
+                            }
+                            network.waiting[other] = Waiting::Ready;
+                        }
+                        if let Some(action) = wake_ups.pop_front() {
+                            return Some(action);
+                        }
+                    }
+                    let next = network.next[index];
+                    if next == PASSIVE {
+                        network.waiting[index] = Waiting::Passive;
+                        Some(Action::Passivate)
+                    } else {
+                        network.waiting[index] = Waiting::Holding;
+                        Some(Action::Hold(next - now))
+                    }
+                }),
+            );
+            network.borrow_mut().keys.push(key);
+            simulation.schedule_now(key);
+        }
+        DevsModel {
+            names: flat.names,
+            network,
+        }
+    }
+
+    fn flatten(self, prefix: &str) -> Flat<V> {
+        let mut names = Vec::new();
+        let mut models = Vec::new();
+        let mut routes: HashMap<(usize, Port), Vec<Target>> = HashMap::new();
+        let mut components = HashMap::new();
+        for (name, model) in self.components {
+            let path = format!("{}{}", prefix, name);
+            let offset = models.len();
+            let ports = match model {
+                Model::Atomic(model) => {
+                    names.push(path);
+                    models.push(model);
+                    Ports::Atomic(offset)
+                }
+                Model::Coupled(model) => {
+                    let inner = model.flatten(&format!("{}/", path));
+                    names.extend(inner.names);
+                    models.extend(inner.models);
+                    for ((index, port), targets) in inner.routes {
+                        let targets = targets.into_iter().map(|target| match target {
+                            Target::Atomic(other, port) => Target::Atomic(other + offset, port),
+                            Target::Output(port) => Target::Output(port),
+                        });
+                        routes.entry((index + offset, port)).or_default().extend(targets);
+                    }
+                    inner.ports.shift(offset)
+                }
+            };
+            components.insert(name, ports);
+        }
+        let resolve = |name: &str, port: Port, input: bool| {
+            components
+                .get(name)
+                .unwrap_or_else(|| panic!("no component named `{}{}`", prefix, name))
+                .resolve(port, input)
+        };
+        for ((from, from_port), (to, to_port)) in self.couplings {
+            let targets = resolve(&to, to_port, true);
+            for source in resolve(&from, from_port, false) {
+                let sent = targets.iter().map(|&(index, port)| Target::Atomic(index, port));
+                routes.entry(source).or_default().extend(sent);
+            }
+        }
+        let mut inputs: HashMap<Port, Vec<(usize, Port)>> = HashMap::new();
+        for (port, (to, to_port)) in self.inputs {
+            inputs.entry(port).or_default().extend(resolve(&to, to_port, true));
+        }
+        let mut outputs: HashMap<Port, Vec<(usize, Port)>> = HashMap::new();
+        for ((from, from_port), port) in self.outputs {
+            outputs.entry(port).or_default().extend(resolve(&from, from_port, false));
+        }
+        Flat {
+            names,
+            models,
+            routes,
+            ports: Ports::Coupled { inputs, outputs },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Waiting {
+    // Scheduled to resume now.
+    Ready,
+    Holding,
+    Passive,
+}
+
+// The flattened atomic models, shared by their entities.
+struct Network<V> {
+    models: Vec<Box<dyn Atomic<V>>>,
+    // Time of the last and of the next transition of each model.
+    last: Vec<Duration>,
+    next: Vec<Duration>,
+    keys: Vec<Key>,
+    waiting: Vec<Waiting>,
+    routes: HashMap<(usize, Port), Vec<Target>>,
+    outputs: Vec<(Duration, Port, V)>,
+}
+
+impl<V: Clone> Network<V> {
+    // Go through the internal transition of `index`, routing its output, and record in `changed` the models
+    // whose schedule changed.
+    fn fire(&mut self, index: usize, now: Duration, changed: &mut Vec<usize>) {
+        let output = self.models[index].output();
+        self.models[index].internal();
+        self.reschedule(index, now);
+        changed.push(index);
+        for (port, value) in output {
+            let targets = self.routes.get(&(index, port)).cloned().unwrap_or_default();
+            for target in targets {
+                match target {
+                    Target::Atomic(other, port) => self.deliver(other, port, value.clone(), now, changed),
+                    Target::Output(port) => self.outputs.push((now, port, value.clone())),
+                }
+            }
+        }
+    }
+
+    fn deliver(&mut self, index: usize, port: Port, value: V, now: Duration, changed: &mut Vec<usize>) {
+        if self.next[index] == now {
+            self.fire(index, now, changed);
+        }
+        let elapsed = now - self.last[index];
+        self.models[index].external(elapsed, port, value);
+        self.reschedule(index, now);
+        changed.push(index);
+    }
+
+    fn reschedule(&mut self, index: usize, now: Duration) {
+        self.last[index] = now;
+        self.next[index] = now.saturating_add(self.models[index].time_advance());
+    }
+}
+
+/// A DEVS model built into a simulation with [`Coupled::build`].
+pub struct DevsModel<V> {
+    names: Vec<String>,
+    network: Rc<RefCell<Network<V>>>,
+}
+
+impl<V: Clone> DevsModel<V> {
+    /// Returns the values sent on the output ports of the model, with their time, in order.
+    #[must_use]
+    pub fn outputs(&self) -> Vec<(Duration, Port, V)> {
+        self.network.borrow().outputs.clone()
+    }
+
+    /// Returns the time of the next internal transition of the atomic model at `path`, `None` if it's
+    /// passive or doesn't exist.
+    #[must_use]
+    pub fn next_transition(&self, path: &str) -> Option<Duration> {
+        let index = self.names.iter().position(|name| name == path)?;
+        Some(self.network.borrow().next[index]).filter(|&next| next != PASSIVE)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn secs(seconds: u64) -> Duration {
+        Duration::from_secs(seconds)
+    }
+
+    // Emits a numbered job every `period`.
+    struct Generator {
+        period: Duration,
+        count: u32,
+    }
+
+    impl Atomic<u32> for Generator {
+        fn time_advance(&self) -> Duration {
+            self.period
+        }
+
+        fn output(&self) -> Vec<(Port, u32)> {
+            vec![("out", self.count)]
+        }
+
+        fn internal(&mut self) {
+            self.count += 1;
+        }
+
+        fn external(&mut self, _: Duration, _: Port, _: u32) {}
+    }
+
+    // Serves one job at a time, dropping the jobs arriving while busy.
+    struct Processor {
+        service: Duration,
+        job: Option<u32>,
+        remaining: Duration,
+    }
+
+    impl Atomic<u32> for Processor {
+        fn time_advance(&self) -> Duration {
+            if self.job.is_some() {
+                self.remaining
+            } else {
+                PASSIVE
+            }
+        }
+
+        fn output(&self) -> Vec<(Port, u32)> {
+            self.job.map(|job| ("done", job)).into_iter().collect()
+        }
+
+        fn internal(&mut self) {
+            self.job = None;
+        }
+
+        fn external(&mut self, elapsed: Duration, _: Port, job: u32) {
+            if self.job.is_some() {
+                self.remaining -= elapsed;
+            } else {
+                self.job = Some(job);
+                self.remaining = self.service;
+            }
+        }
+    }
+
+    #[test]
+    fn coupled_models_route_outputs() {
+        let mut server = Coupled::new();
+        server.add_atomic(
+            "processor",
+            Processor {
+                service: secs(3),
+                job: None,
+                remaining: Duration::ZERO,
+            },
+        );
+        server.couple_input("in", ("processor", "in"));
+        server.couple_output(("processor", "done"), "done");
+        let mut model = Coupled::new();
+        model.add_atomic("generator", Generator { period: secs(2), count: 0 });
+        model.add_coupled("server", server);
+        model.couple(("generator", "out"), ("server", "in"));
+        model.couple_output(("server", "done"), "done");
+
+        let mut simulation = Simulation::default();
+        let devs = model.build(&mut simulation);
+        simulation.run_until(|simulation| simulation.next_event_time().is_none_or(|next| next > secs(10)));
+
+        // Jobs 1 and 3 arrive while the processor is busy.
+        assert_eq!(vec![(secs(5), "done", 0), (secs(9), "done", 2)], devs.outputs());
+        assert_eq!(secs(10), simulation.time());
+        assert_eq!(Some(secs(12)), devs.next_transition("generator"));
+        assert_eq!(Some(secs(13)), devs.next_transition("server/processor"));
+        assert!(simulation.key_of("server/processor").is_some());
+    }
+}
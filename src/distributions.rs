@@ -0,0 +1,298 @@
+//! Random variates returned directly as [`Duration`]s.
+//!
+//! Parameters are durations too, samples are drawn from a [`SimRng`], usually the one of the simulation:
+//!
+//! ```ignore
+//! let service = Exponential::new(Duration::from_secs(5));
+//! let rng = simulation.rng();
+//! yield Action::Hold(service.sample(&rng));
+//! ```
+//!
+//! Samples are computed in seconds as `f64` and converted with [`to_duration`], negative values become zero.
+
+use std::time::Duration;
+
+use crate::SimRng;
+
+/// Convert a number of seconds to a [`Duration`], clamping negative values to zero and saturating
+/// at [`Duration::MAX`].
+///
+/// # Panics
+///
+/// Panics if `seconds` is NaN.
+#[must_use]
+pub fn to_duration(seconds: f64) -> Duration {
+    assert!(!seconds.is_nan(), "Sampled a NaN duration");
+    if seconds <= 0.0 {
+        Duration::ZERO
+    } else {
+        Duration::try_from_secs_f64(seconds).unwrap_or(Duration::MAX)
+    }
+}
+
+/// A distribution of durations.
+pub trait Distribution {
+    /// Draw a sample, in seconds.
+    fn sample_secs(&self, rng: &SimRng) -> f64;
+
+    /// Draw a sample.
+    fn sample(&self, rng: &SimRng) -> Duration {
+        to_duration(self.sample_secs(rng))
+    }
+
+    /// Returns a closure drawing samples from `rng`, e.g. the interarrival times of a [`Source`](crate::Source).
+    fn sampler(self, rng: SimRng) -> Box<dyn FnMut() -> Duration>
+    where
+        Self: Sized + 'static,
+    {
+        Box::new(move || self.sample(&rng))
+    }
+}
+
+/// Exponential distribution with the given mean.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Exponential {
+    mean: f64,
+}
+
+impl Exponential {
+    #[must_use]
+    pub fn new(mean: Duration) -> Self {
+        Self {
+            mean: mean.as_secs_f64(),
+        }
+    }
+}
+
+impl Distribution for Exponential {
+    fn sample_secs(&self, rng: &SimRng) -> f64 {
+        // `1 - u` is in (0, 1], so the logarithm is finite.
+        -self.mean * (1.0 - rng.next_f64()).ln()
+    }
+}
+
+/// Uniform distribution in `[low, high)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Uniform {
+    low: f64,
+    high: f64,
+}
+
+impl Uniform {
+    /// # Panics
+    ///
+    /// Panics if `low` is greater than `high`.
+    #[must_use]
+    pub fn new(low: Duration, high: Duration) -> Self {
+        assert!(low <= high, "Invalid uniform bounds: {:?} > {:?}", low, high);
+        Self {
+            low: low.as_secs_f64(),
+            high: high.as_secs_f64(),
+        }
+    }
+}
+
+impl Distribution for Uniform {
+    fn sample_secs(&self, rng: &SimRng) -> f64 {
+        rng.uniform(self.low, self.high)
+    }
+}
+
+/// Normal distribution, negative samples are truncated to zero.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Normal {
+    mean: f64,
+    std_dev: f64,
+}
+
+impl Normal {
+    #[must_use]
+    pub fn new(mean: Duration, std_dev: Duration) -> Self {
+        Self {
+            mean: mean.as_secs_f64(),
+            std_dev: std_dev.as_secs_f64(),
+        }
+    }
+}
+
+impl Distribution for Normal {
+    fn sample_secs(&self, rng: &SimRng) -> f64 {
+        // Box-Muller transform, using a single output so every sample takes exactly two draws.
+        let radius = (-2.0 * (1.0 - rng.next_f64()).ln()).sqrt();
+        let angle = 2.0 * std::f64::consts::PI * rng.next_f64();
+        self.mean + self.std_dev * radius * angle.cos()
+    }
+}
+
+/// Triangular distribution between `min` and `max` peaking at `mode`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Triangular {
+    min: f64,
+    mode: f64,
+    max: f64,
+}
+
+impl Triangular {
+    /// # Panics
+    ///
+    /// Panics unless `min <= mode <= max`.
+    #[must_use]
+    pub fn new(min: Duration, mode: Duration, max: Duration) -> Self {
+        assert!(
+            min <= mode && mode <= max,
+            "Invalid triangular parameters: min = {:?}, mode = {:?}, max = {:?}",
+            min,
+            mode,
+            max
+        );
+        Self {
+            min: min.as_secs_f64(),
+            mode: mode.as_secs_f64(),
+            max: max.as_secs_f64(),
+        }
+    }
+}
+
+impl Distribution for Triangular {
+    fn sample_secs(&self, rng: &SimRng) -> f64 {
+        let Self { min, mode, max } = *self;
+        let range = max - min;
+        if range == 0.0 {
+            return min;
+        }
+        let u = rng.next_f64();
+        if u < (mode - min) / range {
+            min + (u * range * (mode - min)).sqrt()
+        } else {
+            max - ((1.0 - u) * range * (max - mode)).sqrt()
+        }
+    }
+}
+
+/// Erlang distribution, the sum of `shape` exponentials, with the given overall mean.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Erlang {
+    shape: u32,
+    mean: f64,
+}
+
+impl Erlang {
+    /// # Panics
+    ///
+    /// Panics if `shape` is zero.
+    #[must_use]
+    pub fn new(shape: u32, mean: Duration) -> Self {
+        assert!(shape > 0, "The shape of an Erlang distribution must be positive");
+        Self {
+            shape,
+            mean: mean.as_secs_f64(),
+        }
+    }
+}
+
+impl Distribution for Erlang {
+    fn sample_secs(&self, rng: &SimRng) -> f64 {
+        let phase_mean = self.mean / f64::from(self.shape);
+        (0..self.shape)
+            .map(|_| -phase_mean * (1.0 - rng.next_f64()).ln())
+            .sum()
+    }
+}
+
+/// Weibull distribution with a dimensionless `shape` and a `scale`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Weibull {
+    shape: f64,
+    scale: f64,
+}
+
+impl Weibull {
+    /// # Panics
+    ///
+    /// Panics if `shape` isn't positive.
+    #[must_use]
+    pub fn new(shape: f64, scale: Duration) -> Self {
+        assert!(shape > 0.0, "The shape of a Weibull distribution must be positive");
+        Self {
+            shape,
+            scale: scale.as_secs_f64(),
+        }
+    }
+}
+
+impl Distribution for Weibull {
+    fn sample_secs(&self, rng: &SimRng) -> f64 {
+        self.scale * (-(1.0 - rng.next_f64()).ln()).powf(1.0 / self.shape)
+    }
+}
+
+/// Empirical distribution resampling observed durations, each with the same probability.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Empirical {
+    values: Vec<f64>,
+}
+
+impl Empirical {
+    /// # Panics
+    ///
+    /// Panics if `observations` is empty.
+    #[must_use]
+    pub fn new(observations: impl IntoIterator<Item = Duration>) -> Self {
+        let values: Vec<f64> = observations.into_iter().map(|value| value.as_secs_f64()).collect();
+        assert!(!values.is_empty(), "An empirical distribution needs at least one observation");
+        Self { values }
+    }
+}
+
+impl Distribution for Empirical {
+    fn sample_secs(&self, rng: &SimRng) -> f64 {
+        let index = (rng.next_f64() * self.values.len() as f64) as usize;
+        self.values[index.min(self.values.len() - 1)]
+    }
+}
+
+/// A fixed duration, always sampled as itself.
+impl Distribution for Duration {
+    fn sample_secs(&self, _: &SimRng) -> f64 {
+        self.as_secs_f64()
+    }
+
+    fn sample(&self, _: &SimRng) -> Duration {
+        *self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn mean(distribution: impl Distribution, rng: &SimRng) -> f64 {
+        let samples = 20_000;
+        (0..samples).map(|_| distribution.sample(rng).as_secs_f64()).sum::<f64>() / f64::from(samples)
+    }
+
+    #[test]
+    fn sample_means_match() {
+        let rng = SimRng::new(1);
+        let secs = Duration::from_secs;
+        let cases = [
+            (mean(Exponential::new(secs(4)), &rng), 4.0),
+            (mean(Uniform::new(secs(2), secs(6)), &rng), 4.0),
+            (mean(Normal::new(secs(10), secs(1)), &rng), 10.0),
+            (mean(Triangular::new(secs(0), secs(3), secs(9)), &rng), 4.0),
+            (mean(Erlang::new(3, secs(6)), &rng), 6.0),
+            // Shape 1 is the exponential distribution.
+            (mean(Weibull::new(1.0, secs(2)), &rng), 2.0),
+            (mean(Empirical::new([secs(1), secs(2), secs(6)]), &rng), 3.0),
+        ];
+        for (sampled, expected) in cases {
+            assert!((sampled - expected).abs() < 0.05 * expected, "{} vs {}", sampled, expected);
+        }
+
+        assert_eq!(secs(3), secs(3).sample(&rng));
+        assert_eq!(Duration::ZERO, to_duration(-1.0));
+        let mut sampler = Exponential::new(secs(1)).sampler(SimRng::new(5));
+        let reference = SimRng::new(5);
+        assert_eq!(Exponential::new(secs(1)).sample(&reference), sampler());
+    }
+}
@@ -0,0 +1,143 @@
+use crate::{Attributes, GenBoxed, Key, LocalStore, SharedState, SimRng};
+
+/// An entity defined as a struct, its fields holding the parameters of each instance.
+///
+/// With the `macros` feature it's derived with `#[derive(Entity)]`, which takes the name of the struct as
+/// [`Entity::CLASS`] unless given with `#[entity(class = "...")]`, and attaches the fields marked with
+/// `#[entity(attribute)]` as [`Attributes`] of the entity. Together with [`Process`] the struct is added with
+/// [`Simulation::add_entity`](crate::Simulation::add_entity), or in bulk from configuration data with
+/// [`Simulation::add_entities`](crate::Simulation::add_entities).
+///
+/// ```ignore
+/// #[derive(Entity)]
+/// struct Truck {
+///     #[entity(attribute)]
+///     capacity: Capacity,
+///     trip: Duration,
+/// }
+///
+/// impl Process for Truck {
+///     fn run(self, _: EntityContext) -> GenBoxed<()> {
+///         Box::new(move |_| loop {
+///             yield Action::Hold(self.trip);
+///         })
+///     }
+/// }
+///
+/// let trucks = simulation.add_entities(config.trucks);
+/// ```
+pub trait Entity {
+    const CLASS: &'static str;
+
+    /// Returns the attributes the entity is added with, its class by default.
+    fn attributes(&self) -> Attributes {
+        Attributes::new().class(Self::CLASS)
+    }
+}
+
+/// Converts an entity struct into the generator running it.
+pub trait Process<R = ()> {
+    fn run(self, context: EntityContext) -> GenBoxed<R>;
+}
+
+/// What an entity struct is given to build its generator, see [`Process`].
+///
+/// The handles are shared with the simulation, so they can be moved into the generator.
+#[derive(Clone)]
+pub struct EntityContext {
+    key: Key,
+    state: SharedState,
+    local: LocalStore,
+    rng: SimRng,
+}
+
+impl EntityContext {
+    pub(crate) fn new(key: Key, state: SharedState, local: LocalStore, rng: SimRng) -> Self {
+        Self { key, state, local, rng }
+    }
+
+    /// Returns the key the entity is added with.
+    #[must_use]
+    pub fn key(&self) -> Key {
+        self.key
+    }
+
+    /// Returns the state shared by every entity, see [`Simulation::state`](crate::Simulation::state).
+    #[must_use]
+    pub fn state(&self) -> SharedState {
+        self.state.clone()
+    }
+
+    /// Returns the store of entity-private data, see [`LocalStore`].
+    #[must_use]
+    pub fn local_store(&self) -> LocalStore {
+        self.local.clone()
+    }
+
+    /// Returns the random number generator of the simulation.
+    #[must_use]
+    pub fn rng(&self) -> SimRng {
+        self.rng.clone()
+    }
+}
+
+#[cfg(all(test, feature = "macros"))]
+mod test {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{process, Action, Simulation};
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    struct Capacity(u32);
+
+    #[derive(crate::Entity)]
+    #[entity(class = "vehicle")]
+    struct Truck {
+        #[entity(attribute)]
+        capacity: Capacity,
+        trip: Duration,
+        arrivals: Rc<RefCell<Vec<(Key, Duration)>>>,
+    }
+
+    impl Process for Truck {
+        fn run(self, context: EntityContext) -> GenBoxed<()> {
+            let mut travelled = false;
+            process(move |_| {
+                if travelled {
+                    self.arrivals.borrow_mut().push((context.key(), self.trip));
+                    return None;
+                }
+                travelled = true;
+                Some(Action::Hold(self.trip))
+            })
+        }
+    }
+
+    #[test]
+    fn derived_entities_are_added_with_their_attributes() {
+        let mut simulation = Simulation::default();
+        let arrivals = Rc::default();
+        let trucks = simulation.add_entities((1..=3).map(|n| Truck {
+            capacity: Capacity(10 * n),
+            trip: Duration::from_secs(u64::from(n)),
+            arrivals: Rc::clone(&arrivals),
+        }));
+        for &truck in &trucks {
+            simulation.schedule_now(truck);
+        }
+        simulation.run_until_empty();
+
+        let attributes = simulation.attributes();
+        assert_eq!(trucks, attributes.of_class("vehicle"));
+        assert_eq!(Some(Capacity(20)), attributes.get(trucks[1]));
+        let expected: Vec<_> = trucks
+            .iter()
+            .zip(1..)
+            .map(|(&key, seconds)| (key, Duration::from_secs(seconds)))
+            .collect();
+        assert_eq!(expected, *arrivals.borrow());
+    }
+}
@@ -0,0 +1,144 @@
+use std::fmt;
+
+use crate::Key;
+
+/// An invalid action yielded by an entity, returned by [`Simulation::step_with`](crate::Simulation::step_with)
+/// and reported by the run methods as [`RunStatus::Failed`](crate::RunStatus::Failed).
+///
+/// `key` is always the entity that yielded the action, it isn't rescheduled so the rest of the model
+/// can keep running. In [strict mode](crate::Simulation::set_strict) these errors panic instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimulationError {
+    /// A passive entity yielded a hold.
+    HoldWhilePassive { key: Key },
+    /// A passive entity yielded a passivate.
+    PassivateWhilePassive { key: Key },
+    /// A passive entity activated another entity.
+    ActivateWhilePassive { key: Key },
+    /// An entity activated `other`, which was already active.
+    AlreadyActive { key: Key, other: Key },
+    /// A passive entity cancelled another entity.
+    CancelWhilePassive { key: Key, other: Key },
+    /// An entity referred to `other`, which doesn't exist, usually because it already completed.
+    StaleKey { key: Key, other: Key },
+    /// A passive entity preempted another entity.
+    PreemptWhilePassive { key: Key, other: Key },
+    /// An entity preempted `other`, which wasn't holding.
+    NotHolding { key: Key, other: Key },
+    /// A passive entity broadcast a signal.
+    BroadcastWhilePassive { key: Key },
+    /// A passive entity terminated another entity.
+    TerminateWhilePassive { key: Key, other: Key },
+}
+
+impl SimulationError {
+    /// Returns the entity that yielded the invalid action.
+    #[must_use]
+    pub fn key(&self) -> Key {
+        match *self {
+            Self::HoldWhilePassive { key }
+            | Self::PassivateWhilePassive { key }
+            | Self::ActivateWhilePassive { key }
+            | Self::AlreadyActive { key, .. }
+            | Self::CancelWhilePassive { key, .. }
+            | Self::StaleKey { key, .. }
+            | Self::PreemptWhilePassive { key, .. }
+            | Self::NotHolding { key, .. }
+            | Self::BroadcastWhilePassive { key }
+            | Self::TerminateWhilePassive { key, .. } => key,
+        }
+    }
+}
+
+impl fmt::Display for SimulationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::HoldWhilePassive { key } => write!(f, "A passive entity received a hold command. ID = {}", key.id()),
+            Self::PassivateWhilePassive { key } => {
+                write!(f, "A passive entity received a passivate command. ID = {}", key.id())
+            }
+            Self::ActivateWhilePassive { key } => write!(f, "A passive entity sent an activate. ID = {}", key.id()),
+            Self::AlreadyActive { key, other } => write!(
+                f,
+                "Entity ID = {} tried to Activate Entity ID = {} but it was already active",
+                key.id(),
+                other.id()
+            ),
+            Self::CancelWhilePassive { key, other } => write!(
+                f,
+                "A passive entity did a Cancel. ID = {} to ID = {}",
+                key.id(),
+                other.id()
+            ),
+            Self::StaleKey { key, other } => write!(
+                f,
+                "Entity ID = {} referred to Entity ID = {} which doesn't exist",
+                key.id(),
+                other.id()
+            ),
+            Self::PreemptWhilePassive { key, other } => write!(
+                f,
+                "A passive entity did a Preempt. ID = {} to ID = {}",
+                key.id(),
+                other.id()
+            ),
+            Self::NotHolding { key, other } => write!(
+                f,
+                "Entity ID = {} sent Preempt to ID = {} and it wasn't holding",
+                key.id(),
+                other.id()
+            ),
+            Self::BroadcastWhilePassive { key } => write!(f, "A passive entity did a Broadcast. ID = {}", key.id()),
+            Self::TerminateWhilePassive { key, other } => write!(
+                f,
+                "A passive entity did a Terminate. ID = {} to ID = {}",
+                key.id(),
+                other.id()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SimulationError {}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{process, Action, RunStatus, Simulation, StepOutcome};
+
+    #[test]
+    fn invalid_actions_are_reported() {
+        let mut simulation = Simulation::default();
+        let holder = simulation.add_generator(process(|_| Some(Action::Hold(Duration::from_secs(5)))));
+        let mut activated = false;
+        let activator = simulation.add_generator(process(move |_| {
+            if activated {
+                return None;
+            }
+            activated = true;
+            Some(Action::ActivateOne(holder))
+        }));
+        simulation.schedule_now(holder);
+        simulation.schedule(Duration::from_secs(1), activator);
+
+        assert_eq!(Ok(StepOutcome::Advance), simulation.step().map(|step| step.outcome()));
+        let error = simulation.step().unwrap_err();
+        assert_eq!(SimulationError::AlreadyActive { key: activator, other: holder }, error);
+        assert_eq!(activator, error.key());
+
+        // The holder is still scheduled, the failing entity isn't.
+        assert_eq!(Ok(StepOutcome::Advance), simulation.step().map(|step| step.outcome()));
+        assert_eq!(Duration::from_secs(5), simulation.time());
+
+        let completed = simulation.add_generator(process(|_| None));
+        let activator = simulation.add_generator(process(move |_| Some(Action::ActivateOne(completed))));
+        simulation.schedule_now(completed);
+        simulation.schedule(Duration::from_secs(1), activator);
+        assert_eq!(
+            RunStatus::Failed(SimulationError::StaleKey { key: activator, other: completed }),
+            simulation.run_with_limit(Duration::from_secs(20))
+        );
+    }
+}
@@ -0,0 +1,346 @@
+//! Experiments described by configuration files, enabled by the `serde` feature.
+//!
+//! An [`Experiment`] names a model registered in a [`ModelRegistry`] and gives its parameters, the seed, the run
+//! length, the warm-up and the number of replications, optionally for several scenarios overriding parameters.
+//! New scenarios are then a matter of editing a file instead of recompiling the model:
+//!
+//! ```json
+//! {
+//!     "model": "bank",
+//!     "parameters": { "tellers": 2, "mean service": 4.5 },
+//!     "seed": 42,
+//!     "replications": 30,
+//!     "run_length": 28800,
+//!     "warm_up": 3600,
+//!     "scenarios": [{ "name": "three tellers", "parameters": { "tellers": 3 } }]
+//! }
+//! ```
+//!
+//! ```ignore
+//! let mut models = ModelRegistry::new();
+//! models.register("bank", |run| {
+//!     let tellers: usize = run.parameter("tellers")?;
+//!     let mut simulation = Simulation::default();
+//!     // Build the model...
+//!     run.output("mean wait", move || wait.mean().unwrap_or(0.0));
+//!     Ok(simulation)
+//! });
+//! let results = models.run(&Experiment::read_json("bank.json")?)?;
+//! ```
+//!
+//! [`Experiment`] implements `Deserialize`, so other formats work through their serde crate, e.g.
+//! `toml::from_str::<Experiment>(&text)` for TOML files. Times are in seconds.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{Replication, Replications, Replicator, Simulation};
+
+fn one() -> usize {
+    1
+}
+
+/// The description of the runs of an experiment, see the [module documentation](self).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Experiment {
+    /// The name the model was registered with.
+    pub model: String,
+    #[serde(default)]
+    pub parameters: Parameters,
+    /// The base seed of the replications, see [`Replicator::base_seed`].
+    #[serde(default)]
+    pub seed: u64,
+    #[serde(default = "one")]
+    pub replications: usize,
+    /// The time limit of every run in seconds, runs go on until no more events are left if it's missing.
+    #[serde(default)]
+    pub run_length: Option<f64>,
+    /// The warm-up period in seconds, see [`Simulation::set_warm_up`].
+    #[serde(default)]
+    pub warm_up: Option<f64>,
+    /// Variants of the base parameters, every scenario is replicated on its own. Without scenarios the base
+    /// parameters are run as a scenario named `"base"`.
+    #[serde(default)]
+    pub scenarios: Vec<Scenario>,
+}
+
+/// A named variant of the parameters of an [`Experiment`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    pub name: String,
+    /// The parameters replacing those of the experiment.
+    #[serde(default)]
+    pub parameters: Parameters,
+}
+
+/// The parameters of a model, any JSON value by name.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Parameters(pub BTreeMap<String, Value>);
+
+impl Parameters {
+    /// Returns the parameter `name` converted to `T`.
+    pub fn get<T: DeserializeOwned>(&self, name: &str) -> Result<T, ExperimentError> {
+        let value = self
+            .0
+            .get(name)
+            .ok_or_else(|| ExperimentError::MissingParameter(name.to_owned()))?;
+        serde_json::from_value(value.clone()).map_err(|error| ExperimentError::InvalidParameter {
+            name: name.to_owned(),
+            message: error.to_string(),
+        })
+    }
+
+    // Returns these parameters with `overrides` replacing them.
+    fn merged(&self, overrides: &Parameters) -> Parameters {
+        let mut parameters = self.0.clone();
+        parameters.extend(overrides.0.iter().map(|(name, value)| (name.clone(), value.clone())));
+        Parameters(parameters)
+    }
+}
+
+/// Error produced while loading or running an [`Experiment`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExperimentError {
+    /// The configuration couldn't be read or parsed.
+    Config(String),
+    /// No model was registered with this name.
+    UnknownModel(String),
+    MissingParameter(String),
+    /// The parameter can't be converted to the type asked by the model.
+    InvalidParameter { name: String, message: String },
+    /// The model factory failed for another reason.
+    Model(String),
+}
+
+impl fmt::Display for ExperimentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Config(message) => write!(f, "invalid experiment configuration: {}", message),
+            Self::UnknownModel(name) => write!(f, "no model named `{}`", name),
+            Self::MissingParameter(name) => write!(f, "missing parameter `{}`", name),
+            Self::InvalidParameter { name, message } => write!(f, "invalid parameter `{}`: {}", name, message),
+            Self::Model(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for ExperimentError {}
+
+impl Experiment {
+    /// Parse an experiment from JSON.
+    pub fn from_json(json: &str) -> Result<Self, ExperimentError> {
+        serde_json::from_str(json).map_err(|error| ExperimentError::Config(error.to_string()))
+    }
+
+    /// Read an experiment from a JSON file.
+    pub fn read_json(path: impl AsRef<Path>) -> Result<Self, ExperimentError> {
+        let json = fs::read_to_string(path).map_err(|error| ExperimentError::Config(error.to_string()))?;
+        Self::from_json(&json)
+    }
+
+    /// Returns the scenarios to run, `"base"` alone if none is given, with their complete parameters.
+    #[must_use]
+    pub fn resolved_scenarios(&self) -> Vec<(String, Parameters)> {
+        if self.scenarios.is_empty() {
+            return vec![("base".to_owned(), self.parameters.clone())];
+        }
+        self.scenarios
+            .iter()
+            .map(|scenario| (scenario.name.clone(), self.parameters.merged(&scenario.parameters)))
+            .collect()
+    }
+}
+
+/// The context of a run, passed to the model factories registered in a [`ModelRegistry`].
+pub struct ModelRun<'a> {
+    parameters: &'a Parameters,
+    outputs: Vec<(String, Box<dyn Fn() -> f64>)>,
+}
+
+impl ModelRun<'_> {
+    /// Returns the parameter `name` converted to `T`, with the scenario applied.
+    pub fn parameter<T: DeserializeOwned>(&self, name: &str) -> Result<T, ExperimentError> {
+        self.parameters.get(name)
+    }
+
+    #[must_use]
+    pub fn parameters(&self) -> &Parameters {
+        self.parameters
+    }
+
+    /// Record the value returned by `value` at the end of the run as the metric `name` of the replication.
+    pub fn output(&mut self, name: impl Into<String>, value: impl Fn() -> f64 + 'static) {
+        self.outputs.push((name.into(), Box::new(value)));
+    }
+}
+
+type ModelFactory = Box<dyn Fn(&mut ModelRun<'_>) -> Result<Simulation<()>, ExperimentError>>;
+
+/// The models experiments can run, by name.
+#[derive(Default)]
+pub struct ModelRegistry {
+    factories: HashMap<String, ModelFactory>,
+}
+
+/// The results of [`ModelRegistry::run`], the replications of every scenario in the order of the experiment.
+#[derive(Debug, Clone)]
+pub struct ExperimentResults {
+    pub scenarios: Vec<(String, Replications)>,
+}
+
+impl ExperimentResults {
+    /// Returns the replications of the scenario `name`.
+    #[must_use]
+    pub fn scenario(&self, name: &str) -> Option<&Replications> {
+        self.scenarios
+            .iter()
+            .find(|(scenario, _)| scenario == name)
+            .map(|(_, replications)| replications)
+    }
+}
+
+impl ModelRegistry {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the function building the model `name`, with its entities scheduled, from the parameters of a run.
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        factory: impl Fn(&mut ModelRun<'_>) -> Result<Simulation<()>, ExperimentError> + 'static,
+    ) -> &mut Self {
+        self.factories.insert(name.into(), Box::new(factory));
+        self
+    }
+
+    /// Run every replication of every scenario of `experiment`, stopping at the first error.
+    pub fn run(&self, experiment: &Experiment) -> Result<ExperimentResults, ExperimentError> {
+        let factory = self
+            .factories
+            .get(&experiment.model)
+            .ok_or_else(|| ExperimentError::UnknownModel(experiment.model.clone()))?;
+        let replicator = Replicator::new(experiment.replications).base_seed(experiment.seed);
+        let seconds = |value: Option<f64>, field: &str| {
+            value
+                .map(|seconds| {
+                    std::time::Duration::try_from_secs_f64(seconds)
+                        .map_err(|error| ExperimentError::Config(format!("{}: {}", field, error)))
+                })
+                .transpose()
+        };
+        let run_length = seconds(experiment.run_length, "run_length")?;
+        let warm_up = seconds(experiment.warm_up, "warm_up")?;
+
+        let mut scenarios = Vec::new();
+        for (name, parameters) in experiment.resolved_scenarios() {
+            let mut failure = None;
+            let replications = replicator.run(|replication: &mut Replication| {
+                if failure.is_some() {
+                    return;
+                }
+                let mut run = ModelRun {
+                    parameters: &parameters,
+                    outputs: Vec::new(),
+                };
+                let mut simulation = match factory(&mut run) {
+                    Ok(simulation) => simulation,
+                    Err(error) => {
+                        failure = Some(error);
+                        return;
+                    }
+                };
+                simulation.set_seed(replication.seed());
+                if let Some(warm_up) = warm_up {
+                    simulation.set_warm_up(warm_up);
+                }
+                match run_length {
+                    Some(limit) => simulation.run_with_limit(limit),
+                    None => simulation.run_until_empty(),
+                };
+                for (metric, value) in &run.outputs {
+                    replication.record(metric.clone(), value());
+                }
+            });
+            if let Some(error) = failure {
+                return Err(error);
+            }
+            scenarios.push((name, replications));
+        }
+        Ok(ExperimentResults { scenarios })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{process, Action};
+
+    fn registry() -> ModelRegistry {
+        let mut models = ModelRegistry::new();
+        models.register("ticker", |run| {
+            let period: f64 = run.parameter("period")?;
+            let mut simulation = Simulation::default();
+            let ticks = Rc::new(Cell::new(0u32));
+            let counter = Rc::clone(&ticks);
+            let key = simulation.add_generator(process(move |_| {
+                counter.set(counter.get() + 1);
+                Some(Action::Hold(Duration::from_secs_f64(period)))
+            }));
+            simulation.schedule_now(key);
+            run.output("ticks", move || f64::from(ticks.get()));
+            Ok(simulation)
+        });
+        models
+    }
+
+    #[test]
+    fn experiments_run_every_scenario() {
+        let experiment = Experiment::from_json(
+            r#"{
+                "model": "ticker",
+                "parameters": { "period": 2.0 },
+                "replications": 3,
+                "run_length": 10,
+                "warm_up": 4,
+                "scenarios": [{ "name": "slow" }, { "name": "fast", "parameters": { "period": 0.5 } }]
+            }"#,
+        )
+        .unwrap();
+        let results = registry().run(&experiment).unwrap();
+
+        assert_eq!(vec![6.0; 3], results.scenario("slow").unwrap().values("ticks"));
+        assert_eq!(vec![21.0; 3], results.scenario("fast").unwrap().values("ticks"));
+        let seeds: Vec<u64> = results.scenarios[0].1.runs().iter().map(Replication::seed).collect();
+        assert_eq!(Replicator::new(3).seed(1), seeds[1]);
+    }
+
+    #[test]
+    fn invalid_experiments_are_reported() {
+        let unknown = Experiment::from_json(r#"{ "model": "bank" }"#).unwrap();
+        assert_eq!(Err(ExperimentError::UnknownModel("bank".to_owned())), registry().run(&unknown).map(|_| ()));
+        let missing = Experiment::from_json(r#"{ "model": "ticker" }"#).unwrap();
+        assert_eq!(
+            Err(ExperimentError::MissingParameter("period".to_owned())),
+            registry().run(&missing).map(|_| ())
+        );
+        assert!(matches!(
+            Experiment::from_json(r#"{ "model": "ticker", "length": 10 }"#),
+            Err(ExperimentError::Config(_))
+        ));
+    }
+}
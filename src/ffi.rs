@@ -0,0 +1,421 @@
+//! A C API to embed the engine in C and C++ applications, enabled by the `ffi` feature.
+//!
+//! Build the crate as a `cdylib` or `staticlib` (e.g. `cargo rustc --release --features ffi --crate-type cdylib`)
+//! and include `include/rustsim.h` in the host. The simulation is an opaque pointer and entities are identified by
+//! nonzero `uint64_t` keys. Entities are C callbacks resumed with their user data and the simulation time, returning
+//! the next action:
+//!
+//! ```c
+//! RustsimAction customer(void *data, double time) {
+//!     int *visits = data;
+//!     if ((*visits)++ < 3) return (RustsimAction){ RUSTSIM_HOLD, 1.5, 0 };
+//!     return (RustsimAction){ RUSTSIM_COMPLETE, 0.0, 0 };
+//! }
+//!
+//! RustsimSimulation *simulation = rustsim_simulation_new();
+//! uint64_t key = rustsim_add_entity(simulation, customer, &visits);
+//! rustsim_schedule(simulation, key, 0.0);
+//! while (rustsim_step(simulation) == RUSTSIM_OK) {}
+//! rustsim_simulation_free(simulation);
+//! ```
+//!
+//! No panic crosses the boundary: functions report failures through their return value and
+//! [`rustsim_last_error`] describes the last one.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_void, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::{process, Action, Key, Simulation, TraceRecorder};
+
+/// The step was executed.
+pub const RUSTSIM_OK: i32 = 0;
+/// No more events are left.
+pub const RUSTSIM_EXHAUSTED: i32 = 1;
+/// An entity yielded an invalid action, or its callback returned one, the simulation can still be stepped.
+pub const RUSTSIM_INVALID_ACTION: i32 = -1;
+/// A null pointer, a stale key or an invalid time was passed.
+pub const RUSTSIM_INVALID_ARGUMENT: i32 = -2;
+/// The engine panicked, the simulation shouldn't be used anymore.
+pub const RUSTSIM_PANICKED: i32 = -3;
+
+pub const RUSTSIM_COMPLETE: u32 = 0;
+/// Hold for `duration` seconds, which must be a non-negative number.
+pub const RUSTSIM_HOLD: u32 = 1;
+pub const RUSTSIM_PASSIVATE: u32 = 2;
+/// Activate the passive entity `other`.
+pub const RUSTSIM_ACTIVATE: u32 = 3;
+/// Cancel the pending event of `other`.
+pub const RUSTSIM_CANCEL: u32 = 4;
+
+/// The action returned by an entity callback.
+///
+/// An unknown kind, an invalid duration or a zero key makes the step return [`RUSTSIM_INVALID_ACTION`], and the
+/// entity is left passive.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct RustsimAction {
+    pub kind: u32,
+    pub duration: f64,
+    pub other: u64,
+}
+
+/// An entity: called with its user data and the simulation time in seconds every time it's resumed.
+pub type RustsimEntity = extern "C" fn(user_data: *mut c_void, time: f64) -> RustsimAction;
+
+/// The opaque simulation handed to C.
+pub struct RustsimSimulation {
+    simulation: Simulation<()>,
+    trace: TraceRecorder,
+    last_error: Option<CString>,
+    // Why the callback resumed in the current step returned an invalid action.
+    rejected: Rc<RefCell<Option<String>>>,
+}
+
+impl RustsimSimulation {
+    fn fail(&mut self, code: i32, message: impl Into<String>) -> i32 {
+        // Interior NUL bytes are the only reason `CString::new` fails, they can't come from our messages.
+        self.last_error = CString::new(message.into()).ok();
+        code
+    }
+}
+
+// Ids are offset by one so no key is 0, which reports failures.
+fn encode(key: Key) -> u64 {
+    (u64::from(key.generation) << 32) | (key.id as u64 + 1)
+}
+
+fn decode(key: u64) -> Option<Key> {
+    let id = (key & u64::from(u32::MAX)).checked_sub(1)?;
+    Some(Key::with_generation(id as usize, (key >> 32) as u32))
+}
+
+// The engine action for what a callback returned, or why it's invalid.
+fn to_action(action: RustsimAction) -> Result<Option<Action>, String> {
+    let other = || decode(action.other).ok_or_else(|| "an entity referred to the invalid key 0".to_owned());
+    match action.kind {
+        RUSTSIM_COMPLETE => Ok(None),
+        RUSTSIM_HOLD => match to_duration(action.duration) {
+            Some(duration) => Ok(Some(Action::Hold(duration))),
+            None => Err(format!("an entity returned the invalid hold duration {}", action.duration)),
+        },
+        RUSTSIM_PASSIVATE => Ok(Some(Action::Passivate)),
+        RUSTSIM_ACTIVATE => Ok(Some(Action::ActivateOne(other()?))),
+        RUSTSIM_CANCEL => Ok(Some(Action::Cancel(other()?))),
+        kind => Err(format!("an entity returned the unknown action kind {}", kind)),
+    }
+}
+
+fn to_duration(seconds: f64) -> Option<Duration> {
+    Duration::try_from_secs_f64(seconds).ok()
+}
+
+// Run `f` on the simulation behind `simulation`, turning null pointers and panics into error codes.
+unsafe fn guarded(simulation: *mut RustsimSimulation, f: impl FnOnce(&mut RustsimSimulation) -> i32) -> i32 {
+    // SAFETY: the caller guarantees the pointer is null or was returned by `rustsim_simulation_new`.
+    let Some(simulation) = (unsafe { simulation.as_mut() }) else {
+        return RUSTSIM_INVALID_ARGUMENT;
+    };
+    match panic::catch_unwind(AssertUnwindSafe(|| f(simulation))) {
+        Ok(code) => code,
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<String>()
+                .map(String::as_str)
+                .or_else(|| payload.downcast_ref::<&str>().copied())
+                .unwrap_or("the engine panicked")
+                .to_owned();
+            simulation.fail(RUSTSIM_PANICKED, message)
+        }
+    }
+}
+
+/// Create a simulation recording its trace, to be released with [`rustsim_simulation_free`].
+#[no_mangle]
+pub extern "C" fn rustsim_simulation_new() -> *mut RustsimSimulation {
+    let mut simulation = Simulation::default();
+    let trace = simulation.record_trace();
+    Box::into_raw(Box::new(RustsimSimulation {
+        simulation,
+        trace,
+        last_error: None,
+        rejected: Rc::default(),
+    }))
+}
+
+/// Release a simulation created with [`rustsim_simulation_new`], null is ignored.
+///
+/// # Safety
+///
+/// `simulation` must be null or a pointer returned by [`rustsim_simulation_new`] not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn rustsim_simulation_free(simulation: *mut RustsimSimulation) {
+    if !simulation.is_null() {
+        // SAFETY: guaranteed by the caller.
+        drop(unsafe { Box::from_raw(simulation) });
+    }
+}
+
+/// Add an entity resumed by calling `entity` with `user_data`, returning its key, or 0 if `simulation` is null.
+///
+/// # Safety
+///
+/// `simulation` must be null or a live pointer returned by [`rustsim_simulation_new`], and `user_data` must stay
+/// valid for as long as the entity can be resumed.
+#[no_mangle]
+pub unsafe extern "C" fn rustsim_add_entity(
+    simulation: *mut RustsimSimulation,
+    entity: RustsimEntity,
+    user_data: *mut c_void,
+) -> u64 {
+    // SAFETY: guaranteed by the caller.
+    let Some(simulation) = (unsafe { simulation.as_mut() }) else {
+        return 0;
+    };
+    let clock = simulation.simulation.clock();
+    let rejected = Rc::clone(&simulation.rejected);
+    let key = simulation.simulation.add_generator(process(move |_| {
+        match to_action(entity(user_data, clock.time().as_secs_f64())) {
+            Ok(action) => action,
+            Err(message) => {
+                // Reported by `rustsim_step`, the entity waits to be activated like after an engine error.
+                *rejected.borrow_mut() = Some(message);
+                Some(Action::Passivate)
+            }
+        }
+    }));
+    encode(key)
+}
+
+/// Schedule the entity `key` `delay` seconds from now.
+///
+/// # Safety
+///
+/// `simulation` must be null or a live pointer returned by [`rustsim_simulation_new`].
+#[no_mangle]
+pub unsafe extern "C" fn rustsim_schedule(simulation: *mut RustsimSimulation, key: u64, delay: f64) -> i32 {
+    // SAFETY: guaranteed by the caller.
+    unsafe {
+        guarded(simulation, |simulation| {
+            let Some(delay) = to_duration(delay) else {
+                return simulation.fail(RUSTSIM_INVALID_ARGUMENT, format!("invalid delay {}", delay));
+            };
+            let Some(key) = decode(key) else {
+                return simulation.fail(RUSTSIM_INVALID_ARGUMENT, "invalid key 0");
+            };
+            match simulation.simulation.schedule(delay, key) {
+                Some(_) => RUSTSIM_OK,
+                None => simulation.fail(RUSTSIM_INVALID_ARGUMENT, "the entity is stale or already scheduled"),
+            }
+        })
+    }
+}
+
+/// Execute the next event.
+///
+/// # Safety
+///
+/// `simulation` must be null or a live pointer returned by [`rustsim_simulation_new`].
+#[no_mangle]
+pub unsafe extern "C" fn rustsim_step(simulation: *mut RustsimSimulation) -> i32 {
+    // SAFETY: guaranteed by the caller.
+    unsafe {
+        guarded(simulation, |simulation| {
+            let result = simulation.simulation.step();
+            let rejected = simulation.rejected.borrow_mut().take();
+            if let Some(message) = rejected {
+                return simulation.fail(RUSTSIM_INVALID_ACTION, message);
+            }
+            match result {
+                Ok(step) if step.should_continue() => RUSTSIM_OK,
+                Ok(_) => RUSTSIM_EXHAUSTED,
+                Err(error) => simulation.fail(RUSTSIM_INVALID_ACTION, error.to_string()),
+            }
+        })
+    }
+}
+
+/// Returns the simulation time in seconds, NaN if `simulation` is null.
+///
+/// # Safety
+///
+/// `simulation` must be null or a live pointer returned by [`rustsim_simulation_new`].
+#[no_mangle]
+pub unsafe extern "C" fn rustsim_time(simulation: *const RustsimSimulation) -> f64 {
+    // SAFETY: guaranteed by the caller.
+    unsafe { simulation.as_ref() }.map_or(f64::NAN, |simulation| simulation.simulation.time().as_secs_f64())
+}
+
+/// Returns the trace recorded so far as CSV, to be released with [`rustsim_string_free`], or null if
+/// `simulation` is null.
+///
+/// # Safety
+///
+/// `simulation` must be null or a live pointer returned by [`rustsim_simulation_new`].
+#[no_mangle]
+pub unsafe extern "C" fn rustsim_trace_csv(simulation: *const RustsimSimulation) -> *mut c_char {
+    // SAFETY: guaranteed by the caller.
+    let Some(simulation) = (unsafe { simulation.as_ref() }) else {
+        return ptr::null_mut();
+    };
+    // Entity names can't be given through this API, so the CSV has no interior NUL bytes.
+    CString::new(simulation.trace.to_csv()).map_or(ptr::null_mut(), CString::into_raw)
+}
+
+/// Release a string returned by this API, null is ignored.
+///
+/// # Safety
+///
+/// `string` must be null or a pointer returned by [`rustsim_trace_csv`] not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn rustsim_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        // SAFETY: guaranteed by the caller.
+        drop(unsafe { CString::from_raw(string) });
+    }
+}
+
+/// Returns the message of the last failure, or null if there was none. The string belongs to the simulation and
+/// is valid until the next call taking it.
+///
+/// # Safety
+///
+/// `simulation` must be null or a live pointer returned by [`rustsim_simulation_new`].
+#[no_mangle]
+pub unsafe extern "C" fn rustsim_last_error(simulation: *const RustsimSimulation) -> *const c_char {
+    // SAFETY: guaranteed by the caller.
+    unsafe { simulation.as_ref() }
+        .and_then(|simulation| simulation.last_error.as_ref())
+        .map_or(ptr::null(), |error| error.as_ptr())
+}
+
+#[cfg(test)]
+mod test {
+    use std::ffi::CStr;
+
+    use super::*;
+
+    extern "C" fn customer(user_data: *mut c_void, _time: f64) -> RustsimAction {
+        // SAFETY: the tests pass a pointer to a live counter.
+        let visits = unsafe { &mut *user_data.cast::<u32>() };
+        *visits += 1;
+        let kind = if *visits <= 3 { RUSTSIM_HOLD } else { RUSTSIM_COMPLETE };
+        RustsimAction {
+            kind,
+            duration: 1.5,
+            other: 0,
+        }
+    }
+
+    extern "C" fn activator(user_data: *mut c_void, _time: f64) -> RustsimAction {
+        RustsimAction {
+            kind: RUSTSIM_ACTIVATE,
+            duration: 0.0,
+            other: user_data as u64,
+        }
+    }
+
+    #[test]
+    fn c_hosts_drive_simulations() {
+        let mut visits = 0u32;
+        unsafe {
+            let simulation = rustsim_simulation_new();
+            let key = rustsim_add_entity(simulation, customer, ptr::addr_of_mut!(visits).cast());
+            assert_eq!(RUSTSIM_OK, rustsim_schedule(simulation, key, 0.0));
+            assert_eq!(RUSTSIM_INVALID_ARGUMENT, rustsim_schedule(simulation, key, -1.0));
+            while rustsim_step(simulation) == RUSTSIM_OK {}
+            assert_eq!(4, visits);
+            assert_eq!(4.5, rustsim_time(simulation));
+
+            // The customer completed, activating it is an invalid action.
+            let other = rustsim_add_entity(simulation, activator, key as *mut c_void);
+            rustsim_schedule(simulation, other, 0.0);
+            assert_eq!(RUSTSIM_INVALID_ACTION, rustsim_step(simulation));
+            let error = CStr::from_ptr(rustsim_last_error(simulation)).to_str().unwrap();
+            assert!(error.contains("doesn't exist"));
+
+            let csv = rustsim_trace_csv(simulation);
+            assert!(CStr::from_ptr(csv).to_str().unwrap().starts_with("time,entity,action,argument\n"));
+            rustsim_string_free(csv);
+            rustsim_simulation_free(simulation);
+            assert_eq!(RUSTSIM_INVALID_ARGUMENT, rustsim_step(ptr::null_mut()));
+        }
+    }
+
+    // Returns the action pointed to by `user_data`.
+    extern "C" fn replaying(user_data: *mut c_void, _time: f64) -> RustsimAction {
+        // SAFETY: the tests pass a pointer to a live action.
+        unsafe { *user_data.cast::<RustsimAction>() }
+    }
+
+    #[test]
+    fn invalid_callback_actions_are_reported() {
+        let invalid = [
+            (RUSTSIM_HOLD, f64::NAN, "invalid hold duration NaN"),
+            (RUSTSIM_HOLD, -1.0, "invalid hold duration -1"),
+            (7, 0.0, "unknown action kind 7"),
+            (RUSTSIM_ACTIVATE, 0.0, "invalid key 0"),
+        ];
+        for (kind, duration, expected) in invalid {
+            let mut action = RustsimAction { kind, duration, other: 0 };
+            unsafe {
+                let simulation = rustsim_simulation_new();
+                assert!(rustsim_last_error(simulation).is_null());
+                let key = rustsim_add_entity(simulation, replaying, ptr::addr_of_mut!(action).cast());
+                assert_ne!(0, key);
+                rustsim_schedule(simulation, key, 0.0);
+                assert_eq!(RUSTSIM_INVALID_ACTION, rustsim_step(simulation));
+                let error = CStr::from_ptr(rustsim_last_error(simulation)).to_str().unwrap();
+                assert!(error.ends_with(expected), "{}", error);
+                // The entity was left passive instead of completing, so it can be resumed again.
+                assert_eq!(RUSTSIM_EXHAUSTED, rustsim_step(simulation));
+                assert_eq!(RUSTSIM_OK, rustsim_schedule(simulation, key, 1.0));
+                assert_eq!(RUSTSIM_INVALID_ACTION, rustsim_step(simulation));
+                assert_eq!(RUSTSIM_INVALID_ARGUMENT, rustsim_schedule(simulation, 0, 0.0));
+                rustsim_simulation_free(simulation);
+            }
+        }
+    }
+
+    #[test]
+    fn the_header_declares_the_api() {
+        let header = include_str!("../include/rustsim.h");
+        let constants = [
+            ("RUSTSIM_OK", RUSTSIM_OK),
+            ("RUSTSIM_EXHAUSTED", RUSTSIM_EXHAUSTED),
+            ("RUSTSIM_INVALID_ACTION", RUSTSIM_INVALID_ACTION),
+            ("RUSTSIM_INVALID_ARGUMENT", RUSTSIM_INVALID_ARGUMENT),
+            ("RUSTSIM_PANICKED", RUSTSIM_PANICKED),
+        ];
+        for (name, value) in constants {
+            assert!(header.contains(&format!("#define {} {}\n", name, value)), "{}", name);
+        }
+        let kinds = [
+            ("RUSTSIM_COMPLETE", RUSTSIM_COMPLETE),
+            ("RUSTSIM_HOLD", RUSTSIM_HOLD),
+            ("RUSTSIM_PASSIVATE", RUSTSIM_PASSIVATE),
+            ("RUSTSIM_ACTIVATE", RUSTSIM_ACTIVATE),
+            ("RUSTSIM_CANCEL", RUSTSIM_CANCEL),
+        ];
+        for (name, value) in kinds {
+            assert!(header.contains(&format!("#define {} {}u\n", name, value)), "{}", name);
+        }
+        let functions = [
+            "rustsim_simulation_new(",
+            "rustsim_simulation_free(",
+            "rustsim_add_entity(",
+            "rustsim_schedule(",
+            "rustsim_step(",
+            "rustsim_time(",
+            "rustsim_trace_csv(",
+            "rustsim_string_free(",
+            "rustsim_last_error(",
+        ];
+        for function in functions {
+            assert!(header.contains(function), "{}", function);
+        }
+    }
+}
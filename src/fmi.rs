@@ -0,0 +1,1016 @@
+//! Export a model as an FMI 3.0 co-simulation FMU, enabled by the `fmi` feature.
+//!
+//! An [`FmuModel`] wraps a [`Simulation`] and maps [`State`](crate::State) values and monitors to FMI variables.
+//! [`export_fmu!`](crate::export_fmu) defines the `fmi3*` functions of a `cdylib` crate around the function
+//! building the model, `fmi3DoStep` advancing simulated time:
+//!
+//! ```ignore
+//! fn bank() -> FmuModel {
+//!     let mut simulation = Simulation::default();
+//!     let waiting = simulation.state().with_mut(|state| state.insert(Vec::<Key>::new()));
+//!     // Add and schedule the entities...
+//!     let mut model = FmuModel::new("bank", simulation);
+//!     model.output("queue length", waiting, |queue| queue.len() as f64);
+//!     model
+//! }
+//!
+//! rustsim::export_fmu!(bank);
+//! ```
+//!
+//! The FMU is a zip archive with the model description and the library renamed after the model, written by
+//! [`FmuModel::write_fmu`] once the `cdylib` is built, e.g. from a test of the crate exporting the model:
+//!
+//! ```ignore
+//! bank().write_fmu("bank.fmu", "target/release/libbank.so")?;
+//! ```
+//!
+//! ```text
+//! bank.fmu
+//! ├── modelDescription.xml
+//! └── binaries/x86_64-linux/bank.so
+//! ```
+//!
+//! Only co-simulation and `Float64` variables are supported. Every function of the standard is defined, the others
+//! return `fmi3Error`, and instantiating another interface type returns null.
+
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::state::StateKey;
+use crate::trace::xml_escape;
+use crate::{Simulation, SimulationError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Causality {
+    Parameter,
+    Input,
+    Output,
+}
+
+type Getter = Box<dyn Fn() -> f64>;
+type Setter = Box<dyn Fn(f64)>;
+
+struct Variable {
+    name: String,
+    causality: Causality,
+    get: Getter,
+    set: Option<Setter>,
+}
+
+/// A model exported as an FMU, see the [module documentation](self).
+///
+/// Value reference 0 is the simulation time, the variables follow from 1 in the order they were added.
+pub struct FmuModel {
+    name: String,
+    simulation: Simulation<()>,
+    variables: Vec<Variable>,
+    time: Duration,
+}
+
+impl FmuModel {
+    /// Wrap a model whose entities are already added and scheduled. `name` is the model identifier, the name of
+    /// the library in the FMU.
+    pub fn new(name: impl Into<String>, simulation: Simulation<()>) -> Self {
+        let time = simulation.time();
+        Self {
+            name: name.into(),
+            simulation,
+            variables: Vec::new(),
+            time,
+        }
+    }
+
+    /// Add an output reading the value of `key` in the shared [`State`](crate::State) through `sample`.
+    pub fn output<V: 'static>(
+        &mut self,
+        name: impl Into<String>,
+        key: StateKey<V>,
+        sample: impl Fn(&V) -> f64 + 'static,
+    ) -> &mut Self {
+        let state = self.simulation.state();
+        let get = move || state.with(|state| state.get(key).map_or(f64::NAN, &sample));
+        self.add(name.into(), Causality::Output, Box::new(get), None)
+    }
+
+    /// Add an output returning the value of `value`, e.g. the mean of a [`Tally`](crate::Tally).
+    pub fn output_with(&mut self, name: impl Into<String>, value: impl Fn() -> f64 + 'static) -> &mut Self {
+        self.add(name.into(), Causality::Output, Box::new(value), None)
+    }
+
+    /// Add an input set by the importer between steps, written to the value of `key` with `apply`.
+    pub fn input<V: 'static>(
+        &mut self,
+        name: impl Into<String>,
+        key: StateKey<V>,
+        sample: impl Fn(&V) -> f64 + 'static,
+        apply: impl Fn(&mut V, f64) + 'static,
+    ) -> &mut Self {
+        let (get, set) = self.accessors(key, sample, apply);
+        self.add(name.into(), Causality::Input, get, Some(set))
+    }
+
+    /// Add a parameter, set by the importer before the initialization, written to the value of `key` with `apply`.
+    pub fn parameter<V: 'static>(
+        &mut self,
+        name: impl Into<String>,
+        key: StateKey<V>,
+        sample: impl Fn(&V) -> f64 + 'static,
+        apply: impl Fn(&mut V, f64) + 'static,
+    ) -> &mut Self {
+        let (get, set) = self.accessors(key, sample, apply);
+        self.add(name.into(), Causality::Parameter, get, Some(set))
+    }
+
+    fn accessors<V: 'static>(
+        &self,
+        key: StateKey<V>,
+        sample: impl Fn(&V) -> f64 + 'static,
+        apply: impl Fn(&mut V, f64) + 'static,
+    ) -> (Getter, Setter) {
+        let state = self.simulation.state();
+        let get = move || state.with(|state| state.get(key).map_or(f64::NAN, &sample));
+        let state = self.simulation.state();
+        let set = move |value| {
+            state.with_mut(|state| {
+                if let Some(current) = state.get_mut(key) {
+                    apply(current, value);
+                }
+            });
+        };
+        (Box::new(get), Box::new(set))
+    }
+
+    fn add(
+        &mut self,
+        name: String,
+        causality: Causality,
+        get: Getter,
+        set: Option<Setter>,
+    ) -> &mut Self {
+        assert!(
+            name != "time" && self.variables.iter().all(|variable| variable.name != name),
+            "an FMU variable named `{}` already exists",
+            name
+        );
+        self.variables.push(Variable {
+            name,
+            causality,
+            get,
+            set,
+        });
+        self
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the token identifying the model description, derived from the model name and its variables.
+    #[must_use]
+    pub fn instantiation_token(&self) -> String {
+        // FNV-1a, stable across builds unlike the standard library hasher.
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        let variables = self.variables.iter().map(|variable| (variable.name.as_str(), variable.causality));
+        let description = format!("{}{:?}", self.name, variables.collect::<Vec<_>>());
+        for byte in description.bytes() {
+            hash = (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3);
+        }
+        format!("{{rustsim-{:016x}}}", hash)
+    }
+
+    /// Returns the `modelDescription.xml` of the FMU, with the current values as start values.
+    #[must_use]
+    pub fn model_description(&self) -> String {
+        let name = xml_escape(&self.name);
+        let mut output = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let _ = writeln!(
+            output,
+            r#"<fmiModelDescription fmiVersion="3.0" modelName="{}" instantiationToken="{}" generationTool="RustSim">"#,
+            name,
+            self.instantiation_token()
+        );
+        let _ = writeln!(
+            output,
+            r#"  <CoSimulation modelIdentifier="{}" canHandleVariableCommunicationStepSize="true"/>"#,
+            name
+        );
+        output.push_str("  <ModelVariables>\n");
+        output.push_str(
+            "    <Float64 name=\"time\" valueReference=\"0\" causality=\"independent\" variability=\"continuous\"/>\n",
+        );
+        for (reference, variable) in (1..).zip(&self.variables) {
+            let (causality, variability) = match variable.causality {
+                Causality::Parameter => ("parameter", "fixed"),
+                Causality::Input => ("input", "discrete"),
+                Causality::Output => ("output", "discrete"),
+            };
+            let _ = write!(
+                output,
+                r#"    <Float64 name="{}" valueReference="{}" causality="{}" variability="{}""#,
+                xml_escape(&variable.name),
+                reference,
+                causality,
+                variability
+            );
+            if variable.causality == Causality::Output {
+                output.push_str("/>\n");
+            } else {
+                let _ = writeln!(output, r#" start="{}"/>"#, (variable.get)());
+            }
+        }
+        output.push_str("  </ModelVariables>\n  <ModelStructure>\n");
+        for (reference, variable) in (1..).zip(&self.variables) {
+            if variable.causality == Causality::Output {
+                let _ = writeln!(output, r#"    <Output valueReference="{}"/>"#, reference);
+            }
+        }
+        output.push_str("  </ModelStructure>\n</fmiModelDescription>\n");
+        output
+    }
+
+    /// Write the model description to `path`, see [`FmuModel::model_description`].
+    pub fn write_model_description(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.model_description())
+    }
+
+    /// Write the FMU to `path`: the model description, and `library`, the `cdylib` exporting the model with
+    /// [`export_fmu!`](crate::export_fmu) built for the current platform.
+    pub fn write_fmu(&self, path: impl AsRef<Path>, library: impl AsRef<Path>) -> io::Result<()> {
+        let binary = format!("binaries/{}/{}.{}", platform(), self.name, env::consts::DLL_EXTENSION);
+        let entries = [
+            ("modelDescription.xml".to_owned(), self.model_description().into_bytes()),
+            (binary, fs::read(library)?),
+        ];
+        fs::write(path, zip(&entries))
+    }
+
+    /// Execute every event up to and including the next communication point, `step` after the current one.
+    ///
+    /// An invalid action doesn't stop the step, the first one is returned once the communication point is reached.
+    pub fn do_step(&mut self, step: Duration) -> Result<(), SimulationError> {
+        let target = self.time + step;
+        let mut result = Ok(());
+        while self.simulation.next_event_time().is_some_and(|next| next <= target) {
+            if let Err(error) = self.simulation.step() {
+                result = result.and(Err(error));
+            }
+        }
+        self.time = target;
+        result
+    }
+
+    /// Returns the time of the last communication point.
+    #[must_use]
+    pub fn time(&self) -> Duration {
+        self.time
+    }
+
+    /// Returns the value of the variable with value reference `reference`.
+    #[must_use]
+    pub fn get(&self, reference: u32) -> Option<f64> {
+        match reference {
+            0 => Some(self.time.as_secs_f64()),
+            _ => self.variables.get(reference as usize - 1).map(|variable| (variable.get)()),
+        }
+    }
+
+    /// Set the input or parameter with value reference `reference`, returning `false` if there's none.
+    pub fn set(&mut self, reference: u32, value: f64) -> bool {
+        let variable = (reference as usize).checked_sub(1).and_then(|index| self.variables.get(index));
+        match variable.and_then(|variable| variable.set.as_ref()) {
+            Some(set) => {
+                set(value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the wrapped simulation.
+    pub fn simulation_mut(&mut self) -> &mut Simulation<()> {
+        &mut self.simulation
+    }
+}
+
+// The platform tuple of the standard for the current target, e.g. `x86_64-linux`.
+fn platform() -> String {
+    let architecture = match env::consts::ARCH {
+        "arm" => "aarch32",
+        architecture => architecture,
+    };
+    let system = match env::consts::OS {
+        "macos" => "darwin",
+        system => system,
+    };
+    format!("{}-{}", architecture, system)
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+// A zip archive storing `entries` uncompressed, by name.
+fn zip(entries: &[(String, Vec<u8>)]) -> Vec<u8> {
+    // Version 2.0, no flags, stored, at midnight on 1980-01-01.
+    const HEADER: [u16; 5] = [20, 0, 0, 0, 0x21];
+    let mut archive = Vec::new();
+    let mut directory = Vec::new();
+    for (name, data) in entries {
+        let offset = archive.len() as u32;
+        let mut fields = Vec::new();
+        for field in HEADER {
+            fields.extend_from_slice(&field.to_le_bytes());
+        }
+        for field in [crc32(data), data.len() as u32, data.len() as u32] {
+            fields.extend_from_slice(&field.to_le_bytes());
+        }
+        fields.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        // No extra field.
+        fields.extend_from_slice(&[0, 0]);
+
+        archive.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        archive.extend_from_slice(&fields);
+        archive.extend_from_slice(name.as_bytes());
+        archive.extend_from_slice(data);
+
+        directory.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        // Made by version 2.0.
+        directory.extend_from_slice(&20u16.to_le_bytes());
+        directory.extend_from_slice(&fields);
+        // No comment, disk 0, no attributes.
+        directory.extend_from_slice(&[0; 10]);
+        directory.extend_from_slice(&offset.to_le_bytes());
+        directory.extend_from_slice(name.as_bytes());
+    }
+    let offset = archive.len() as u32;
+    archive.extend_from_slice(&directory);
+    archive.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+    // Disk 0, with the whole directory.
+    archive.extend_from_slice(&[0; 4]);
+    for _ in 0..2 {
+        archive.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    }
+    archive.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+    archive.extend_from_slice(&offset.to_le_bytes());
+    // No comment.
+    archive.extend_from_slice(&[0, 0]);
+    archive
+}
+
+/// The implementation of the `fmi3*` functions defined by [`export_fmu!`](crate::export_fmu), not meant to be
+/// called directly.
+#[doc(hidden)]
+pub mod raw {
+    use std::ffi::{c_char, c_void, CStr};
+    use std::panic::{self, AssertUnwindSafe};
+    use std::ptr;
+    use std::time::Duration;
+
+    use super::FmuModel;
+
+    pub const OK: i32 = 0;
+    pub const WARNING: i32 = 1;
+    pub const ERROR: i32 = 3;
+    pub const FATAL: i32 = 4;
+
+    pub const VERSION: &CStr = c"3.0";
+
+    pub struct Instance {
+        build: fn() -> FmuModel,
+        model: FmuModel,
+        start: f64,
+    }
+
+    // Run `f` on the instance, turning null pointers and panics into error codes.
+    unsafe fn guarded(instance: *mut c_void, f: impl FnOnce(&mut Instance) -> i32) -> i32 {
+        // SAFETY: the importer passes null or a pointer returned by `instantiate`.
+        let Some(instance) = (unsafe { instance.cast::<Instance>().as_mut() }) else {
+            return ERROR;
+        };
+        panic::catch_unwind(AssertUnwindSafe(|| f(instance))).unwrap_or(FATAL)
+    }
+
+    /// # Safety
+    ///
+    /// `token` must be null or a NUL-terminated string.
+    pub unsafe fn instantiate(build: fn() -> FmuModel, token: *const c_char) -> *mut c_void {
+        let Ok(model) = panic::catch_unwind(build) else {
+            return ptr::null_mut();
+        };
+        if !token.is_null() {
+            // SAFETY: guaranteed by the caller.
+            let token = unsafe { CStr::from_ptr(token) };
+            if token.to_bytes() != model.instantiation_token().as_bytes() {
+                return ptr::null_mut();
+            }
+        }
+        let instance = Instance {
+            build,
+            model,
+            start: 0.0,
+        };
+        Box::into_raw(Box::new(instance)).cast()
+    }
+
+    /// # Safety
+    ///
+    /// `instance` must be null or a pointer returned by [`instantiate`] not freed yet.
+    pub unsafe fn free(instance: *mut c_void) {
+        if !instance.is_null() {
+            // SAFETY: guaranteed by the caller.
+            let instance = unsafe { Box::from_raw(instance.cast::<Instance>()) };
+            let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(instance)));
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `instance` must be null or a live pointer returned by [`instantiate`].
+    pub unsafe fn enter_initialization_mode(instance: *mut c_void, start: f64) -> i32 {
+        // SAFETY: guaranteed by the caller.
+        unsafe {
+            guarded(instance, |instance| {
+                instance.start = start;
+                OK
+            })
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `instance` must be null or a live pointer returned by [`instantiate`].
+    pub unsafe fn terminate(instance: *mut c_void) -> i32 {
+        // SAFETY: guaranteed by the caller.
+        unsafe {
+            guarded(instance, |instance| {
+                instance.model.simulation.finalize_with(|_| ());
+                OK
+            })
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `instance` must be null or a live pointer returned by [`instantiate`].
+    pub unsafe fn reset(instance: *mut c_void) -> i32 {
+        // SAFETY: guaranteed by the caller.
+        unsafe {
+            guarded(instance, |instance| {
+                instance.model = (instance.build)();
+                instance.start = 0.0;
+                OK
+            })
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `instance` must be null or a live pointer returned by [`instantiate`], `last_successful_time` null or valid
+    /// for writes.
+    pub unsafe fn do_step(instance: *mut c_void, current: f64, step: f64, last_successful_time: *mut f64) -> i32 {
+        // SAFETY: guaranteed by the caller.
+        unsafe {
+            guarded(instance, |instance| {
+                let Ok(step) = Duration::try_from_secs_f64(step) else {
+                    return ERROR;
+                };
+                // The importer and the model must agree on the communication point, up to the rounding of
+                // times the importer accumulates in `f64`.
+                let tolerance = (current.abs() * 1e-12).max(1e-9);
+                if (instance.start + instance.model.time.as_secs_f64() - current).abs() > tolerance {
+                    return ERROR;
+                }
+                let status = match instance.model.do_step(step) {
+                    Ok(()) => OK,
+                    Err(_) => WARNING,
+                };
+                if !last_successful_time.is_null() {
+                    *last_successful_time = instance.start + instance.model.time.as_secs_f64();
+                }
+                status
+            })
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `instance` must be null or a live pointer returned by [`instantiate`], `references` valid for `count` reads
+    /// and `values` for `count` writes.
+    pub unsafe fn get_float64(instance: *mut c_void, references: *const u32, values: *mut f64, count: usize) -> i32 {
+        // SAFETY: guaranteed by the caller.
+        unsafe {
+            guarded(instance, |instance| {
+                for index in 0..count {
+                    let reference = *references.add(index);
+                    let Some(value) = instance.model.get(reference) else {
+                        return ERROR;
+                    };
+                    let value = if reference == 0 { value + instance.start } else { value };
+                    *values.add(index) = value;
+                }
+                OK
+            })
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `instance` must be null or a live pointer returned by [`instantiate`], `references` and `values` valid for
+    /// `count` reads.
+    pub unsafe fn set_float64(instance: *mut c_void, references: *const u32, values: *const f64, count: usize) -> i32 {
+        // SAFETY: guaranteed by the caller.
+        unsafe {
+            guarded(instance, |instance| {
+                for index in 0..count {
+                    if !instance.model.set(*references.add(index), *values.add(index)) {
+                        return ERROR;
+                    }
+                }
+                OK
+            })
+        }
+    }
+}
+
+/// Define the `fmi3*` functions of an FMU around a function building an [`FmuModel`](crate::fmi::FmuModel), see
+/// the [`fmi`](crate::fmi) module.
+#[macro_export]
+macro_rules! export_fmu {
+    ($build:path) => {
+        #[doc(hidden)]
+        const __RUSTSIM_FMU_BUILD: fn() -> $crate::fmi::FmuModel = $build;
+
+        #[doc(hidden)]
+        #[allow(non_snake_case, clippy::missing_safety_doc)]
+        pub mod __rustsim_fmu {
+            use ::std::ffi::{c_char, c_void};
+
+            use $crate::fmi::raw;
+
+            #[no_mangle]
+            pub extern "C" fn fmi3GetVersion() -> *const c_char {
+                raw::VERSION.as_ptr()
+            }
+
+            #[no_mangle]
+            pub unsafe extern "C" fn fmi3InstantiateCoSimulation(
+                _instance_name: *const c_char,
+                instantiation_token: *const c_char,
+                _resource_path: *const c_char,
+                _visible: bool,
+                _logging_on: bool,
+                _event_mode_used: bool,
+                _early_return_allowed: bool,
+                _required_intermediate_variables: *const u32,
+                _required_intermediate_variables_count: usize,
+                _instance_environment: *mut c_void,
+                _log_message: *const c_void,
+                _intermediate_update: *const c_void,
+            ) -> *mut c_void {
+                unsafe { raw::instantiate(super::__RUSTSIM_FMU_BUILD, instantiation_token) }
+            }
+
+            #[no_mangle]
+            pub unsafe extern "C" fn fmi3FreeInstance(instance: *mut c_void) {
+                unsafe { raw::free(instance) }
+            }
+
+            #[no_mangle]
+            pub unsafe extern "C" fn fmi3SetDebugLogging(
+                _instance: *mut c_void,
+                _logging_on: bool,
+                _categories_count: usize,
+                _categories: *const *const c_char,
+            ) -> i32 {
+                raw::OK
+            }
+
+            #[no_mangle]
+            pub unsafe extern "C" fn fmi3EnterInitializationMode(
+                instance: *mut c_void,
+                _tolerance_defined: bool,
+                _tolerance: f64,
+                start_time: f64,
+                _stop_time_defined: bool,
+                _stop_time: f64,
+            ) -> i32 {
+                unsafe { raw::enter_initialization_mode(instance, start_time) }
+            }
+
+            #[no_mangle]
+            pub unsafe extern "C" fn fmi3ExitInitializationMode(_instance: *mut c_void) -> i32 {
+                raw::OK
+            }
+
+            #[no_mangle]
+            pub unsafe extern "C" fn fmi3EnterStepMode(_instance: *mut c_void) -> i32 {
+                raw::OK
+            }
+
+            #[no_mangle]
+            pub unsafe extern "C" fn fmi3EnterEventMode(_instance: *mut c_void) -> i32 {
+                raw::ERROR
+            }
+
+            #[no_mangle]
+            pub unsafe extern "C" fn fmi3Terminate(instance: *mut c_void) -> i32 {
+                unsafe { raw::terminate(instance) }
+            }
+
+            #[no_mangle]
+            pub unsafe extern "C" fn fmi3Reset(instance: *mut c_void) -> i32 {
+                unsafe { raw::reset(instance) }
+            }
+
+            #[no_mangle]
+            pub unsafe extern "C" fn fmi3DoStep(
+                instance: *mut c_void,
+                current_communication_point: f64,
+                communication_step_size: f64,
+                _no_set_fmu_state_prior_to_current_point: bool,
+                event_handling_needed: *mut bool,
+                terminate_simulation: *mut bool,
+                early_return: *mut bool,
+                last_successful_time: *mut f64,
+            ) -> i32 {
+                for flag in [event_handling_needed, terminate_simulation, early_return] {
+                    if !flag.is_null() {
+                        unsafe { *flag = false };
+                    }
+                }
+                unsafe {
+                    raw::do_step(
+                        instance,
+                        current_communication_point,
+                        communication_step_size,
+                        last_successful_time,
+                    )
+                }
+            }
+
+            #[no_mangle]
+            pub unsafe extern "C" fn fmi3GetFloat64(
+                instance: *mut c_void,
+                value_references: *const u32,
+                value_references_count: usize,
+                values: *mut f64,
+                values_count: usize,
+            ) -> i32 {
+                if value_references_count != values_count {
+                    return raw::ERROR;
+                }
+                unsafe { raw::get_float64(instance, value_references, values, values_count) }
+            }
+
+            #[no_mangle]
+            pub unsafe extern "C" fn fmi3SetFloat64(
+                instance: *mut c_void,
+                value_references: *const u32,
+                value_references_count: usize,
+                values: *const f64,
+                values_count: usize,
+            ) -> i32 {
+                if value_references_count != values_count {
+                    return raw::ERROR;
+                }
+                unsafe { raw::set_float64(instance, value_references, values, values_count) }
+            }
+
+            #[no_mangle]
+            pub unsafe extern "C" fn fmi3GetFMUState(_instance: *mut c_void, _state: *mut *mut c_void) -> i32 {
+                raw::ERROR
+            }
+
+            #[no_mangle]
+            pub unsafe extern "C" fn fmi3SetFMUState(_instance: *mut c_void, _state: *mut c_void) -> i32 {
+                raw::ERROR
+            }
+
+            #[no_mangle]
+            pub unsafe extern "C" fn fmi3FreeFMUState(_instance: *mut c_void, _state: *mut *mut c_void) -> i32 {
+                raw::ERROR
+            }
+
+            #[no_mangle]
+            pub unsafe extern "C" fn fmi3InstantiateModelExchange(
+                _instance_name: *const c_char,
+                _instantiation_token: *const c_char,
+                _resource_path: *const c_char,
+                _visible: bool,
+                _logging_on: bool,
+                _instance_environment: *mut c_void,
+                _log_message: *const c_void,
+            ) -> *mut c_void {
+                ::std::ptr::null_mut()
+            }
+
+            #[no_mangle]
+            pub unsafe extern "C" fn fmi3InstantiateScheduledExecution(
+                _instance_name: *const c_char,
+                _instantiation_token: *const c_char,
+                _resource_path: *const c_char,
+                _visible: bool,
+                _logging_on: bool,
+                _instance_environment: *mut c_void,
+                _log_message: *const c_void,
+                _clock_update: *const c_void,
+                _lock_preemption: *const c_void,
+                _unlock_preemption: *const c_void,
+            ) -> *mut c_void {
+                ::std::ptr::null_mut()
+            }
+
+            // Arrays of other types are passed as untyped pointers, they're never read.
+            $crate::__rustsim_fmi_unsupported! {
+                fmi3GetFloat32(references: *const u32, references_count: usize, values: *mut c_void, count: usize);
+                fmi3GetInt8(references: *const u32, references_count: usize, values: *mut c_void, count: usize);
+                fmi3GetUInt8(references: *const u32, references_count: usize, values: *mut c_void, count: usize);
+                fmi3GetInt16(references: *const u32, references_count: usize, values: *mut c_void, count: usize);
+                fmi3GetUInt16(references: *const u32, references_count: usize, values: *mut c_void, count: usize);
+                fmi3GetInt32(references: *const u32, references_count: usize, values: *mut c_void, count: usize);
+                fmi3GetUInt32(references: *const u32, references_count: usize, values: *mut c_void, count: usize);
+                fmi3GetInt64(references: *const u32, references_count: usize, values: *mut c_void, count: usize);
+                fmi3GetUInt64(references: *const u32, references_count: usize, values: *mut c_void, count: usize);
+                fmi3GetBoolean(references: *const u32, references_count: usize, values: *mut bool, count: usize);
+                fmi3GetString(references: *const u32, references_count: usize, values: *mut c_void, count: usize);
+                fmi3GetBinary(
+                    references: *const u32,
+                    references_count: usize,
+                    sizes: *mut usize,
+                    values: *mut c_void,
+                    count: usize,
+                );
+                fmi3GetClock(references: *const u32, references_count: usize, values: *mut bool);
+                fmi3SetFloat32(references: *const u32, references_count: usize, values: *const c_void, count: usize);
+                fmi3SetInt8(references: *const u32, references_count: usize, values: *const c_void, count: usize);
+                fmi3SetUInt8(references: *const u32, references_count: usize, values: *const c_void, count: usize);
+                fmi3SetInt16(references: *const u32, references_count: usize, values: *const c_void, count: usize);
+                fmi3SetUInt16(references: *const u32, references_count: usize, values: *const c_void, count: usize);
+                fmi3SetInt32(references: *const u32, references_count: usize, values: *const c_void, count: usize);
+                fmi3SetUInt32(references: *const u32, references_count: usize, values: *const c_void, count: usize);
+                fmi3SetInt64(references: *const u32, references_count: usize, values: *const c_void, count: usize);
+                fmi3SetUInt64(references: *const u32, references_count: usize, values: *const c_void, count: usize);
+                fmi3SetBoolean(references: *const u32, references_count: usize, values: *const bool, count: usize);
+                fmi3SetString(references: *const u32, references_count: usize, values: *const c_void, count: usize);
+                fmi3SetBinary(
+                    references: *const u32,
+                    references_count: usize,
+                    sizes: *const usize,
+                    values: *const c_void,
+                    count: usize,
+                );
+                fmi3SetClock(references: *const u32, references_count: usize, values: *const bool);
+                fmi3GetNumberOfVariableDependencies(reference: u32, count: *mut usize);
+                fmi3GetVariableDependencies(
+                    dependent: u32,
+                    dependent_indices: *mut usize,
+                    independents: *mut u32,
+                    independent_indices: *mut usize,
+                    kinds: *mut c_void,
+                    count: usize,
+                );
+                fmi3SerializedFMUStateSize(state: *mut c_void, size: *mut usize);
+                fmi3SerializeFMUState(state: *mut c_void, serialized: *mut u8, size: usize);
+                fmi3DeserializeFMUState(serialized: *const u8, size: usize, state: *mut *mut c_void);
+                fmi3GetDirectionalDerivative(
+                    unknowns: *const u32,
+                    unknowns_count: usize,
+                    knowns: *const u32,
+                    knowns_count: usize,
+                    seed: *const f64,
+                    seed_count: usize,
+                    sensitivity: *mut f64,
+                    sensitivity_count: usize,
+                );
+                fmi3GetAdjointDerivative(
+                    unknowns: *const u32,
+                    unknowns_count: usize,
+                    knowns: *const u32,
+                    knowns_count: usize,
+                    seed: *const f64,
+                    seed_count: usize,
+                    sensitivity: *mut f64,
+                    sensitivity_count: usize,
+                );
+                fmi3EnterConfigurationMode();
+                fmi3ExitConfigurationMode();
+                fmi3GetIntervalDecimal(
+                    references: *const u32,
+                    references_count: usize,
+                    intervals: *mut f64,
+                    qualifiers: *mut c_void,
+                );
+                fmi3GetIntervalFraction(
+                    references: *const u32,
+                    references_count: usize,
+                    counters: *mut u64,
+                    resolutions: *mut u64,
+                    qualifiers: *mut c_void,
+                );
+                fmi3GetShiftDecimal(references: *const u32, references_count: usize, shifts: *mut f64);
+                fmi3GetShiftFraction(
+                    references: *const u32,
+                    references_count: usize,
+                    counters: *mut u64,
+                    resolutions: *mut u64,
+                );
+                fmi3SetIntervalDecimal(references: *const u32, references_count: usize, intervals: *const f64);
+                fmi3SetIntervalFraction(
+                    references: *const u32,
+                    references_count: usize,
+                    counters: *const u64,
+                    resolutions: *const u64,
+                );
+                fmi3SetShiftDecimal(references: *const u32, references_count: usize, shifts: *const f64);
+                fmi3SetShiftFraction(
+                    references: *const u32,
+                    references_count: usize,
+                    counters: *const u64,
+                    resolutions: *const u64,
+                );
+                fmi3EvaluateDiscreteStates();
+                fmi3UpdateDiscreteStates(
+                    discrete_states_need_update: *mut bool,
+                    terminate_simulation: *mut bool,
+                    nominals_changed: *mut bool,
+                    values_changed: *mut bool,
+                    next_event_time_defined: *mut bool,
+                    next_event_time: *mut f64,
+                );
+                fmi3EnterContinuousTimeMode();
+                fmi3CompletedIntegratorStep(
+                    no_set_fmu_state_prior: bool,
+                    enter_event_mode: *mut bool,
+                    terminate_simulation: *mut bool,
+                );
+                fmi3SetTime(time: f64);
+                fmi3SetContinuousStates(states: *const f64, count: usize);
+                fmi3GetContinuousStateDerivatives(derivatives: *mut f64, count: usize);
+                fmi3GetEventIndicators(indicators: *mut f64, count: usize);
+                fmi3GetContinuousStates(states: *mut f64, count: usize);
+                fmi3GetNominalsOfContinuousStates(nominals: *mut f64, count: usize);
+                fmi3GetNumberOfEventIndicators(count: *mut usize);
+                fmi3GetNumberOfContinuousStates(count: *mut usize);
+                fmi3GetOutputDerivatives(
+                    references: *const u32,
+                    references_count: usize,
+                    orders: *const i32,
+                    values: *mut f64,
+                    count: usize,
+                );
+                fmi3ActivateModelPartition(clock_reference: u32, activation_time: f64);
+            }
+        }
+    };
+}
+
+/// Define `fmi3*` functions taking an instance and the given arguments and returning `fmi3Error`, for the parts of
+/// the standard [`export_fmu!`](crate::export_fmu) doesn't support.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __rustsim_fmi_unsupported {
+    ($($name:ident($($argument:ident: $type:ty),* $(,)?);)*) => {
+        $(
+            #[no_mangle]
+            pub unsafe extern "C" fn $name(_instance: *mut ::std::ffi::c_void, $($argument: $type),*) -> i32 {
+                $(let _ = $argument;)*
+                $crate::fmi::raw::ERROR
+            }
+        )*
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use std::ffi::{c_void, CString};
+
+    use super::*;
+    use crate::{process, Action};
+
+    fn counter() -> FmuModel {
+        let mut simulation = Simulation::default();
+        let state = simulation.state();
+        let count = state.with_mut(|state| state.insert(0.0f64));
+        let increment = state.with_mut(|state| state.insert(1.0f64));
+        let key = simulation.add_generator(process(move |_| {
+            state.with_mut(|state| {
+                let increment = *state.get(increment).unwrap();
+                *state.get_mut(count).unwrap() += increment;
+            });
+            Some(Action::Hold(Duration::from_secs(1)))
+        }));
+        simulation.schedule(Duration::from_secs(1), key);
+        let mut model = FmuModel::new("counter", simulation);
+        model
+            .output("count", count, |&count| count)
+            .input("increment", increment, |&increment| increment, |increment, value| *increment = value);
+        model
+    }
+
+    crate::export_fmu!(counter);
+
+    #[test]
+    fn models_are_described_and_stepped() {
+        let model = counter();
+        let description = model.model_description();
+        assert!(description.contains(r#"<CoSimulation modelIdentifier="counter""#));
+        assert!(description.contains(r#"name="count" valueReference="1" causality="output" variability="discrete"/>"#));
+        assert!(description.contains(r#"name="increment" valueReference="2" causality="input" variability="discrete" start="1"/>"#));
+        assert!(description.contains(r#"<Output valueReference="1"/>"#));
+
+        let token = CString::new(model.instantiation_token()).unwrap();
+        unsafe {
+            assert!(raw::instantiate(counter, c"{rustsim-0}".as_ptr()).is_null());
+            let instance = raw::instantiate(counter, token.as_ptr());
+            assert_eq!(raw::OK, raw::enter_initialization_mode(instance, 10.0));
+            let mut last = 0.0;
+            assert_eq!(raw::OK, raw::do_step(instance, 10.0, 2.5, &mut last));
+            assert_eq!(12.5, last);
+            assert_eq!(raw::ERROR, raw::do_step(instance, 10.0, 1.0, &mut last));
+
+            assert_eq!(raw::OK, raw::set_float64(instance, &2, &10.0, 1));
+            assert_eq!(raw::ERROR, raw::set_float64(instance, &1, &0.0, 1));
+            assert_eq!(raw::OK, raw::do_step(instance, 12.5, 1.0, &mut last));
+            let mut values = [0.0; 2];
+            assert_eq!(raw::OK, raw::get_float64(instance, [0, 1].as_ptr(), values.as_mut_ptr(), 2));
+            assert_eq!([13.5, 12.0], values);
+
+            assert_eq!(raw::OK, raw::reset(instance));
+            assert_eq!(raw::OK, raw::get_float64(instance, &1, values.as_mut_ptr(), 1));
+            assert_eq!(0.0, values[0]);
+            raw::free(instance);
+            assert_eq!(c"3.0", std::ffi::CStr::from_ptr(__rustsim_fmu::fmi3GetVersion()));
+            assert_eq!(raw::ERROR, raw::terminate(std::ptr::null_mut::<c_void>()));
+        }
+    }
+
+    #[test]
+    fn communication_points_are_compared_relative_to_their_magnitude() {
+        let token = CString::new(counter().instantiation_token()).unwrap();
+        unsafe {
+            let instance = raw::instantiate(counter, token.as_ptr());
+            assert_eq!(raw::OK, raw::enter_initialization_mode(instance, 1e9));
+            let mut current = 1e9;
+            let mut last = 0.0;
+            for _ in 0..10 {
+                assert_eq!(raw::OK, raw::do_step(instance, current, 0.1, &mut last));
+                current += 0.1;
+            }
+            assert_eq!(raw::ERROR, raw::do_step(instance, current + 1e-2, 0.1, &mut last));
+            raw::free(instance);
+        }
+    }
+
+    #[test]
+    fn unsupported_functions_fail() {
+        let null = std::ptr::null_mut::<c_void>();
+        let name = c"counter".as_ptr();
+        unsafe {
+            assert_eq!(raw::ERROR, __rustsim_fmu::fmi3GetInt32(null, &1, 1, null, 1));
+            assert_eq!(raw::ERROR, __rustsim_fmu::fmi3SetBoolean(null, &1, 1, &true, 1));
+            assert_eq!(raw::ERROR, __rustsim_fmu::fmi3EnterContinuousTimeMode(null));
+            let model_exchange =
+                __rustsim_fmu::fmi3InstantiateModelExchange(name, name, name, false, false, null, null);
+            assert!(model_exchange.is_null());
+        }
+    }
+
+    #[test]
+    fn fmus_are_zipped() {
+        assert_eq!(0xCBF4_3926, crc32(b"123456789"));
+
+        let directory = env::temp_dir().join(format!("rustsim-fmu-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let library = directory.join("library");
+        fs::write(&library, b"binary").unwrap();
+        let fmu = directory.join("counter.fmu");
+        counter().write_fmu(&fmu, &library).unwrap();
+        let bytes = fs::read(&fmu).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert!(bytes.starts_with(b"PK\x03\x04"));
+        let text = String::from_utf8_lossy(&bytes);
+        assert!(text.contains("modelDescription.xml"));
+        let binary = format!("binaries/{}/counter.{}", platform(), env::consts::DLL_EXTENSION);
+        assert!(text.contains(&binary));
+        let end = &bytes[bytes.len() - 22..];
+        assert_eq!(b"PK\x05\x06", &end[..4]);
+        assert_eq!([2, 0], end[10..12]);
+    }
+}
@@ -0,0 +1,435 @@
+//! A small interpreter for GPSS-style block descriptions.
+//!
+//! A model is a list of segments, each starting with a `GENERATE` block and ending with a `TERMINATE` block:
+//!
+//! ```text
+//! * Single server queue
+//!         GENERATE 10,0,0,100
+//!         SEIZE    teller
+//!         ADVANCE  8
+//!         RELEASE  teller
+//!         TERMINATE 1
+//!         START    50
+//! ```
+//!
+//! Supported blocks are `GENERATE interval[,spread[,offset[,limit]]]`, `SEIZE facility`,
+//! `ADVANCE time[,spread]`, `RELEASE facility` and `TERMINATE [count]`, plus the `START count` control statement.
+//! Lines starting with `*` are comments and anything after a `;` is ignored.
+//! Spreads must be zero as they require random numbers.
+//! Within a segment a facility must be seized before it's released, and released before the `TERMINATE`.
+
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::{process, Action, ComponentKind, GenBoxed, Key, Simulation, Spawner};
+
+/// Error produced while parsing a GPSS model.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GpssError {
+    line: usize,
+    message: String,
+}
+
+impl GpssError {
+    fn new(line: usize, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            message: message.into(),
+        }
+    }
+
+    /// Returns the line (starting at 1) where the error was found.
+    #[must_use]
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    #[must_use]
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for GpssError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for GpssError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Block {
+    Seize(String),
+    Advance(f64),
+    Release(String),
+    Terminate(u64),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Segment {
+    interval: f64,
+    offset: Option<f64>,
+    limit: Option<u64>,
+    blocks: Vec<Block>,
+}
+
+/// A parsed GPSS model, ready to be built into a [`Simulation`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Model {
+    segments: Vec<Segment>,
+    start: Option<u64>,
+}
+
+impl Model {
+    /// Parse a model from its block description.
+    pub fn parse(source: &str) -> Result<Self, GpssError> {
+        let mut model = Model::default();
+        let mut current: Option<Segment> = None;
+        // Facilities seized and not yet released in the current segment.
+        let mut held: Vec<String> = Vec::new();
+
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.split(';').next().unwrap_or_default().trim();
+            if line.is_empty() || line.starts_with('*') {
+                continue;
+            }
+            let mut words = line.split_whitespace();
+            let block = words.next().unwrap_or_default().to_ascii_uppercase();
+            let operands: Vec<&str> = words
+                .next()
+                .map(|operands| operands.split(',').map(str::trim).collect())
+                .unwrap_or_default();
+            if words.next().is_some() {
+                return Err(GpssError::new(line_number, "operands must be separated by commas without spaces"));
+            }
+            let operand = |position: usize| operands.get(position).copied().filter(|operand| !operand.is_empty());
+            let number = |position: usize| -> Result<Option<f64>, GpssError> {
+                operand(position)
+                    .map(|operand| match operand.parse::<f64>() {
+                        Ok(value) if value.is_finite() && value >= 0.0 => Ok(value),
+                        _ => Err(GpssError::new(line_number, format!("invalid time `{}`", operand))),
+                    })
+                    .transpose()
+            };
+            let count = |position: usize| -> Result<Option<u64>, GpssError> {
+                operand(position)
+                    .map(|operand| {
+                        operand
+                            .parse::<u64>()
+                            .map_err(|_| GpssError::new(line_number, format!("invalid count `{}`", operand)))
+                    })
+                    .transpose()
+            };
+            let no_spread = |position: usize| -> Result<(), GpssError> {
+                match number(position)? {
+                    Some(spread) if spread != 0.0 => Err(GpssError::new(
+                        line_number,
+                        "spreads require random numbers and are not supported",
+                    )),
+                    _ => Ok(()),
+                }
+            };
+            let name = |position: usize| -> Result<String, GpssError> {
+                operand(position)
+                    .map(str::to_owned)
+                    .ok_or_else(|| GpssError::new(line_number, format!("{} requires a facility name", block)))
+            };
+
+            if block == "START" {
+                let start = count(0)?.ok_or_else(|| GpssError::new(line_number, "START requires a count"))?;
+                model.start = Some(start);
+                continue;
+            }
+            if block == "GENERATE" {
+                if current.is_some() {
+                    return Err(GpssError::new(line_number, "GENERATE found before the previous segment's TERMINATE"));
+                }
+                let interval = number(0)?.ok_or_else(|| GpssError::new(line_number, "GENERATE requires an interval"))?;
+                no_spread(1)?;
+                current = Some(Segment {
+                    interval,
+                    offset: number(2)?,
+                    limit: count(3)?,
+                    blocks: Vec::new(),
+                });
+                continue;
+            }
+
+            let segment = current
+                .as_mut()
+                .ok_or_else(|| GpssError::new(line_number, format!("{} found outside of a segment", block)))?;
+            match block.as_str() {
+                "SEIZE" => {
+                    let facility = name(0)?;
+                    if held.contains(&facility) {
+                        return Err(GpssError::new(line_number, format!("facility `{}` is already seized", facility)));
+                    }
+                    held.push(facility.clone());
+                    segment.blocks.push(Block::Seize(facility));
+                }
+                "RELEASE" => {
+                    let facility = name(0)?;
+                    let Some(position) = held.iter().position(|seized| *seized == facility) else {
+                        return Err(GpssError::new(
+                            line_number,
+                            format!("facility `{}` is released without being seized", facility),
+                        ));
+                    };
+                    held.remove(position);
+                    segment.blocks.push(Block::Release(facility));
+                }
+                "ADVANCE" => {
+                    no_spread(1)?;
+                    segment.blocks.push(Block::Advance(number(0)?.unwrap_or_default()));
+                }
+                "TERMINATE" => {
+                    if let Some(facility) = held.first() {
+                        return Err(GpssError::new(
+                            line_number,
+                            format!("TERMINATE reached while facility `{}` is still seized", facility),
+                        ));
+                    }
+                    segment.blocks.push(Block::Terminate(count(0)?.unwrap_or_default()));
+                    model.segments.extend(current.take());
+                }
+                _ => return Err(GpssError::new(line_number, format!("unknown block `{}`", block))),
+            }
+        }
+
+        if current.is_some() {
+            return Err(GpssError::new(source.lines().count(), "segment without TERMINATE"));
+        }
+        Ok(model)
+    }
+
+    /// Add the entities of this model to `simulation`.
+    ///
+    /// `time_unit` is the simulated duration of one GPSS time unit.
+    /// Facilities are registered as resources and `GENERATE` blocks as sources in the simulation components.
+    pub fn build(&self, simulation: &mut Simulation<()>, time_unit: Duration) -> Program {
+        let runtime = Rc::new(RefCell::new(Runtime::default()));
+        for segment in &self.segments {
+            for block in &segment.blocks {
+                if let Block::Seize(facility) | Block::Release(facility) = block {
+                    if !runtime.borrow().facilities.contains_key(facility) {
+                        runtime
+                            .borrow_mut()
+                            .facilities
+                            .insert(facility.clone(), Facility::default());
+                        simulation.register_component::<Facility>(facility.clone(), ComponentKind::Resource);
+                    }
+                }
+            }
+        }
+
+        let spawner = simulation.spawner();
+        for (index, segment) in self.segments.iter().enumerate() {
+            simulation.register_component::<Model>(format!("GENERATE {}", index + 1), ComponentKind::Source);
+            let key = simulation.add_generator(generate(
+                segment.clone(),
+                time_unit,
+                spawner.clone(),
+                Rc::clone(&runtime),
+            ));
+            simulation.schedule_now(key);
+        }
+
+        Program {
+            runtime,
+            start: self.start,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Facility {
+    owner: Option<Key>,
+    waiting: VecDeque<Key>,
+    entries: u64,
+}
+
+#[derive(Debug, Default)]
+struct Runtime {
+    facilities: HashMap<String, Facility>,
+    terminations: u64,
+}
+
+/// A GPSS model built into a simulation, used to inspect the run.
+pub struct Program {
+    runtime: Rc<RefCell<Runtime>>,
+    start: Option<u64>,
+}
+
+impl Program {
+    /// Returns the sum of the counts of every `TERMINATE` executed so far.
+    #[must_use]
+    pub fn terminations(&self) -> u64 {
+        self.runtime.borrow().terminations
+    }
+
+    /// Returns `true` once the termination count of the `START` statement is reached.
+    ///
+    /// Meant to be used with [`Simulation::run_until`].
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        matches!(self.start, Some(start) if self.terminations() >= start)
+    }
+
+    /// Returns how many transactions have seized `facility`.
+    #[must_use]
+    pub fn facility_entries(&self, facility: &str) -> Option<u64> {
+        self.runtime
+            .borrow()
+            .facilities
+            .get(facility)
+            .map(|facility| facility.entries)
+    }
+}
+
+fn generate(segment: Segment, time_unit: Duration, spawner: Spawner<()>, runtime: Rc<RefCell<Runtime>>) -> GenBoxed<()> {
+    let interval = time_unit.mul_f64(segment.interval);
+    let limit = segment.limit.unwrap_or(u64::MAX);
+    // `None` until the first transaction is due.
+    let mut generated: Option<u64> = None;
+    process(move |_| {
+        let Some(count) = generated.as_mut() else {
+            generated = Some(0);
+            return Some(Action::Hold(segment.offset.map_or(interval, |offset| time_unit.mul_f64(offset))));
+        };
+        if *count >= limit {
+            return None;
+        }
+        // A transaction needs its own key to wait in a facility, which is only known after spawning it.
+        let own_key = Rc::new(Cell::new(None));
+        let key = spawner.spawn_detached(transaction(
+            segment.blocks.clone(),
+            time_unit,
+            Rc::clone(&runtime),
+            Rc::clone(&own_key),
+        ));
+        own_key.set(Some(key));
+        *count += 1;
+        Some(Action::Hold(interval))
+    })
+}
+
+fn transaction(
+    blocks: Vec<Block>,
+    time_unit: Duration,
+    runtime: Rc<RefCell<Runtime>>,
+    own_key: Rc<Cell<Option<Key>>>,
+) -> GenBoxed<()> {
+    // Position of the next block to enter.
+    let mut position = 0;
+    process(move |_| {
+        let key = own_key.get().expect("set right after spawning");
+        while let Some(block) = blocks.get(position) {
+            position += 1;
+            match block {
+                Block::Seize(name) => {
+                    let mut runtime = runtime.borrow_mut();
+                    let facility = runtime.facilities.get_mut(name).expect("facilities are created on build");
+                    if facility.owner.is_none() {
+                        facility.owner = Some(key);
+                        facility.entries += 1;
+                    } else {
+                        facility.waiting.push_back(key);
+                        // The releasing transaction hands the facility over before activating this one.
+                        return Some(Action::Passivate);
+                    }
+                }
+                Block::Release(name) => {
+                    let mut runtime = runtime.borrow_mut();
+                    let facility = runtime.facilities.get_mut(name).expect("facilities are created on build");
+                    debug_assert_eq!(Some(key), facility.owner, "checked when parsing");
+                    facility.owner = facility.waiting.pop_front();
+                    if let Some(next) = facility.owner {
+                        facility.entries += 1;
+                        return Some(Action::ActivateOne(next));
+                    }
+                }
+                Block::Advance(time) => return Some(Action::Hold(time_unit.mul_f64(*time))),
+                Block::Terminate(count) => {
+                    runtime.borrow_mut().terminations += count;
+                    return None;
+                }
+            }
+        }
+        None
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SINGLE_SERVER: &str = "
+* Single server queue
+        GENERATE 10,0,0,5
+        SEIZE    teller   ; one customer at a time
+        ADVANCE  15
+        RELEASE  teller
+        TERMINATE 1
+        START    5
+";
+
+    #[test]
+    fn parse_single_server() {
+        let model = Model::parse(SINGLE_SERVER).unwrap();
+        assert_eq!(Some(5), model.start);
+        assert_eq!(1, model.segments.len());
+        let segment = &model.segments[0];
+        assert_eq!((10.0, Some(0.0), Some(5)), (segment.interval, segment.offset, segment.limit));
+        assert_eq!(
+            vec![
+                Block::Seize("teller".to_owned()),
+                Block::Advance(15.0),
+                Block::Release("teller".to_owned()),
+                Block::Terminate(1),
+            ],
+            segment.blocks
+        );
+    }
+
+    #[test]
+    fn parse_errors_report_the_line() {
+        let error = Model::parse("GENERATE 10\nSEIZE\nTERMINATE").unwrap_err();
+        assert_eq!(2, error.line());
+        let error = Model::parse("GENERATE 10,2\nTERMINATE").unwrap_err();
+        assert_eq!(1, error.line());
+        let error = Model::parse("ADVANCE 3").unwrap_err();
+        assert_eq!(1, error.line());
+        let error = Model::parse("GENERATE 10\nADVANCE 3").unwrap_err();
+        assert_eq!(2, error.line());
+    }
+
+    #[test]
+    fn facilities_are_seized_and_released_in_pairs() {
+        let error = Model::parse("GENERATE 10\nADVANCE 3\nRELEASE teller\nTERMINATE").unwrap_err();
+        assert_eq!((3, "facility `teller` is released without being seized"), (error.line(), error.message()));
+        let error = Model::parse("GENERATE 10\nSEIZE teller\nADVANCE 3\nTERMINATE 1").unwrap_err();
+        assert_eq!(4, error.line());
+        let error = Model::parse("GENERATE 10\nSEIZE teller\nSEIZE teller").unwrap_err();
+        assert_eq!(3, error.line());
+        // Releasing in another order than seizing is fine.
+        assert!(Model::parse("GENERATE 10\nSEIZE a\nSEIZE b\nRELEASE a\nRELEASE b\nTERMINATE").is_ok());
+    }
+
+    #[test]
+    fn single_server_runs_to_completion() {
+        let model = Model::parse(SINGLE_SERVER).unwrap();
+        let mut simulation = Simulation::default();
+        let program = model.build(&mut simulation, Duration::from_secs(1));
+        simulation.run_until(|_| program.is_finished());
+
+        // Arrivals at 0, 10, 20, 30 and 40 served back to back, 15 seconds each.
+        assert_eq!(5, program.terminations());
+        assert_eq!(Some(5), program.facility_entries("teller"));
+        assert_eq!(Duration::from_secs(75), simulation.time());
+    }
+}
@@ -0,0 +1,75 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::error::SimulationError;
+
+/// How a run of the simulation ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunStatus {
+    /// No more events are left in the scheduler.
+    Exhausted,
+    /// No more events are left but some entities are still passive, see [`Simulation::deadlock`](crate::Simulation::deadlock).
+    Deadlocked,
+    /// The limit of the run (time, step count or stop condition) was reached.
+    LimitReached,
+    /// The run was interrupted through a [`RunHandle`]. Calling the run method again resumes it.
+    Paused,
+    /// The run stopped at a [`Breakpoint`](crate::Breakpoint). Calling the run method again resumes it.
+    Breakpoint(crate::Breakpoint),
+    /// An entity yielded an invalid action. The simulation can still be stepped, but the entity isn't rescheduled.
+    Failed(SimulationError),
+}
+
+/// Where a long run stands, reported by
+/// [`Simulation::run_with_limit_and_progress`](crate::Simulation::run_with_limit_and_progress).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    /// The simulation time.
+    pub time: Duration,
+    /// Events executed since the run started.
+    pub events: u64,
+    /// Wall-clock time since the run started.
+    pub elapsed: Duration,
+}
+
+impl Progress {
+    /// Returns the events executed per second of wall-clock time, zero if no time has elapsed.
+    #[must_use]
+    pub fn events_per_sec(&self) -> f64 {
+        let elapsed = self.elapsed.as_secs_f64();
+        if elapsed > 0.0 {
+            self.events as f64 / elapsed
+        } else {
+            0.0
+        }
+    }
+}
+
+/// A handle to interrupt a run of the simulation from the outside.
+///
+/// Obtained from [`Simulation::run_handle`](crate::Simulation::run_handle). It can be cloned and moved
+/// into callbacks or generators, calling [`RunHandle::pause`] makes the ongoing run return
+/// [`RunStatus::Paused`] after the current event, leaving the simulation ready to resume.
+#[derive(Debug, Clone, Default)]
+pub struct RunHandle {
+    pause_requested: Rc<Cell<bool>>,
+}
+
+impl RunHandle {
+    /// Request the ongoing run to stop after the current event.
+    pub fn pause(&self) {
+        self.pause_requested.set(true);
+    }
+
+    /// Returns `true` if a pause was requested and not yet honored by a run.
+    #[must_use]
+    pub fn is_pause_requested(&self) -> bool {
+        self.pause_requested.get()
+    }
+
+    // Clears the pause request, returning whether there was one.
+    pub(crate) fn take_pause_request(&self) -> bool {
+        self.pause_requested.replace(false)
+    }
+}
@@ -0,0 +1,47 @@
+use std::time::Duration;
+
+use crate::{Action, Key};
+
+type StepHook = Box<dyn FnMut(Duration, Key, &Action)>;
+type CompleteHook = Box<dyn FnMut(Key)>;
+type ScheduleHook = Box<dyn FnMut(Duration, Key)>;
+
+/// Callbacks registered on a [`Simulation`](crate::Simulation) to observe its execution.
+#[derive(Default)]
+pub(crate) struct Hooks {
+    on_step: Vec<StepHook>,
+    on_complete: Vec<CompleteHook>,
+    on_schedule: Vec<ScheduleHook>,
+}
+
+impl Hooks {
+    pub(crate) fn add_on_step(&mut self, hook: impl FnMut(Duration, Key, &Action) + 'static) {
+        self.on_step.push(Box::new(hook));
+    }
+
+    pub(crate) fn add_on_complete(&mut self, hook: impl FnMut(Key) + 'static) {
+        self.on_complete.push(Box::new(hook));
+    }
+
+    pub(crate) fn add_on_schedule(&mut self, hook: impl FnMut(Duration, Key) + 'static) {
+        self.on_schedule.push(Box::new(hook));
+    }
+
+    pub(crate) fn step(&mut self, time: Duration, key: Key, action: &Action) {
+        for hook in &mut self.on_step {
+            hook(time, key, action);
+        }
+    }
+
+    pub(crate) fn complete(&mut self, key: Key) {
+        for hook in &mut self.on_complete {
+            hook(key);
+        }
+    }
+
+    pub(crate) fn schedule(&mut self, time: Duration, key: Key) {
+        for hook in &mut self.on_schedule {
+            hook(time, key);
+        }
+    }
+}
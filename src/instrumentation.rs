@@ -0,0 +1,62 @@
+//! Instrumentation emitted through `tracing` when the `tracing` feature is enabled.
+//!
+//! Every entity step runs inside a `step` span tagged with the simulated time and the entity key,
+//! so events emitted from generators can be filtered and correlated by existing subscribers.
+//! Without the feature every function here is a no-op.
+
+use std::time::Duration;
+
+use crate::{Action, Deadlock, Key};
+
+#[cfg(feature = "tracing")]
+pub(crate) type StepGuard = tracing::span::EnteredSpan;
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct StepGuard;
+
+/// Enter the span of a step, exited when the returned guard is dropped.
+#[cfg(feature = "tracing")]
+pub(crate) fn enter_step(time: Duration, key: Key) -> StepGuard {
+    tracing::trace_span!("step", time = ?time, entity = key.id()).entered()
+}
+
+#[cfg(not(feature = "tracing"))]
+#[inline]
+pub(crate) fn enter_step(_time: Duration, _key: Key) -> StepGuard {
+    StepGuard
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn yielded(action: &Action) {
+    tracing::trace!(action = ?action, "entity yielded");
+}
+
+#[cfg(not(feature = "tracing"))]
+#[inline]
+pub(crate) fn yielded(_action: &Action) {}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn completed(key: Key) {
+    tracing::debug!(entity = key.id(), "entity completed");
+}
+
+#[cfg(not(feature = "tracing"))]
+#[inline]
+pub(crate) fn completed(_key: Key) {}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn scheduled(time: Duration, key: Key) {
+    tracing::trace!(at = ?time, entity = key.id(), "event scheduled");
+}
+
+#[cfg(not(feature = "tracing"))]
+#[inline]
+pub(crate) fn scheduled(_time: Duration, _key: Key) {}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn deadlocked(deadlock: &Deadlock) {
+    tracing::warn!(passive = deadlock.entities().len(), "{}", deadlock);
+}
+
+#[cfg(not(feature = "tracing"))]
+#[inline]
+pub(crate) fn deadlocked(_deadlock: &Deadlock) {}
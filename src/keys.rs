@@ -1,12 +1,21 @@
+/// Identifies an entity of the simulation.
+///
+/// Besides the slot of the entity a key carries the generation of the slot, so a key kept after its entity
+/// completed never refers to another entity: the simulation treats it as stale instead.
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub struct Key {
     pub(crate) id: usize,
+    pub(crate) generation: u32,
 }
 
 impl Key {
     #[allow(dead_code)]
     pub(crate) fn new(id: usize) -> Self {
-        Self { id }
+        Self::with_generation(id, 0)
+    }
+
+    pub(crate) fn with_generation(id: usize, generation: u32) -> Self {
+        Self { id, generation }
     }
 
     #[must_use]
@@ -15,9 +24,45 @@ impl Key {
         self.id
     }
 
+    /// Returns the generation of the slot this key refers to.
+    #[must_use]
+    pub fn generation(self) -> u32 {
+        self.generation
+    }
+
     #[allow(dead_code)]
     pub fn dummy() -> Self {
-        Self { id: usize::MAX }
+        Self::new(usize::MAX)
+    }
+
+    /// Create a [`WeakKey`] to this entity.
+    ///
+    /// Unlike a [`Key`], a [`WeakKey`] has to be upgraded through the simulation before being used,
+    /// which fails once the entity no longer exists.
+    #[must_use]
+    pub fn downgrade(self) -> WeakKey {
+        WeakKey {
+            id: self.id,
+            generation: self.generation,
+        }
+    }
+}
+
+/// A liveness-aware reference to an entity.
+///
+/// Meant to be kept in long-lived registries (dispatch lists, subscribers, etc.).
+/// Use [`Simulation::upgrade`](crate::Simulation::upgrade) to get back a [`Key`] only if the entity still exists.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct WeakKey {
+    pub(crate) id: usize,
+    pub(crate) generation: u32,
+}
+
+impl WeakKey {
+    #[must_use]
+    /// Return the ID of the entity this key correspond
+    pub fn id(self) -> usize {
+        self.id
     }
 }
 
@@ -0,0 +1,277 @@
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use crate::scheduler::ClockRef;
+use crate::stats::{Accumulate, Statistic};
+use crate::{Action, Key};
+
+#[derive(Debug, Clone, Copy)]
+struct Waiting {
+    key: Key,
+    amount: f64,
+}
+
+#[derive(Debug)]
+struct Inner {
+    name: String,
+    capacity: f64,
+    level: f64,
+    // In arrival order, only the first of each queue can be served.
+    puts: VecDeque<Waiting>,
+    gets: VecDeque<Waiting>,
+}
+
+impl Inner {
+    // Serve the waiting puts and gets that fit the level, in order, returning their keys.
+    fn serve(&mut self) -> Vec<Key> {
+        let mut served = Vec::new();
+        loop {
+            if let Some(get) = self.gets.front().filter(|get| get.amount <= self.level) {
+                self.level -= get.amount;
+                served.push(get.key);
+                self.gets.pop_front();
+            } else if let Some(put) = self.puts.front().filter(|put| self.level + put.amount <= self.capacity) {
+                self.level += put.amount;
+                served.push(put.key);
+                self.puts.pop_front();
+            } else {
+                return served;
+            }
+        }
+    }
+}
+
+/// A continuous quantity, such as the fluid in a tank or the stock of a buffer, that entities put and get.
+///
+/// Created with [`Simulation::add_level_container`](crate::Simulation::add_level_container). Puts wait until the
+/// amount fits under the capacity and gets until the amount is available, each in arrival order, and an entity changing
+/// the level activates those it allowed to complete. It can be cloned and moved into generators:
+///
+/// ```ignore
+/// for action in tank.put(25.0) {
+///     yield action;
+/// }
+/// // The 25 units are in the tank.
+/// ```
+#[derive(Clone)]
+pub struct LevelContainer {
+    inner: Rc<RefCell<Inner>>,
+    level: Accumulate,
+    current: Rc<Cell<Option<Key>>>,
+}
+
+impl LevelContainer {
+    pub(crate) fn new(
+        name: String,
+        capacity: f64,
+        initial: f64,
+        clock: ClockRef,
+        current: Rc<Cell<Option<Key>>>,
+    ) -> Self {
+        assert!(
+            (0.0..=capacity).contains(&initial),
+            "The initial level of `{}` must be between 0 and its capacity",
+            name
+        );
+        let level = Accumulate::new(format!("{} level", name), clock, initial);
+        let inner = Inner {
+            name,
+            capacity,
+            level: initial,
+            puts: VecDeque::new(),
+            gets: VecDeque::new(),
+        };
+        Self {
+            inner: Rc::new(RefCell::new(inner)),
+            level,
+            current,
+        }
+    }
+
+    /// Put `amount` in the container with the entity currently being executed.
+    ///
+    /// Returns the actions the entity has to yield, in order. Once they are done the amount is in the container: if it
+    /// doesn't fit yet the entity passivates until the put completes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of an entity, if `amount` is negative or if it exceeds the capacity.
+    #[must_use]
+    pub fn put(&self, amount: f64) -> Vec<Action> {
+        let capacity = self.capacity();
+        assert!(
+            (0.0..=capacity).contains(&amount),
+            "Put {} in `{}`, which holds between 0 and {}",
+            amount,
+            self.name(),
+            capacity
+        );
+        self.enqueue(amount, |inner| &mut inner.puts)
+    }
+
+    /// Get `amount` from the container with the entity currently being executed.
+    ///
+    /// Returns the actions the entity has to yield, in order. Once they are done the amount was taken from the
+    /// container: if it isn't available yet the entity passivates until the get completes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of an entity, if `amount` is negative or if it exceeds the capacity.
+    #[must_use]
+    pub fn get(&self, amount: f64) -> Vec<Action> {
+        let capacity = self.capacity();
+        assert!(
+            (0.0..=capacity).contains(&amount),
+            "Got {} from `{}`, which holds between 0 and {}",
+            amount,
+            self.name(),
+            capacity
+        );
+        self.enqueue(amount, |inner| &mut inner.gets)
+    }
+
+    fn enqueue(&self, amount: f64, queue: fn(&mut Inner) -> &mut VecDeque<Waiting>) -> Vec<Action> {
+        let key = self
+            .current
+            .get()
+            .expect("level containers can only be used from inside an entity");
+        let mut inner = self.inner.borrow_mut();
+        queue(&mut inner).push_back(Waiting { key, amount });
+        let mut served = inner.serve();
+        self.level.set(inner.level);
+        match served.iter().position(|&other| other == key) {
+            Some(position) => {
+                served.remove(position);
+                if served.is_empty() {
+                    Vec::new()
+                } else {
+                    vec![Action::ActivateMany(served)]
+                }
+            }
+            None => {
+                debug_assert!(served.is_empty(), "only the new request can let others complete");
+                vec![Action::Passivate]
+            }
+        }
+    }
+
+    #[must_use]
+    pub fn name(&self) -> String {
+        self.inner.borrow().name.clone()
+    }
+
+    #[must_use]
+    pub fn capacity(&self) -> f64 {
+        self.inner.borrow().capacity
+    }
+
+    /// Returns the amount currently in the container.
+    #[must_use]
+    pub fn level(&self) -> f64 {
+        self.inner.borrow().level
+    }
+
+    /// Returns the time-weighted statistics of the level.
+    #[must_use]
+    pub fn level_statistic(&self) -> Accumulate {
+        self.level.clone()
+    }
+
+    /// Returns the number of puts waiting for room.
+    #[must_use]
+    pub fn puts_waiting(&self) -> usize {
+        self.inner.borrow().puts.len()
+    }
+
+    /// Returns the number of gets waiting for material.
+    #[must_use]
+    pub fn gets_waiting(&self) -> usize {
+        self.inner.borrow().gets.len()
+    }
+}
+
+impl Statistic for LevelContainer {
+    fn reset(&self) {
+        self.level.reset();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+    use std::vec;
+
+    use super::*;
+    use crate::{process, GenBoxed, Simulation};
+
+    type Log = Rc<RefCell<Vec<(&'static str, Duration)>>>;
+
+    // Waits until `at`, then puts (positive `amount`) or gets (negative `amount`) and logs when it's done.
+    fn worker(
+        tank: LevelContainer,
+        name: &'static str,
+        at: u64,
+        amount: f64,
+        clock: ClockRef,
+        log: Log,
+    ) -> GenBoxed<()> {
+        let mut held = false;
+        let mut actions: Option<vec::IntoIter<Action>> = None;
+        process(move |_| {
+            if !held {
+                held = true;
+                return Some(Action::Hold(Duration::from_secs(at)));
+            }
+            let actions = actions
+                .get_or_insert_with(|| if amount >= 0.0 { tank.put(amount) } else { tank.get(-amount) }.into_iter());
+            if let Some(action) = actions.next() {
+                return Some(action);
+            }
+            log.borrow_mut().push((name, clock.time()));
+            None
+        })
+    }
+
+    fn run(
+        capacity: f64,
+        initial: f64,
+        workers: &[(&'static str, u64, f64)],
+    ) -> (LevelContainer, Vec<(&'static str, u64)>) {
+        let mut simulation = Simulation::default();
+        let tank = simulation.add_level_container("tank", capacity, initial);
+        let log = Log::default();
+        for &(name, at, amount) in workers {
+            let key = simulation.add_generator(worker(tank.clone(), name, at, amount, simulation.clock(), log.clone()));
+            simulation.schedule_now(key);
+        }
+        simulation.run_until_empty();
+        let log = log.borrow().iter().map(|&(name, time)| (name, time.as_secs())).collect();
+        (tank, log)
+    }
+
+    #[test]
+    fn gets_wait_for_material_in_order() {
+        // The small get waits behind the large one although there's enough for it.
+        let (tank, log) = run(100.0, 20.0, &[("large", 0, -50.0), ("small", 1, -10.0), ("supplier", 2, 40.0)]);
+        assert_eq!(vec![("supplier", 2), ("large", 2), ("small", 2)], log);
+        assert_eq!(0.0, tank.level());
+        assert_eq!(0, tank.gets_waiting());
+        // 20 for 2 seconds.
+        assert_eq!(20.0, tank.level_statistic().time_average());
+    }
+
+    #[test]
+    fn puts_wait_for_room() {
+        let (tank, log) = run(100.0, 0.0, &[("first", 0, 80.0), ("second", 1, 30.0), ("consumer", 3, -50.0)]);
+        assert_eq!(vec![("first", 0), ("consumer", 3), ("second", 3)], log);
+        assert_eq!(60.0, tank.level());
+        assert_eq!(0, tank.puts_waiting());
+    }
+
+    #[test]
+    #[should_panic(expected = "which holds between 0 and 10")]
+    fn amounts_over_the_capacity_panic() {
+        run(10.0, 0.0, &[("too much", 0, 11.0)]);
+    }
+}
@@ -1,32 +1,194 @@
-#![feature(generators, generator_trait)]
+#![cfg_attr(not(feature = "stable"), feature(generators, generator_trait))]
 // use std::cell::Cell;
 
+// Lets the code generated by the macros refer to `::rustsim` inside this crate too.
+extern crate self as rustsim;
+
+// Tests written as generators use `yield`, which stable Rust rejects even in code removed by `cfg`, so their tokens
+// go through this macro, dropping them with the `stable` feature.
+#[cfg(all(test, not(feature = "stable")))]
+macro_rules! generator_tests {
+    ($($tokens:tt)*) => { $($tokens)* };
+}
+#[cfg(all(test, feature = "stable"))]
+macro_rules! generator_tests {
+    ($($tokens:tt)*) => {};
+}
+
+pub mod abm;
+#[cfg(feature = "async-process")]
+mod async_process;
+mod attributes;
+mod breakpoint;
+mod bulk;
+#[cfg(feature = "chrono")]
+mod calendar;
+mod cancel;
+pub mod checkpoint;
+mod components;
 mod container;
+mod continuous;
+#[cfg(feature = "cosim")]
+pub mod cosim;
+mod csv;
+#[cfg(feature = "debugger")]
+pub mod debugger;
+mod deadlock;
+pub mod devs;
+pub mod distributions;
+mod entity;
+mod error;
+#[cfg(feature = "serde")]
+pub mod experiment;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "fmi")]
+pub mod fmi;
+pub mod gpss;
+mod handle;
+mod hooks;
+mod instrumentation;
 mod keys;
+mod level;
+mod local;
+mod logger;
+mod metadata;
+mod module;
+mod names;
+mod nested;
+pub mod net;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod parallel;
+pub mod petri;
+mod preempt;
+mod process;
+pub mod qnet;
+mod queue;
+#[cfg(not(target_arch = "wasm32"))]
+mod realtime;
+mod rendezvous;
+mod replay;
+mod replication;
+mod resource;
+mod sampler;
+mod resume;
+mod retry;
+mod rng;
 mod scheduler;
+mod source;
+pub mod ssa;
+mod signal;
 mod simulation;
+mod sink;
+mod spawner;
 mod state;
+mod stats;
+mod steps;
+mod sweep;
+pub mod time;
+mod timeline;
+mod trace;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
-use std::{ops::Generator, time::Duration};
+use std::time::Duration;
 
-pub use keys::Key;
-pub use simulation::{Simulation, ShouldContinue};
-pub use state::{State, StateKey};
+#[cfg(feature = "async-process")]
+pub use async_process::{async_process, Co, YieldFuture};
+pub use attributes::{Attributes, EntityAttributes};
+pub use breakpoint::Breakpoint;
+pub use bulk::{BulkServer, BulkService};
+#[cfg(feature = "chrono")]
+pub use calendar::Calendar;
+pub use cancel::{CancelOutcome, Cancellations};
+pub use checkpoint::{Checkpoint, CheckpointError, CheckpointRecorder};
+pub use components::{Component, ComponentKind};
+pub use container::EntityState;
+pub use continuous::{Continuous, Crossing, CrossingDirection, Values, Var};
+pub use deadlock::{Deadlock, PassiveEntity};
+pub use entity::{Entity, EntityContext, Process};
+#[cfg(feature = "macros")]
+pub use rustsim_macros::Entity;
+pub use error::SimulationError;
+pub use handle::{Progress, RunHandle, RunStatus};
+pub use keys::{Key, WeakKey};
+pub use level::LevelContainer;
+pub use local::LocalStore;
+pub use logger::SimLogger;
+pub use metadata::RunMetadata;
+pub use module::Module;
+pub use names::EntityNames;
+pub use nested::NestedSimulation;
+pub use preempt::Preemptions;
+pub use process::{process, FnProcess, Generator, GeneratorState};
+#[cfg(feature = "macros")]
+pub use rustsim_macros::process;
+pub use queue::SimQueue;
+#[cfg(not(target_arch = "wasm32"))]
+pub use realtime::RealTimeRunner;
+pub use rendezvous::{Call, Rendezvous};
+pub use replay::Divergence;
+pub use replication::{Comparison, Replication, Replications, Replicator, Verdict};
+pub use resource::{QueuedRequest, Request, Resource, ResourceAttempt, ResourceStats};
+pub use resume::{Interrupt, Resume};
+pub use retry::{retry, Attempt, Retry, RetryPolicy};
+pub use rng::{RngStreams, SimRng};
+pub use scheduler::{CalendarQueue, ClockRef, EventEntry, EventId, FutureEventList};
+pub use source::{Source, SourceHandle};
+pub use signal::{Signal, Signals};
+pub use sampler::Sampler;
+pub use sink::Sink;
+pub use simulation::{Simulation, SimulationBuilder, StepContext, StepOutcome, StepResult};
+pub use steps::Steps;
+pub use sweep::{Sweep, SweepPoint, SweepResults};
+pub use spawner::Spawner;
+pub use state::{SharedState, State, StateError, StateGuard, StateKey};
+pub use stats::{
+    mser, mser5, t_critical, Accumulate, BatchMeans, BatchMeansResult, Histogram, Statistic, Summary, Tally, Truncation,
+};
+pub use time::{SimTime, Ticks};
+#[doc(hidden)]
+pub use time::{ticks_from_duration as __ticks_from_duration, ticks_to_duration as __ticks_to_duration};
+pub use timeline::Timeline;
+pub use trace::{GanttBar, TraceEvent, TraceEventKind, TraceRecorder};
 
 pub type GenBoxed<R, C = ()> = Box<dyn Generator<R, Yield = Action, Return = C> + Unpin>;
 
 // Action Define que acción realiza la simulación
 // Este enum es devuelto tras ejecutar un step de los generadores
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Action {
     Hold(Duration),
+    /// Hold, then run before the entities resumed at the same time with a lower priority (`Hold` has priority 0).
+    HoldWithPriority(Duration, i32),
     Passivate,
     ActivateOne(Key),
     ActivateMany(Vec<Key>),
+    /// Remove the pending event of another entity, leaving it passive. The entity is resumed right away and
+    /// can learn from [`Cancellations`] whether the other entity was scheduled at all.
     Cancel(Key),
+    /// Interrupt the hold of another entity, which is resumed right away and can learn how much of its hold
+    /// remained from [`Preemptions`].
+    Preempt(Key),
+    /// Publish a payload on a topic to every entity subscribed to it, activating the passive ones, see [`Signals`].
+    /// The entity is resumed right away.
+    Broadcast(String, String),
+    /// Kill another entity with [`Simulation::kill`], removing it with its descendants and their pending events.
+    /// The entity is resumed right away, unless it killed itself or one of its ancestors.
+    Terminate(Key),
 }
 
 impl Action {
+    /// Hold for `time`, given in any [`SimTime`] representation and converted to a [`Duration`].
+    #[inline]
+    pub fn hold(time: impl SimTime) -> Self {
+        Action::Hold(time.to_duration())
+    }
+    /// Hold for `time` with `priority`, see [`Action::HoldWithPriority`].
+    #[inline]
+    pub fn hold_with_priority(time: impl SimTime, priority: i32) -> Self {
+        Action::HoldWithPriority(time.to_duration(), priority)
+    }
     #[inline]
     pub fn activate_one(key: Key) -> Self {
         Action::ActivateOne(key)
@@ -37,6 +199,45 @@ impl Action {
     }
 }
 
+// Runs the generators the macro expands to, its expansion is tested in the macros crate.
+#[cfg(all(test, feature = "macros", not(feature = "stable")))]
+mod test {
+    use std::time::Duration;
+
+    use crate::state::State;
+    use crate::{Key, Simulation};
+
+    #[test]
+    fn process_macro_expands_actions_and_state() {
+        let mut simulation = Simulation::default();
+        let shared_state = simulation.state();
+        let mut state = State::default();
+        let sleeper_key = state.insert(None::<Key>);
+        let wakes = state.insert(0u32);
+        shared_state.set(state);
+        let sleeper = simulation.add_generator(crate::process!(shared_state.clone() => {
+            for _ in 0..2 {
+                passivate!();
+                state!(wakes) += 1;
+            }
+        }));
+        shared_state.with_mut(|state| *state.get_mut(sleeper_key).unwrap() = Some(sleeper));
+        let waker = simulation.add_generator(crate::process!(shared_state.clone() => {
+            hold!(1.5s);
+            activate!(state!(sleeper_key).unwrap());
+            hold!(500ms);
+            hold!(Duration::from_secs(1));
+            activate!(state!(sleeper_key).unwrap());
+        }));
+        simulation.schedule_now(sleeper);
+        simulation.schedule_now(waker);
+        simulation.run_until_empty();
+
+        assert_eq!(Duration::from_secs(3), simulation.time());
+        assert_eq!(2, shared_state.with(|state| *state.get(wakes).unwrap()));
+    }
+}
+
 // thread_local! {
 //     static ID_COUNTER: Cell<usize> = Cell::new(0);
 // }
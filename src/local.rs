@@ -0,0 +1,134 @@
+use std::any::{Any, TypeId};
+use std::cell::{Cell, Ref, RefCell, RefMut};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::Key;
+
+type Store = HashMap<(Key, TypeId), Box<dyn Any>>;
+
+/// Data private to each entity, at most one value per type and entity.
+///
+/// Obtained from [`Simulation::local_store`](crate::Simulation::local_store), clones share the store so
+/// entities can keep their bookkeeping in it while observers read it through
+/// [`Simulation::entity_data`](crate::Simulation::entity_data). The data of an entity is dropped when it completes,
+/// after the `on_complete` hooks ran.
+///
+/// ```ignore
+/// let local = simulation.local_store();
+/// simulation.add_generator(Box::new(move |_| loop {
+///     local.local_mut(|served: &mut u32| *served += 1);
+///     yield Action::Hold(service_time);
+/// }));
+/// ```
+#[derive(Clone)]
+pub struct LocalStore {
+    store: Rc<RefCell<Store>>,
+    current: Rc<Cell<Option<Key>>>,
+}
+
+impl LocalStore {
+    pub(crate) fn new(current: Rc<Cell<Option<Key>>>) -> Self {
+        Self {
+            store: Rc::default(),
+            current,
+        }
+    }
+
+    /// Store `value` for the entity, returning its previous value of the same type.
+    pub fn insert<T: 'static>(&self, key: Key, value: T) -> Option<T> {
+        self.store
+            .borrow_mut()
+            .insert((key, TypeId::of::<T>()), Box::new(value))
+            .map(|previous| *previous.downcast::<T>().expect("Ensured by the type id."))
+    }
+
+    /// Returns a copy of the value of type `T` stored for the entity.
+    #[must_use]
+    pub fn get<T: Clone + 'static>(&self, key: Key) -> Option<T> {
+        self.borrow::<T>(key).map(|value| value.clone())
+    }
+
+    /// Call `f` with the value of type `T` stored for the entity, `None` if there is none.
+    pub fn with_mut<T: 'static, U>(&self, key: Key, f: impl FnOnce(&mut T) -> U) -> Option<U> {
+        self.borrow_mut::<T>(key).map(|mut value| f(&mut value))
+    }
+
+    /// Call `f` with the value of type `T` of the entity currently being executed, inserting the default value first if needed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of an entity.
+    pub fn local_mut<T: Default + 'static, U>(&self, f: impl FnOnce(&mut T) -> U) -> U {
+        let key = self.current.get().expect("LocalStore::local_mut called outside of an entity");
+        let mut store = self.store.borrow_mut();
+        let value = store
+            .entry((key, TypeId::of::<T>()))
+            .or_insert_with(|| Box::<T>::default());
+        f(value.downcast_mut::<T>().expect("Ensured by the type id."))
+    }
+
+    pub fn remove<T: 'static>(&self, key: Key) -> Option<T> {
+        self.store
+            .borrow_mut()
+            .remove(&(key, TypeId::of::<T>()))
+            .map(|value| *value.downcast::<T>().expect("Ensured by the type id."))
+    }
+
+    pub(crate) fn borrow<T: 'static>(&self, key: Key) -> Option<Ref<'_, T>> {
+        Ref::filter_map(self.store.borrow(), |store| {
+            store
+                .get(&(key, TypeId::of::<T>()))
+                .map(|value| value.downcast_ref::<T>().expect("Ensured by the type id."))
+        })
+        .ok()
+    }
+
+    pub(crate) fn borrow_mut<T: 'static>(&self, key: Key) -> Option<RefMut<'_, T>> {
+        RefMut::filter_map(self.store.borrow_mut(), |store| {
+            store
+                .get_mut(&(key, TypeId::of::<T>()))
+                .map(|value| value.downcast_mut::<T>().expect("Ensured by the type id."))
+        })
+        .ok()
+    }
+
+    // Drop every value of the entity.
+    pub(crate) fn clear(&self, key: Key) {
+        self.store.borrow_mut().retain(|&(owner, _), _| owner != key);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use crate::{process, Action, Simulation};
+
+    #[test]
+    fn entities_keep_private_data() {
+        let mut simulation = Simulation::default();
+        let local = simulation.local_store();
+        let mut holds = 0;
+        let counter = simulation.add_generator(process(move |_| {
+            holds += 1;
+            if holds > 3 {
+                return None;
+            }
+            local.local_mut(|count: &mut u32| *count += 1);
+            Some(Action::Hold(Duration::from_secs(1)))
+        }));
+        simulation.insert_entity_data(counter, "counter");
+        simulation.schedule_now(counter);
+
+        simulation.step_n(2);
+        assert_eq!(Some(2), simulation.entity_data::<u32>(counter).map(|count| *count));
+        *simulation.entity_data_mut::<u32>(counter).unwrap() += 10;
+        simulation.step().unwrap();
+        assert_eq!(Some(13), simulation.local_store().get::<u32>(counter));
+        assert_eq!(Some("counter"), simulation.local_store().get::<&str>(counter));
+
+        simulation.run_until_empty();
+        assert!(simulation.entity_data::<u32>(counter).is_none());
+    }
+}
@@ -0,0 +1,118 @@
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::io::Write;
+use std::rc::Rc;
+
+use crate::names::EntityNames;
+use crate::scheduler::ClockRef;
+use crate::Key;
+
+/// Writes log records prefixed with the simulation time and the entity being executed.
+///
+/// Obtained from [`Simulation::logger`](crate::Simulation::logger) or
+/// [`Simulation::logger_to`](crate::Simulation::logger_to), clones share the output so it can be moved into
+/// generators:
+///
+/// ```ignore
+/// let log = simulation.logger();
+/// simulation.add_generator_named("teller", Box::new(move |_| {
+///     yield Action::Hold(Duration::from_millis(1500));
+///     log.log(format_args!("served {} customers", served));
+///     // [1.500s teller] served 3 customers
+/// }));
+/// ```
+///
+/// Entities without a name are labelled `entity <id>`, records written outside of an entity only carry the time.
+#[derive(Clone)]
+pub struct SimLogger {
+    output: Rc<RefCell<Box<dyn Write>>>,
+    clock: ClockRef,
+    current: Rc<Cell<Option<Key>>>,
+    names: EntityNames,
+}
+
+impl SimLogger {
+    pub(crate) fn new(
+        output: Box<dyn Write>,
+        clock: ClockRef,
+        current: Rc<Cell<Option<Key>>>,
+        names: EntityNames,
+    ) -> Self {
+        Self {
+            output: Rc::new(RefCell::new(output)),
+            clock,
+            current,
+            names,
+        }
+    }
+
+    /// Returns the prefix of the records written now, e.g. `[1.500s teller]`.
+    #[must_use]
+    pub fn prefix(&self) -> String {
+        let time = self.clock.time().as_secs_f64();
+        match self.current.get() {
+            Some(key) => format!("[{:.3}s {}]", time, self.names.label(key)),
+            None => format!("[{:.3}s]", time),
+        }
+    }
+
+    /// Write `message` as a record on its own line.
+    ///
+    /// Errors writing to the output are ignored, logging never stops the simulation.
+    pub fn log(&self, message: impl fmt::Display) {
+        let _ = writeln!(self.output.borrow_mut(), "{} {}", self.prefix(), message);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{process, Action, Simulation};
+
+    #[derive(Clone, Default)]
+    struct Output(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn records_carry_the_simulation_time_and_entity() {
+        let mut simulation = Simulation::default();
+        let output = Output::default();
+        let log = simulation.logger_to(output.clone());
+        let teller_log = log.clone();
+        let mut served = 0;
+        let teller = simulation.add_generator_named(
+            "teller",
+            process(move |_| {
+                served += 1;
+                teller_log.log(format_args!("served {}", served));
+                (served < 2).then_some(Action::Hold(Duration::from_millis(1500)))
+            }),
+        );
+        let anonymous_log = log.clone();
+        let anonymous = simulation.add_generator(process(move |_| {
+            anonymous_log.log("hello");
+            None
+        }));
+        simulation.schedule_now(teller);
+        simulation.schedule(Duration::from_secs(1), anonymous);
+        simulation.run_until_empty();
+        log.log("done");
+
+        let expected = format!(
+            "[0.000s teller] served 1\n[1.000s entity {}] hello\n[1.500s teller] served 2\n[1.500s] done\n",
+            anonymous.id()
+        );
+        assert_eq!(expected, String::from_utf8(output.0.borrow().clone()).unwrap());
+    }
+}
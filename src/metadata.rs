@@ -0,0 +1,150 @@
+use std::collections::BTreeMap;
+
+/// Metadata describing a run of the simulation (scenario, parameters, seed, revision, ...).
+///
+/// Attached to a [`Simulation`](crate::Simulation) and embedded in every export so output files
+/// can be traced back to the run that produced them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RunMetadata {
+    scenario: Option<String>,
+    parameter_hash: Option<u64>,
+    seed: Option<u64>,
+    revision: Option<String>,
+    extra: BTreeMap<String, String>,
+}
+
+impl RunMetadata {
+    #[must_use]
+    pub fn scenario(&self) -> Option<&str> {
+        self.scenario.as_deref()
+    }
+
+    pub fn set_scenario(&mut self, scenario: impl Into<String>) {
+        self.scenario = Some(scenario.into());
+    }
+
+    #[must_use]
+    pub fn parameter_hash(&self) -> Option<u64> {
+        self.parameter_hash
+    }
+
+    pub fn set_parameter_hash(&mut self, hash: u64) {
+        self.parameter_hash = Some(hash);
+    }
+
+    #[must_use]
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = Some(seed);
+    }
+
+    /// Returns the revision (e.g. a git commit) of the model that produced the run.
+    #[must_use]
+    pub fn revision(&self) -> Option<&str> {
+        self.revision.as_deref()
+    }
+
+    pub fn set_revision(&mut self, revision: impl Into<String>) {
+        self.revision = Some(revision.into());
+    }
+
+    /// Insert a user defined entry, replacing and returning the previous value under `name`.
+    pub fn insert(&mut self, name: impl Into<String>, value: impl ToString) -> Option<String> {
+        self.extra.insert(name.into(), value.to_string())
+    }
+
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.extra.get(name).map(String::as_str)
+    }
+
+    /// Returns every entry that has a value as `(name, value)` pairs, the well known ones first.
+    ///
+    /// This is the representation embedded by exporters.
+    #[must_use]
+    pub fn entries(&self) -> Vec<(String, String)> {
+        let known = [
+            ("scenario", self.scenario.clone()),
+            ("parameter_hash", self.parameter_hash.map(|hash| format!("{:016x}", hash))),
+            ("seed", self.seed.map(|seed| seed.to_string())),
+            ("revision", self.revision.clone()),
+        ];
+        known
+            .into_iter()
+            .filter_map(|(name, value)| value.map(|value| (name.to_owned(), value)))
+            .chain(self.extra.iter().map(|(name, value)| (name.clone(), value.clone())))
+            .collect()
+    }
+
+    // Set an entry by the name it has in `entries`, the inverse of the exporters.
+    pub(crate) fn set_entry(&mut self, name: &str, value: &str) -> Result<(), String> {
+        match name {
+            "scenario" => self.set_scenario(value),
+            "parameter_hash" => self.set_parameter_hash(
+                u64::from_str_radix(value, 16).map_err(|_| format!("invalid parameter hash `{}`", value))?,
+            ),
+            "seed" => self.set_seed(value.parse().map_err(|_| format!("invalid seed `{}`", value))?),
+            "revision" => self.set_revision(value),
+            _ => {
+                self.insert(name, value);
+            }
+        }
+        Ok(())
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries().is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Simulation;
+
+    #[test]
+    fn entries_list_the_known_fields_first() {
+        let mut metadata = RunMetadata::default();
+        assert!(metadata.is_empty());
+        assert_eq!(None, metadata.insert("operator", "ana"));
+        assert_eq!(Some("ana".to_owned()), metadata.insert("operator", "luis"));
+        metadata.insert("arrival rate", 0.5);
+        metadata.set_revision("3f2a9c1");
+        metadata.set_parameter_hash(0xbeef);
+        metadata.set_scenario("peak hour");
+
+        let entries = vec![
+            ("scenario", "peak hour"),
+            ("parameter_hash", "000000000000beef"),
+            ("revision", "3f2a9c1"),
+            ("arrival rate", "0.5"),
+            ("operator", "luis"),
+        ];
+        let found = metadata.entries();
+        let found: Vec<(&str, &str)> = found.iter().map(|(name, value)| (name.as_str(), value.as_str())).collect();
+        assert_eq!(entries, found);
+
+        // The exporters' representation reads back into the same metadata.
+        let mut parsed = RunMetadata::default();
+        for (name, value) in metadata.entries() {
+            parsed.set_entry(&name, &value).unwrap();
+        }
+        assert_eq!(metadata, parsed);
+        assert!(parsed.set_entry("seed", "forty-two").is_err());
+        assert!(parsed.set_entry("parameter_hash", "xyz").is_err());
+    }
+
+    #[test]
+    fn simulations_record_their_seed() {
+        let mut simulation = Simulation::<()>::default();
+        assert_eq!(None, simulation.metadata().seed());
+        simulation.set_seed(42);
+        simulation.metadata_mut().set_scenario("baseline");
+        assert_eq!(Some(42), simulation.metadata().seed());
+        assert_eq!(Some("baseline"), simulation.metadata().scenario());
+    }
+}
@@ -0,0 +1,166 @@
+use crate::queue::SimQueue;
+use crate::resource::Resource;
+use crate::sink::Sink;
+use crate::state::SharedState;
+use crate::stats::Statistic;
+use crate::{GenBoxed, Key, Simulation};
+
+/// A named scope of a model, e.g. a production line reused in several factories.
+///
+/// Created with [`Simulation::module`](crate::Simulation::module) and [`Module::module`]. Entities and components
+/// added through a module are named after its path, `"factory.line1.machine2"`, so traces and statistics show the
+/// hierarchy and building blocks added twice under different modules don't collide. Every module has its own
+/// [`State`](crate::State), keys inserted in it can't clash with those of other modules:
+///
+/// ```ignore
+/// fn line(mut line: Module<'_, ()>) {
+///     let produced = line.state().with_mut(|state| state.insert(0u32));
+///     let buffer = line.add_queue::<Key>("buffer");
+///     let machine = line.add_generator("machine", machine(line.state(), produced, buffer));
+///     line.simulation().schedule_now(machine);
+/// }
+///
+/// let mut factory = simulation.module("factory");
+/// line(factory.module("line1"));
+/// line(factory.module("line2"));
+/// ```
+pub struct Module<'a, R> {
+    simulation: &'a mut Simulation<R>,
+    path: String,
+}
+
+impl<'a, R: 'static> Module<'a, R> {
+    pub(crate) fn new(simulation: &'a mut Simulation<R>, path: String) -> Self {
+        Self { simulation, path }
+    }
+
+    /// Returns the path of the module, its name prefixed by the names of its parents.
+    #[must_use]
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns the name of `name` in this module, `"<path>.<name>"`.
+    #[must_use]
+    pub fn qualify(&self, name: &str) -> String {
+        format!("{}.{}", self.path, name)
+    }
+
+    /// Returns the module `name` nested in this one.
+    pub fn module(&mut self, name: &str) -> Module<'_, R> {
+        let path = self.qualify(name);
+        Module::new(self.simulation, path)
+    }
+
+    /// Returns the state of this module, shared by its entities and distinct from the state of the simulation and of
+    /// the other modules.
+    #[must_use]
+    pub fn state(&mut self) -> SharedState {
+        self.simulation.module_state(&self.path)
+    }
+
+    /// Add an entity named `name` in this module, see
+    /// [`Simulation::add_generator_named`](crate::Simulation::add_generator_named).
+    pub fn add_generator(&mut self, name: &str, gen: GenBoxed<R>) -> Key {
+        let name = self.qualify(name);
+        self.simulation.add_generator_named(name, gen)
+    }
+
+    /// Returns the key of the entity named `name` in this module.
+    #[must_use]
+    pub fn key_of(&self, name: &str) -> Option<Key> {
+        self.simulation.key_of(&self.qualify(name))
+    }
+
+    /// Returns the keys of the entities of this module and of the modules nested in it, in slot order.
+    #[must_use]
+    pub fn entity_keys(&self) -> Vec<Key> {
+        let prefix = format!("{}.", self.path);
+        let names = self.simulation.names();
+        let mut keys = self.simulation.entity_keys();
+        keys.retain(|&key| names.name_of(key).is_some_and(|name| name.starts_with(&prefix)));
+        keys
+    }
+
+    /// Add a [`Resource`] named `name` in this module, see [`Simulation::add_resource`].
+    pub fn add_resource(&mut self, name: &str, capacity: usize) -> Resource {
+        let name = self.qualify(name);
+        self.simulation.add_resource(name, capacity)
+    }
+
+    /// Add a [`SimQueue`] named `name` in this module, see [`Simulation::add_queue`].
+    pub fn add_queue<T: 'static>(&mut self, name: &str) -> SimQueue<T> {
+        let name = self.qualify(name);
+        self.simulation.add_queue(name)
+    }
+
+    /// Add a [`Sink`] named `name` in this module, see [`Simulation::add_sink`].
+    pub fn add_sink(&mut self, name: &str) -> Sink {
+        let name = self.qualify(name);
+        self.simulation.add_sink(name)
+    }
+
+    /// Attach `statistic`, registered as a component named `name` in this module, see
+    /// [`Simulation::add_statistic`].
+    pub fn add_statistic<S: Statistic + 'static>(&mut self, name: &str, statistic: S) {
+        let name = self.qualify(name);
+        self.simulation.add_statistic(name, statistic);
+    }
+
+    /// Returns the simulation, e.g. to schedule the entities of the module.
+    pub fn simulation(&mut self) -> &mut Simulation<R> {
+        self.simulation
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{process, Action};
+
+    fn line(mut line: Module<'_, ()>) -> Key {
+        let state = line.state();
+        // Every line names its counter the same way.
+        let produced = state.with_mut(|state| state.insert_named("produced", 0u32)).unwrap();
+        line.add_queue::<Key>("buffer");
+        let machine = line.add_generator(
+            "machine",
+            process(move |_| {
+                let produced = state.with_mut(|state| {
+                    let produced = state.get_mut(produced).unwrap();
+                    *produced += 1;
+                    *produced
+                });
+                (produced < 3).then_some(Action::Hold(Duration::from_secs(1)))
+            }),
+        );
+        line.simulation().schedule_now(machine);
+        machine
+    }
+
+    #[test]
+    fn modules_namespace_entities_and_state() {
+        let mut simulation = Simulation::default();
+        let mut factory = simulation.module("factory");
+        let first = line(factory.module("line1"));
+        let second = line(factory.module("line2"));
+        assert_eq!(vec![first, second], factory.entity_keys());
+        assert_eq!(vec![second], factory.module("line2").entity_keys());
+        assert_eq!(Some(first), factory.module("line1").key_of("machine"));
+        let trace = simulation.record_trace();
+        simulation.run_until_empty();
+
+        assert_eq!(Some("factory.line2.machine".to_owned()), simulation.name_of(second));
+        let components: Vec<String> = simulation.components().iter().map(|c| c.name().to_owned()).collect();
+        assert!(components.contains(&"factory.line1.buffer".to_owned()));
+        assert!(components.contains(&"factory.line2.buffer".to_owned()));
+        for path in ["factory.line1", "factory.line2"] {
+            let state = simulation.module(path).state();
+            assert_eq!(Ok(3), state.with(|state| state.get_named::<u32>("produced").copied()));
+        }
+        assert!(simulation.state().with(|state| state.is_empty()));
+        assert!(trace.to_chrome_json().contains("factory.line1.machine"));
+    }
+}
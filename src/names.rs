@@ -0,0 +1,46 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::Key;
+
+#[derive(Debug, Default)]
+struct Names {
+    by_name: HashMap<String, Key>,
+    by_key: HashMap<Key, String>,
+}
+
+/// The names given to entities with [`Simulation::add_generator_named`](crate::Simulation::add_generator_named).
+///
+/// Obtained from [`Simulation::names`](crate::Simulation::names), clones share the registry so it can be moved
+/// into hooks to label their output. Names are kept after their entity completes, so traces exported at the end
+/// of the run still show them.
+#[derive(Debug, Clone, Default)]
+pub struct EntityNames {
+    inner: Rc<RefCell<Names>>,
+}
+
+impl EntityNames {
+    pub(crate) fn insert(&self, name: String, key: Key) {
+        let mut inner = self.inner.borrow_mut();
+        inner.by_key.insert(key, name.clone());
+        inner.by_name.insert(name, key);
+    }
+
+    /// Returns the key of the last entity named `name`.
+    #[must_use]
+    pub fn key_of(&self, name: &str) -> Option<Key> {
+        self.inner.borrow().by_name.get(name).copied()
+    }
+
+    #[must_use]
+    pub fn name_of(&self, key: Key) -> Option<String> {
+        self.inner.borrow().by_key.get(&key).cloned()
+    }
+
+    /// Returns the name of the entity, or `entity <id>` if it has none.
+    #[must_use]
+    pub fn label(&self, key: Key) -> String {
+        self.name_of(key).unwrap_or_else(|| format!("entity {}", key.id()))
+    }
+}
@@ -0,0 +1,132 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::{process, Action, GenBoxed, Key, Simulation, SimulationError};
+
+struct Inner {
+    simulation: Simulation<()>,
+    errors: Vec<SimulationError>,
+}
+
+/// A simulation embedded as an entity of another one, e.g. a detailed sub-model of a coarse system model.
+///
+/// Created with [`Simulation::add_simulation`](crate::Simulation::add_simulation). Every time the entity is resumed
+/// it executes the events of the child due by then, and holds until the next one, so the child runs in lockstep
+/// with the parent, its time 0 being the time the entity was added. The entity completes once the child has no more
+/// events:
+///
+/// ```ignore
+/// let cell = simulation.add_simulation("machining cell", detailed_cell_model());
+/// simulation.run_with_limit(Duration::from_secs(3600));
+/// let produced = cell.with(|cell| cell.state().with(|state| *state.get(produced).unwrap()));
+/// ```
+#[derive(Clone)]
+pub struct NestedSimulation {
+    key: Key,
+    inner: Rc<RefCell<Inner>>,
+}
+
+impl NestedSimulation {
+    pub(crate) fn new(key: Key, simulation: Simulation<()>) -> Self {
+        Self {
+            key,
+            inner: Rc::new(RefCell::new(Inner {
+                simulation,
+                errors: Vec::new(),
+            })),
+        }
+    }
+
+    pub(crate) fn generator<R: 'static>(&self) -> GenBoxed<R> {
+        let inner = Rc::clone(&self.inner);
+        let mut now = inner.borrow().simulation.time();
+        process(move |_| {
+            let mut inner = inner.borrow_mut();
+            let Inner { simulation, errors } = &mut *inner;
+            while simulation.next_event_time().is_some_and(|next| next <= now) {
+                if let Err(error) = simulation.step() {
+                    errors.push(error);
+                }
+            }
+            let next = simulation.next_event_time()?;
+            let delay = next - now;
+            now = next;
+            Some(Action::Hold(delay))
+        })
+    }
+
+    /// Returns the key of the entity advancing the child simulation.
+    #[must_use]
+    pub fn key(&self) -> Key {
+        self.key
+    }
+
+    /// Returns the time of the child simulation.
+    #[must_use]
+    pub fn time(&self) -> Duration {
+        self.inner.borrow().simulation.time()
+    }
+
+    /// Call `f` with the child simulation, e.g. to read its state or statistics.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called by an entity of the child simulation.
+    pub fn with<T>(&self, f: impl FnOnce(&mut Simulation<()>) -> T) -> T {
+        f(&mut self.inner.borrow_mut().simulation)
+    }
+
+    /// Returns the invalid actions yielded by entities of the child simulation.
+    #[must_use]
+    pub fn errors(&self) -> Vec<SimulationError> {
+        self.inner.borrow().errors.clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::EntityState;
+
+    #[test]
+    fn children_advance_in_lockstep_with_their_parent() {
+        let mut child = Simulation::default();
+        let steps = child.state().with_mut(|state| state.insert(Vec::new()));
+        let state = child.state();
+        let clock = child.clock();
+        let mut holds = 0;
+        let worker = child.add_generator(process(move |_| {
+            state.with_mut(|state| state.get_mut(steps).unwrap().push(clock.time()));
+            holds += 1;
+            (holds < 3).then_some(Action::Hold(Duration::from_millis(250)))
+        }));
+        child.schedule(Duration::from_secs(1), worker);
+
+        let mut parent = Simulation::default();
+        let parent_clock = parent.clock();
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let starter = parent.add_generator(process(move |_| None));
+        parent.schedule(Duration::from_secs(2), starter);
+        parent.run_until_empty();
+        let nested = parent.add_simulation("cell", child);
+        let observer = {
+            let (nested, seen) = (nested.clone(), Rc::clone(&seen));
+            parent.add_generator(process(move |_| {
+                seen.borrow_mut().push((parent_clock.time(), nested.time()));
+                Some(Action::Hold(Duration::from_secs(1)))
+            }))
+        };
+        parent.schedule(Duration::from_millis(1100), observer);
+        parent.run_with_limit(Duration::from_secs(5));
+
+        let millis = |times: &[Duration]| times.iter().map(Duration::as_millis).collect::<Vec<_>>();
+        let child_steps = nested.with(|child| child.state().with(|state| state.get(steps).unwrap().clone()));
+        assert_eq!(vec![1000, 1250, 1500], millis(&child_steps));
+        let seen: Vec<_> = seen.borrow().iter().map(|&(parent, child)| (parent.as_millis(), child.as_millis())).collect();
+        assert_eq!((3100, 1000), seen[0]);
+        assert_eq!((4100, 1500), seen[1]);
+        assert_eq!(Some(EntityState::Completed), parent.entity_state(nested.key()));
+        assert!(nested.errors().is_empty());
+    }
+}
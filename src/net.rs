@@ -0,0 +1,567 @@
+//! Networks of entities exchanging packets over links with latency and bandwidth.
+//!
+//! Nodes are entities, connected by directed [`Link`]s added with [`Network::connect`]. A node sends a packet to any
+//! node it has a path to, the packet follows the path of lowest latency and is stored and forwarded by every node on
+//! the way. On each link it waits for the packets sent before it, takes its size over the bandwidth to be
+//! transmitted and then the latency of the link, plus its jitter, to arrive:
+//!
+//! ```ignore
+//! let network = simulation.add_network::<Request>("network");
+//! network.connect_both(client, router, Link::new(Duration::from_millis(5)).bandwidth(1_250_000.0));
+//! network.connect_both(router, server, Link::new(Duration::from_millis(20)));
+//!
+//! // Client
+//! if let Some(action) = network.send(server, 1500, Request::Get)? {
+//!     yield action;
+//! }
+//!
+//! // Server
+//! let packet = loop {
+//!     if let Some(packet) = network.receive() {
+//!         break packet;
+//!     }
+//!     yield Action::Passivate;
+//! };
+//! ```
+//!
+//! The packets in flight are delivered by an entity of the network, the action returned by [`Network::send`] wakes it
+//! up when the new packet is the next one to deliver.
+
+use std::cell::{Cell, RefCell};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap, VecDeque};
+use std::fmt;
+use std::mem;
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::distributions::Distribution;
+use crate::scheduler::ClockRef;
+use crate::{process, Action, GenBoxed, Key, SimRng, Tally};
+
+/// A directed link between two nodes of a [`Network`].
+pub struct Link {
+    latency: Duration,
+    // In bytes per second, unlimited if `None`.
+    bandwidth: Option<f64>,
+    jitter: Option<Box<dyn Distribution>>,
+}
+
+impl Link {
+    /// A link taking `latency` to carry a packet, with unlimited bandwidth.
+    #[must_use]
+    pub fn new(latency: Duration) -> Self {
+        Self {
+            latency,
+            bandwidth: None,
+            jitter: None,
+        }
+    }
+
+    /// Transmit `bytes_per_second`, one packet after the other.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bytes_per_second` isn't positive.
+    #[must_use]
+    pub fn bandwidth(mut self, bytes_per_second: f64) -> Self {
+        assert!(bytes_per_second > 0.0, "The bandwidth of a link must be positive");
+        self.bandwidth = Some(bytes_per_second);
+        self
+    }
+
+    /// Add a delay drawn from `jitter` to the latency of every packet, which may then arrive out of order.
+    #[must_use]
+    pub fn jitter(mut self, jitter: impl Distribution + 'static) -> Self {
+        self.jitter = Some(Box::new(jitter));
+        self
+    }
+}
+
+/// A packet received from a [`Network`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Packet<M> {
+    pub source: Key,
+    pub destination: Key,
+    /// In bytes.
+    pub size: u64,
+    pub payload: M,
+    pub sent_at: Duration,
+    /// Number of links the packet went through.
+    pub hops: usize,
+}
+
+/// Error returned by [`Network::send`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetError {
+    /// No path of links leads from the sender to the destination.
+    Unreachable { source: Key, destination: Key },
+}
+
+impl fmt::Display for NetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unreachable { source, destination } => write!(
+                f,
+                "no path leads from the entity ID = {} to the entity ID = {}",
+                source.id(),
+                destination.id()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for NetError {}
+
+struct LinkState {
+    link: Link,
+    // Time at which the link finishes transmitting the packets sent so far.
+    free_at: Duration,
+}
+
+struct InFlight<M> {
+    packet: Packet<M>,
+    // The remaining nodes of the path, the next one first.
+    path: VecDeque<Key>,
+}
+
+// Where the entity delivering the packets stands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Passive,
+    Holding(Duration),
+    // Resumed, or about to be, so it will pick up new packets by itself.
+    Busy,
+}
+
+struct Inbox<M> {
+    packets: VecDeque<Packet<M>>,
+    waiting: bool,
+}
+
+impl<M> Default for Inbox<M> {
+    fn default() -> Self {
+        Self {
+            packets: VecDeque::new(),
+            waiting: false,
+        }
+    }
+}
+
+struct Inner<M> {
+    links: HashMap<(Key, Key), LinkState>,
+    // Outgoing links of every node, sorted so routes don't depend on the order links were added.
+    neighbors: BTreeMap<(usize, u32), Vec<Key>>,
+    // Packets arriving at the end of their current link, by arrival time and then send order.
+    in_flight: BinaryHeap<Reverse<(Duration, u64)>>,
+    packets: HashMap<u64, InFlight<M>>,
+    sent: u64,
+    inboxes: HashMap<Key, Inbox<M>>,
+    status: Status,
+    delivered: u64,
+}
+
+impl<M> Inner<M> {
+    // Nodes from `source` (excluded) to `destination` along the path of lowest latency.
+    fn route(&self, source: Key, destination: Key) -> Option<VecDeque<Key>> {
+        let order = |key: Key| (key.id, key.generation);
+        let mut best: HashMap<Key, (Duration, Option<Key>)> = HashMap::from([(source, (Duration::ZERO, None))]);
+        let mut frontier = BinaryHeap::from([Reverse((Duration::ZERO, order(source)))]);
+        while let Some(Reverse((distance, (id, generation)))) = frontier.pop() {
+            let node = Key { id, generation };
+            if node == destination {
+                break;
+            }
+            if best[&node].0 < distance {
+                continue;
+            }
+            for &next in self.neighbors.get(&order(node)).into_iter().flatten() {
+                let candidate = distance + self.links[&(node, next)].link.latency;
+                if best.get(&next).is_none_or(|&(known, _)| candidate < known) {
+                    best.insert(next, (candidate, Some(node)));
+                    frontier.push(Reverse((candidate, order(next))));
+                }
+            }
+        }
+        best.get(&destination)?;
+        let mut path = VecDeque::new();
+        let mut node = destination;
+        while node != source {
+            path.push_front(node);
+            node = best[&node].1.expect("every reached node but the source has a predecessor");
+        }
+        Some(path)
+    }
+
+    // Put the packet `id` on the link from `node` to the next node of its path, arriving at the returned time.
+    fn transmit(&mut self, id: u64, node: Key, now: Duration, rng: &SimRng) -> Duration {
+        let in_flight = self.packets.get_mut(&id).expect("packets in flight are stored");
+        let next = *in_flight.path.front().expect("packets in flight have a next node");
+        in_flight.packet.hops += 1;
+        let size = in_flight.packet.size;
+        let link = self.links.get_mut(&(node, next)).expect("routes follow existing links");
+        let start = now.max(link.free_at);
+        let transmission = link
+            .link
+            .bandwidth
+            .map_or(Duration::ZERO, |bandwidth| Duration::from_secs_f64(size as f64 / bandwidth));
+        link.free_at = start + transmission;
+        let jitter = link.link.jitter.as_ref().map_or(Duration::ZERO, |jitter| jitter.sample(rng));
+        let arrival = link.free_at + link.link.latency + jitter;
+        self.in_flight.push(Reverse((arrival, id)));
+        arrival
+    }
+}
+
+/// A network of links between entities, created with [`Simulation::add_network`](crate::Simulation::add_network).
+///
+/// Clones share the same network so it can be moved into generators. See the [module documentation](self).
+pub struct Network<M> {
+    inner: Rc<RefCell<Inner<M>>>,
+    key: Rc<Cell<Option<Key>>>,
+    delay: Tally,
+    clock: ClockRef,
+    rng: SimRng,
+    current: Rc<Cell<Option<Key>>>,
+}
+
+impl<M> Clone for Network<M> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Rc::clone(&self.inner),
+            key: Rc::clone(&self.key),
+            delay: self.delay.clone(),
+            clock: self.clock.clone(),
+            rng: self.rng.clone(),
+            current: Rc::clone(&self.current),
+        }
+    }
+}
+
+impl<M: 'static> Network<M> {
+    pub(crate) fn new(name: &str, clock: ClockRef, rng: SimRng, current: Rc<Cell<Option<Key>>>) -> Self {
+        let inner = Inner {
+            links: HashMap::new(),
+            neighbors: BTreeMap::new(),
+            in_flight: BinaryHeap::new(),
+            packets: HashMap::new(),
+            sent: 0,
+            inboxes: HashMap::new(),
+            status: Status::Busy,
+            delivered: 0,
+        };
+        Self {
+            inner: Rc::new(RefCell::new(inner)),
+            key: Rc::default(),
+            delay: Tally::new(format!("{} delay", name)),
+            clock,
+            rng,
+            current,
+        }
+    }
+
+    /// Add a link from `from` to `to`, replacing any previous one.
+    pub fn connect(&self, from: Key, to: Key, link: Link) {
+        let mut inner = self.inner.borrow_mut();
+        let previous = inner.links.insert(
+            (from, to),
+            LinkState {
+                link,
+                free_at: Duration::ZERO,
+            },
+        );
+        if previous.is_none() {
+            let neighbors = inner.neighbors.entry((from.id, from.generation)).or_default();
+            neighbors.push(to);
+            neighbors.sort_by_key(|key| (key.id, key.generation));
+        }
+    }
+
+    /// Add a link in each direction between `a` and `b`, `link` and a copy without its jitter.
+    pub fn connect_both(&self, a: Key, b: Key, link: Link) {
+        let back = Link {
+            latency: link.latency,
+            bandwidth: link.bandwidth,
+            jitter: None,
+        };
+        self.connect(a, b, link);
+        self.connect(b, a, back);
+    }
+
+    /// Send `payload` of `size` bytes from the entity currently being executed to `destination`.
+    ///
+    /// Returns the action the sender has to yield, if any, to wake up the entity delivering the packets.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of an entity.
+    pub fn send(&self, destination: Key, size: u64, payload: M) -> Result<Option<Action>, NetError> {
+        let source = self
+            .current
+            .get()
+            .expect("packets can only be sent from inside an entity");
+        let now = self.clock.time();
+        let mut inner = self.inner.borrow_mut();
+        let path = inner
+            .route(source, destination)
+            .ok_or(NetError::Unreachable { source, destination })?;
+        let packet = Packet {
+            source,
+            destination,
+            size,
+            payload,
+            sent_at: now,
+            hops: 0,
+        };
+        let id = inner.sent;
+        inner.sent += 1;
+        if path.is_empty() {
+            // Sent to itself, delivered right away.
+            inner.packets.insert(id, InFlight { packet, path });
+            inner.in_flight.push(Reverse((now, id)));
+        } else {
+            inner.packets.insert(id, InFlight { packet, path });
+            inner.transmit(id, source, now, &self.rng);
+        }
+        let next = inner.in_flight.peek().map(|Reverse((arrival, _))| *arrival);
+        let key = self.key.get().expect("set when the network is added");
+        let action = match inner.status {
+            Status::Passive => Some(Action::ActivateOne(key)),
+            Status::Holding(until) if next.is_some_and(|next| next < until) => Some(Action::Preempt(key)),
+            _ => None,
+        };
+        if action.is_some() {
+            inner.status = Status::Busy;
+        }
+        Ok(action)
+    }
+
+    /// Take the oldest packet delivered to the entity currently being executed.
+    ///
+    /// When `None` is returned the entity must yield `Action::Passivate` and try again once activated.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of an entity.
+    pub fn receive(&self) -> Option<Packet<M>> {
+        let key = self
+            .current
+            .get()
+            .expect("packets can only be received from inside an entity");
+        let mut inner = self.inner.borrow_mut();
+        let inbox = inner.inboxes.entry(key).or_default();
+        let packet = inbox.packets.pop_front();
+        inbox.waiting = packet.is_none();
+        packet
+    }
+
+    /// Returns how many packets were delivered.
+    #[must_use]
+    pub fn delivered(&self) -> u64 {
+        self.inner.borrow().delivered
+    }
+
+    /// Returns how many packets are on their way.
+    #[must_use]
+    pub fn in_flight(&self) -> usize {
+        self.inner.borrow().packets.len()
+    }
+
+    /// Returns the time from sending to delivery of the packets, in seconds.
+    #[must_use]
+    pub fn delay(&self) -> Tally {
+        self.delay.clone()
+    }
+
+    pub(crate) fn set_key(&self, key: Key) {
+        self.key.set(Some(key));
+    }
+
+    pub(crate) fn into_generator<R: 'static>(self) -> GenBoxed<R> {
+        let mut pending = VecDeque::new();
+        // Status once the last pending action is yielded.
+        let mut after = Status::Busy;
+        process(move |_| {
+            if let Some(action) = pending.pop_front() {
+                if pending.is_empty() {
+                    self.inner.borrow_mut().status = after;
+                }
+                return Some(action);
+            }
+            let now = self.clock.time();
+            let mut inner = self.inner.borrow_mut();
+            while let Some(&Reverse((arrival, id))) = inner.in_flight.peek() {
+                if arrival > now {
+                    break;
+                }
+                inner.in_flight.pop();
+                let in_flight = inner.packets.get_mut(&id).expect("packets in flight are stored");
+                let Some(node) = in_flight.path.pop_front() else {
+                    let packet = inner.packets.remove(&id).expect("packets in flight are stored").packet;
+                    inner.delivered += 1;
+                    self.delay.record_duration(now - packet.sent_at);
+                    let inbox = inner.inboxes.entry(packet.destination).or_default();
+                    if mem::take(&mut inbox.waiting) {
+                        pending.push_back(Action::ActivateOne(packet.destination));
+                    }
+                    inbox.packets.push_back(packet);
+                    continue;
+                };
+                if in_flight.path.is_empty() {
+                    // Arrived at its destination, delivered at the same time.
+                    inner.in_flight.push(Reverse((now, id)));
+                } else {
+                    inner.transmit(id, node, now, &self.rng);
+                }
+            }
+            let (action, status) = match inner.in_flight.peek() {
+                Some(&Reverse((arrival, _))) => (Action::Hold(arrival - now), Status::Holding(arrival)),
+                None => (Action::Passivate, Status::Passive),
+            };
+            if pending.is_empty() {
+                inner.status = status;
+                return Some(action);
+            }
+            // Stays busy until the activations are done.
+            inner.status = Status::Busy;
+            after = status;
+            pending.push_back(action);
+            pending.pop_front()
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::process::script;
+    use crate::Simulation;
+
+    type Received = Rc<RefCell<Vec<(Duration, Packet<u32>)>>>;
+
+    fn receiver(network: Network<u32>, clock: ClockRef, received: Received, count: usize) -> GenBoxed<()> {
+        process(move |_| {
+            while received.borrow().len() < count {
+                match network.receive() {
+                    Some(packet) => received.borrow_mut().push((clock.time(), packet)),
+                    None => return Some(Action::Passivate),
+                }
+            }
+            None
+        })
+    }
+
+    enum Step {
+        // The destination, size and payload of a packet.
+        Send(Key, u64, u32),
+        Hold(Duration),
+    }
+
+    fn sender(network: Network<u32>, steps: Vec<Step>) -> GenBoxed<()> {
+        let mut steps = steps.into_iter();
+        process(move |_| {
+            for step in steps.by_ref() {
+                match step {
+                    Step::Send(destination, size, payload) => {
+                        if let Some(action) = network.send(destination, size, payload).unwrap() {
+                            return Some(action);
+                        }
+                    }
+                    Step::Hold(pause) => return Some(Action::Hold(pause)),
+                }
+            }
+            None
+        })
+    }
+
+    #[test]
+    fn packets_are_forwarded_along_the_fastest_path() {
+        let mut simulation = Simulation::default();
+        let network = simulation.add_network::<u32>("network");
+        let received = Received::default();
+        let server = simulation.add_generator(receiver(network.clone(), simulation.clock(), received.clone(), 4));
+        // Sends 1 and 2 in a row, then waits a second and sends 11 and 12.
+        let steps = vec![
+            Step::Send(server, 100, 1),
+            Step::Send(server, 100, 2),
+            Step::Hold(Duration::from_secs(1)),
+            Step::Send(server, 100, 11),
+            Step::Send(server, 100, 12),
+        ];
+        let client = simulation.add_generator(sender(network.clone(), steps));
+        let router = simulation.add_generator(script(vec![Action::Passivate]));
+        network.connect(client, router, Link::new(Duration::from_millis(5)).bandwidth(1000.0));
+        network.connect(router, server, Link::new(Duration::from_millis(20)));
+        // Slower than going through the router.
+        network.connect(client, server, Link::new(Duration::from_millis(200)));
+        for key in [server, client, router] {
+            simulation.schedule_now(key);
+        }
+        simulation.run_with_limit(Duration::from_secs(5));
+
+        // Each packet takes 100 ms to transmit to the router, the second one waits for the first.
+        let times: Vec<_> = received.borrow().iter().map(|(time, _)| time.as_millis()).collect();
+        assert_eq!(vec![125, 225, 1125, 1225], times);
+        let payloads: Vec<_> = received.borrow().iter().map(|(_, packet)| packet.payload).collect();
+        assert_eq!(vec![1, 2, 11, 12], payloads);
+        let first = &received.borrow()[0].1;
+        assert_eq!((client, server, 2), (first.source, first.destination, first.hops));
+        assert_eq!(4, network.delivered());
+        assert_eq!(0, network.in_flight());
+        assert!((network.delay().mean().unwrap() - 0.175).abs() < 1e-9);
+    }
+
+    #[test]
+    fn earlier_packets_interrupt_the_delivery_of_later_ones() {
+        let mut simulation = Simulation::default();
+        let network = simulation.add_network::<u32>("network");
+        let near = Received::default();
+        let far = Received::default();
+        let far_node = simulation.add_generator(receiver(network.clone(), simulation.clock(), far.clone(), 1));
+        let near_node = simulation.add_generator(receiver(network.clone(), simulation.clock(), near.clone(), 1));
+        let steps = vec![
+            Step::Send(far_node, 1, 1),
+            Step::Hold(Duration::from_millis(10)),
+            Step::Send(near_node, 1, 2),
+        ];
+        let sender = simulation.add_generator(sender(network.clone(), steps));
+        network.connect(sender, far_node, Link::new(Duration::from_secs(1)));
+        network.connect(sender, near_node, Link::new(Duration::from_millis(100)));
+        for key in [far_node, near_node, sender] {
+            simulation.schedule_now(key);
+        }
+        simulation.run_with_limit(Duration::from_secs(5));
+
+        assert_eq!(110, near.borrow()[0].0.as_millis());
+        assert_eq!(1000, far.borrow()[0].0.as_millis());
+    }
+
+    #[test]
+    fn sending_without_a_path_fails() {
+        let mut simulation = Simulation::default();
+        let network = simulation.add_network::<u32>("network");
+        let result = Rc::new(RefCell::new(None));
+        let server = simulation.add_generator(script(vec![Action::Passivate]));
+        let client = simulation.add_generator({
+            let (network, result) = (network.clone(), result.clone());
+            process(move |_| {
+                *result.borrow_mut() = Some(network.send(server, 1, 1));
+                Some(Action::Passivate)
+            })
+        });
+        // Only leads from the server to the client.
+        network.connect(server, client, Link::new(Duration::from_millis(1)));
+        simulation.schedule_now(client);
+        simulation.run_with_limit(Duration::from_secs(1));
+
+        let expected = NetError::Unreachable {
+            source: client,
+            destination: server,
+        };
+        assert_eq!(Some(Err(expected)), *result.borrow());
+        assert_eq!(0, network.in_flight());
+    }
+}
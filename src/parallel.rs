@@ -0,0 +1,614 @@
+//! Conservative parallel simulation of a model split into partitions.
+//!
+//! Each partition is a logical process: a [`Simulation`] of its own, built and run on its own thread, that
+//! talks to the other partitions only through messages. A message is sent on a channel declared with
+//! [`ParallelSimulation::connect`], whose lookahead is the least delay of any message sent on it: a message sent at
+//! time `t` arrives at `t + delay` with `delay >= lookahead`.
+//!
+//! Partitions are synchronized with the Chandy–Misra–Bryant algorithm: a partition only executes an event once
+//! no message can arrive before it, and a blocked partition sends null messages carrying the earliest time it
+//! could send a real message, so its neighbours can move on. Every lookahead must be positive, and the longer
+//! the lookaheads the less partitions wait on each other. Messages at the same time are delivered by sending
+//! partition, before the local events at that time, so a run gives the same results on any number of cores.
+//! [`TimeWarp`] runs the same partitions optimistically instead, rolling them back when a message arrives late.
+//!
+//! ```ignore
+//! let mut engine = ParallelSimulation::new();
+//! let arrivals = engine.add_partition(|partition: &mut Partition<Job>| {
+//!     let outbox = partition.outbox();
+//!     let source = partition.simulation().add_generator(Box::new(move |_| loop {
+//!         outbox.send(1, Duration::from_secs(5), Job::default());
+//!         yield Action::Hold(Duration::from_secs(1));
+//!     }));
+//!     partition.simulation().schedule_now(source);
+//! });
+//! let service = engine.add_partition(|partition: &mut Partition<Job>| {
+//!     let queue = partition.simulation().add_queue("jobs");
+//!     partition.on_message(move |_, job| queue.push(job));
+//! });
+//! engine.connect(arrivals, service, Duration::from_secs(5));
+//! let reports = engine.run_until(Duration::from_secs(3600));
+//! ```
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+mod time_warp;
+
+pub use time_warp::{TimeWarp, TimeWarpReport};
+
+use crate::scheduler::ClockRef;
+use crate::{SimTime, Simulation, SimulationError};
+
+/// The index of a partition, returned by [`ParallelSimulation::add_partition`].
+pub type PartitionId = usize;
+
+type Builder<'a, M> = Box<dyn FnOnce(&mut Partition<M>) + Send + 'a>;
+type Handler<M> = Box<dyn FnMut(&mut Simulation<()>, M)>;
+type Finish = Box<dyn FnOnce(&mut Simulation<()>)>;
+// Saves the partition at the current time, returning how to build it again from there.
+type Save<M> = Box<dyn Fn(&mut Simulation<()>) -> Restore<M>>;
+type Restore<M> = Box<dyn Fn(&mut Partition<M>)>;
+// Envelopes are tagged with the partition that sent them.
+type EnvelopeSender<M> = Sender<(PartitionId, Envelope<M>)>;
+
+enum Envelope<M> {
+    Message(Duration, M),
+    // No message will follow on the channel before this time.
+    Null(Duration),
+}
+
+/// A model split into partitions run in parallel, see the [module documentation](self).
+pub struct ParallelSimulation<'a, M> {
+    builders: Vec<Builder<'a, M>>,
+    // Source, destination and lookahead.
+    channels: Vec<(PartitionId, PartitionId, Duration)>,
+}
+
+impl<M> Default for ParallelSimulation<'_, M> {
+    fn default() -> Self {
+        Self {
+            builders: Vec::new(),
+            channels: Vec::new(),
+        }
+    }
+}
+
+impl<'a, M> ParallelSimulation<'a, M>
+where
+    M: Send + 'static,
+{
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a partition, built by `builder` on the thread that runs it.
+    pub fn add_partition(&mut self, builder: impl FnOnce(&mut Partition<M>) + Send + 'a) -> PartitionId {
+        self.builders.push(Box::new(builder));
+        self.builders.len() - 1
+    }
+
+    /// Let `from` send messages to `to`, each delayed by at least `lookahead`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a partition doesn't exist, if they're the same partition or already connected, or if
+    /// `lookahead` is zero.
+    pub fn connect(&mut self, from: PartitionId, to: PartitionId, lookahead: impl SimTime) {
+        connect(&mut self.channels, self.builders.len(), from, to, lookahead.to_duration());
+    }
+
+    /// Build every partition on its own thread and run them until `end`, returning a report per partition.
+    ///
+    /// Every event and message up to `end` is processed, and the clock of every partition is left at `end`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a partition panics, e.g. in strict mode or on a message sent with too short a delay.
+    pub fn run_until(self, end: impl SimTime) -> Vec<PartitionReport> {
+        let end = end.to_duration();
+        let (senders, receivers): (Vec<EnvelopeSender<M>>, Vec<_>) =
+            self.builders.iter().map(|_| mpsc::channel()).unzip();
+        thread::scope(|scope| {
+            let handles: Vec<_> = self
+                .builders
+                .into_iter()
+                .zip(receivers)
+                .enumerate()
+                .map(|(id, (builder, receiver))| {
+                    let outgoing: Vec<_> = self
+                        .channels
+                        .iter()
+                        .filter(|&&(source, _, _)| source == id)
+                        .map(|&(_, destination, lookahead)| (destination, lookahead, senders[destination].clone()))
+                        .collect();
+                    let incoming: Vec<_> = self
+                        .channels
+                        .iter()
+                        .filter(|&&(_, destination, _)| destination == id)
+                        .map(|&(source, _, _)| source)
+                        .collect();
+                    scope.spawn(move || {
+                        let mut process = LogicalProcess::new(id, receiver, outgoing, &incoming);
+                        builder(&mut process.partition);
+                        process.run(end)
+                    })
+                })
+                .collect();
+            // Only the partitions hold senders now, so they see each other stop.
+            drop(senders);
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic)))
+                .collect()
+        })
+    }
+}
+
+// Add the channel from `from` to `to` among `partitions` partitions, see `ParallelSimulation::connect`.
+fn connect(
+    channels: &mut Vec<(PartitionId, PartitionId, Duration)>,
+    partitions: usize,
+    from: PartitionId,
+    to: PartitionId,
+    lookahead: Duration,
+) {
+    assert!(from < partitions && to < partitions, "unknown partition");
+    assert_ne!(from, to, "a partition can't send messages to itself");
+    assert!(!lookahead.is_zero(), "the lookahead of a channel must be positive");
+    assert!(
+        !channels.iter().any(|&(source, destination, _)| (source, destination) == (from, to)),
+        "partitions {} and {} are already connected",
+        from,
+        to
+    );
+    channels.push((from, to, lookahead));
+}
+
+/// A partition being built, passed to the builder given to [`ParallelSimulation::add_partition`].
+pub struct Partition<M> {
+    id: PartitionId,
+    simulation: Simulation<()>,
+    outbox: Outbox<M>,
+    handler: Option<Handler<M>>,
+    finish: Option<Finish>,
+    save: Option<Save<M>>,
+}
+
+impl<M> Partition<M> {
+    #[must_use]
+    pub fn id(&self) -> PartitionId {
+        self.id
+    }
+
+    /// Returns the simulation of the partition, where its entities are added and scheduled.
+    pub fn simulation(&mut self) -> &mut Simulation<()> {
+        &mut self.simulation
+    }
+
+    /// Returns the outbox entities send messages to other partitions with.
+    #[must_use]
+    pub fn outbox(&self) -> Outbox<M> {
+        self.outbox.clone()
+    }
+
+    /// Call `handler` with each message received, at its arrival time, replacing any previous handler.
+    ///
+    /// Messages are dropped if there is no handler.
+    pub fn on_message(&mut self, handler: impl FnMut(&mut Simulation<()>, M) + 'static) {
+        self.handler = Some(Box::new(handler));
+    }
+
+    /// Call `finish` once the run ended, e.g. to collect the statistics of the partition.
+    pub fn on_finish(&mut self, finish: impl FnOnce(&mut Simulation<()>) + 'static) {
+        self.finish = Some(Box::new(finish));
+    }
+
+    /// Let [`TimeWarp`] checkpoint the partition, ignored by [`ParallelSimulation`].
+    ///
+    /// At every global virtual time computation `save` is called between two steps and returns what the partition
+    /// needs to go on from there, e.g. the [`State`](crate::state::State) and the phase of each entity. A rollback
+    /// then builds the partition again with `restore` from the latest checkpoint before the straggler instead of
+    /// with the builder from the start, and the history before the global virtual time is discarded.
+    ///
+    /// `restore` is given a partition with its clock at the time of the checkpoint and no entity, and sets it up
+    /// like the builder does: the entities, scheduled as they were pending, the message handler and the finish
+    /// callback. It may call `checkpoint_with` again, replacing these closures.
+    pub fn checkpoint_with<S: 'static>(
+        &mut self,
+        save: impl Fn(&mut Simulation<()>) -> S + 'static,
+        restore: impl Fn(&mut Partition<M>, &S) + 'static,
+    ) where
+        M: 'static,
+    {
+        let restore = Rc::new(restore);
+        self.save = Some(Box::new(move |simulation| {
+            let saved = save(simulation);
+            let restore = Rc::clone(&restore);
+            Box::new(move |partition| restore(partition, &saved))
+        }));
+    }
+
+    fn new(id: PartitionId, lookaheads: HashMap<PartitionId, Duration>) -> Self {
+        Self {
+            id,
+            simulation: Simulation::default(),
+            outbox: Outbox::new(lookaheads),
+            handler: None,
+            finish: None,
+            save: None,
+        }
+    }
+
+    // Attach the outbox to the clock of the simulation, which can no longer be replaced.
+    fn start(&mut self) {
+        self.outbox.attach(self.simulation.clock());
+    }
+
+    fn deliver(&mut self, time: Duration, payload: M) {
+        self.simulation.advance_to(time);
+        if let Some(handler) = &mut self.handler {
+            handler(&mut self.simulation, payload);
+        }
+    }
+}
+
+/// Sends messages to other partitions, obtained from [`Partition::outbox`].
+///
+/// Clones share the outbox, so it can be moved into the entities of the partition.
+pub struct Outbox<M> {
+    outgoing: Rc<RefCell<Outgoing<M>>>,
+}
+
+impl<M> Clone for Outbox<M> {
+    fn clone(&self) -> Self {
+        Self {
+            outgoing: Rc::clone(&self.outgoing),
+        }
+    }
+}
+
+struct Outgoing<M> {
+    // Lookahead of the channel to each partition.
+    lookaheads: HashMap<PartitionId, Duration>,
+    clock: Option<ClockRef>,
+    // Destination, arrival time and payload of the messages sent since the engine last took them.
+    sent: Vec<(PartitionId, Duration, M)>,
+}
+
+impl<M> Outbox<M> {
+    fn new(lookaheads: HashMap<PartitionId, Duration>) -> Self {
+        Self {
+            outgoing: Rc::new(RefCell::new(Outgoing {
+                lookaheads,
+                clock: None,
+                sent: Vec::new(),
+            })),
+        }
+    }
+
+    /// Send `payload` to partition `to`, arriving `delay` from now.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the partition isn't connected to `to`, if `delay` is shorter than the lookahead of the
+    /// channel, or if called while the partition is being built.
+    pub fn send(&self, to: PartitionId, delay: impl SimTime, payload: M) {
+        let delay = delay.to_duration();
+        let mut outgoing = self.outgoing.borrow_mut();
+        let now = outgoing
+            .clock
+            .as_ref()
+            .expect("Outbox::send called before the run started")
+            .time();
+        let lookahead = *outgoing
+            .lookaheads
+            .get(&to)
+            .unwrap_or_else(|| panic!("no channel to partition {}", to));
+        assert!(
+            delay >= lookahead,
+            "a message to partition {} was sent with a delay of {:?}, below the lookahead of {:?}",
+            to,
+            delay,
+            lookahead
+        );
+        outgoing.sent.push((to, now + delay, payload));
+    }
+
+    fn attach(&self, clock: ClockRef) {
+        self.outgoing.borrow_mut().clock = Some(clock);
+    }
+
+    fn take(&self) -> Vec<(PartitionId, Duration, M)> {
+        std::mem::take(&mut self.outgoing.borrow_mut().sent)
+    }
+}
+
+struct Channel<M> {
+    lookahead: Duration,
+    sender: EnvelopeSender<M>,
+    // Messages are held back until no earlier message can be sent on the channel, so they're sent in order.
+    pending: BTreeMap<(Duration, u64), M>,
+    // Time of the last envelope sent.
+    sent_until: Duration,
+}
+
+// A partition being run, with its view of the incoming channels.
+struct LogicalProcess<M> {
+    partition: Partition<M>,
+    receiver: Receiver<(PartitionId, Envelope<M>)>,
+    // By sending partition: no message will arrive before the time, and the messages received but not
+    // delivered yet.
+    incoming: BTreeMap<PartitionId, (Duration, VecDeque<(Duration, M)>)>,
+    outgoing: HashMap<PartitionId, Channel<M>>,
+    sequence: u64,
+    sent: usize,
+    received: usize,
+    nulls: usize,
+    steps: usize,
+    errors: Vec<SimulationError>,
+}
+
+enum Next {
+    Message(PartitionId),
+    Event,
+    Wait,
+    Done,
+}
+
+impl<M> LogicalProcess<M> {
+    fn new(
+        id: PartitionId,
+        receiver: Receiver<(PartitionId, Envelope<M>)>,
+        outgoing: Vec<(PartitionId, Duration, EnvelopeSender<M>)>,
+        incoming: &[PartitionId],
+    ) -> Self {
+        let lookaheads = outgoing
+            .iter()
+            .map(|&(destination, lookahead, _)| (destination, lookahead))
+            .collect();
+        Self {
+            partition: Partition::new(id, lookaheads),
+            receiver,
+            incoming: incoming
+                .iter()
+                .map(|&source| (source, (Duration::ZERO, VecDeque::new())))
+                .collect(),
+            outgoing: outgoing
+                .into_iter()
+                .map(|(destination, lookahead, sender)| {
+                    let channel = Channel {
+                        lookahead,
+                        sender,
+                        pending: BTreeMap::new(),
+                        sent_until: Duration::ZERO,
+                    };
+                    (destination, channel)
+                })
+                .collect(),
+            sequence: 0,
+            sent: 0,
+            received: 0,
+            nulls: 0,
+            steps: 0,
+            errors: Vec::new(),
+        }
+    }
+
+    // Hold back the messages sent by the entities until they can be sent in order.
+    fn collect(&mut self) {
+        for (to, time, payload) in self.partition.outbox.take() {
+            let channel = self.outgoing.get_mut(&to).expect("Ensured by `Outbox::send`.");
+            channel.pending.insert((time, self.sequence), payload);
+            self.sequence += 1;
+            self.sent += 1;
+        }
+    }
+
+    // Send the messages up to `bound` plus the lookahead of each channel, where `bound` is the earliest
+    // time the partition can still act at, followed by a null message with that time.
+    fn flush(&mut self, bound: Duration) {
+        let from = self.partition.id;
+        for channel in self.outgoing.values_mut() {
+            let until = bound.saturating_add(channel.lookahead);
+            while let Some(entry) = channel.pending.first_entry() {
+                if entry.key().0 > until {
+                    break;
+                }
+                let ((time, _), payload) = entry.remove_entry();
+                // A partition that stopped no longer reads its messages.
+                let _ = channel.sender.send((from, Envelope::Message(time, payload)));
+            }
+            if until > channel.sent_until {
+                channel.sent_until = until;
+                let _ = channel.sender.send((from, Envelope::Null(until)));
+                self.nulls += 1;
+            }
+        }
+    }
+
+    fn run(mut self, end: Duration) -> PartitionReport {
+        self.partition.start();
+        loop {
+            while let Ok((from, envelope)) = self.receiver.try_recv() {
+                self.receive(from, envelope);
+            }
+            match self.next(end) {
+                Next::Message(from) => {
+                    self.deliver(from);
+                    self.collect();
+                }
+                Next::Event => {
+                    self.steps += 1;
+                    if let Err(error) = self.partition.simulation.step() {
+                        self.errors.push(error);
+                    }
+                    self.collect();
+                }
+                Next::Wait => {
+                    self.flush(self.bound());
+                    let (from, envelope) = self
+                        .receiver
+                        .recv()
+                        .expect("a partition stopped before the end of the run");
+                    self.receive(from, envelope);
+                }
+                Next::Done => break,
+            }
+        }
+        // Nothing else is sent, so the other partitions can finish.
+        self.flush(Duration::MAX);
+        self.partition.simulation.advance_to(end);
+        if let Some(finish) = self.partition.finish.take() {
+            finish(&mut self.partition.simulation);
+        }
+        PartitionReport {
+            id: self.partition.id,
+            time: self.partition.simulation.time(),
+            steps: self.steps,
+            messages_sent: self.sent,
+            messages_received: self.received,
+            null_messages: self.nulls,
+            errors: self.errors,
+        }
+    }
+
+    fn receive(&mut self, from: PartitionId, envelope: Envelope<M>) {
+        let (clock, queue) = self.incoming.get_mut(&from).expect("Ensured by the channels.");
+        match envelope {
+            Envelope::Message(time, payload) => {
+                *clock = time;
+                queue.push_back((time, payload));
+            }
+            Envelope::Null(time) => *clock = time,
+        }
+    }
+
+    fn deliver(&mut self, from: PartitionId) {
+        let (_, queue) = self.incoming.get_mut(&from).expect("Ensured by the channels.");
+        let (time, payload) = queue.pop_front().expect("Ensured by `next`.");
+        self.received += 1;
+        self.partition.deliver(time, payload);
+    }
+
+    // The earliest message received, by time then sending partition.
+    fn first_message(&self) -> Option<(Duration, PartitionId)> {
+        self.incoming
+            .iter()
+            .filter_map(|(&source, (_, queue))| queue.front().map(|&(time, _)| (time, source)))
+            .min()
+    }
+
+    fn min_clock(&self) -> Duration {
+        self.incoming
+            .values()
+            .map(|&(clock, _)| clock)
+            .min()
+            .unwrap_or(Duration::MAX)
+    }
+
+    // The earliest time the partition can still act at.
+    fn bound(&self) -> Duration {
+        let next_event = self.partition.simulation.next_event_time().unwrap_or(Duration::MAX);
+        let first_message = self.first_message().map_or(Duration::MAX, |(time, _)| time);
+        next_event.min(first_message).min(self.min_clock())
+    }
+
+    fn next(&self, end: Duration) -> Next {
+        let next_event = self.partition.simulation.next_event_time().filter(|&time| time <= end);
+        match self.first_message().filter(|&(time, _)| time <= end) {
+            Some((time, from)) if next_event.is_none_or(|event| time <= event) => {
+                // Messages at the same time from a partition before `from` would go first.
+                let safe = self
+                    .incoming
+                    .iter()
+                    .all(|(&source, &(clock, _))| source >= from || clock > time);
+                if safe {
+                    Next::Message(from)
+                } else {
+                    Next::Wait
+                }
+            }
+            _ => match next_event {
+                // Messages at the same time go first.
+                Some(time) if time < self.min_clock() => Next::Event,
+                Some(_) => Next::Wait,
+                None if self.min_clock() > end => Next::Done,
+                None => Next::Wait,
+            },
+        }
+    }
+}
+
+/// What a partition did in [`ParallelSimulation::run_until`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartitionReport {
+    pub id: PartitionId,
+    /// The time the partition stopped at, the end of the run.
+    pub time: Duration,
+    /// Events executed.
+    pub steps: usize,
+    pub messages_sent: usize,
+    pub messages_received: usize,
+    /// Null messages sent to synchronize with the other partitions.
+    pub null_messages: usize,
+    /// The invalid actions yielded by the entities, the partition kept running as with
+    /// [`Simulation::run_until_empty`].
+    pub errors: Vec<SimulationError>,
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::{process, Action};
+
+    fn secs(seconds: u64) -> Duration {
+        Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn partitions_exchange_messages_in_time_order() {
+        let arrivals = Arc::new(Mutex::new(Vec::new()));
+        let echoes = Arc::new(Mutex::new(Vec::new()));
+        let mut engine = ParallelSimulation::new();
+        let record = Arc::clone(&echoes);
+        let source = engine.add_partition(move |partition: &mut Partition<u32>| {
+            let outbox = partition.outbox();
+            let mut sent = 0;
+            let key = partition.simulation().add_generator(process(move |_| {
+                outbox.send(1, secs(1), sent);
+                sent += 1;
+                Some(Action::Hold(secs(2)))
+            }));
+            partition.simulation().schedule_now(key);
+            partition.on_message(move |simulation, job| record.lock().unwrap().push((simulation.time(), job)));
+        });
+        let record = Arc::clone(&arrivals);
+        let echo = engine.add_partition(move |partition: &mut Partition<u32>| {
+            let outbox = partition.outbox();
+            partition.on_message(move |simulation, job| {
+                record.lock().unwrap().push((simulation.time(), job));
+                outbox.send(0, secs(1), job);
+            });
+        });
+        engine.connect(source, echo, secs(1));
+        engine.connect(echo, source, secs(1));
+        let reports = engine.run_until(secs(10));
+
+        let expected: Vec<(Duration, u32)> = (0..5).map(|job| (secs(2 * u64::from(job) + 1), job)).collect();
+        assert_eq!(expected, *arrivals.lock().unwrap());
+        let expected: Vec<(Duration, u32)> = (0..5).map(|job| (secs(2 * u64::from(job) + 2), job)).collect();
+        assert_eq!(expected, *echoes.lock().unwrap());
+        assert_eq!(vec![secs(10), secs(10)], reports.iter().map(|report| report.time).collect::<Vec<_>>());
+        assert_eq!(6, reports[source].steps);
+        assert_eq!(5, reports[echo].messages_received);
+        assert!(reports.iter().all(|report| report.errors.is_empty()));
+    }
+}
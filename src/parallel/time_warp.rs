@@ -0,0 +1,670 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Bound;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Condvar, Mutex, PoisonError};
+use std::thread;
+use std::time::Duration;
+
+use super::{connect, Partition, PartitionId, Restore, Save};
+use crate::{SimTime, SimulationError};
+
+type Builder<'a, M> = Box<dyn Fn(&mut Partition<M>) + Send + 'a>;
+
+// Where a message or an event falls in the history of the partition processing it: by time, messages before
+// the local events at that time, then by sending partition and by the step of the sender that sent it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Order {
+    time: Duration,
+    event: bool,
+    sender: PartitionId,
+    // Position of the step in the history of the sender, and of the message among those sent in that step.
+    position: usize,
+    index: usize,
+}
+
+impl Order {
+    fn event(time: Duration) -> Self {
+        Self {
+            time,
+            event: true,
+            sender: 0,
+            position: 0,
+            index: 0,
+        }
+    }
+}
+
+enum Envelope<M> {
+    Message(Order, M),
+    // Cancels the message, which was sent by a step that was rolled back.
+    Anti(Order),
+}
+
+impl<M> Envelope<M> {
+    fn time(&self) -> Duration {
+        match self {
+            Self::Message(order, _) | Self::Anti(order) => order.time,
+        }
+    }
+}
+
+/// A model split into partitions run optimistically in parallel, with the Time Warp algorithm.
+///
+/// Partitions are built and connected as with [`ParallelSimulation`](super::ParallelSimulation), but they
+/// don't wait on each other: each executes its events as soon as it can. A message arriving in the past of a
+/// partition, a straggler, rolls it back to the time of the message, and the messages it sent since are
+/// cancelled by anti-messages, which may roll back their receivers in turn.
+///
+/// Generators can't be saved, so a partition is rolled back by building it again and re-executing its history
+/// up to the straggler, which is why the builder is called again and must build the same model every time,
+/// keeping its state in the partition (its [`Simulation`](crate::Simulation), [`State`](crate::state::State)
+/// or the entities) rather than in anything shared with the outside. Results should be collected in
+/// [`Partition::on_finish`], which is only called on the final history.
+///
+/// Every [`set_gvt_interval`](TimeWarp::set_gvt_interval) steps the partitions agree on the global virtual
+/// time, the time no rollback can reach anymore, which ends the run once it's past the end. By default rollbacks
+/// re-execute from the start, so each partition keeps its history for the whole run. A partition that can save
+/// its model with [`Partition::checkpoint_with`] is checkpointed at every global virtual time instead: rollbacks
+/// re-execute from the latest checkpoint before the straggler, and the history before the global virtual time
+/// is discarded.
+///
+/// ```ignore
+/// let mut engine = TimeWarp::new();
+/// let arrivals = engine.add_partition(build_arrivals);
+/// let service = engine.add_partition(build_service);
+/// engine.connect(arrivals, service, Duration::from_millis(1));
+/// let reports = engine.run_until(Duration::from_secs(3600));
+/// println!("{} rollbacks", reports[service].rollbacks);
+/// ```
+pub struct TimeWarp<'a, M> {
+    builders: Vec<Builder<'a, M>>,
+    // Source, destination and lookahead.
+    channels: Vec<(PartitionId, PartitionId, Duration)>,
+    gvt_interval: usize,
+}
+
+impl<M> Default for TimeWarp<'_, M> {
+    fn default() -> Self {
+        Self {
+            builders: Vec::new(),
+            channels: Vec::new(),
+            gvt_interval: 1000,
+        }
+    }
+}
+
+impl<'a, M> TimeWarp<'a, M>
+where
+    M: Clone + Send + 'static,
+{
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a partition, built by `builder` on the thread that runs it, and built again on every rollback.
+    pub fn add_partition(&mut self, builder: impl Fn(&mut Partition<M>) + Send + 'a) -> PartitionId {
+        self.builders.push(Box::new(builder));
+        self.builders.len() - 1
+    }
+
+    /// See [`ParallelSimulation::connect`](super::ParallelSimulation::connect).
+    ///
+    /// # Panics
+    ///
+    /// Panics if a partition doesn't exist, if they're the same partition or already connected, or if
+    /// `lookahead` is zero.
+    pub fn connect(&mut self, from: PartitionId, to: PartitionId, lookahead: impl SimTime) {
+        connect(&mut self.channels, self.builders.len(), from, to, lookahead.to_duration());
+    }
+
+    /// Compute the global virtual time every `steps` steps of each partition, 1000 by default.
+    ///
+    /// Partitions go at most that many steps ahead of the slowest one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `steps` is zero.
+    pub fn set_gvt_interval(&mut self, steps: usize) {
+        assert!(steps > 0, "the GVT interval must be positive");
+        self.gvt_interval = steps;
+    }
+
+    /// Build every partition on its own thread and run them until `end`, returning a report per partition.
+    ///
+    /// Every event and message up to `end` is processed, and the clock of every partition is left at `end`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a partition panics, e.g. in strict mode or on a message sent with too short a delay.
+    pub fn run_until(self, end: impl SimTime) -> Vec<TimeWarpReport> {
+        let end = end.to_duration();
+        let (senders, receivers): (Vec<Sender<Envelope<M>>>, Vec<_>) =
+            self.builders.iter().map(|_| mpsc::channel()).unzip();
+        let rendezvous = Rendezvous::new(self.builders.len());
+        let lvts = Mutex::new(vec![Duration::ZERO; self.builders.len()]);
+        let results: Vec<_> = thread::scope(|scope| {
+            let handles: Vec<_> = self
+                .builders
+                .into_iter()
+                .zip(receivers)
+                .enumerate()
+                .map(|(id, (builder, receiver))| {
+                    let outgoing: HashMap<_, _> = self
+                        .channels
+                        .iter()
+                        .filter(|&&(source, _, _)| source == id)
+                        .map(|&(_, destination, lookahead)| (destination, (lookahead, senders[destination].clone())))
+                        .collect();
+                    let (rendezvous, lvts) = (&rendezvous, &lvts);
+                    let gvt_interval = self.gvt_interval;
+                    scope.spawn(move || {
+                        let _abort = AbortOnPanic(rendezvous);
+                        let process = Process::new(id, builder, receiver, outgoing);
+                        process.run(end, gvt_interval, rendezvous, lvts)
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|handle| handle.join()).collect()
+        });
+        // Partitions stop early when another one panics, report that panic.
+        let reports: Vec<_> = results
+            .into_iter()
+            .map(|result| result.unwrap_or_else(|panic| std::panic::resume_unwind(panic)))
+            .collect();
+        reports
+            .into_iter()
+            .map(|report| report.expect("Only partitions stopped by a panic have no report."))
+            .collect()
+    }
+}
+
+// A partition being run optimistically.
+struct Process<'a, M> {
+    builder: Builder<'a, M>,
+    lookaheads: HashMap<PartitionId, Duration>,
+    partition: Partition<M>,
+    receiver: Receiver<Envelope<M>>,
+    senders: HashMap<PartitionId, Sender<Envelope<M>>>,
+    // Every message received and not cancelled, processed or not, except those discarded with the history.
+    inputs: BTreeMap<Order, M>,
+    // The messages and events processed, in order, since the oldest checkpoint.
+    history: Vec<Order>,
+    // Steps discarded from the front of the history, which the positions of the steps count from, and the events
+    // among them.
+    discarded: usize,
+    discarded_events: usize,
+    save: Option<Save<M>>,
+    // The position and the time of each checkpoint a rollback can still reach, and how to restore it.
+    checkpoints: Vec<(usize, Duration, Restore<M>)>,
+    // The position of the step that sent each message, its destination and the message, by position.
+    outputs: Vec<(usize, PartitionId, Order)>,
+    // Envelopes held back while the global virtual time is computed.
+    held: Option<Vec<(PartitionId, Envelope<M>)>>,
+    sent: usize,
+    received: usize,
+    anti_messages: usize,
+    rollbacks: usize,
+    gvt_rounds: usize,
+    // The invalid actions, with the position of the step.
+    errors: Vec<(usize, SimulationError)>,
+}
+
+impl<'a, M> Process<'a, M>
+where
+    M: Clone,
+{
+    fn new(
+        id: PartitionId,
+        builder: Builder<'a, M>,
+        receiver: Receiver<Envelope<M>>,
+        outgoing: HashMap<PartitionId, (Duration, Sender<Envelope<M>>)>,
+    ) -> Self {
+        let lookaheads: HashMap<_, _> = outgoing
+            .iter()
+            .map(|(&destination, &(lookahead, _))| (destination, lookahead))
+            .collect();
+        let mut partition = Partition::new(id, lookaheads.clone());
+        builder(&mut partition);
+        partition.start();
+        let save = partition.save.take();
+        Self {
+            builder,
+            lookaheads,
+            partition,
+            receiver,
+            senders: outgoing
+                .into_iter()
+                .map(|(destination, (_, sender))| (destination, sender))
+                .collect(),
+            inputs: BTreeMap::new(),
+            history: Vec::new(),
+            discarded: 0,
+            discarded_events: 0,
+            save,
+            checkpoints: Vec::new(),
+            outputs: Vec::new(),
+            held: None,
+            sent: 0,
+            received: 0,
+            anti_messages: 0,
+            rollbacks: 0,
+            gvt_rounds: 0,
+            errors: Vec::new(),
+        }
+    }
+
+    // Returns `None` if another partition panicked.
+    fn run(
+        mut self,
+        end: Duration,
+        gvt_interval: usize,
+        rendezvous: &Rendezvous,
+        lvts: &Mutex<Vec<Duration>>,
+    ) -> Option<TimeWarpReport> {
+        let mut steps = 0;
+        loop {
+            while let Ok(envelope) = self.receiver.try_recv() {
+                self.receive(envelope);
+            }
+            match self.next().filter(|order| order.time <= end) {
+                Some(order) if steps < gvt_interval => {
+                    self.execute(order);
+                    steps += 1;
+                }
+                _ => {
+                    steps = 0;
+                    let gvt = self.gvt(rendezvous, lvts)?;
+                    if gvt > end {
+                        break;
+                    }
+                }
+            }
+        }
+        self.partition.simulation.advance_to(end);
+        if let Some(finish) = self.partition.finish.take() {
+            finish(&mut self.partition.simulation);
+        }
+        Some(TimeWarpReport {
+            id: self.partition.id,
+            time: self.partition.simulation.time(),
+            steps: self.discarded_events + self.history.iter().filter(|order| order.event).count(),
+            messages_sent: self.sent,
+            messages_received: self.received,
+            anti_messages: self.anti_messages,
+            rollbacks: self.rollbacks,
+            gvt_rounds: self.gvt_rounds,
+            errors: self.errors.into_iter().map(|(_, error)| error).collect(),
+        })
+    }
+
+    // Agree with the other partitions on the global virtual time: the earliest time of any message or event
+    // not processed yet, including the messages sent but not received.
+    fn gvt(&mut self, rendezvous: &Rendezvous, lvts: &Mutex<Vec<Duration>>) -> Option<Duration> {
+        // Once every partition stopped sending, every message sent is waiting in a channel.
+        if !rendezvous.wait() {
+            return None;
+        }
+        self.held = Some(Vec::new());
+        while let Ok(envelope) = self.receiver.try_recv() {
+            self.receive(envelope);
+        }
+        let held = self.held.take().expect("Set above.");
+        let lvt = held
+            .iter()
+            .map(|(_, envelope)| envelope.time())
+            .chain(self.next().map(|order| order.time))
+            .min()
+            .unwrap_or(Duration::MAX);
+        lock(lvts)[self.partition.id] = lvt;
+        if !rendezvous.wait() {
+            return None;
+        }
+        // Nobody writes again before every partition went through the next first rendezvous.
+        let gvt = lock(lvts).iter().copied().min().unwrap_or(Duration::MAX);
+        for (to, envelope) in held {
+            self.post(to, envelope);
+        }
+        // No step before the global virtual time is rolled back, so the messages it sent are never cancelled.
+        let committed = self.discarded + self.history.partition_point(|order| order.time < gvt);
+        let kept = self.outputs.partition_point(|&(position, _, _)| position < committed);
+        self.outputs.drain(..kept);
+        self.checkpoint();
+        self.collect_fossils(committed);
+        self.gvt_rounds += 1;
+        Some(gvt)
+    }
+
+    // Save the partition, if it can be, unless it didn't move since the last checkpoint.
+    fn checkpoint(&mut self) {
+        let Some(save) = &self.save else {
+            return;
+        };
+        let position = self.discarded + self.history.len();
+        if matches!(self.checkpoints.last(), Some(&(last, _, _)) if last == position) {
+            return;
+        }
+        let time = self.partition.simulation.time();
+        let restore = save(&mut self.partition.simulation);
+        self.checkpoints.push((position, time, restore));
+    }
+
+    // Discard the checkpoints before the latest one at or before `committed`, and the history before it.
+    fn collect_fossils(&mut self, committed: usize) {
+        let reachable = self.checkpoints.partition_point(|&(position, _, _)| position <= committed);
+        if reachable == 0 {
+            return;
+        }
+        self.checkpoints.drain(..reachable - 1);
+        let start = self.checkpoints[0].0;
+        let discarded: Vec<Order> = self.history.drain(..start - self.discarded).collect();
+        if let Some(last) = discarded.last() {
+            // Every message up to the last step was processed.
+            self.inputs = self.inputs.split_off(last);
+            self.inputs.remove(last);
+        }
+        self.discarded_events += discarded.iter().filter(|order| order.event).count();
+        self.discarded = start;
+    }
+
+    // The next message or event to process.
+    fn next(&self) -> Option<Order> {
+        let message = match self.history.last() {
+            Some(&last) => self.inputs.range((Bound::Excluded(last), Bound::Unbounded)).next(),
+            None => self.inputs.iter().next(),
+        };
+        let event = self.partition.simulation.next_event_time().map(Order::event);
+        message.map(|(&order, _)| order).into_iter().chain(event).min()
+    }
+
+    fn execute(&mut self, order: Order) {
+        let position = self.discarded + self.history.len();
+        self.apply(order);
+        for (index, (to, time, payload)) in self.partition.outbox.take().into_iter().enumerate() {
+            let message = Order {
+                time,
+                event: false,
+                sender: self.partition.id,
+                position,
+                index,
+            };
+            self.outputs.push((position, to, message));
+            self.sent += 1;
+            self.post(to, Envelope::Message(message, payload));
+        }
+    }
+
+    // Process the message or the event, without sending the messages it sends.
+    fn apply(&mut self, order: Order) {
+        if order.event {
+            if let Err(error) = self.partition.simulation.step() {
+                self.errors.push((self.discarded + self.history.len(), error));
+            }
+        } else {
+            let payload = self.inputs[&order].clone();
+            self.partition.deliver(order.time, payload);
+        }
+        self.history.push(order);
+    }
+
+    fn receive(&mut self, envelope: Envelope<M>) {
+        match envelope {
+            Envelope::Message(order, payload) => {
+                self.received += 1;
+                self.inputs.insert(order, payload);
+                if matches!(self.history.last(), Some(&last) if order < last) {
+                    self.rollback(self.history.partition_point(|&processed| processed < order));
+                }
+            }
+            Envelope::Anti(order) => {
+                // The message was received before, the channels keep their order.
+                if let Ok(position) = self.history.binary_search(&order) {
+                    self.rollback(position);
+                }
+                self.inputs.remove(&order);
+            }
+        }
+    }
+
+    // Undo the history from `index` on: build the partition again, from the latest checkpoint before it or from
+    // the start, and re-execute the steps in between.
+    fn rollback(&mut self, index: usize) {
+        self.rollbacks += 1;
+        let position = self.discarded + index;
+        let cancelled = self.outputs.partition_point(|&(sent_at, _, _)| sent_at < position);
+        for (_, to, message) in self.outputs.split_off(cancelled) {
+            self.anti_messages += 1;
+            self.post(to, Envelope::Anti(message));
+        }
+        let reachable = self.checkpoints.partition_point(|&(at, _, _)| at <= position);
+        self.checkpoints.truncate(reachable);
+        let mut partition = Partition::new(self.partition.id, self.lookaheads.clone());
+        let start = match self.checkpoints.last() {
+            Some((at, time, restore)) => {
+                partition.simulation.advance_to(*time);
+                restore(&mut partition);
+                *at
+            }
+            None => {
+                (self.builder)(&mut partition);
+                0
+            }
+        };
+        partition.start();
+        if let Some(save) = partition.save.take() {
+            self.save = Some(save);
+        }
+        self.partition = partition;
+        self.errors.retain(|&(at, _)| at < start);
+        let replayed: Vec<Order> = self.history.drain(start - self.discarded..).take(position - start).collect();
+        for order in replayed {
+            self.apply(order);
+            // These messages were already sent.
+            let _ = self.partition.outbox.take();
+        }
+    }
+
+    fn post(&mut self, to: PartitionId, envelope: Envelope<M>) {
+        match &mut self.held {
+            Some(held) => held.push((to, envelope)),
+            // A partition that stopped no longer reads its messages.
+            None => {
+                let _ = self.senders[&to].send(envelope);
+            }
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// A reusable barrier that releases every partition when one of them panics.
+struct Rendezvous {
+    partitions: usize,
+    // Partitions waiting, the round and whether a partition panicked.
+    state: Mutex<(usize, u64, bool)>,
+    released: Condvar,
+}
+
+impl Rendezvous {
+    fn new(partitions: usize) -> Self {
+        Self {
+            partitions,
+            state: Mutex::new((0, 0, false)),
+            released: Condvar::new(),
+        }
+    }
+
+    // Wait for every partition, returns false if a partition panicked.
+    fn wait(&self) -> bool {
+        let mut state = lock(&self.state);
+        let round = state.1;
+        state.0 += 1;
+        if state.0 == self.partitions {
+            state.0 = 0;
+            state.1 += 1;
+            self.released.notify_all();
+        }
+        while state.1 == round && !state.2 {
+            state = self.released.wait(state).unwrap_or_else(PoisonError::into_inner);
+        }
+        !state.2
+    }
+
+    fn abort(&self) {
+        lock(&self.state).2 = true;
+        self.released.notify_all();
+    }
+}
+
+struct AbortOnPanic<'a>(&'a Rendezvous);
+
+impl Drop for AbortOnPanic<'_> {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            self.0.abort();
+        }
+    }
+}
+
+/// What a partition did in [`TimeWarp::run_until`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimeWarpReport {
+    pub id: PartitionId,
+    /// The time the partition stopped at, the end of the run.
+    pub time: Duration,
+    /// Events executed in the final history, without those rolled back.
+    pub steps: usize,
+    /// Messages sent, including those cancelled later.
+    pub messages_sent: usize,
+    pub messages_received: usize,
+    /// Anti-messages sent to cancel the messages of rolled back steps.
+    pub anti_messages: usize,
+    pub rollbacks: usize,
+    /// Times the global virtual time was computed.
+    pub gvt_rounds: usize,
+    /// The invalid actions yielded by the entities in the final history.
+    pub errors: Vec<SimulationError>,
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{process, Action};
+
+    fn secs(seconds: f64) -> Duration {
+        Duration::from_secs_f64(seconds)
+    }
+
+    #[test]
+    fn stragglers_roll_partitions_back() {
+        let arrivals = Arc::new(Mutex::new(Vec::new()));
+        let mut engine = TimeWarp::new();
+        let record = Arc::clone(&arrivals);
+        let clock = engine.add_partition(move |partition: &mut Partition<&'static str>| {
+            let ticks = Rc::new(Cell::new(0));
+            let count = Rc::clone(&ticks);
+            let key = partition.simulation().add_generator(process(move |_| {
+                count.set(count.get() + 1);
+                Some(Action::Hold(secs(1.0)))
+            }));
+            partition.simulation().schedule_now(key);
+            let received = Rc::new(RefCell::new(Vec::new()));
+            let receive = Rc::clone(&received);
+            partition.on_message(move |simulation, message| {
+                receive.borrow_mut().push((simulation.time(), message, ticks.get()));
+            });
+            let record = Arc::clone(&record);
+            partition.on_finish(move |_| *lock(&record) = received.take());
+        });
+        let late = engine.add_partition(|partition: &mut Partition<&'static str>| {
+            // Let the other partition run ahead.
+            thread::sleep(Duration::from_millis(50));
+            let outbox = partition.outbox();
+            let key = partition.simulation().add_generator(process(move |_| {
+                outbox.send(0, secs(3.5), "late");
+                None
+            }));
+            partition.simulation().schedule_now(key);
+        });
+        engine.connect(late, clock, secs(1.0));
+        let reports = engine.run_until(secs(10.0));
+
+        assert_eq!(vec![(secs(3.5), "late", 4)], *lock(&arrivals));
+        assert_eq!(11, reports[clock].steps);
+        assert_eq!(1, reports[clock].messages_received);
+        assert!(reports[clock].rollbacks > 0);
+        assert_eq!(vec![secs(10.0), secs(10.0)], reports.iter().map(|report| report.time).collect::<Vec<_>>());
+    }
+
+    type Arrivals = Arc<Mutex<Vec<(Duration, &'static str, u32)>>>;
+
+    // Ticks every second from `first`, counting from `ticks`, records the messages with the ticks so far and
+    // checkpoints both, counting the restores in `restores`.
+    fn build_ticking(
+        partition: &mut Partition<&'static str>,
+        ticks: u32,
+        first: Duration,
+        arrivals: &Arrivals,
+        restores: &Arc<Mutex<usize>>,
+    ) {
+        let ticks = Rc::new(Cell::new(ticks));
+        let count = Rc::clone(&ticks);
+        let key = partition.simulation().add_generator(process(move |_| {
+            count.set(count.get() + 1);
+            Some(Action::Hold(secs(1.0)))
+        }));
+        let delay = first - partition.simulation().time();
+        partition.simulation().schedule(delay, key);
+        let (record, count) = (Arc::clone(arrivals), Rc::clone(&ticks));
+        partition.on_message(move |simulation, message| {
+            lock(&record).push((simulation.time(), message, count.get()));
+        });
+        let (arrivals, restores) = (Arc::clone(arrivals), Arc::clone(restores));
+        partition.checkpoint_with(
+            move |simulation| (ticks.get(), simulation.next_event_time().unwrap()),
+            move |partition, &(ticks, next)| {
+                *lock(&restores) += 1;
+                build_ticking(partition, ticks, next, &arrivals, &restores);
+            },
+        );
+    }
+
+    #[test]
+    fn rollbacks_restart_from_checkpoints() {
+        let arrivals = Arc::new(Mutex::new(Vec::new()));
+        let restores = Arc::new(Mutex::new(0));
+        let mut engine = TimeWarp::new();
+        engine.set_gvt_interval(4);
+        let (record, restored) = (Arc::clone(&arrivals), Arc::clone(&restores));
+        let clock = engine.add_partition(move |partition: &mut Partition<&'static str>| {
+            build_ticking(partition, 0, Duration::ZERO, &record, &restored);
+        });
+        // Four steps of half a second per round, half as fast as the other partition, so it's rolled back past
+        // its checkpoints.
+        let late = engine.add_partition(|partition: &mut Partition<&'static str>| {
+            let outbox = partition.outbox();
+            let mut steps = 0;
+            let key = partition.simulation().add_generator(process(move |_| {
+                steps += 1;
+                if steps == 13 {
+                    outbox.send(0, secs(0.5), "late");
+                }
+                Some(Action::Hold(secs(0.5)))
+            }));
+            partition.simulation().schedule_now(key);
+        });
+        engine.connect(late, clock, secs(0.5));
+        let reports = engine.run_until(secs(10.0));
+
+        assert_eq!(Some(&(secs(6.5), "late", 7)), lock(&arrivals).last());
+        assert_eq!(11, reports[clock].steps);
+        assert_eq!(1, reports[clock].rollbacks);
+        assert_eq!(1, *lock(&restores));
+    }
+}
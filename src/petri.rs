@@ -0,0 +1,369 @@
+//! Timed Petri nets executed on top of the scheduler.
+//!
+//! A [`PetriNet`] is made of places holding tokens and transitions connected to them by weighted arcs. A
+//! transition is enabled when each of its input places holds at least the weight of its arc and each of its
+//! inhibitor places holds fewer tokens than the threshold of its arc. An enabled transition fires: it takes
+//! the tokens of its input arcs right away, waits a delay drawn from its distribution and then puts the tokens
+//! of its output arcs. A transition fires once at a time, like a machine, and transitions competing for the same
+//! tokens are served in the order they are resumed, the order they were added at the start of the run.
+//!
+//! ```ignore
+//! // A machine taking parts from a buffer holding at most 5 of them.
+//! let mut net = PetriNet::new();
+//! let buffer = net.add_place("buffer", 0);
+//! let idle = net.add_place("idle", 1);
+//! let arrive = net.add_transition("arrive", Exponential::new(Duration::from_secs(4)));
+//! let machine = net.add_transition("machine", Duration::from_secs(3));
+//! net.inhibitor(buffer, arrive, 5);
+//! net.output(arrive, buffer, 1);
+//! net.input(buffer, machine, 1);
+//! net.input(idle, machine, 1);
+//! net.output(machine, idle, 1);
+//!
+//! let model = net.build(&mut simulation);
+//! simulation.run_with_limit(Duration::from_secs(10_000));
+//! println!("{}", model.place_statistic("buffer").unwrap().time_average());
+//! ```
+//!
+//! The marking of every place is traced by an [`Accumulate`] registered as a collector of the simulation.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::rc::Rc;
+
+use crate::distributions::Distribution;
+use crate::{process, Accumulate, Action, GenBoxed, Key, SimRng, Simulation};
+
+/// A place of a [`PetriNet`], returned by [`PetriNet::add_place`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PlaceId(usize);
+
+/// A transition of a [`PetriNet`], returned by [`PetriNet::add_transition`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TransitionId(usize);
+
+struct Transition {
+    name: String,
+    delay: Box<dyn Distribution>,
+    inputs: Vec<(usize, u64)>,
+    outputs: Vec<(usize, u64)>,
+    inhibitors: Vec<(usize, u64)>,
+}
+
+impl Transition {
+    // Whether a change in the marking of `place` may enable the transition.
+    fn watches(&self, place: usize) -> bool {
+        self.inputs
+            .iter()
+            .chain(&self.inhibitors)
+            .any(|&(input, _)| input == place)
+    }
+}
+
+/// Description of a timed Petri net, see the [module documentation](self).
+#[derive(Default)]
+pub struct PetriNet {
+    places: Vec<(String, u64)>,
+    transitions: Vec<Transition>,
+}
+
+impl PetriNet {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a place holding `tokens` tokens at the start.
+    ///
+    /// # Panics
+    ///
+    /// Panics if another place is named `name`.
+    pub fn add_place(&mut self, name: impl Into<String>, tokens: u64) -> PlaceId {
+        let name = name.into();
+        assert!(
+            self.places.iter().all(|(other, _)| *other != name),
+            "A place named {:?} already exists",
+            name
+        );
+        self.places.push((name, tokens));
+        PlaceId(self.places.len() - 1)
+    }
+
+    /// Add a transition taking a delay drawn from `delay` to fire, a `Duration` for a fixed delay.
+    ///
+    /// # Panics
+    ///
+    /// Panics if another transition is named `name`.
+    pub fn add_transition(&mut self, name: impl Into<String>, delay: impl Distribution + 'static) -> TransitionId {
+        let name = name.into();
+        assert!(
+            self.transitions.iter().all(|other| other.name != name),
+            "A transition named {:?} already exists",
+            name
+        );
+        self.transitions.push(Transition {
+            name,
+            delay: Box::new(delay),
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            inhibitors: Vec::new(),
+        });
+        TransitionId(self.transitions.len() - 1)
+    }
+
+    /// Add an arc taking `weight` tokens from `place` when `transition` fires.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `weight` is zero.
+    pub fn input(&mut self, place: PlaceId, transition: TransitionId, weight: u64) {
+        assert!(weight > 0, "Arcs must have a positive weight");
+        self.transitions[transition.0].inputs.push((place.0, weight));
+    }
+
+    /// Add an arc putting `weight` tokens in `place` when `transition` finishes firing.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `weight` is zero.
+    pub fn output(&mut self, transition: TransitionId, place: PlaceId, weight: u64) {
+        assert!(weight > 0, "Arcs must have a positive weight");
+        self.transitions[transition.0].outputs.push((place.0, weight));
+    }
+
+    /// Add an inhibitor arc, disabling `transition` while `place` holds `threshold` tokens or more.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `threshold` is zero.
+    pub fn inhibitor(&mut self, place: PlaceId, transition: TransitionId, threshold: u64) {
+        assert!(threshold > 0, "Inhibitor arcs must have a positive threshold");
+        self.transitions[transition.0].inhibitors.push((place.0, threshold));
+    }
+
+    /// Add the transitions of the net to `simulation` as entities named after them, scheduled to start now.
+    ///
+    /// The marking of every place is registered as a collector named `<place> tokens`.
+    pub fn build(self, simulation: &mut Simulation<()>) -> PetriModel {
+        let clock = simulation.clock();
+        let places = self
+            .places
+            .into_iter()
+            .map(|(name, tokens)| {
+                let statistic = Accumulate::new(format!("{} tokens", name), clock.clone(), tokens as f64);
+                simulation.add_statistic(statistic.name(), statistic.clone());
+                Place { name, tokens, statistic }
+            })
+            .collect();
+        let count = self.transitions.len();
+        let runtime = Rc::new(RefCell::new(Runtime {
+            places,
+            transitions: self.transitions,
+            keys: Vec::with_capacity(count),
+            idle: vec![false; count],
+            firings: vec![0; count],
+        }));
+        for index in 0..count {
+            let name = runtime.borrow().transitions[index].name.clone();
+            let key = simulation.add_generator_named(name, transition(Rc::clone(&runtime), index, simulation.rng()));
+            runtime.borrow_mut().keys.push(key);
+            simulation.schedule_now(key);
+        }
+        PetriModel { runtime }
+    }
+}
+
+struct Place {
+    name: String,
+    tokens: u64,
+    statistic: Accumulate,
+}
+
+struct Runtime {
+    places: Vec<Place>,
+    transitions: Vec<Transition>,
+    keys: Vec<Key>,
+    // Transitions passive until the marking of a place they watch changes.
+    idle: Vec<bool>,
+    firings: Vec<u64>,
+}
+
+impl Runtime {
+    fn is_enabled(&self, transition: usize) -> bool {
+        let transition = &self.transitions[transition];
+        transition
+            .inputs
+            .iter()
+            .all(|&(place, weight)| self.places[place].tokens >= weight)
+            && transition
+                .inhibitors
+                .iter()
+                .all(|&(place, threshold)| self.places[place].tokens < threshold)
+    }
+
+    // Apply the arcs of `transition` to the marking, returning the idle transitions to wake up.
+    fn apply(&mut self, transition: usize, output: bool) -> Vec<Key> {
+        let arcs = if output {
+            self.transitions[transition].outputs.clone()
+        } else {
+            self.transitions[transition].inputs.clone()
+        };
+        for &(place, weight) in &arcs {
+            let place = &mut self.places[place];
+            place.tokens = if output { place.tokens + weight } else { place.tokens - weight };
+            place.statistic.set(place.tokens as f64);
+        }
+        let mut woken = Vec::new();
+        for (other, idle) in self.idle.iter_mut().enumerate() {
+            if *idle && arcs.iter().any(|&(place, _)| self.transitions[other].watches(place)) {
+                *idle = false;
+                woken.push(self.keys[other]);
+            }
+        }
+        woken
+    }
+}
+
+/// A [`PetriNet`] built into a simulation, used to read its marking.
+pub struct PetriModel {
+    runtime: Rc<RefCell<Runtime>>,
+}
+
+impl PetriModel {
+    /// Returns the tokens held by the place named `name`.
+    #[must_use]
+    pub fn tokens(&self, name: &str) -> Option<u64> {
+        self.place(name, |place| place.tokens)
+    }
+
+    /// Returns the tokens held by every place, by name.
+    #[must_use]
+    pub fn marking(&self) -> HashMap<String, u64> {
+        self.runtime
+            .borrow()
+            .places
+            .iter()
+            .map(|place| (place.name.clone(), place.tokens))
+            .collect()
+    }
+
+    /// Returns the time-weighted statistics of the marking of the place named `name`.
+    #[must_use]
+    pub fn place_statistic(&self, name: &str) -> Option<Accumulate> {
+        self.place(name, |place| place.statistic.clone())
+    }
+
+    /// Returns how many times the transition named `name` finished firing.
+    #[must_use]
+    pub fn firings(&self, name: &str) -> Option<u64> {
+        let runtime = self.runtime.borrow();
+        let index = runtime
+            .transitions
+            .iter()
+            .position(|transition| transition.name == name)?;
+        Some(runtime.firings[index])
+    }
+
+    /// Returns the key of the entity firing the transition named `name`.
+    #[must_use]
+    pub fn transition_key(&self, name: &str) -> Option<Key> {
+        let runtime = self.runtime.borrow();
+        let index = runtime
+            .transitions
+            .iter()
+            .position(|transition| transition.name == name)?;
+        runtime.keys.get(index).copied()
+    }
+
+    fn place<T>(&self, name: &str, f: impl FnOnce(&Place) -> T) -> Option<T> {
+        self.runtime.borrow().places.iter().find(|place| place.name == name).map(f)
+    }
+}
+
+fn transition(runtime: Rc<RefCell<Runtime>>, index: usize, rng: SimRng) -> GenBoxed<()> {
+    let mut pending = VecDeque::new();
+    // Whether the next resume ends a firing delay.
+    let mut firing = false;
+    process(move |_| loop {
+        if let Some(action) = pending.pop_front() {
+            return Some(action);
+        }
+        let mut runtime = runtime.borrow_mut();
+        if firing {
+            firing = false;
+            runtime.firings[index] += 1;
+            let woken = runtime.apply(index, true);
+            if !woken.is_empty() {
+                pending.push_back(Action::ActivateMany(woken));
+            }
+        } else if runtime.is_enabled(index) {
+            firing = true;
+            // Taking tokens may lift an inhibitor arc.
+            let woken = runtime.apply(index, false);
+            if !woken.is_empty() {
+                pending.push_back(Action::ActivateMany(woken));
+            }
+            pending.push_back(Action::Hold(runtime.transitions[index].delay.sample(&rng)));
+        } else {
+            runtime.idle[index] = true;
+            return Some(Action::Passivate);
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn producer_consumer_with_bounded_buffer() {
+        let secs = Duration::from_secs;
+        let mut net = PetriNet::new();
+        let buffer = net.add_place("buffer", 0);
+        let parts = net.add_place("parts", 5);
+        let done = net.add_place("done", 0);
+        let produce = net.add_transition("produce", secs(1));
+        let consume = net.add_transition("consume", secs(3));
+        net.input(parts, produce, 1);
+        net.inhibitor(buffer, produce, 2);
+        net.output(produce, buffer, 1);
+        net.input(buffer, consume, 1);
+        net.output(consume, done, 1);
+        let mut simulation = Simulation::default();
+        let model = net.build(&mut simulation);
+        simulation.run_until_empty();
+
+        // `consume` takes a part from the buffer at 1, 4, 7, 10 and 13, the buffer holding two parts from 3
+        // to 4 and from 5 to 7 while `produce` is inhibited.
+        assert_eq!(Duration::from_secs(16), simulation.time());
+        assert_eq!(Some(5), model.tokens("done"));
+        assert_eq!(Some(0), model.tokens("parts"));
+        assert_eq!(Some(5), model.firings("produce"));
+        let buffer = model.place_statistic("buffer").unwrap();
+        assert_eq!(2.0, buffer.max());
+        let expected = HashMap::from([("buffer".to_owned(), 0), ("parts".to_owned(), 0), ("done".to_owned(), 5)]);
+        assert_eq!(expected, model.marking());
+    }
+
+    #[test]
+    fn conflicts_go_to_the_first_transition_resumed() {
+        let mut net = PetriNet::new();
+        let token = net.add_place("token", 1);
+        let left = net.add_place("left", 0);
+        let right = net.add_place("right", 0);
+        let take_left = net.add_transition("take left", Duration::ZERO);
+        let take_right = net.add_transition("take right", Duration::ZERO);
+        net.input(token, take_left, 1);
+        net.output(take_left, left, 1);
+        net.input(token, take_right, 1);
+        net.output(take_right, right, 1);
+        let mut simulation = Simulation::default();
+        let model = net.build(&mut simulation);
+        simulation.run_until_empty();
+
+        assert_eq!((Some(1), Some(0)), (model.tokens("left"), model.tokens("right")));
+        assert!(model.transition_key("take right").is_some());
+    }
+}
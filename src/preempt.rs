@@ -0,0 +1,118 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::Key;
+
+/// The remaining hold of the entities interrupted by [`Action::Preempt`](crate::Action::Preempt).
+///
+/// Obtained from [`Simulation::preemptions`](crate::Simulation::preemptions), clones share the record. A preempted
+/// entity is resumed right away and can look up how much of its hold was left to resume it later, the record is
+/// dropped when the entity yields again:
+///
+/// ```ignore
+/// let preemptions = simulation.preemptions();
+/// simulation.add_generator(Box::new(move |_| {
+///     let mut work = Duration::from_secs(60);
+///     while !work.is_zero() {
+///         yield Action::Hold(work);
+///         work = preemptions.remaining().unwrap_or_default();
+///         if !work.is_zero() {
+///             yield Action::Passivate; // Wait for the repair.
+///         }
+///     }
+/// }));
+/// ```
+#[derive(Clone)]
+pub struct Preemptions {
+    remaining: Rc<RefCell<HashMap<Key, Duration>>>,
+    current: Rc<Cell<Option<Key>>>,
+}
+
+impl Preemptions {
+    pub(crate) fn new(current: Rc<Cell<Option<Key>>>) -> Self {
+        Self {
+            remaining: Rc::default(),
+            current,
+        }
+    }
+
+    pub(crate) fn insert(&self, key: Key, remaining: Duration) {
+        self.remaining.borrow_mut().insert(key, remaining);
+    }
+
+    pub(crate) fn clear(&self, key: Key) {
+        self.remaining.borrow_mut().remove(&key);
+    }
+
+    /// Returns how much of its hold the entity currently being executed had left when it was preempted,
+    /// `None` if it wasn't preempted.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of an entity.
+    #[must_use]
+    pub fn remaining(&self) -> Option<Duration> {
+        let key = self.current.get().expect("Preemptions::remaining called outside of an entity");
+        self.remaining_of(key)
+    }
+
+    /// Returns how much of its hold `key` had left when it was preempted, until it yields again.
+    #[must_use]
+    pub fn remaining_of(&self, key: Key) -> Option<Duration> {
+        self.remaining.borrow().get(&key).copied()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    use crate::{process, Action, Simulation, SimulationError};
+
+    #[test]
+    fn preempted_entities_learn_their_remaining_hold() {
+        let mut simulation = Simulation::default();
+        let preemptions = simulation.preemptions();
+        let recorded = Rc::new(Cell::new(None));
+        let record = Rc::clone(&recorded);
+        let mut started = false;
+        let machine = simulation.add_generator(process(move |_| {
+            if !started {
+                started = true;
+                return Some(Action::Hold(Duration::from_secs(10)));
+            }
+            let left = preemptions.remaining()?;
+            record.set(Some(left));
+            Some(Action::Hold(left))
+        }));
+        let mut preempted = false;
+        let breakdown = simulation.add_generator(process(move |_| {
+            if preempted {
+                return None;
+            }
+            preempted = true;
+            Some(Action::Preempt(machine))
+        }));
+        simulation.schedule_now(machine);
+        simulation.schedule(Duration::from_secs(4), breakdown);
+        simulation.run_until_empty();
+
+        assert_eq!(Some(Duration::from_secs(6)), recorded.get());
+        assert_eq!(Duration::from_secs(10), simulation.time());
+        assert!(simulation.preemptions().remaining_of(machine).is_none());
+
+        let passive = simulation.add_generator(process(|_| Some(Action::Passivate)));
+        let preemptor = simulation.add_generator(process(move |_| Some(Action::Preempt(passive))));
+        simulation.schedule_now(passive);
+        simulation.step().unwrap();
+        simulation.schedule_now(preemptor);
+        assert_eq!(
+            Err(SimulationError::NotHolding { key: preemptor, other: passive }),
+            simulation.step()
+        );
+    }
+}
@@ -0,0 +1,123 @@
+use std::pin::Pin;
+
+use crate::{Action, GenBoxed};
+
+#[cfg(not(feature = "stable"))]
+pub use std::ops::{Generator, GeneratorState};
+
+/// Stand-in for the nightly `std::ops::Generator` trait, used by the `stable` feature.
+///
+/// It has the same shape, so entities are resumed exactly like native generators.
+#[cfg(feature = "stable")]
+pub trait Generator<R = ()> {
+    type Yield;
+    type Return;
+
+    fn resume(self: Pin<&mut Self>, arg: R) -> GeneratorState<Self::Yield, Self::Return>;
+}
+
+/// Stand-in for the nightly `std::ops::GeneratorState` enum, used by the `stable` feature.
+#[cfg(feature = "stable")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum GeneratorState<Y, R> {
+    Yielded(Y),
+    Complete(R),
+}
+
+/// An entity written as a closure, created with [`process`].
+pub struct FnProcess<F> {
+    step: F,
+    complete: bool,
+}
+
+impl<R, F> Generator<R> for FnProcess<F>
+where
+    F: FnMut(R) -> Option<Action> + Unpin,
+{
+    type Yield = Action;
+    type Return = ();
+
+    fn resume(self: Pin<&mut Self>, arg: R) -> GeneratorState<Action, ()> {
+        let this = self.get_mut();
+        assert!(!this.complete, "a process was resumed after completing");
+        match (this.step)(arg) {
+            Some(action) => GeneratorState::Yielded(action),
+            None => {
+                this.complete = true;
+                GeneratorState::Complete(())
+            }
+        }
+    }
+}
+
+/// Create an entity from a closure that is called every time the entity is resumed.
+///
+/// The closure returns the next action, or `None` to complete. It has to keep track of where it
+/// left off itself, usually with an enum, but it compiles on stable Rust and can be mixed with
+/// entities written as generators:
+///
+/// ```ignore
+/// let mut arrived = false;
+/// let customer = process(move |_| {
+///     if arrived {
+///         return None;
+///     }
+///     arrived = true;
+///     Some(Action::Hold(Duration::from_secs(5)))
+/// });
+/// ```
+pub fn process<R, F>(step: F) -> GenBoxed<R>
+where
+    F: FnMut(R) -> Option<Action> + Unpin + 'static,
+{
+    Box::new(FnProcess { step, complete: false })
+}
+
+/// Create an entity yielding `actions` in order, then completing.
+#[cfg(test)]
+pub(crate) fn script(actions: Vec<Action>) -> GenBoxed<()> {
+    let mut actions = actions.into_iter();
+    process(move |_| actions.next())
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{Key, Simulation};
+
+    // Holds twice, then wakes up `sleeper` and completes.
+    fn waker(sleeper: Key) -> GenBoxed<()> {
+        let mut holds = 0;
+        process(move |_| {
+            holds += 1;
+            match holds {
+                1 | 2 => Some(Action::Hold(Duration::from_secs(3))),
+                3 => Some(Action::ActivateOne(sleeper)),
+                _ => None,
+            }
+        })
+    }
+
+    #[test]
+    fn closure_processes_follow_action_semantics() {
+        let mut simulation = Simulation::default();
+        let mut passivated = false;
+        let sleeper = simulation.add_generator(process(move |_| {
+            if passivated {
+                return None;
+            }
+            passivated = true;
+            Some(Action::Passivate)
+        }));
+        let waker = simulation.add_generator(waker(sleeper));
+        simulation.schedule_now(sleeper);
+        simulation.schedule_now(waker);
+        simulation.run_until_empty();
+
+        assert_eq!(Duration::from_secs(6), simulation.time());
+        assert!(simulation.upgrade(sleeper.downgrade()).is_none());
+        assert!(simulation.upgrade(waker.downgrade()).is_none());
+    }
+}
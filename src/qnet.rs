@@ -0,0 +1,395 @@
+//! Queueing networks built from a declarative description.
+//!
+//! A [`Network`] lists stations, each with a number of servers and a service time distribution, the external
+//! arrivals feeding them and the routing probabilities between them. Customers leave the network after a station
+//! with the probability not routed anywhere else. Building it adds a resource per station, the sources of the
+//! arrivals and the statistics of every station:
+//!
+//! ```ignore
+//! // Jackson network: a dispatcher sending jobs to one of two machines, a fifth of them coming back.
+//! let mut network = Network::new();
+//! let dispatcher = network.add_station("dispatcher", 1, Exponential::new(Duration::from_secs(2)));
+//! let fast = network.add_station("fast", 2, Exponential::new(Duration::from_secs(6)));
+//! let slow = network.add_station("slow", 1, Exponential::new(Duration::from_secs(9)));
+//! network.add_arrivals(dispatcher, Exponential::new(Duration::from_secs(4)));
+//! network.route(dispatcher, fast, 0.7);
+//! network.route(dispatcher, slow, 0.3);
+//! network.route(slow, dispatcher, 0.2);
+//!
+//! let model = network.build(&mut simulation);
+//! simulation.run_with_limit(Duration::from_secs(100_000));
+//! println!("{:?}", model.station("slow").unwrap().waiting().mean());
+//! ```
+//!
+//! [`Network::mm1`], [`Network::mmc`] and [`Network::tandem`] describe the classic systems in one call.
+
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::distributions::{Distribution, Exponential};
+use crate::scheduler::ClockRef;
+use crate::{process, Accumulate, Action, GenBoxed, Resource, SimRng, Simulation, Source, Tally};
+
+/// A station of a [`Network`], returned by [`Network::add_station`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StationId(usize);
+
+struct StationSpec {
+    name: String,
+    servers: usize,
+    service: Box<dyn Distribution>,
+    routes: Vec<(usize, f64)>,
+}
+
+/// Description of a queueing network, see the [module documentation](self).
+#[derive(Default)]
+pub struct Network {
+    stations: Vec<StationSpec>,
+    arrivals: Vec<(usize, Box<dyn Distribution>)>,
+}
+
+impl Network {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// M/M/1 queue: exponential interarrival and service times with the given means and one server.
+    #[must_use]
+    pub fn mm1(interarrival: Duration, service: Duration) -> Self {
+        Self::mmc(interarrival, service, 1)
+    }
+
+    /// M/M/c queue: exponential interarrival and service times with the given means and `servers` servers.
+    #[must_use]
+    pub fn mmc(interarrival: Duration, service: Duration, servers: usize) -> Self {
+        Self::tandem(interarrival, &[(servers, service)])
+    }
+
+    /// Tandem line: Poisson arrivals with mean `interarrival` visiting every stage in order, each a number of
+    /// servers with exponential service times of the given mean. Stations are named `station 1`, `station 2`...
+    #[must_use]
+    pub fn tandem(interarrival: Duration, stages: &[(usize, Duration)]) -> Self {
+        let mut network = Self::new();
+        let mut previous = None;
+        for (index, &(servers, service)) in stages.iter().enumerate() {
+            let station = network.add_station(format!("station {}", index + 1), servers, Exponential::new(service));
+            match previous {
+                Some(previous) => network.route(previous, station, 1.0),
+                None => network.add_arrivals(station, Exponential::new(interarrival)),
+            }
+            previous = Some(station);
+        }
+        network
+    }
+
+    /// Add a station with `servers` servers sharing a single first come, first served queue.
+    ///
+    /// # Panics
+    ///
+    /// Panics if another station is named `name`.
+    pub fn add_station(
+        &mut self,
+        name: impl Into<String>,
+        servers: usize,
+        service: impl Distribution + 'static,
+    ) -> StationId {
+        let name = name.into();
+        assert!(
+            self.stations.iter().all(|station| station.name != name),
+            "A station named {:?} already exists",
+            name
+        );
+        self.stations.push(StationSpec {
+            name,
+            servers,
+            service: Box::new(service),
+            routes: Vec::new(),
+        });
+        StationId(self.stations.len() - 1)
+    }
+
+    /// Add a stream of external arrivals to `station`, separated by samples of `interarrival`.
+    pub fn add_arrivals(&mut self, station: StationId, interarrival: impl Distribution + 'static) {
+        self.arrivals.push((station.0, Box::new(interarrival)));
+    }
+
+    /// Send the customers leaving `from` to `to` with `probability`, adding to any previous route between them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the probabilities of the routes leaving `from` add up to more than one.
+    pub fn route(&mut self, from: StationId, to: StationId, probability: f64) {
+        let routes = &mut self.stations[from.0].routes;
+        match routes.iter_mut().find(|(station, _)| *station == to.0) {
+            Some((_, previous)) => *previous += probability,
+            None => routes.push((to.0, probability)),
+        }
+        let total: f64 = routes.iter().map(|(_, probability)| probability).sum();
+        assert!(
+            (0.0..=1.0 + 1e-9).contains(&probability) && total <= 1.0 + 1e-9,
+            "The routes leaving {:?} add up to {}, more than one",
+            self.stations[from.0].name,
+            total
+        );
+    }
+
+    /// Add the stations, arrivals and statistics of the network to `simulation`.
+    ///
+    /// Stations are registered as resources, arrivals as sources named `arrivals 1`, `arrivals 2`... and the
+    /// statistics as collectors, so they are reset after a warm-up period.
+    pub fn build(self, simulation: &mut Simulation<()>) -> NetworkModel {
+        let clock = simulation.clock();
+        let stations: Vec<Station> = self
+            .stations
+            .into_iter()
+            .map(|spec| {
+                let resource = simulation.add_resource(spec.name.clone(), spec.servers);
+                let stats = StationStats {
+                    resource,
+                    waiting: Tally::new(format!("{} waiting time", spec.name)),
+                    in_system: Accumulate::new(format!("{} number in system", spec.name), clock.clone(), 0.0),
+                    served: Rc::default(),
+                };
+                simulation.add_statistic(stats.waiting.name(), stats.waiting.clone());
+                simulation.add_statistic(stats.in_system.name(), stats.in_system.clone());
+                Station {
+                    name: spec.name,
+                    service: spec.service,
+                    routes: spec.routes,
+                    stats,
+                }
+            })
+            .collect();
+        let network = Rc::new(Runtime {
+            stations,
+            sojourn: Tally::new("sojourn time"),
+            clock,
+            rng: simulation.rng(),
+        });
+        simulation.add_statistic("sojourn time", network.sojourn.clone());
+
+        for (index, (station, interarrival)) in self.arrivals.into_iter().enumerate() {
+            let rng = simulation.rng();
+            let runtime = Rc::clone(&network);
+            let source = Source::new(move || interarrival.sample(&rng), move || customer(Rc::clone(&runtime), station));
+            simulation.add_source(format!("arrivals {}", index + 1), source);
+        }
+        NetworkModel { runtime: network }
+    }
+}
+
+struct Station {
+    name: String,
+    service: Box<dyn Distribution>,
+    routes: Vec<(usize, f64)>,
+    stats: StationStats,
+}
+
+struct Runtime {
+    stations: Vec<Station>,
+    sojourn: Tally,
+    clock: ClockRef,
+    rng: SimRng,
+}
+
+impl Runtime {
+    // Draw the station visited after `station`, `None` if the customer leaves the network.
+    fn next(&self, station: usize) -> Option<usize> {
+        let mut draw = self.rng.next_f64();
+        for &(next, probability) in &self.stations[station].routes {
+            if draw < probability {
+                return Some(next);
+            }
+            draw -= probability;
+        }
+        None
+    }
+}
+
+/// Statistics of a station of a built network.
+///
+/// The collectors are shared with the simulation, so they can be read during and after the run.
+#[derive(Clone)]
+pub struct StationStats {
+    resource: Resource,
+    waiting: Tally,
+    in_system: Accumulate,
+    served: Rc<Cell<u64>>,
+}
+
+impl StationStats {
+    /// Returns the resource of the servers of the station.
+    #[must_use]
+    pub fn resource(&self) -> Resource {
+        self.resource.clone()
+    }
+
+    /// Returns the time customers waited before their service started, in seconds.
+    #[must_use]
+    pub fn waiting(&self) -> Tally {
+        self.waiting.clone()
+    }
+
+    /// Returns the number of customers waiting or in service over time.
+    #[must_use]
+    pub fn number_in_system(&self) -> Accumulate {
+        self.in_system.clone()
+    }
+
+    /// Returns how many services were completed.
+    #[must_use]
+    pub fn served(&self) -> u64 {
+        self.served.get()
+    }
+}
+
+/// A [`Network`] built into a simulation, used to read its statistics.
+pub struct NetworkModel {
+    runtime: Rc<Runtime>,
+}
+
+impl NetworkModel {
+    /// Returns the statistics of the station named `name`.
+    #[must_use]
+    pub fn station(&self, name: &str) -> Option<StationStats> {
+        self.runtime
+            .stations
+            .iter()
+            .find(|station| station.name == name)
+            .map(|station| station.stats.clone())
+    }
+
+    /// Returns the statistics of every station, by name.
+    #[must_use]
+    pub fn stations(&self) -> HashMap<String, StationStats> {
+        self.runtime
+            .stations
+            .iter()
+            .map(|station| (station.name.clone(), station.stats.clone()))
+            .collect()
+    }
+
+    /// Returns the time customers spent in the network, from their arrival to their departure, in seconds.
+    #[must_use]
+    pub fn sojourn(&self) -> Tally {
+        self.runtime.sojourn.clone()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Step {
+    Arrive(usize),
+    Serve(usize),
+    Depart(usize),
+}
+
+fn customer(network: Rc<Runtime>, first: usize) -> GenBoxed<()> {
+    let entered = network.clock.time();
+    let mut arrived = entered;
+    let mut step = Some(Step::Arrive(first));
+    process(move |_| loop {
+        let now = network.clock.time();
+        match step {
+            Some(Step::Arrive(index)) => {
+                let stats = &network.stations[index].stats;
+                arrived = now;
+                stats.in_system.add(1.0);
+                step = Some(Step::Serve(index));
+                if !stats.resource.request() {
+                    // The departing customer hands its server over before activating this one.
+                    return Some(Action::Passivate);
+                }
+            }
+            Some(Step::Serve(index)) => {
+                let station = &network.stations[index];
+                station.stats.waiting.record_duration(now - arrived);
+                step = Some(Step::Depart(index));
+                return Some(Action::Hold(station.service.sample(&network.rng)));
+            }
+            Some(Step::Depart(index)) => {
+                let stats = &network.stations[index].stats;
+                stats.in_system.add(-1.0);
+                stats.served.set(stats.served.get() + 1);
+                step = network.next(index).map(Step::Arrive);
+                if let Some(next) = stats.resource.release() {
+                    return Some(Action::ActivateOne(next));
+                }
+            }
+            None => {
+                network.sojourn.record_duration(now - entered);
+                return None;
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::distributions::Uniform;
+
+    #[test]
+    fn deterministic_tandem_line() {
+        let mut network = Network::new();
+        let cut = network.add_station("cut", 1, Uniform::new(Duration::from_secs(3), Duration::from_secs(3)));
+        let paint = network.add_station("paint", 1, Uniform::new(Duration::from_secs(8), Duration::from_secs(8)));
+        network.add_arrivals(cut, Uniform::new(Duration::from_secs(5), Duration::from_secs(5)));
+        network.route(cut, paint, 1.0);
+        let mut simulation = Simulation::default();
+        let model = network.build(&mut simulation);
+        simulation.run_with_limit(Duration::from_secs(33));
+
+        // Arrivals every 5 seconds from 5 to 30 leave `cut` 3 seconds later; `paint` serves them back to back
+        // from 8, ending at 16, 24 and 32 and starting the fourth one after it waited 9 seconds.
+        let cut = model.station("cut").unwrap();
+        let paint = model.station("paint").unwrap();
+        assert_eq!((6, Some(0.0)), (cut.served(), cut.waiting().max()));
+        assert_eq!(3, paint.served());
+        assert_eq!(Some(9.0), paint.waiting().max());
+        assert_eq!(Some(11.0), model.sojourn().min());
+        assert_eq!(3, model.sojourn().count());
+    }
+
+    #[test]
+    fn mm1_waiting_time_matches_theory() {
+        let mut simulation = Simulation::default();
+        simulation.set_seed(7);
+        let model = Network::mm1(Duration::from_secs(10), Duration::from_secs(5)).build(&mut simulation);
+        simulation.run_with_limit(Duration::from_secs(200_000));
+
+        // Wq = rho / (mu - lambda) = 0.5 / (1/5 - 1/10) = 5 seconds.
+        let waiting = model.station("station 1").unwrap().waiting().mean().unwrap();
+        assert!((waiting - 5.0).abs() < 0.5, "mean waiting time {}", waiting);
+    }
+
+    #[test]
+    fn routes_split_customers() {
+        let mut network = Network::new();
+        let front = network.add_station("front", 1, Exponential::new(Duration::from_secs(1)));
+        let back = network.add_station("back", 4, Exponential::new(Duration::from_secs(1)));
+        network.add_arrivals(front, Exponential::new(Duration::from_secs(4)));
+        network.route(front, back, 0.25);
+        network.route(back, front, 0.5);
+        let mut simulation = Simulation::default();
+        let model = network.build(&mut simulation);
+        simulation.run_with_limit(Duration::from_secs(40_000));
+
+        // Visits to `front` are 1 / (1 - 0.25 * 0.5) times the arrivals, a fourth of them continue to `back`.
+        let front = model.station("front").unwrap().served() as f64;
+        let back = model.station("back").unwrap().served() as f64;
+        assert!((back / front - 0.25).abs() < 0.03, "{} of {}", back, front);
+        assert_eq!(2, model.stations().len());
+    }
+
+    #[test]
+    #[should_panic(expected = "add up to")]
+    fn routes_cannot_exceed_one() {
+        let mut network = Network::mm1(Duration::from_secs(1), Duration::from_secs(1));
+        let other = network.add_station("other", 1, Exponential::new(Duration::from_secs(1)));
+        network.route(StationId(0), other, 0.6);
+        network.route(StationId(0), other, 0.6);
+    }
+}
@@ -0,0 +1,412 @@
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::scheduler::ClockRef;
+use crate::stats::{Accumulate, Statistic, Tally};
+use crate::Key;
+
+type Filter<T> = Box<dyn Fn(&T) -> bool>;
+
+struct Consumer<T> {
+    key: Key,
+    // Only items it accepts wake it up, any item if `None`.
+    filter: Option<Filter<T>>,
+}
+
+struct Item<T> {
+    item: T,
+    pushed_at: Duration,
+    // The consumer activated for it, which the item waits for.
+    reserved: Option<Key>,
+}
+
+struct Inner<T> {
+    // In arrival order.
+    items: VecDeque<Item<T>>,
+    // Entities passivated until an item arrives, in arrival order.
+    consumers: VecDeque<Consumer<T>>,
+    max_len: usize,
+}
+
+// Remove the entity waiting the longest among the `consumers` accepting `item`, returning its key.
+fn reserve<T>(consumers: &mut VecDeque<Consumer<T>>, item: &T) -> Option<Key> {
+    let position = consumers
+        .iter()
+        .position(|consumer| consumer.filter.as_ref().is_none_or(|filter| filter(item)))?;
+    consumers.remove(position).map(|consumer| consumer.key)
+}
+
+impl<T> Inner<T> {
+    fn wait(&mut self, key: Key, filter: Option<Filter<T>>) {
+        match self.consumers.iter_mut().find(|consumer| consumer.key == key) {
+            Some(consumer) => consumer.filter = filter,
+            None => self.consumers.push_back(Consumer { key, filter }),
+        }
+    }
+}
+
+/// A FIFO queue of items that tracks its own statistics using the simulation clock:
+/// the time-weighted length, the maximum length and the time every item waited.
+///
+/// Created with [`Simulation::add_queue`](crate::Simulation::add_queue). It can be cloned and moved into generators:
+///
+/// ```ignore
+/// // Producer
+/// if let Some(consumer) = jobs.push(job) {
+///     yield Action::ActivateOne(consumer);
+/// }
+///
+/// // Consumer
+/// let job = loop {
+///     if let Some(job) = jobs.take() {
+///         break job;
+///     }
+///     // The queue is empty, wait until a producer activates us.
+///     yield Action::Passivate;
+/// };
+/// ```
+///
+/// Consumers can also wait for particular items with [`SimQueue::take_matching`].
+pub struct SimQueue<T> {
+    inner: Rc<RefCell<Inner<T>>>,
+    length: Accumulate,
+    waiting_time: Tally,
+    clock: ClockRef,
+    current: Rc<Cell<Option<Key>>>,
+}
+
+impl<T> Clone for SimQueue<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Rc::clone(&self.inner),
+            length: self.length.clone(),
+            waiting_time: self.waiting_time.clone(),
+            clock: self.clock.clone(),
+            current: Rc::clone(&self.current),
+        }
+    }
+}
+
+impl<T> SimQueue<T> {
+    pub(crate) fn new(name: &str, clock: ClockRef, current: Rc<Cell<Option<Key>>>) -> Self {
+        let inner = Inner {
+            items: VecDeque::new(),
+            consumers: VecDeque::new(),
+            max_len: 0,
+        };
+        Self {
+            inner: Rc::new(RefCell::new(inner)),
+            length: Accumulate::new(format!("{} length", name), clock.clone(), 0.0),
+            waiting_time: Tally::new(format!("{} waiting time", name)),
+            clock,
+            current,
+        }
+    }
+
+    /// Add an item at the back of the queue.
+    ///
+    /// If an entity is waiting for an item its key is returned, it must be activated with `Action::ActivateOne`.
+    /// The item goes to the entity waiting the longest among those accepting it, or to the next one if that entity
+    /// is removed before taking it.
+    pub fn push(&self, item: T) -> Option<Key> {
+        let mut inner = self.inner.borrow_mut();
+        let reserved = reserve(&mut inner.consumers, &item);
+        inner.items.push_back(Item {
+            item,
+            pushed_at: self.clock.time(),
+            reserved,
+        });
+        inner.max_len = inner.max_len.max(inner.items.len());
+        self.length.set(inner.items.len() as f64);
+        reserved
+    }
+
+    /// Remove the item at the front of the queue, recording the time it waited.
+    pub fn pop(&self) -> Option<T> {
+        self.remove(0)
+    }
+
+    fn remove(&self, position: usize) -> Option<T> {
+        let mut inner = self.inner.borrow_mut();
+        let Item { item, pushed_at, .. } = inner.items.remove(position)?;
+        self.length.set(inner.items.len() as f64);
+        self.waiting_time
+            .record_duration(self.clock.time().saturating_sub(pushed_at));
+        Some(item)
+    }
+
+    /// Same as [`SimQueue::pop`], but if the queue is empty the entity currently being executed waits for an item.
+    ///
+    /// When `None` is returned the entity must yield `Action::Passivate` and try again once activated. Items handed
+    /// to another waiting entity by [`SimQueue::push`] are kept for it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of an entity.
+    pub fn take(&self) -> Option<T> {
+        self.take_first(None)
+    }
+
+    /// Remove the first item accepted by `filter`, or wait for one with the entity currently being executed.
+    ///
+    /// When `None` is returned the entity must yield `Action::Passivate` and try again once activated. Only items
+    /// accepted by `filter` activate it, before any entity that started waiting later.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of an entity.
+    pub fn take_matching(&self, filter: impl Fn(&T) -> bool + 'static) -> Option<T> {
+        self.take_first(Some(Box::new(filter)))
+    }
+
+    fn take_first(&self, filter: Option<Filter<T>>) -> Option<T> {
+        let key = self
+            .current
+            .get()
+            .expect("queues can only be waited on from inside an entity");
+        let position = self.inner.borrow().items.iter().position(|item| {
+            item.reserved.is_none_or(|reserved| reserved == key)
+                && filter.as_ref().is_none_or(|filter| filter(&item.item))
+        });
+        match position {
+            Some(position) => self.remove(position),
+            None => {
+                self.inner.borrow_mut().wait(key, filter);
+                None
+            }
+        }
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.inner.borrow().items.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.inner.borrow().items.is_empty()
+    }
+
+    /// Returns the maximum length the queue has reached.
+    #[must_use]
+    pub fn max_len(&self) -> usize {
+        self.inner.borrow().max_len
+    }
+
+    /// Returns the number of entities waiting for an item.
+    #[must_use]
+    pub fn consumers(&self) -> usize {
+        self.inner.borrow().consumers.len()
+    }
+
+    /// Returns the time-weighted statistics of the queue length.
+    #[must_use]
+    pub fn length(&self) -> Accumulate {
+        self.length.clone()
+    }
+
+    /// Returns the statistics of the time items waited in the queue, in seconds.
+    #[must_use]
+    pub fn waiting_time(&self) -> Tally {
+        self.waiting_time.clone()
+    }
+}
+
+// The queues of a simulation, whatever their item type.
+pub(crate) trait Consumers {
+    // Forget the removed entities waiting for items and hand the items reserved for them to other waiting
+    // entities, returning those with the removed entity each replaces. The simulation activates them.
+    fn withdraw(&self, removed: &[Key]) -> Vec<(Key, Key)>;
+}
+
+impl<T> Consumers for SimQueue<T> {
+    fn withdraw(&self, removed: &[Key]) -> Vec<(Key, Key)> {
+        let mut inner = self.inner.borrow_mut();
+        let Inner { items, consumers, .. } = &mut *inner;
+        consumers.retain(|consumer| !removed.contains(&consumer.key));
+        let mut handed = Vec::new();
+        for item in items {
+            let Some(previous) = item.reserved.filter(|key| removed.contains(key)) else {
+                continue;
+            };
+            item.reserved = reserve(consumers, &item.item);
+            handed.extend(item.reserved.map(|key| (key, previous)));
+        }
+        handed
+    }
+}
+
+impl<T> Statistic for SimQueue<T> {
+    /// Reset the length and waiting time statistics, the maximum length restarts from the current length.
+    fn reset(&self) {
+        self.length.reset();
+        self.waiting_time.reset();
+        let mut inner = self.inner.borrow_mut();
+        inner.max_len = inner.items.len();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{process, Action, EntityState, GenBoxed, Simulation};
+
+    fn producer(jobs: SimQueue<u32>) -> GenBoxed<()> {
+        let mut next = 0;
+        let mut held = true;
+        process(move |_| {
+            if held {
+                if next == 3 {
+                    return None;
+                }
+                held = false;
+                let job = next;
+                next += 1;
+                if let Some(consumer) = jobs.push(job) {
+                    return Some(Action::ActivateOne(consumer));
+                }
+            }
+            held = true;
+            Some(Action::Hold(Duration::from_secs(1)))
+        })
+    }
+
+    fn consumer(jobs: SimQueue<u32>, done: Rc<RefCell<Vec<u32>>>) -> GenBoxed<()> {
+        let mut started = false;
+        process(move |_| {
+            if !started {
+                started = true;
+                return Some(Action::Hold(Duration::from_millis(1500)));
+            }
+            match jobs.take() {
+                Some(job) => {
+                    done.borrow_mut().push(job);
+                    Some(Action::Hold(Duration::from_secs(2)))
+                }
+                None => Some(Action::Passivate),
+            }
+        })
+    }
+
+    #[test]
+    fn statistics_are_tracked() {
+        let mut simulation = Simulation::default();
+        let jobs = simulation.add_queue("jobs");
+        let done = Rc::new(RefCell::new(Vec::new()));
+        let producer = simulation.add_generator(producer(jobs.clone()));
+        let consumer = simulation.add_generator(consumer(jobs.clone(), Rc::clone(&done)));
+        simulation.schedule_now(producer);
+        simulation.schedule_now(consumer);
+        simulation.run_until_empty();
+
+        // Jobs pushed at 0, 1 and 2 are taken at 1.5, 3.5 and 5.5, then the consumer waits from 7.5 on.
+        assert_eq!(vec![0, 1, 2], *done.borrow());
+        assert_eq!(Duration::from_millis(7500), simulation.time());
+        assert_eq!(2, jobs.max_len());
+        assert_eq!(Some(2.5), jobs.waiting_time().mean());
+        // Length 1 during [0, 1), 2 during [1, 1.5), 1 during [1.5, 2), 2 during [2, 3.5) and 1 during [3.5, 5.5).
+        assert_eq!(1.0, jobs.length().time_average());
+        assert_eq!(1, jobs.consumers());
+    }
+
+    type JobFilter = Option<fn(&u32) -> bool>;
+
+    // Starts waiting at `start`, then takes one job accepted by `filter`, or any job without one.
+    fn machine(jobs: SimQueue<u32>, start: u64, filter: JobFilter, taken: Rc<Cell<Option<u32>>>) -> GenBoxed<()> {
+        let mut started = false;
+        process(move |_| {
+            if !started {
+                started = true;
+                return Some(Action::Hold(Duration::from_millis(start)));
+            }
+            let job = match filter {
+                Some(filter) => jobs.take_matching(filter),
+                None => jobs.take(),
+            };
+            match job {
+                Some(job) => {
+                    taken.set(Some(job));
+                    None
+                }
+                None => Some(Action::Passivate),
+            }
+        })
+    }
+
+    #[test]
+    fn jobs_go_to_the_longest_waiting_machine_accepting_them() {
+        let mut simulation = Simulation::default();
+        let jobs = simulation.add_queue("jobs");
+        let filters: [JobFilter; 3] = [Some(|job| job % 2 == 0), Some(|job| job % 2 == 1), None];
+        let taken: Vec<Rc<Cell<Option<u32>>>> = filters.iter().map(|_| Rc::default()).collect();
+        for (start, (filter, taken)) in filters.into_iter().zip(&taken).enumerate() {
+            let machine = simulation.add_generator(machine(jobs.clone(), start as u64, filter, Rc::clone(taken)));
+            simulation.schedule_now(machine);
+        }
+        let pushed = jobs.clone();
+        let mut arrivals = [1, 3, 2].into_iter();
+        let mut started = false;
+        let producer = simulation.add_generator(process(move |_| {
+            if !started {
+                started = true;
+                return Some(Action::Hold(Duration::from_secs(1)));
+            }
+            for job in arrivals.by_ref() {
+                if let Some(consumer) = pushed.push(job) {
+                    return Some(Action::ActivateOne(consumer));
+                }
+            }
+            None
+        }));
+        simulation.schedule_now(producer);
+        simulation.run_until_empty();
+
+        // Each job goes to the machine waiting the longest among those accepting it, 3 skips the even machine.
+        let taken: Vec<_> = taken.iter().map(|taken| taken.get()).collect();
+        assert_eq!(vec![Some(2), Some(1), Some(3)], taken);
+        assert!(jobs.is_empty());
+        assert_eq!(0, jobs.consumers());
+    }
+
+    #[test]
+    fn items_reserved_for_terminated_consumers_go_to_the_next_one() {
+        let mut simulation = Simulation::default();
+        let jobs = simulation.add_queue("jobs");
+        let taken: Vec<Rc<Cell<Option<u32>>>> = (0..3).map(|_| Rc::default()).collect();
+        // The first two wait for any job, the last one for jobs that never come.
+        let filters: [JobFilter; 3] = [None, None, Some(|job| *job > 100)];
+        let machines: Vec<Key> = filters
+            .into_iter()
+            .zip(&taken)
+            .enumerate()
+            .map(|(start, (filter, taken))| {
+                let machine = simulation.add_generator(machine(jobs.clone(), start as u64, filter, Rc::clone(taken)));
+                simulation.schedule_now(machine);
+                machine
+            })
+            .collect();
+        let pushed = jobs.clone();
+        let mut step = 0;
+        let producer = simulation.add_generator(process(move |_| {
+            step += 1;
+            match step {
+                1 => Some(Action::Hold(Duration::from_secs(5))),
+                // The job is reserved for the first machine, terminated before it takes it.
+                2 => pushed.push(7).map(Action::Terminate),
+                _ => None,
+            }
+        }));
+        simulation.schedule_now(producer);
+        simulation.run_until_empty();
+
+        assert_eq!(Some(EntityState::Completed), simulation.entity_state(machines[0]));
+        assert_eq!(vec![None, Some(7), None], taken.iter().map(|taken| taken.get()).collect::<Vec<_>>());
+        assert!(jobs.is_empty());
+        assert_eq!(1, jobs.consumers());
+
+        simulation.kill(machines[2]);
+        assert_eq!(0, jobs.consumers());
+    }
+}
@@ -0,0 +1,146 @@
+use std::thread;
+use std::time::Instant;
+
+use crate::{RunStatus, SimTime, Simulation, StepContext};
+
+/// Drives a [`Simulation`] so that simulated time tracks wall-clock time.
+///
+/// Before executing each event the runner sleeps until the wall-clock time corresponding to it,
+/// scaled by a speed factor (a factor of `10.0` runs ten simulated seconds per real second).
+#[derive(Debug, Clone, Copy)]
+pub struct RealTimeRunner {
+    speed: f64,
+}
+
+impl Default for RealTimeRunner {
+    fn default() -> Self {
+        Self { speed: 1.0 }
+    }
+}
+
+impl RealTimeRunner {
+    /// Create a runner advancing `speed` simulated seconds per real second.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `speed` is not a positive finite number.
+    #[must_use]
+    pub fn new(speed: f64) -> Self {
+        assert!(
+            speed.is_finite() && speed > 0.0,
+            "The speed factor must be positive, got {}",
+            speed
+        );
+        Self { speed }
+    }
+
+    #[must_use]
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    /// Advance `simulation` in real time until `limit` is reached or no more events are left.
+    ///
+    /// Unlike [`Simulation::run_with_limit_with`] events scheduled after `limit` are never executed,
+    /// as that would require waiting past the limit. The run stops at the
+    /// [breakpoints](Simulation::set_breakpoint) of the simulation like the other run methods, without waiting for
+    /// the event a breakpoint stops before.
+    /// Each entity is resumed with the value returned by `provider`.
+    pub fn run_with_limit_with<R, F>(&self, simulation: &mut Simulation<R>, limit: impl SimTime, mut provider: F) -> RunStatus
+    where
+        R: 'static,
+        F: FnMut(&StepContext) -> R,
+    {
+        let limit = limit.to_duration();
+        let wall_start = Instant::now();
+        let simulation_start = simulation.time();
+        loop {
+            let next_time = match simulation.next_event_time() {
+                Some(time) if time > limit => return RunStatus::LimitReached,
+                Some(time) => time,
+                None => return simulation.exhausted_status(),
+            };
+            if let Some(breakpoint) = simulation.breakpoint_before() {
+                return RunStatus::Breakpoint(breakpoint);
+            }
+            let deadline = wall_start + next_time.saturating_sub(simulation_start).div_f64(self.speed);
+            let now = Instant::now();
+            if deadline > now {
+                thread::sleep(deadline - now);
+            }
+            let hit = match simulation.step_with_provider(&mut provider) {
+                Ok(step) if !step.should_continue() => return simulation.exhausted_status(),
+                Err(error) => return RunStatus::Failed(error),
+                Ok(step) => simulation.breakpoint_after(&step),
+            };
+            if simulation.take_pause_request() {
+                return RunStatus::Paused;
+            }
+            if let Some(breakpoint) = hit {
+                return RunStatus::Breakpoint(breakpoint);
+            }
+        }
+    }
+
+    /// Advance `simulation` in real time until `limit` is reached or no more events are left.
+    pub fn run_with_limit(&self, simulation: &mut Simulation<()>, limit: impl SimTime) -> RunStatus {
+        self.run_with_limit_with(simulation, limit, |_| ())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{process, Action, Breakpoint};
+
+    // Ticks every second until time 4.
+    fn ticking() -> Simulation<()> {
+        let mut simulation = Simulation::default();
+        let clock = simulation.clock();
+        let key = simulation.add_generator(process(move |_| {
+            (clock.time() < Duration::from_secs(4)).then_some(Action::Hold(Duration::from_secs(1)))
+        }));
+        simulation.schedule_now(key);
+        simulation
+    }
+
+    #[test]
+    fn simulated_time_tracks_scaled_wall_clock_time() {
+        let mut simulation = ticking();
+        let runner = RealTimeRunner::new(100.0);
+        let start = Instant::now();
+        let status = runner.run_with_limit(&mut simulation, Duration::from_millis(2500));
+        assert_eq!(RunStatus::LimitReached, status);
+        // The event at 3 isn't waited for.
+        assert_eq!(Duration::from_secs(2), simulation.time());
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        let status = runner.run_with_limit(&mut simulation, Duration::from_secs(10));
+        assert_eq!(RunStatus::Exhausted, status);
+        assert_eq!(Duration::from_secs(4), simulation.time());
+    }
+
+    #[test]
+    fn runs_stop_at_breakpoints() {
+        let mut simulation = ticking();
+        simulation.set_breakpoint(Breakpoint::time(Duration::from_secs(2)));
+        let runner = RealTimeRunner::new(1000.0);
+        let status = runner.run_with_limit(&mut simulation, Duration::from_secs(10));
+        assert_eq!(RunStatus::Breakpoint(Breakpoint::time(Duration::from_secs(2))), status);
+        assert_eq!(Duration::from_secs(1), simulation.time());
+        assert_eq!(Some(Duration::from_secs(2)), simulation.next_event_time());
+
+        let hold = Breakpoint::action(&Action::Hold(Duration::ZERO));
+        simulation.set_breakpoint(hold);
+        let status = runner.run_with_limit(&mut simulation, Duration::from_secs(10));
+        assert_eq!(RunStatus::Breakpoint(hold), status);
+        assert_eq!(Duration::from_secs(2), simulation.time());
+    }
+
+    #[test]
+    #[should_panic(expected = "The speed factor must be positive")]
+    fn speeds_must_be_positive() {
+        let _ = RealTimeRunner::new(0.0);
+    }
+}
@@ -0,0 +1,297 @@
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::scheduler::ClockRef;
+use crate::stats::Tally;
+use crate::{Action, Key, Resume};
+
+/// A request accepted from a [`Rendezvous`], to be answered with [`Rendezvous::reply`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Call<Q> {
+    pub caller: Key,
+    pub request: Q,
+    /// Simulation time at which the call was made.
+    pub called_at: Duration,
+}
+
+// An entity to resume now that a rendezvous can serve it.
+pub(crate) enum Wake<R> {
+    // A callee waiting for calls, activated by the caller.
+    Callee { key: Key, by: Key },
+    // A caller, resumed with its reply.
+    Caller { key: Key, reply: R },
+}
+
+// The rendezvous of a simulation, whatever their request type.
+pub(crate) trait Wakes<R> {
+    // Takes the wakes of the entities `passive` accepts, keeping the others until they passivate.
+    fn take_wakes(&self, passive: &dyn Fn(Key) -> bool) -> Vec<Wake<R>>;
+
+    // Forget the calls, and the wakes of the removed entities.
+    fn clear(&self, removed: &[Key]);
+}
+
+struct Inner<Q, A> {
+    // Calls not accepted yet, in arrival order for every callee.
+    calls: HashMap<Key, VecDeque<Call<Q>>>,
+    // Callers with a call in progress and when they called.
+    pending: HashMap<Key, Duration>,
+    // Entities passivated until a call arrives.
+    callees: HashSet<Key>,
+    wakes: Vec<Wake<A>>,
+}
+
+/// Synchronous calls between entities: the caller is passivated until the callee replies, and resumed with
+/// `Resume::Message(reply)`.
+///
+/// Created with [`Simulation::add_rendezvous`](crate::Simulation::add_rendezvous) on a simulation resumed with
+/// [`Resume`] values. It can be cloned and moved into generators:
+///
+/// ```ignore
+/// // Client
+/// let Resume::Message(price) = yield rpc.call(server, Query::Price(item)) else {
+///     unreachable!("callers are resumed with the reply");
+/// };
+///
+/// // Server
+/// loop {
+///     let Some(call) = rpc.accept() else {
+///         yield Action::Passivate;
+///         continue;
+///     };
+///     yield Action::Hold(lookup_time);
+///     rpc.reply(call.caller, price_of(call.request));
+/// }
+/// ```
+///
+/// A callee waiting for calls is activated by the next one, with `Resume::Activated { by: caller }`.
+pub struct Rendezvous<Q, A> {
+    inner: Rc<RefCell<Inner<Q, A>>>,
+    response_time: Tally,
+    clock: ClockRef,
+    current: Rc<Cell<Option<Key>>>,
+}
+
+impl<Q, A> Clone for Rendezvous<Q, A> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Rc::clone(&self.inner),
+            response_time: self.response_time.clone(),
+            clock: self.clock.clone(),
+            current: Rc::clone(&self.current),
+        }
+    }
+}
+
+impl<Q, A> Rendezvous<Q, A> {
+    pub(crate) fn new(name: &str, clock: ClockRef, current: Rc<Cell<Option<Key>>>) -> Self {
+        let inner = Inner {
+            calls: HashMap::new(),
+            pending: HashMap::new(),
+            callees: HashSet::new(),
+            wakes: Vec::new(),
+        };
+        Self {
+            inner: Rc::new(RefCell::new(inner)),
+            response_time: Tally::new(format!("{} response time", name)),
+            clock,
+            current,
+        }
+    }
+
+    fn current(&self) -> Key {
+        self.current
+            .get()
+            .expect("rendezvous can only be used from inside an entity")
+    }
+
+    /// Call `callee` with `request` from the entity currently being executed, returning the action it must yield
+    /// to wait for the reply. It's resumed with `Resume::Message(reply)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of an entity or if the entity is already waiting for a reply.
+    #[must_use = "the caller must yield the returned action to wait for the reply"]
+    pub fn call(&self, callee: Key, request: Q) -> Action {
+        let caller = self.current();
+        let called_at = self.clock.time();
+        let mut inner = self.inner.borrow_mut();
+        assert!(
+            inner.pending.insert(caller, called_at).is_none(),
+            "Entity ID = {} made a call before getting the reply to the previous one",
+            caller.id()
+        );
+        inner.calls.entry(callee).or_default().push_back(Call {
+            caller,
+            request,
+            called_at,
+        });
+        if inner.callees.remove(&callee) {
+            inner.wakes.push(Wake::Callee { key: callee, by: caller });
+        }
+        Action::Passivate
+    }
+
+    /// Accept the oldest call made to the entity currently being executed.
+    ///
+    /// When `None` is returned the entity must yield `Action::Passivate`, it's activated by the next call.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of an entity.
+    pub fn accept(&self) -> Option<Call<Q>> {
+        let callee = self.current();
+        let mut inner = self.inner.borrow_mut();
+        let call = inner.calls.get_mut(&callee).and_then(VecDeque::pop_front);
+        if call.is_none() {
+            inner.callees.insert(callee);
+        }
+        call
+    }
+
+    /// Answer the call of `caller` with `reply`, which resumes it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `caller` has no call in progress.
+    pub fn reply(&self, caller: Key, reply: A) {
+        let mut inner = self.inner.borrow_mut();
+        let called_at = inner
+            .pending
+            .remove(&caller)
+            .unwrap_or_else(|| panic!("Replied to entity ID = {} which has no call in progress", caller.id()));
+        self.response_time
+            .record_duration(self.clock.time().saturating_sub(called_at));
+        inner.wakes.push(Wake::Caller { key: caller, reply });
+    }
+
+    /// Returns the number of calls made to `callee` and not accepted yet.
+    #[must_use]
+    pub fn calls_waiting(&self, callee: Key) -> usize {
+        self.inner.borrow().calls.get(&callee).map_or(0, VecDeque::len)
+    }
+
+    /// Returns the statistics of the time from calls to their replies, in seconds.
+    #[must_use]
+    pub fn response_time(&self) -> Tally {
+        self.response_time.clone()
+    }
+}
+
+impl<Q, A> Wakes<Resume<A>> for Rendezvous<Q, A> {
+    fn take_wakes(&self, passive: &dyn Fn(Key) -> bool) -> Vec<Wake<Resume<A>>> {
+        let mut inner = self.inner.borrow_mut();
+        let (ready, waiting) = inner.wakes.drain(..).partition(|wake| match *wake {
+            Wake::Callee { key, .. } | Wake::Caller { key, .. } => passive(key),
+        });
+        inner.wakes = waiting;
+        ready
+            .into_iter()
+            .map(|wake| match wake {
+                Wake::Callee { key, by } => Wake::Callee { key, by },
+                Wake::Caller { key, reply } => Wake::Caller {
+                    key,
+                    reply: Resume::Message(reply),
+                },
+            })
+            .collect()
+    }
+
+    fn clear(&self, removed: &[Key]) {
+        let mut inner = self.inner.borrow_mut();
+        for key in removed {
+            inner.calls.remove(key);
+            inner.pending.remove(key);
+            inner.callees.remove(key);
+        }
+        for calls in inner.calls.values_mut() {
+            calls.retain(|call| !removed.contains(&call.caller));
+        }
+        inner.wakes.retain(|wake| match *wake {
+            Wake::Callee { key, .. } | Wake::Caller { key, .. } => !removed.contains(&key),
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{process, GenBoxed, Simulation};
+
+    type Rpc = Rendezvous<u32, u32>;
+
+    fn server(rpc: Rpc) -> GenBoxed<Resume<u32>> {
+        let mut serving: Option<Call<u32>> = None;
+        process(move |_| {
+            // Resumed after serving a call.
+            if let Some(call) = serving.take() {
+                rpc.reply(call.caller, call.request * 10);
+            }
+            match rpc.accept() {
+                Some(call) => {
+                    serving = Some(call);
+                    Some(Action::Hold(Duration::from_secs(2)))
+                }
+                None => Some(Action::Passivate),
+            }
+        })
+    }
+
+    type Answers = Rc<RefCell<Vec<(Resume<u32>, Duration)>>>;
+
+    fn client(rpc: Rpc, server: Key, at: u64, request: u32, answers: Answers) -> GenBoxed<Resume<u32>> {
+        let mut step = 0;
+        process(move |resume| {
+            step += 1;
+            match step {
+                1 => Some(Action::Hold(Duration::from_secs(at))),
+                2 => Some(rpc.call(server, request)),
+                _ => {
+                    answers.borrow_mut().push((resume, rpc.clock.time()));
+                    None
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn callers_are_resumed_with_the_reply() {
+        let mut simulation = Simulation::default();
+        let rpc: Rpc = simulation.add_rendezvous("rpc");
+        let answers = Rc::new(RefCell::new(Vec::new()));
+        let server = simulation.add_generator(server(rpc.clone()));
+        simulation.schedule_now(server);
+        for (at, request) in [(0, 1), (1, 2)] {
+            let client = simulation.add_generator(client(rpc.clone(), server, at, request, Rc::clone(&answers)));
+            simulation.schedule_now(client);
+        }
+        simulation.run_until_empty_with(Resume::from_context);
+
+        // The second call waits for the server to finish the first one.
+        let expected = vec![
+            (Resume::Message(10), Duration::from_secs(2)),
+            (Resume::Message(20), Duration::from_secs(4)),
+        ];
+        assert_eq!(expected, *answers.borrow());
+        assert_eq!(Some(2.5), rpc.response_time().mean());
+        assert_eq!(0, rpc.calls_waiting(server));
+    }
+
+    #[test]
+    fn removed_callers_are_forgotten() {
+        let mut simulation = Simulation::default();
+        let rpc: Rpc = simulation.add_rendezvous("rpc");
+        let answers = Rc::new(RefCell::new(Vec::new()));
+        let server = simulation.add_generator(process(|_| Some(Action::Passivate)));
+        let client = simulation.add_generator(client(rpc.clone(), server, 0, 1, Rc::clone(&answers)));
+        simulation.schedule_now(client);
+        simulation.run_until_empty_with(Resume::from_context);
+        assert_eq!(1, rpc.calls_waiting(server));
+
+        simulation.kill(client);
+        assert_eq!(0, rpc.calls_waiting(server));
+        assert!(answers.borrow().is_empty());
+    }
+}
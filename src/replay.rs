@@ -0,0 +1,50 @@
+use std::fmt;
+
+use crate::trace::{TraceEvent, TraceEventKind};
+
+/// The first step where a replayed model didn't match the trace it was replayed from,
+/// returned boxed by [`Simulation::replay`](crate::Simulation::replay).
+#[derive(Debug, Clone)]
+pub struct Divergence {
+    /// Index of the step, starting at zero.
+    pub step: usize,
+    pub expected: TraceEvent,
+    /// What the model did instead, `None` if it ran out of events.
+    pub found: Option<TraceEvent>,
+}
+
+impl Divergence {
+    // Compare a replayed step with the recorded one, ignoring watched values.
+    pub(crate) fn check(step: usize, expected: &TraceEvent, found: Option<TraceEvent>) -> Result<(), Box<Self>> {
+        match &found {
+            Some(found) if found.time == expected.time && found.key == expected.key && found.kind == expected.kind => {
+                Ok(())
+            }
+            _ => Err(Box::new(Self {
+                step,
+                expected: expected.clone(),
+                found,
+            })),
+        }
+    }
+}
+
+fn describe(event: &TraceEvent) -> String {
+    let what = match &event.kind {
+        TraceEventKind::Yielded(action) => format!("{:?}", action),
+        TraceEventKind::Completed => "Completed".to_owned(),
+    };
+    format!("entity {} {} at {:?}", event.key.id(), what, event.time)
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "step {} diverged, expected {}", self.step, describe(&self.expected))?;
+        match &self.found {
+            Some(found) => write!(f, " but found {}", describe(found)),
+            None => write!(f, " but no events were left"),
+        }
+    }
+}
+
+impl std::error::Error for Divergence {}
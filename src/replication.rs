@@ -0,0 +1,380 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::csv;
+use crate::stats::Summary;
+
+// SplitMix64, so consecutive replications get unrelated seeds.
+fn mix(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// A single run of an experiment, passed to the model closure by [`Replicator::run`].
+#[derive(Debug, Clone)]
+pub struct Replication {
+    index: usize,
+    seed: u64,
+    metrics: BTreeMap<String, f64>,
+}
+
+impl Replication {
+    /// Returns the position of the replication, starting from zero.
+    #[must_use]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the seed the model should use for this replication.
+    #[must_use]
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Record the value of an output metric, replacing any previous value under `name`.
+    pub fn record(&mut self, name: impl Into<String>, value: f64) {
+        self.metrics.insert(name.into(), value);
+    }
+
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<f64> {
+        self.metrics.get(name).copied()
+    }
+}
+
+/// Runs a model several times with distinct seeds.
+///
+/// ```ignore
+/// let results = Replicator::new(30).base_seed(7).run(|replication| {
+///     let mut simulation = Simulation::default();
+///     simulation.set_seed(replication.seed());
+///     // Build the model and run it.
+///     replication.record("mean wait", wait.mean().unwrap_or(0.0));
+/// });
+/// let wait = results.summary("mean wait").unwrap();
+/// println!("{} ± {}", wait.mean, wait.half_width);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Replicator {
+    replications: usize,
+    base_seed: u64,
+}
+
+impl Replicator {
+    #[must_use]
+    pub fn new(replications: usize) -> Self {
+        Self {
+            replications,
+            base_seed: 0,
+        }
+    }
+
+    /// Derive the seeds of the replications from `seed`, the same base seed always gives the same seeds.
+    #[must_use]
+    pub fn base_seed(mut self, seed: u64) -> Self {
+        self.base_seed = seed;
+        self
+    }
+
+    /// Returns the seed of the replication at `index`.
+    #[must_use]
+    pub fn seed(&self, index: usize) -> u64 {
+        mix(self.base_seed ^ mix(index as u64))
+    }
+
+    /// Call `model` once per replication, in order, and collect the metrics it records.
+    pub fn run(&self, mut model: impl FnMut(&mut Replication)) -> Replications {
+        let runs = (0..self.replications)
+            .map(|index| {
+                let mut replication = self.replication(index);
+                model(&mut replication);
+                replication
+            })
+            .collect();
+        Replications { runs }
+    }
+
+    /// Same as [`Replicator::run`], running the replications on `threads` threads.
+    ///
+    /// `model` builds and runs its own [`Simulation`](crate::Simulation) on the thread it's called on, so the
+    /// simulation itself never crosses threads. Seeds and results are the same as with [`Replicator::run`],
+    /// in replication order. Zero threads uses as many threads as CPUs.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the thread pool can't be created.
+    #[cfg(feature = "rayon")]
+    pub fn run_parallel(&self, threads: usize, model: impl Fn(&mut Replication) + Sync) -> Replications {
+        use rayon::prelude::*;
+
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .expect("failed to create the thread pool");
+        let runs = pool.install(|| {
+            (0..self.replications)
+                .into_par_iter()
+                .map(|index| {
+                    let mut replication = self.replication(index);
+                    model(&mut replication);
+                    replication
+                })
+                .collect()
+        });
+        Replications { runs }
+    }
+
+    fn replication(&self, index: usize) -> Replication {
+        Replication {
+            index,
+            seed: self.seed(index),
+            metrics: BTreeMap::new(),
+        }
+    }
+}
+
+/// Which scenario a [`Comparison`] favours.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// The confidence interval of the differences is above zero, the first scenario has larger values.
+    FirstLarger,
+    /// The confidence interval of the differences is below zero, the second scenario has larger values.
+    SecondLarger,
+    /// The confidence interval contains zero.
+    NotSignificant,
+}
+
+/// The paired comparison of a metric between two scenarios, returned by [`Replications::compare`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub metric: String,
+    /// The differences, first minus second scenario, of the replications run with the same seed, in the order of
+    /// the first scenario.
+    pub differences: Vec<f64>,
+    /// The summary of the differences with its confidence interval, `None` without any pair.
+    pub summary: Option<Summary>,
+    pub verdict: Verdict,
+}
+
+/// The results of [`Replicator::run`].
+#[derive(Debug, Clone)]
+pub struct Replications {
+    runs: Vec<Replication>,
+}
+
+impl Replications {
+    #[must_use]
+    pub fn runs(&self) -> &[Replication] {
+        &self.runs
+    }
+
+    /// Returns the name of every metric recorded by any replication, sorted.
+    #[must_use]
+    pub fn metrics(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .runs
+            .iter()
+            .flat_map(|run| run.metrics.keys().cloned())
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Returns the values of a metric, in replication order, skipping replications that didn't record it.
+    #[must_use]
+    pub fn values(&self, name: &str) -> Vec<f64> {
+        self.runs.iter().filter_map(|run| run.get(name)).collect()
+    }
+
+    /// Returns the summary of a metric across replications with a 95% confidence interval,
+    /// `None` if no replication recorded it.
+    #[must_use]
+    pub fn summary(&self, name: &str) -> Option<Summary> {
+        self.summary_at(name, 0.95)
+    }
+
+    /// Same as [`Replications::summary`] with a confidence level in `(0, 1)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `confidence` isn't in `(0, 1)`.
+    #[must_use]
+    pub fn summary_at(&self, name: &str, confidence: f64) -> Option<Summary> {
+        Summary::from_values(&self.values(name), confidence)
+    }
+
+    /// Compare every metric recorded by both `self` and `other` with a paired-t confidence interval at 95%, see
+    /// [`Replications::compare_at`].
+    #[must_use]
+    pub fn compare(&self, other: &Replications) -> Vec<Comparison> {
+        self.compare_at(other, 0.95)
+    }
+
+    /// Compare every metric recorded by both `self` and `other` with a paired-t confidence interval at
+    /// `confidence`, the replications being paired by seed.
+    ///
+    /// Running both scenarios with the same [`Replicator`] gives every pair common random numbers, which
+    /// usually narrows the interval a lot compared to independent runs:
+    ///
+    /// ```ignore
+    /// let replicator = Replicator::new(20).base_seed(1);
+    /// let two_tellers = replicator.run(|replication| bank(2, replication));
+    /// let three_tellers = replicator.run(|replication| bank(3, replication));
+    /// for comparison in two_tellers.compare(&three_tellers) {
+    ///     println!("{}: {:?} {:?}", comparison.metric, comparison.summary, comparison.verdict);
+    /// }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `confidence` isn't in `(0, 1)`.
+    #[must_use]
+    pub fn compare_at(&self, other: &Replications, confidence: f64) -> Vec<Comparison> {
+        let theirs: BTreeMap<u64, &Replication> = other.runs.iter().map(|run| (run.seed, run)).collect();
+        let metrics = other.metrics();
+        self.metrics()
+            .into_iter()
+            .filter(|metric| metrics.contains(metric))
+            .map(|metric| {
+                let differences: Vec<f64> = self
+                    .runs
+                    .iter()
+                    .filter_map(|run| Some(run.get(&metric)? - theirs.get(&run.seed)?.get(&metric)?))
+                    .collect();
+                let summary = Summary::from_values(&differences, confidence);
+                let verdict = match summary.map(|summary| summary.interval()) {
+                    Some((low, _)) if low > 0.0 => Verdict::FirstLarger,
+                    Some((_, high)) if high < 0.0 => Verdict::SecondLarger,
+                    _ => Verdict::NotSignificant,
+                };
+                Comparison {
+                    metric,
+                    differences,
+                    summary,
+                    verdict,
+                }
+            })
+            .collect()
+    }
+
+    /// Returns one row per replication with its seed and a column per metric.
+    #[must_use]
+    pub fn to_csv(&self) -> String {
+        let metrics = self.metrics();
+        let mut output = String::from("replication,seed");
+        for name in &metrics {
+            output.push(',');
+            output.push_str(&csv::field(name));
+        }
+        output.push('\n');
+        for run in &self.runs {
+            let _ = write!(output, "{},{}", run.index, run.seed);
+            for name in &metrics {
+                output.push(',');
+                if let Some(value) = run.get(name) {
+                    let _ = write!(output, "{}", value);
+                }
+            }
+            output.push('\n');
+        }
+        output
+    }
+
+    /// Write the results as CSV to `path`, see [`Replications::to_csv`].
+    pub fn write_csv(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_csv())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{process, Action, GenBoxed, Simulation};
+
+    fn sleeper(seconds: u64) -> GenBoxed<()> {
+        let mut hold = Some(Action::Hold(Duration::from_secs(seconds)));
+        process(move |_| hold.take())
+    }
+
+    #[test]
+    fn metrics_are_aggregated_over_replications() {
+        let replicator = Replicator::new(4).base_seed(11);
+        let results = replicator.run(|replication| {
+            let mut simulation = Simulation::default();
+            simulation.set_seed(replication.seed());
+            let key = simulation.add_generator(sleeper(replication.index() as u64 + 1));
+            simulation.schedule_now(key);
+            simulation.run_until_empty();
+            replication.record("end", simulation.time().as_secs_f64());
+        });
+
+        assert_eq!(vec![1.0, 2.0, 3.0, 4.0], results.values("end"));
+        let summary = results.summary("end").unwrap();
+        assert_eq!(2.5, summary.mean);
+        assert!((summary.half_width - 3.182 * (5.0f64 / 3.0).sqrt() / 2.0).abs() < 1e-3);
+        assert_eq!(None, results.summary("missing"));
+
+        let seeds: Vec<u64> = results.runs().iter().map(Replication::seed).collect();
+        assert_eq!(seeds, (0..4).map(|index| replicator.seed(index)).collect::<Vec<_>>());
+        assert!(seeds.windows(2).all(|pair| pair[0] != pair[1]));
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_replications_match_sequential_ones() {
+        let replicator = Replicator::new(8).base_seed(3);
+        let model = |replication: &mut Replication| {
+            let mut simulation = Simulation::default();
+            simulation.set_seed(replication.seed());
+            let key = simulation.add_generator(sleeper(replication.seed() % 100));
+            simulation.schedule_now(key);
+            simulation.run_until_empty();
+            replication.record("end", simulation.time().as_secs_f64());
+        };
+
+        let parallel = replicator.run_parallel(4, model);
+        assert_eq!(replicator.run(model).values("end"), parallel.values("end"));
+        assert!(parallel.runs().iter().enumerate().all(|(index, run)| run.index() == index));
+    }
+
+    #[test]
+    fn scenarios_are_compared_pairwise() {
+        let replicator = Replicator::new(10).base_seed(5);
+        // The noise depends on the seed only, as common random numbers would make it.
+        let scenario = |offset: f64| {
+            move |replication: &mut Replication| {
+                let noise = (replication.seed() % 1000) as f64;
+                replication.record("wait", noise + offset);
+                let sign = if replication.index().is_multiple_of(2) { 1.0 } else { -1.0 };
+                replication.record("served", 100.0 + sign * offset);
+                if offset == 0.0 {
+                    replication.record("only first", 1.0);
+                }
+            }
+        };
+        let first = replicator.run(scenario(0.0));
+        let second = replicator.run(scenario(2.0));
+
+        let comparisons = first.compare(&second);
+        let metrics: Vec<&str> = comparisons.iter().map(|comparison| comparison.metric.as_str()).collect();
+        assert_eq!(vec!["served", "wait"], metrics);
+        let wait = &comparisons[1];
+        assert_eq!(vec![-2.0; 10], wait.differences);
+        assert_eq!(Verdict::SecondLarger, wait.verdict);
+        assert_eq!(0.0, wait.summary.unwrap().half_width);
+        assert_eq!(Verdict::NotSignificant, comparisons[0].verdict);
+        assert_eq!(Verdict::FirstLarger, second.compare(&first)[1].verdict);
+
+        // Replications are paired by seed, those of other seeds are left out.
+        let others = Replicator::new(10).base_seed(6).run(scenario(2.0));
+        assert!(first.compare(&others).iter().all(|comparison| comparison.summary.is_none()));
+    }
+}
@@ -0,0 +1,562 @@
+use std::cell::{Cell, RefCell};
+use std::cmp::Reverse;
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::retry::Attempt;
+use crate::scheduler::ClockRef;
+use crate::stats::{Accumulate, Statistic, Tally};
+use crate::{Action, Key};
+
+/// Parameters of a request made to a [`Resource`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Request {
+    class: u32,
+    priority: i32,
+}
+
+impl Request {
+    /// Set the class of the request, a model defined category (e.g. a job type).
+    #[must_use]
+    pub fn class(mut self, class: u32) -> Self {
+        self.class = class;
+        self
+    }
+
+    /// Set the priority of the request. Requests with higher priority are served first.
+    #[must_use]
+    pub fn priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+}
+
+/// A request waiting in the queue of a [`Resource`], as returned by [`Resource::queue_snapshot`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueuedRequest {
+    pub key: Key,
+    pub class: u32,
+    pub priority: i32,
+    /// Simulation time at which the request was made.
+    pub requested_at: Duration,
+    /// Time the request has been waiting so far.
+    pub waited: Duration,
+}
+
+type DispatchRule = Box<dyn FnMut(&[QueuedRequest], Duration) -> usize>;
+
+#[derive(Debug)]
+struct Waiting {
+    key: Key,
+    request: Request,
+    requested_at: Duration,
+    // Holding for a timeout instead of being passive.
+    timed: bool,
+}
+
+#[derive(Debug)]
+struct Inner {
+    name: String,
+    capacity: usize,
+    users: Vec<Key>,
+    // In arrival order.
+    queue: Vec<Waiting>,
+}
+
+impl Inner {
+    // Position in `queue` of the request to serve next: highest priority first, then first come first served.
+    fn next_position(&self) -> Option<usize> {
+        self.queue
+            .iter()
+            .enumerate()
+            .max_by(|(a_position, a), (b_position, b)| {
+                a.request
+                    .priority
+                    .cmp(&b.request.priority)
+                    .then(b_position.cmp(a_position))
+            })
+            .map(|(position, _)| position)
+    }
+
+    fn snapshot(&self, now: Duration) -> Vec<QueuedRequest> {
+        let mut snapshot: Vec<QueuedRequest> = self
+            .queue
+            .iter()
+            .map(|waiting| QueuedRequest {
+                key: waiting.key,
+                class: waiting.request.class,
+                priority: waiting.request.priority,
+                requested_at: waiting.requested_at,
+                waited: now.saturating_sub(waiting.requested_at),
+            })
+            .collect();
+        // The sort is stable so requests with the same priority keep their arrival order.
+        snapshot.sort_by_key(|request| Reverse(request.priority));
+        snapshot
+    }
+}
+
+/// The statistics attached to every [`Resource`], returned by [`Resource::stats`].
+///
+/// They are reset at the end of the warm-up like the other statistics of the simulation.
+#[derive(Debug, Clone)]
+pub struct ResourceStats {
+    capacity: usize,
+    busy: Accumulate,
+    queue_length: Accumulate,
+    waiting_time: Tally,
+}
+
+impl ResourceStats {
+    /// Returns the time-weighted statistics of the number of units held.
+    #[must_use]
+    pub fn busy(&self) -> Accumulate {
+        self.busy.clone()
+    }
+
+    /// Returns the time-weighted statistics of the number of requests waiting.
+    #[must_use]
+    pub fn queue_length(&self) -> Accumulate {
+        self.queue_length.clone()
+    }
+
+    /// Returns the statistics of the time granted requests waited, in seconds, zero for those granted at once.
+    /// Requests that timed out aren't included.
+    #[must_use]
+    pub fn waiting_time(&self) -> Tally {
+        self.waiting_time.clone()
+    }
+
+    /// Returns the average fraction of the units held, up to the current simulation time, zero for a resource
+    /// without units.
+    #[must_use]
+    pub fn utilization(&self) -> f64 {
+        if self.capacity == 0 {
+            return 0.0;
+        }
+        self.busy.time_average() / self.capacity as f64
+    }
+
+    /// Returns the time-weighted average number of requests waiting, up to the current simulation time.
+    #[must_use]
+    pub fn mean_queue_length(&self) -> f64 {
+        self.queue_length.time_average()
+    }
+}
+
+/// A resource with a limited number of units that entities request and release.
+///
+/// Created with [`Simulation::add_resource`](crate::Simulation::add_resource). It can be cloned and moved into generators:
+///
+/// ```ignore
+/// if !teller.request() {
+///     // No units are available, wait until a releasing entity activates us.
+///     yield Action::Passivate;
+/// }
+/// yield Action::Hold(service_time);
+/// if let Some(next) = teller.release() {
+///     yield Action::ActivateOne(next);
+/// }
+/// ```
+#[derive(Clone)]
+pub struct Resource {
+    inner: Rc<RefCell<Inner>>,
+    // Kept apart from `inner` so the rule can query the resource.
+    dispatch_rule: Rc<RefCell<Option<DispatchRule>>>,
+    clock: ClockRef,
+    current: Rc<Cell<Option<Key>>>,
+    stats: ResourceStats,
+}
+
+impl Resource {
+    pub(crate) fn new(name: String, capacity: usize, clock: ClockRef, current: Rc<Cell<Option<Key>>>) -> Self {
+        let stats = ResourceStats {
+            capacity,
+            busy: Accumulate::new(format!("{} busy", name), clock.clone(), 0.0),
+            queue_length: Accumulate::new(format!("{} queue length", name), clock.clone(), 0.0),
+            waiting_time: Tally::new(format!("{} waiting time", name)),
+        };
+        let inner = Inner {
+            name,
+            capacity,
+            users: Vec::new(),
+            queue: Vec::new(),
+        };
+        Self {
+            inner: Rc::new(RefCell::new(inner)),
+            dispatch_rule: Rc::default(),
+            clock,
+            current,
+            stats,
+        }
+    }
+
+    /// Request one unit for the entity currently being executed.
+    ///
+    /// Returns `true` if the unit was granted. Otherwise the entity is queued and must yield
+    /// `Action::Passivate`, it will be activated by the entity releasing the unit it's granted.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of an entity.
+    pub fn request(&self) -> bool {
+        self.request_with(Request::default())
+    }
+
+    /// Same as [`Resource::request`] with a class and priority.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of an entity.
+    pub fn request_with(&self, request: Request) -> bool {
+        self.enqueue(request, false)
+    }
+
+    /// Request one unit waiting at most `timeout` for it.
+    ///
+    /// Returns the actions the entity has to yield, after them [`Resource::finish_request`] tells
+    /// whether the unit was granted. Units granted to requests with a timeout must be handed over
+    /// with [`Resource::release_actions`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of an entity.
+    #[must_use]
+    pub fn request_timeout(&self, request: Request, timeout: Duration) -> Vec<Action> {
+        if self.enqueue(request, true) {
+            Vec::new()
+        } else {
+            vec![Action::Hold(timeout)]
+        }
+    }
+
+    /// Returns whether the entity currently being executed was granted the unit it requested
+    /// with [`Resource::request_timeout`], withdrawing the request if it timed out.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of an entity.
+    pub fn finish_request(&self) -> bool {
+        let key = self
+            .current
+            .get()
+            .expect("resources can only be requested from inside an entity");
+        let mut inner = self.inner.borrow_mut();
+        if inner.users.contains(&key) {
+            return true;
+        }
+        inner.queue.retain(|waiting| waiting.key != key);
+        self.stats.queue_length.set(inner.queue.len() as f64);
+        false
+    }
+
+    /// Returns an [`Attempt`] that requests one unit, to be used with [`retry`](crate::retry).
+    #[must_use]
+    pub fn attempt(&self, request: Request) -> ResourceAttempt {
+        ResourceAttempt {
+            resource: self.clone(),
+            request,
+        }
+    }
+
+    fn enqueue(&self, request: Request, timed: bool) -> bool {
+        let key = self
+            .current
+            .get()
+            .expect("resources can only be requested from inside an entity");
+        let mut inner = self.inner.borrow_mut();
+        if inner.users.len() < inner.capacity && inner.queue.is_empty() {
+            inner.users.push(key);
+            self.stats.busy.set(inner.users.len() as f64);
+            self.stats.waiting_time.record(0.0);
+            true
+        } else {
+            let requested_at = self.clock.time();
+            inner.queue.push(Waiting {
+                key,
+                request,
+                requested_at,
+                timed,
+            });
+            self.stats.queue_length.set(inner.queue.len() as f64);
+            false
+        }
+    }
+
+    /// Release the unit held by the entity currently being executed.
+    ///
+    /// If a request was waiting the unit is granted to it and its key is returned,
+    /// the releasing entity must then activate it with `Action::ActivateOne`.
+    /// If requests with a timeout may be waiting use [`Resource::release_actions`] instead.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the entity currently being executed doesn't hold a unit.
+    pub fn release(&self) -> Option<Key> {
+        self.hand_over().map(|waiting| waiting.key)
+    }
+
+    /// Same as [`Resource::release`], returning the actions the releasing entity has to yield, in order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the entity currently being executed doesn't hold a unit.
+    #[must_use]
+    pub fn release_actions(&self) -> Vec<Action> {
+        match self.hand_over() {
+            // The request is holding for its timeout, interrupt it before activating it.
+            Some(waiting) if waiting.timed => vec![Action::Cancel(waiting.key), Action::ActivateOne(waiting.key)],
+            Some(waiting) => vec![Action::ActivateOne(waiting.key)],
+            None => Vec::new(),
+        }
+    }
+
+    // Free the unit of the current entity and grant it to the next request, returning it.
+    fn hand_over(&self) -> Option<Waiting> {
+        let key = self
+            .current
+            .get()
+            .expect("resources can only be released from inside an entity");
+        self.hand_over_from(key)
+    }
+
+    // Free a unit of `key` and grant it to the next request, returning it.
+    fn hand_over_from(&self, key: Key) -> Option<Waiting> {
+        let position = {
+            let mut inner = self.inner.borrow_mut();
+            let position = inner
+                .users
+                .iter()
+                .position(|&user| user == key)
+                .unwrap_or_else(|| panic!("Entity ID = {} released `{}` without holding it", key.id(), inner.name));
+            inner.users.swap_remove(position);
+            if inner.queue.is_empty() {
+                self.stats.busy.set(inner.users.len() as f64);
+                return None;
+            }
+            inner.next_position()
+        };
+
+        let position = match self.dispatch_rule.borrow_mut().as_mut() {
+            Some(rule) => {
+                let now = self.clock.time();
+                let snapshot = self.inner.borrow().snapshot(now);
+                let chosen = rule(&snapshot, now);
+                let chosen = snapshot.get(chosen).unwrap_or_else(|| {
+                    panic!("The dispatch rule chose request {} out of {}", chosen, snapshot.len())
+                });
+                self.inner
+                    .borrow()
+                    .queue
+                    .iter()
+                    .position(|waiting| waiting.key == chosen.key)
+            }
+            None => position,
+        };
+
+        let mut inner = self.inner.borrow_mut();
+        let next = position.map(|position| inner.queue.remove(position));
+        inner.users.extend(next.as_ref().map(|waiting| waiting.key));
+        if let Some(waiting) = &next {
+            let waited = self.clock.time().saturating_sub(waiting.requested_at);
+            self.stats.waiting_time.record_duration(waited);
+        }
+        self.stats.busy.set(inner.users.len() as f64);
+        self.stats.queue_length.set(inner.queue.len() as f64);
+        next
+    }
+
+    // Withdraw the requests of the removed entities, so no unit is granted to them.
+    pub(crate) fn withdraw(&self, removed: &[Key]) {
+        let mut inner = self.inner.borrow_mut();
+        inner.queue.retain(|waiting| !removed.contains(&waiting.key));
+        self.stats.queue_length.set(inner.queue.len() as f64);
+    }
+
+    // Release every unit held by the removed entity `key`, returning the entities granted them, which the
+    // simulation activates in its place.
+    pub(crate) fn release_all(&self, key: Key) -> Vec<Key> {
+        let mut granted = Vec::new();
+        while self.inner.borrow().users.contains(&key) {
+            granted.extend(self.hand_over_from(key).map(|waiting| waiting.key));
+        }
+        granted
+    }
+
+    /// Set the rule used to choose which waiting request is served whenever a unit is released.
+    ///
+    /// `rule` receives the [queue snapshot](Resource::queue_snapshot) and the current time
+    /// and returns the index in the snapshot of the request to serve.
+    /// Without a rule requests are served by priority and then in arrival order.
+    ///
+    /// # Panics
+    ///
+    /// Releasing a unit panics if the rule returns an index out of the snapshot bounds.
+    pub fn set_dispatch_rule(&self, rule: impl FnMut(&[QueuedRequest], Duration) -> usize + 'static) {
+        *self.dispatch_rule.borrow_mut() = Some(Box::new(rule));
+    }
+
+    /// Remove the dispatch rule, going back to serving by priority and arrival order.
+    pub fn clear_dispatch_rule(&self) {
+        self.dispatch_rule.borrow_mut().take();
+    }
+
+    /// Returns the requests currently waiting, in the order they would be served.
+    #[must_use]
+    pub fn queue_snapshot(&self) -> Vec<QueuedRequest> {
+        self.inner.borrow().snapshot(self.clock.time())
+    }
+
+    #[must_use]
+    pub fn name(&self) -> String {
+        self.inner.borrow().name.clone()
+    }
+
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.inner.borrow().capacity
+    }
+
+    /// Returns the number of units currently held.
+    #[must_use]
+    pub fn in_use(&self) -> usize {
+        self.inner.borrow().users.len()
+    }
+
+    /// Returns the number of requests waiting.
+    #[must_use]
+    pub fn queue_len(&self) -> usize {
+        self.inner.borrow().queue.len()
+    }
+
+    /// Returns the utilization, queue length and waiting time statistics of the resource.
+    #[must_use]
+    pub fn stats(&self) -> ResourceStats {
+        self.stats.clone()
+    }
+}
+
+impl Statistic for Resource {
+    fn reset(&self) {
+        self.stats.busy.reset();
+        self.stats.queue_length.reset();
+        self.stats.waiting_time.reset();
+    }
+}
+
+/// An [`Attempt`] to get a unit of a [`Resource`], created with [`Resource::attempt`].
+#[derive(Clone)]
+pub struct ResourceAttempt {
+    resource: Resource,
+    request: Request,
+}
+
+impl Attempt for ResourceAttempt {
+    fn start(&mut self, timeout: Duration) -> Vec<Action> {
+        self.resource.request_timeout(self.request, timeout)
+    }
+
+    fn finish(&mut self) -> bool {
+        self.resource.finish_request()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{process, Action, GenBoxed, Simulation};
+
+    fn customer(teller: Resource, priority: i32, arrival: u64, served: Rc<RefCell<Vec<i32>>>) -> GenBoxed<()> {
+        let mut step = 0;
+        process(move |_| {
+            step += 1;
+            match step {
+                1 => Some(Action::Hold(Duration::from_secs(arrival))),
+                2 if !teller.request_with(Request::default().priority(priority)) => Some(Action::Passivate),
+                // Granted at once, or activated once the unit was handed over.
+                2 | 3 => {
+                    step = 3;
+                    served.borrow_mut().push(priority);
+                    Some(Action::Hold(Duration::from_secs(10)))
+                }
+                4 => teller.release().map(Action::ActivateOne),
+                _ => None,
+            }
+        })
+    }
+
+    #[test]
+    fn requests_are_served_by_priority_then_arrival() {
+        let mut simulation = Simulation::default();
+        let teller = simulation.add_resource("teller", 1);
+        let served = Rc::new(RefCell::new(Vec::new()));
+        for (priority, arrival) in [(0, 0), (1, 1), (5, 2), (1, 3)] {
+            let key = simulation.add_generator(customer(teller.clone(), priority, arrival, Rc::clone(&served)));
+            simulation.schedule_now(key);
+        }
+
+        simulation.run_until(|simulation| simulation.time() >= Duration::from_secs(3));
+        let snapshot = teller.queue_snapshot();
+        assert_eq!(vec![5, 1, 1], snapshot.iter().map(|request| request.priority).collect::<Vec<_>>());
+        assert_eq!(Duration::from_secs(2), snapshot[1].waited);
+        assert_eq!(1, teller.in_use());
+
+        simulation.run_until_empty();
+        assert_eq!(vec![0, 5, 1, 1], *served.borrow());
+        assert_eq!(0, teller.queue_len());
+        assert_eq!(0, teller.in_use());
+    }
+
+    #[test]
+    fn dispatch_rule_chooses_the_next_request() {
+        let mut simulation = Simulation::default();
+        let teller = simulation.add_resource("teller", 1);
+        // Serve the lowest priority first, the opposite of the default discipline.
+        teller.set_dispatch_rule(|queue, _| {
+            let lowest = queue.iter().map(|request| request.priority).min().unwrap();
+            queue.iter().position(|request| request.priority == lowest).unwrap()
+        });
+        let served = Rc::new(RefCell::new(Vec::new()));
+        for (priority, arrival) in [(0, 0), (1, 1), (5, 2), (3, 3)] {
+            let key = simulation.add_generator(customer(teller.clone(), priority, arrival, Rc::clone(&served)));
+            simulation.schedule_now(key);
+        }
+
+        simulation.run_until_empty();
+        assert_eq!(vec![0, 1, 3, 5], *served.borrow());
+    }
+
+    #[test]
+    fn resources_collect_utilization_statistics() {
+        let mut simulation = Simulation::default();
+        let teller = simulation.add_resource("teller", 1);
+        let served = Rc::new(RefCell::new(Vec::new()));
+        for (priority, arrival) in [(0, 0), (1, 1), (5, 2), (1, 3)] {
+            let key = simulation.add_generator(customer(teller.clone(), priority, arrival, Rc::clone(&served)));
+            simulation.schedule_now(key);
+        }
+        simulation.run_until_empty();
+
+        let stats = teller.stats();
+        assert_eq!(Duration::from_secs(40), simulation.time());
+        assert!((stats.utilization() - 1.0).abs() < 1e-9);
+        // Three requests queue at 1, 2 and 3s and leave at 10, 20 and 30s.
+        assert!((stats.mean_queue_length() - 54.0 / 40.0).abs() < 1e-9);
+        assert_eq!(4, stats.waiting_time().count());
+        assert_eq!(Some(13.5), stats.waiting_time().mean());
+        assert_eq!(3.0, stats.queue_length().max());
+        assert_eq!("teller busy", stats.busy().name());
+    }
+
+    #[test]
+    fn resources_without_units_are_never_utilized() {
+        let mut simulation = Simulation::default();
+        let closed = simulation.add_resource("closed", 0);
+        let key = simulation.add_generator(process(|_| Some(Action::Passivate)));
+        simulation.schedule(Duration::from_secs(5), key);
+        simulation.run_until_empty();
+
+        assert_eq!(Duration::from_secs(5), simulation.time());
+        assert_eq!(0.0, closed.stats().utilization());
+    }
+}
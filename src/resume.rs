@@ -0,0 +1,151 @@
+use std::time::Duration;
+
+use crate::{Key, StepContext};
+
+/// Why an entity is resumed, the value generators of a `Simulation<Resume<T>>` are resumed with.
+///
+/// The simulation keeps track of the cause of every wake-up, turned into the resume value by the
+/// [`Resume::from_context`] provider, so entities can react to it:
+///
+/// ```ignore
+/// let mut simulation: Simulation<Resume<Order>> = Simulation::default();
+/// let machine = simulation.add_generator(Box::new(|_| loop {
+///     match yield Action::Hold(Duration::from_secs(10)) {
+///         Resume::Interrupted { reason: Interrupt::Preempted { remaining, .. } } => { ... }
+///         Resume::Message(order) => { ... }
+///         _ => { ... }
+///     }
+/// }));
+/// simulation.run_until_empty_with(Resume::from_context);
+/// ```
+///
+/// Custom resume values can be derived from it with [`StepContext::resume`](crate::StepContext::resume).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resume<T = ()> {
+    /// Its hold elapsed, or it was scheduled by the model, e.g. when it starts.
+    Timer,
+    /// Activated from passive by another entity.
+    Activated { by: Key },
+    /// Its hold was interrupted.
+    Interrupted { reason: Interrupt },
+    /// Sent with [`Simulation::send_message`](crate::Simulation::send_message).
+    Message(T),
+    /// The run is over, the last resume given by [`Simulation::finalize_with`](crate::Simulation::finalize_with)
+    /// to flush statistics or release resources.
+    SimulationEnding,
+}
+
+/// Why the hold of an entity was interrupted, see [`Resume::Interrupted`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interrupt {
+    /// By [`Action::Preempt`](crate::Action::Preempt), with what was left of the hold.
+    Preempted { by: Key, remaining: Duration },
+}
+
+impl<T> Resume<T> {
+    /// Returns why the entity of `context` is resumed, the provider to run a `Simulation<Resume<T>>` with.
+    #[must_use]
+    pub fn from_context(context: &StepContext) -> Self {
+        match context.resume() {
+            Resume::Timer => Resume::Timer,
+            Resume::Message(()) => unreachable!("entities are resumed with the messages sent to them"),
+            Resume::Activated { by } => Resume::Activated { by },
+            Resume::Interrupted { reason } => Resume::Interrupted { reason },
+            Resume::SimulationEnding => Resume::SimulationEnding,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::{process, Action, GenBoxed, Simulation};
+
+    type Log = Rc<RefCell<Vec<Resume<u32>>>>;
+
+    // Logs the value it's resumed with every time, starting with the first one.
+    fn worker(log: Log) -> GenBoxed<Resume<u32>> {
+        let mut actions = vec![
+            Action::Hold(Duration::from_secs(1)),
+            Action::Passivate,
+            Action::Hold(Duration::from_secs(5)),
+            Action::Passivate,
+        ]
+        .into_iter();
+        process(move |resume| {
+            log.borrow_mut().push(resume);
+            actions.next()
+        })
+    }
+
+    fn boss(worker: Key) -> GenBoxed<Resume<u32>> {
+        let mut actions = vec![
+            Action::Hold(Duration::from_secs(2)),
+            Action::ActivateOne(worker),
+            Action::Hold(Duration::from_secs(1)),
+            Action::Preempt(worker),
+        ]
+        .into_iter();
+        process(move |_| actions.next())
+    }
+
+    #[test]
+    fn entities_are_resumed_with_the_cause() {
+        let mut simulation: Simulation<Resume<u32>> = Simulation::default();
+        let log = Log::default();
+        let worker = simulation.add_generator(worker(Rc::clone(&log)));
+        let boss = simulation.add_generator(boss(worker));
+        simulation.schedule_now(worker);
+        simulation.schedule_now(boss);
+        simulation.run_until_empty_with(Resume::from_context);
+
+        assert_eq!(Err(8), simulation.send_message(boss, 8));
+        assert_eq!(Ok(()), simulation.send_message(worker, 7));
+        simulation.run_until_empty_with(Resume::from_context);
+
+        let interrupt = Interrupt::Preempted {
+            by: boss,
+            remaining: Duration::from_secs(4),
+        };
+        let expected = vec![
+            Resume::Timer,
+            Resume::Timer,
+            Resume::Activated { by: boss },
+            Resume::Interrupted { reason: interrupt },
+            Resume::Message(7),
+        ];
+        assert_eq!(expected, *log.borrow());
+    }
+
+    #[test]
+    fn registered_providers_see_the_last_action() {
+        let mut simulation: Simulation<(Option<Action>, Duration)> = Simulation::default();
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let entity = simulation.add_generator({
+            let log = Rc::clone(&log);
+            let mut actions =
+                vec![Action::Hold(Duration::from_secs(3)), Action::HoldWithPriority(Duration::from_secs(1), 2)]
+                    .into_iter();
+            process(move |resume| {
+                log.borrow_mut().push(resume);
+                actions.next()
+            })
+        });
+        simulation.schedule_now(entity);
+        simulation.set_resume_provider(move |key, action, time| {
+            assert_eq!(entity, key);
+            (action.cloned(), time)
+        });
+        simulation.run_until_empty_with(simulation.resume_provider());
+
+        let expected = vec![
+            (None, Duration::ZERO),
+            (Some(Action::Hold(Duration::from_secs(3))), Duration::from_secs(3)),
+            (Some(Action::HoldWithPriority(Duration::from_secs(1), 2)), Duration::from_secs(4)),
+        ];
+        assert_eq!(expected, *log.borrow());
+    }
+}
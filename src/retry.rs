@@ -0,0 +1,258 @@
+use std::time::Duration;
+
+use crate::Action;
+
+/// A blocking operation bounded by a timeout, such as requesting a [`Resource`](crate::Resource).
+pub trait Attempt {
+    /// Start the attempt, giving up after `timeout`.
+    ///
+    /// Returns the actions the entity has to yield, in order.
+    fn start(&mut self, timeout: Duration) -> Vec<Action>;
+
+    /// Called once the actions returned by [`Attempt::start`] were yielded, returns whether the attempt succeeded.
+    fn finish(&mut self) -> bool;
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Backoff {
+    Fixed(Duration),
+    Exponential { initial: Duration, factor: f64 },
+}
+
+/// How many times and how often a [`Retry`] attempts an operation.
+pub struct RetryPolicy {
+    timeout: Duration,
+    backoff: Backoff,
+    max_attempts: Option<usize>,
+    max_delay: Option<Duration>,
+    jitter: Option<Box<dyn FnMut() -> f64>>,
+}
+
+impl RetryPolicy {
+    /// Wait `timeout` on every attempt and `delay` between attempts.
+    #[must_use]
+    pub fn fixed(timeout: Duration, delay: Duration) -> Self {
+        Self::new(timeout, Backoff::Fixed(delay))
+    }
+
+    /// Wait `timeout` on every attempt, the delay between attempts starts at `initial`
+    /// and is multiplied by `factor` after every failure.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `factor` is less than one.
+    #[must_use]
+    pub fn exponential(timeout: Duration, initial: Duration, factor: f64) -> Self {
+        assert!(factor >= 1.0, "Invalid backoff factor: {}", factor);
+        Self::new(timeout, Backoff::Exponential { initial, factor })
+    }
+
+    fn new(timeout: Duration, backoff: Backoff) -> Self {
+        Self {
+            timeout,
+            backoff,
+            max_attempts: None,
+            max_delay: None,
+            jitter: None,
+        }
+    }
+
+    /// Give up after `attempts` failed attempts.
+    #[must_use]
+    pub fn max_attempts(mut self, attempts: usize) -> Self {
+        self.max_attempts = Some(attempts);
+        self
+    }
+
+    /// Never wait more than `delay` between attempts.
+    #[must_use]
+    pub fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = Some(delay);
+        self
+    }
+
+    /// Scale every delay by a sample of `uniform`, which must return values in `[0, 1)`,
+    /// so entities failing together don't retry together. Usually `move || rng.next_f64()`
+    /// with the [`SimRng`](crate::SimRng) of the simulation.
+    #[must_use]
+    pub fn jitter(mut self, uniform: impl FnMut() -> f64 + 'static) -> Self {
+        self.jitter = Some(Box::new(uniform));
+        self
+    }
+
+    // Delay after the given number of failed attempts.
+    fn delay(&mut self, failures: usize) -> Duration {
+        let mut delay = match self.backoff {
+            Backoff::Fixed(delay) => delay,
+            Backoff::Exponential { initial, factor } => {
+                let exponent = i32::try_from(failures.saturating_sub(1)).unwrap_or(i32::MAX);
+                Duration::try_from_secs_f64(initial.as_secs_f64() * factor.powi(exponent)).unwrap_or(Duration::MAX)
+            }
+        };
+        if let Some(max_delay) = self.max_delay {
+            delay = delay.min(max_delay);
+        }
+        if let Some(uniform) = self.jitter.as_mut() {
+            delay = delay.mul_f64(uniform());
+        }
+        delay
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    Ready,
+    Attempting,
+    BackingOff,
+    Done { succeeded: bool },
+}
+
+/// Repeats an [`Attempt`] according to a [`RetryPolicy`] until it succeeds or the policy gives up.
+///
+/// ```ignore
+/// let mut send = retry(link.attempt(Request::default()), RetryPolicy::fixed(timeout, delay).max_attempts(3));
+/// while let Some(actions) = send.next_actions() {
+///     for action in actions {
+///         yield action;
+///     }
+/// }
+/// if send.succeeded() {
+///     // The link is ours.
+/// }
+/// ```
+pub struct Retry<A> {
+    attempt: A,
+    policy: RetryPolicy,
+    attempts: usize,
+    phase: Phase,
+}
+
+/// Retry `attempt` according to `policy`.
+pub fn retry<A: Attempt>(attempt: A, policy: RetryPolicy) -> Retry<A> {
+    Retry {
+        attempt,
+        policy,
+        attempts: 0,
+        phase: Phase::Ready,
+    }
+}
+
+impl<A: Attempt> Retry<A> {
+    /// Returns the actions the entity has to yield next, `None` once the operation succeeded or the policy gave up.
+    ///
+    /// Must be called from inside the entity performing the operation.
+    pub fn next_actions(&mut self) -> Option<Vec<Action>> {
+        match self.phase {
+            Phase::Ready | Phase::BackingOff => {
+                self.attempts += 1;
+                self.phase = Phase::Attempting;
+                Some(self.attempt.start(self.policy.timeout))
+            }
+            Phase::Attempting => {
+                if self.attempt.finish() {
+                    self.phase = Phase::Done { succeeded: true };
+                    return None;
+                }
+                if matches!(self.policy.max_attempts, Some(max) if self.attempts >= max) {
+                    self.phase = Phase::Done { succeeded: false };
+                    return None;
+                }
+                self.phase = Phase::BackingOff;
+                Some(vec![Action::Hold(self.policy.delay(self.attempts))])
+            }
+            Phase::Done { .. } => None,
+        }
+    }
+
+    /// Returns the number of attempts started so far.
+    #[must_use]
+    pub fn attempts(&self) -> usize {
+        self.attempts
+    }
+
+    /// Returns `true` if the last attempt succeeded.
+    #[must_use]
+    pub fn succeeded(&self) -> bool {
+        self.phase == Phase::Done { succeeded: true }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::{process, GenBoxed, Request, Resource, Simulation};
+
+    fn holder(link: Resource) -> GenBoxed<()> {
+        let mut step = 0;
+        let mut releasing = Vec::new().into_iter();
+        process(move |_| {
+            step += 1;
+            match step {
+                1 => {
+                    assert!(link.request());
+                    Some(Action::Hold(Duration::from_secs(10)))
+                }
+                2 => {
+                    releasing = link.release_actions().into_iter();
+                    releasing.next()
+                }
+                _ => releasing.next(),
+            }
+        })
+    }
+
+    fn sender(link: Resource, max_attempts: usize, outcome: Rc<RefCell<Option<(usize, bool, Duration)>>>, clock: crate::scheduler::ClockRef) -> GenBoxed<()> {
+        let policy = RetryPolicy::fixed(Duration::from_secs(2), Duration::from_secs(1)).max_attempts(max_attempts);
+        let mut send = retry(link.attempt(Request::default()), policy);
+        let mut pending = Vec::new().into_iter();
+        let mut finished = false;
+        process(move |_| loop {
+            if let Some(action) = pending.next() {
+                return Some(action);
+            }
+            if finished {
+                return None;
+            }
+            match send.next_actions() {
+                Some(actions) => pending = actions.into_iter(),
+                None => {
+                    *outcome.borrow_mut() = Some((send.attempts(), send.succeeded(), clock.time()));
+                    finished = true;
+                    if send.succeeded() {
+                        pending = link.release_actions().into_iter();
+                    }
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn attempts_are_retried_until_the_resource_is_free() {
+        // Attempts time out at 2, 5 and 8 seconds, the fourth one is granted when the link is released at 10.
+        for (max_attempts, expected) in [(3, (3, false, 8)), (5, (4, true, 10))] {
+            let mut simulation = Simulation::default();
+            let link = simulation.add_resource("link", 1);
+            let outcome = Rc::new(RefCell::new(None));
+            let holder = simulation.add_generator(holder(link.clone()));
+            let sender = simulation.add_generator(sender(link.clone(), max_attempts, Rc::clone(&outcome), simulation.clock()));
+            simulation.schedule_now(holder);
+            simulation.schedule_now(sender);
+            simulation.run_until_empty();
+
+            let (attempts, succeeded, time) = expected;
+            assert_eq!(Some((attempts, succeeded, Duration::from_secs(time))), *outcome.borrow());
+            assert_eq!(0, link.queue_len());
+            assert_eq!(0, link.in_use());
+        }
+    }
+
+    #[test]
+    fn exponential_delays_are_capped() {
+        let mut policy = RetryPolicy::exponential(Duration::ZERO, Duration::from_secs(1), 2.0).max_delay(Duration::from_secs(5));
+        let delays: Vec<u64> = (1..=5).map(|failures| policy.delay(failures).as_secs()).collect();
+        assert_eq!(vec![1, 2, 4, 5, 5], delays);
+    }
+}
@@ -0,0 +1,175 @@
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+// SplitMix64, used to expand a seed into the generator state.
+fn split_mix(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Pseudo random number generator of a simulation (xoshiro256**).
+///
+/// Obtained with [`Simulation::rng`](crate::Simulation::rng). Clones share the same state, so it can be
+/// moved into generators and the whole run stays reproducible from a single seed.
+#[derive(Debug, Clone)]
+pub struct SimRng {
+    state: Rc<Cell<[u64; 4]>>,
+}
+
+impl SimRng {
+    #[must_use]
+    pub fn new(seed: u64) -> Self {
+        let rng = Self {
+            state: Rc::new(Cell::new([0; 4])),
+        };
+        rng.reseed(seed);
+        rng
+    }
+
+    /// Restart the sequence from `seed`, affecting every clone.
+    pub fn reseed(&self, seed: u64) {
+        let mut seed = seed;
+        let state = [
+            split_mix(&mut seed),
+            split_mix(&mut seed),
+            split_mix(&mut seed),
+            split_mix(&mut seed),
+        ];
+        self.state.set(state);
+    }
+
+    pub fn next_u64(&self) -> u64 {
+        let mut s = self.state.get();
+        let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+        self.state.set(s);
+        result
+    }
+
+    /// Returns a number uniformly distributed in `[0, 1)`.
+    pub fn next_f64(&self) -> f64 {
+        // The 53 high bits fill the mantissa.
+        (self.next_u64() >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+    }
+
+    /// Returns a number uniformly distributed in `[low, high)`.
+    pub fn uniform(&self, low: f64, high: f64) -> f64 {
+        low + (high - low) * self.next_f64()
+    }
+}
+
+impl Default for SimRng {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+// Seed of a named stream, derived from the name so it doesn't depend on the order streams are declared.
+fn stream_seed(seed: u64, name: &str) -> u64 {
+    // FNV-1a, stable across platforms and compiler versions unlike the standard hasher.
+    let hash = name.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    });
+    let mut state = seed ^ hash;
+    split_mix(&mut state)
+}
+
+/// Registry of independent, named random number streams, e.g. `"arrivals"` and `"service"`.
+///
+/// Passed to [`Simulation::with_streams`](crate::Simulation::with_streams). Every stream is seeded from
+/// the registry seed and its name, so scenarios built with the same registry configuration draw the
+/// same numbers from each stream (common random numbers) even if they use streams differently.
+#[derive(Debug)]
+pub struct RngStreams {
+    seed: u64,
+    streams: BTreeMap<String, SimRng>,
+}
+
+impl RngStreams {
+    #[must_use]
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            streams: BTreeMap::new(),
+        }
+    }
+
+    /// Declare a stream named `name`.
+    #[must_use]
+    pub fn with_stream(mut self, name: impl Into<String>) -> Self {
+        let name = name.into();
+        let rng = SimRng::new(stream_seed(self.seed, &name));
+        self.streams.insert(name, rng);
+        self
+    }
+
+    #[must_use]
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Returns the names of the declared streams, sorted.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.streams.keys().map(String::as_str)
+    }
+
+    /// Returns the stream named `name`, `None` if it wasn't declared.
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<SimRng> {
+        self.streams.get(name).cloned()
+    }
+
+    /// Restart every stream from a new registry seed.
+    pub fn reseed(&mut self, seed: u64) {
+        self.seed = seed;
+        for (name, rng) in &self.streams {
+            rng.reseed(stream_seed(seed, name));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sequences_are_reproducible() {
+        let rng = SimRng::new(42);
+        let clone = rng.clone();
+        let first: Vec<u64> = (0..4).map(|_| rng.next_u64()).collect();
+        // Clones share the state.
+        assert_ne!(first[3], clone.next_u64());
+
+        rng.reseed(42);
+        assert_eq!(first, (0..4).map(|_| clone.next_u64()).collect::<Vec<_>>());
+        assert_ne!(first[0], SimRng::new(43).next_u64());
+        assert!((0..1000).map(|_| rng.next_f64()).all(|u| (0.0..1.0).contains(&u)));
+    }
+
+    #[test]
+    fn streams_do_not_depend_on_declaration_order() {
+        let first = RngStreams::new(9).with_stream("arrivals").with_stream("service");
+        let second = RngStreams::new(9).with_stream("service").with_stream("arrivals");
+        // Drawing from one stream doesn't affect the others.
+        first.get("arrivals").unwrap().next_u64();
+        assert_eq!(
+            first.get("service").unwrap().next_u64(),
+            second.get("service").unwrap().next_u64()
+        );
+        assert_ne!(
+            RngStreams::new(9).with_stream("arrivals").get("arrivals").unwrap().next_u64(),
+            RngStreams::new(9).with_stream("service").get("service").unwrap().next_u64()
+        );
+        assert!(first.get("breakdowns").is_none());
+    }
+}
@@ -0,0 +1,235 @@
+use std::cell::RefCell;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::csv;
+use crate::scheduler::ClockRef;
+use crate::state::{SharedState, StateKey};
+use crate::stats::Accumulate;
+use crate::{process, Action, GenBoxed};
+
+type Probe = dyn Fn() -> Option<f64>;
+
+struct Column {
+    name: String,
+    read: Box<Probe>,
+    // One value per sample time, `None` before the column was added or while the value was missing.
+    values: Vec<Option<f64>>,
+}
+
+struct Inner {
+    interval: Duration,
+    until: Option<Duration>,
+    stopped: bool,
+    times: Vec<Duration>,
+    columns: Vec<Column>,
+}
+
+/// A process reading values every `interval` of simulated time, starting now, and keeping the samples.
+///
+/// Created with [`Simulation::add_sampler`](crate::Simulation::add_sampler), or by
+/// [`SimulationBuilder::sample_every`](crate::SimulationBuilder::sample_every) and then returned by
+/// [`Simulation::sampler`](crate::Simulation::sampler). Unlike [`State::observe`](crate::State::observe) the model
+/// doesn't have to change how it updates its values:
+///
+/// ```ignore
+/// let sampler = simulation.add_sampler("sampler", Duration::from_secs(60));
+/// sampler
+///     .state("queue length", waiting, |queue: &Vec<Key>| queue.len() as f64)
+///     .monitor(&teller.stats().busy());
+/// simulation.run_with_limit(Duration::from_secs(3600));
+/// sampler.write_csv("samples.csv")?;
+/// ```
+///
+/// The sampler is scheduled until it's [stopped](Sampler::stop) or [`Sampler::until`] is reached, runs without a
+/// time limit never end otherwise.
+#[derive(Clone)]
+pub struct Sampler {
+    inner: Rc<RefCell<Inner>>,
+    state: SharedState,
+}
+
+impl Sampler {
+    pub(crate) fn new(interval: Duration, state: SharedState) -> Self {
+        Self {
+            inner: Rc::new(RefCell::new(Inner {
+                interval,
+                until: None,
+                stopped: false,
+                times: Vec::new(),
+                columns: Vec::new(),
+            })),
+            state,
+        }
+    }
+
+    pub(crate) fn generator<R: 'static>(&self, clock: ClockRef) -> GenBoxed<R> {
+        let inner = Rc::clone(&self.inner);
+        process(move |_| {
+            let mut inner = inner.borrow_mut();
+            if inner.stopped {
+                return None;
+            }
+            let now = clock.time();
+            inner.times.push(now);
+            for column in &mut inner.columns {
+                let value = (column.read)();
+                column.values.push(value);
+            }
+            let interval = inner.interval;
+            if inner.until.is_some_and(|until| now + interval > until) {
+                return None;
+            }
+            Some(Action::Hold(interval))
+        })
+    }
+
+    /// Sample the [`State`](crate::State) variable `key`, converted by `sample`, as the column `name`. Samples taken
+    /// while the variable is removed are missing.
+    pub fn state<V: 'static>(
+        &self,
+        name: impl Into<String>,
+        key: StateKey<V>,
+        sample: impl Fn(&V) -> f64 + 'static,
+    ) -> &Self {
+        let state = self.state.clone();
+        self.probe_with(name.into(), move || state.with(|state| state.get(key).map(&sample)))
+    }
+
+    /// Sample the level of `monitor` as a column named after it.
+    pub fn monitor(&self, monitor: &Accumulate) -> &Self {
+        let monitor = monitor.clone();
+        self.probe_with(monitor.name(), move || Some(monitor.level()))
+    }
+
+    /// Sample the value returned by `read` as the column `name`.
+    pub fn probe(&self, name: impl Into<String>, read: impl Fn() -> f64 + 'static) -> &Self {
+        self.probe_with(name.into(), move || Some(read()))
+    }
+
+    fn probe_with(&self, name: String, read: impl Fn() -> Option<f64> + 'static) -> &Self {
+        let mut inner = self.inner.borrow_mut();
+        let values = vec![None; inner.times.len()];
+        inner.columns.push(Column {
+            name,
+            read: Box::new(read),
+            values,
+        });
+        drop(inner);
+        self
+    }
+
+    /// Take the last sample at or before simulation time `time`.
+    pub fn until(&self, time: Duration) -> &Self {
+        self.inner.borrow_mut().until = Some(time);
+        self
+    }
+
+    /// Take no more samples, the process completes the next time it's resumed.
+    pub fn stop(&self) {
+        self.inner.borrow_mut().stopped = true;
+    }
+
+    #[must_use]
+    pub fn interval(&self) -> Duration {
+        self.inner.borrow().interval
+    }
+
+    /// Returns the times the samples were taken.
+    #[must_use]
+    pub fn times(&self) -> Vec<Duration> {
+        self.inner.borrow().times.clone()
+    }
+
+    /// Returns the names of the columns, in the order they were added.
+    #[must_use]
+    pub fn names(&self) -> Vec<String> {
+        self.inner.borrow().columns.iter().map(|column| column.name.clone()).collect()
+    }
+
+    /// Returns the time and the value of every sample of the column `name`, `None` if there's no such column.
+    #[must_use]
+    pub fn samples(&self, name: &str) -> Option<Vec<(Duration, f64)>> {
+        let inner = self.inner.borrow();
+        let column = inner.columns.iter().find(|column| column.name == name)?;
+        Some(
+            inner
+                .times
+                .iter()
+                .zip(&column.values)
+                .filter_map(|(&time, value)| value.map(|value| (time, value)))
+                .collect(),
+        )
+    }
+
+    /// Returns the samples as CSV, one row per sample time with the column `time`, in seconds, and a column per
+    /// value. Missing values are left empty.
+    #[must_use]
+    pub fn to_csv(&self) -> String {
+        let inner = self.inner.borrow();
+        let mut output = String::from("time");
+        for column in &inner.columns {
+            output.push(',');
+            output.push_str(&csv::field(&column.name));
+        }
+        output.push('\n');
+        for (index, time) in inner.times.iter().enumerate() {
+            let _ = write!(output, "{}", time.as_secs_f64());
+            for column in &inner.columns {
+                output.push(',');
+                if let Some(value) = column.values[index] {
+                    let _ = write!(output, "{}", value);
+                }
+            }
+            output.push('\n');
+        }
+        output
+    }
+
+    /// Write the samples as CSV to `path`, see [`Sampler::to_csv`].
+    pub fn write_csv(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_csv())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Simulation;
+
+    #[test]
+    fn samplers_read_values_periodically() {
+        let mut simulation = Simulation::builder()
+            .time_limit(Duration::from_secs(10))
+            .sample_every(Duration::from_secs(3))
+            .build();
+        let length = simulation.state().with_mut(|state| state.insert(0u32));
+        let busy = Accumulate::new("busy", simulation.clock(), 0.0);
+        let sampler = simulation.sampler().unwrap();
+        sampler.state("length", length, |&length| f64::from(length)).monitor(&busy);
+
+        let state = simulation.state();
+        let monitor = busy.clone();
+        let mut steps = 0;
+        let key = simulation.add_generator(process(move |_| {
+            steps += 1;
+            state.with_mut(|state| *state.get_mut(length).unwrap() = steps);
+            monitor.set(f64::from(steps % 2));
+            (steps < 4).then_some(Action::Hold(Duration::from_secs(2)))
+        }));
+        simulation.schedule(Duration::from_millis(1500), key);
+        simulation.run();
+
+        let seconds = |times: Vec<Duration>| times.iter().map(Duration::as_secs).collect::<Vec<_>>();
+        assert_eq!(vec![0, 3, 6, 9], seconds(sampler.times()));
+        let lengths: Vec<f64> = sampler.samples("length").unwrap().iter().map(|&(_, value)| value).collect();
+        assert_eq!(vec![0.0, 1.0, 3.0, 4.0], lengths);
+        assert_eq!(vec!["length".to_owned(), "busy".to_owned()], sampler.names());
+        assert_eq!("time,length,busy\n0,0,0\n3,1,1\n6,3,1\n9,4,0\n", sampler.to_csv());
+        assert_eq!(None, sampler.samples("utilization"));
+    }
+}
@@ -1,32 +1,57 @@
 use crate::keys::Key;
+use crate::time::SimTime;
 
 use std::cell::Cell;
 use std::cmp::{Ordering, Reverse};
-use std::collections::BinaryHeap;
+use std::collections::{BinaryHeap, HashMap};
+use std::fmt;
 use std::rc::Rc;
 use std::time::Duration;
 
+mod calendar_queue;
+
+pub use calendar_queue::CalendarQueue;
+
+/// A pending event: the entity to resume and the time to resume it at.
+///
+/// Events are ordered by time and, at the same time, by priority: the greatest entry is executed first.
 #[derive(Clone, Debug)]
 pub struct EventEntry {
     time: Reverse<Duration>,
     entity_key: Key,
+    priority: i32,
 }
 
 impl EventEntry {
-    pub(crate) fn new(time: Duration, entity_key: Key) -> Self {
+    pub fn new(time: Duration, entity_key: Key) -> Self {
+        Self::with_priority(time, entity_key, 0)
+    }
+
+    /// Create an event executed before the events at the same time with a lower `priority`.
+    pub fn with_priority(time: Duration, entity_key: Key, priority: i32) -> Self {
         Self {
             time: Reverse(time),
             entity_key,
+            priority,
         }
     }
+
     pub fn key(&self) -> Key {
         self.entity_key
     }
+
+    pub fn time(&self) -> Duration {
+        self.time.0
+    }
+
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
 }
 
 impl PartialEq for EventEntry {
     fn eq(&self, other: &Self) -> bool {
-        self.time == other.time
+        self.time == other.time && self.priority == other.priority
     }
 }
 
@@ -34,18 +59,45 @@ impl Eq for EventEntry {}
 
 impl PartialOrd for EventEntry {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.time.partial_cmp(&other.time)
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for EventEntry {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.time.cmp(&other.time)
+        self.time.cmp(&other.time).then(self.priority.cmp(&other.priority))
+    }
+}
+
+/// Handle of a scheduled event, returned by [`Simulation::schedule`](crate::Simulation::schedule).
+///
+/// Passed to [`Simulation::cancel`](crate::Simulation::cancel) it cancels that event only: once the event was
+/// executed or the entity was scheduled again the handle no longer matches and cancelling does nothing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EventId {
+    key: Key,
+    time: Duration,
+    sequence: u64,
+}
+
+impl EventId {
+    /// Returns the key of the scheduled entity.
+    #[must_use]
+    pub fn key(&self) -> Key {
+        self.key
+    }
+
+    /// Returns the simulation time the event is scheduled at.
+    #[must_use]
+    pub fn time(&self) -> Duration {
+        self.time
     }
 }
 
 type Clock = Rc<Cell<Duration>>;
 
+/// Read-only view of the simulation clock, obtained with [`Simulation::clock`](crate::Simulation::clock).
+#[derive(Debug, Clone)]
 pub struct ClockRef {
     clock: Clock,
 }
@@ -62,37 +114,152 @@ impl ClockRef {
     pub fn time(&self) -> Duration {
         self.clock.get()
     }
+
+    /// Return the current simulation time as a `T`, e.g. [`Ticks`](crate::Ticks).
+    #[must_use]
+    pub fn time_as<T: SimTime>(&self) -> T {
+        T::from_duration(self.time())
+    }
+}
+
+/// The pending events of a [`Scheduler`], ordered by time.
+///
+/// The [`BinaryHeap`] is the default, [`CalendarQueue`] can be faster for models with many pending events.
+/// Select one with [`Simulation::with_event_list`](crate::Simulation::with_event_list). Events at the same time
+/// may be popped in any order, the scheduler never holds more than one event per entity.
+pub trait FutureEventList: fmt::Debug {
+    fn push(&mut self, event: EventEntry);
+
+    /// Inserts every event of `events`, at once when the list can do better than pushing them one by one.
+    fn extend(&mut self, events: Vec<EventEntry>) {
+        for event in events {
+            self.push(event);
+        }
+    }
+
+    /// Removes and returns the earliest event.
+    fn pop(&mut self) -> Option<EventEntry>;
+
+    /// Returns the earliest event without removing it.
+    fn peek(&self) -> Option<&EventEntry>;
+
+    /// Removes the event of `key`, scheduled at `time`, returning whether there was one.
+    fn remove(&mut self, key: Key, time: Duration) -> bool;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl FutureEventList for BinaryHeap<EventEntry> {
+    fn push(&mut self, event: EventEntry) {
+        BinaryHeap::push(self, event);
+    }
+
+    fn extend(&mut self, events: Vec<EventEntry>) {
+        Extend::extend(self, events);
+    }
+
+    fn pop(&mut self) -> Option<EventEntry> {
+        BinaryHeap::pop(self)
+    }
+
+    fn peek(&self) -> Option<&EventEntry> {
+        BinaryHeap::peek(self)
+    }
+
+    fn remove(&mut self, key: Key, _time: Duration) -> bool {
+        let len = BinaryHeap::len(self);
+        self.retain(|event| event.key() != key);
+        BinaryHeap::len(self) != len
+    }
+
+    fn len(&self) -> usize {
+        BinaryHeap::len(self)
+    }
 }
 
 #[derive(Debug)]
 pub struct Scheduler {
-    pub(crate) events: BinaryHeap<EventEntry>,
+    events: Box<dyn FutureEventList>,
+    // The pending event of each entity, so scheduling an entity twice is caught without scanning the events.
+    scheduled: HashMap<Key, EventId>,
+    // Sequence number of the next event.
+    sequence: u64,
     clock: Clock,
 }
 
 impl Default for Scheduler {
     fn default() -> Self {
+        Self::with_event_list(Box::<BinaryHeap<EventEntry>>::default())
+    }
+}
+
+impl Scheduler {
+    pub(crate) fn with_event_list(events: Box<dyn FutureEventList>) -> Self {
         Self {
-            events: BinaryHeap::default(),
+            events,
+            scheduled: HashMap::new(),
+            sequence: 0,
             clock: Rc::new(Cell::new(Duration::ZERO)),
         }
     }
-}
 
-impl Scheduler {
     /// Schedules `event` to be executed for `entity` at `self.time() + time`.
     ///
     /// `entity_key` is a [`Key`](crate::keys::Key) corresponding to the [Generator](crate::GenBoxed) to be scheduled.
     /// 
     /// If `entity_key` was already scheduled it will ignore the following calls
-    pub fn schedule(&mut self, time: Duration, entity_key: Key) {
-        let already_inserted = self.events.iter().any(|ev_entry| ev_entry.entity_key == entity_key);
-        if already_inserted {
-            return;
+    ///
+    /// Returns the handle of the event if it was inserted.
+    pub fn schedule(&mut self, time: Duration, entity_key: Key) -> Option<EventId> {
+        self.schedule_with_priority(time, entity_key, 0)
+    }
+
+    /// Schedules `entity_key` like [`Scheduler::schedule`], before the events at the same time with a lower
+    /// `priority`.
+    pub fn schedule_with_priority(&mut self, time: Duration, entity_key: Key, priority: i32) -> Option<EventId> {
+        if self.scheduled.contains_key(&entity_key) {
+            return None;
         }
-        let time = self.time() + time;
-        let event = EventEntry::new(time, entity_key);
-        self.events.push(event);
+        let id = EventId {
+            key: entity_key,
+            time: self.time() + time,
+            sequence: self.sequence,
+        };
+        self.sequence += 1;
+        self.scheduled.insert(entity_key, id);
+        self.events.push(EventEntry::with_priority(id.time, entity_key, priority));
+        Some(id)
+    }
+
+    /// Schedules every `(time, entity_key)` pair like [`Scheduler::schedule`], inserting them into the event list
+    /// at once.
+    ///
+    /// Returns the handles of the events that were inserted.
+    pub fn schedule_all(&mut self, events: impl IntoIterator<Item = (Duration, Key)>) -> Vec<EventId> {
+        let now = self.time();
+        let mut ids = Vec::new();
+        let mut entries = Vec::new();
+        for (time, entity_key) in events {
+            // Keys scheduled earlier in the batch are already in `scheduled`.
+            if self.scheduled.contains_key(&entity_key) {
+                continue;
+            }
+            let id = EventId {
+                key: entity_key,
+                time: now + time,
+                sequence: self.sequence,
+            };
+            self.sequence += 1;
+            self.scheduled.insert(entity_key, id);
+            entries.push(EventEntry::with_priority(id.time, entity_key, 0));
+            ids.push(id);
+        }
+        self.events.extend(entries);
+        ids
     }
 
     /// Schedules `event` to be executed for `entity` at `self.time()`.
@@ -100,8 +267,9 @@ impl Scheduler {
     /// `entity` is a [`Key`](crate::key::Key) corresponding to the [Generator](crate::GenBoxed) to be scheduled.
     /// 
     /// If `entity_key` was already scheduled it will ignore the following calls
-    pub fn schedule_now(&mut self, entity: Key) {
-        self.schedule(Duration::ZERO, entity);
+    #[allow(dead_code)]
+    pub fn schedule_now(&mut self, entity: Key) -> Option<EventId> {
+        self.schedule(Duration::ZERO, entity)
     }
 
     /// Returns the current simulation time.
@@ -118,21 +286,60 @@ impl Scheduler {
         }
     }
 
+    /// Move the clock forward to `time` without executing any event.
+    ///
+    /// `time` must not be past the next scheduled event.
+    pub(crate) fn advance_to(&mut self, time: Duration) {
+        debug_assert!(!matches!(self.next_event_time(), Some(next) if time > next));
+        if time > self.time() {
+            self.clock.set(time);
+        }
+    }
+
+    /// Returns the time and the entity of the next scheduled event without removing it.
+    #[must_use]
+    pub fn peek(&self) -> Option<(Duration, Key)> {
+        self.events.peek().map(|event| (event.time.0, event.entity_key))
+    }
+
+    /// Returns the time of the next scheduled event without removing it.
+    #[must_use]
+    pub fn next_event_time(&self) -> Option<Duration> {
+        self.events.peek().map(|event| event.time.0)
+    }
+
     /// Removes and returns the next scheduled event or `None` if none are left.
     pub fn pop(&mut self) -> Option<EventEntry> {
         self.events.pop().map(|event| {
+            self.scheduled.remove(&event.entity_key);
             self.clock.replace(event.time.0);
             event
         })
     }
 
     pub fn remove(&mut self, key: Key) -> bool {
-        if !self.events.iter().any(|event_entry| event_entry.key() == key) { return false };
-        let mut events = std::mem::take(&mut self.events).into_vec();
-        events.retain(|event_entry| event_entry.key() != key);
-        let events = BinaryHeap::from(events);
-        self.events = events;
-        true
+        self.scheduled
+            .remove(&key)
+            .is_some_and(|id| self.events.remove(key, id.time))
+    }
+
+    /// Returns the pending event of `key`.
+    #[must_use]
+    pub fn event_of(&self, key: Key) -> Option<EventId> {
+        self.scheduled.get(&key).copied()
+    }
+
+    /// Returns every pending event, in the order they were scheduled.
+    #[must_use]
+    pub fn pending(&self) -> Vec<EventId> {
+        let mut events: Vec<EventId> = self.scheduled.values().copied().collect();
+        events.sort_by_key(|event| event.sequence);
+        events
+    }
+
+    /// Removes the event `id`, returning whether it was still pending.
+    pub fn cancel(&mut self, id: EventId) -> bool {
+        self.scheduled.get(&id.key) == Some(&id) && self.remove(id.key)
     }
 
     // Private function to insert `EventEntry` for testing.
@@ -140,7 +347,7 @@ impl Scheduler {
     #[allow(dead_code)]
     fn insert(&mut self, event: EventEntry) {
         // let next = self.get_new_id();
-        self.events.push(event);
+        self.schedule_with_priority(event.time.0 - self.time(), event.entity_key, event.priority);
     }
 }
 
@@ -164,32 +371,38 @@ mod test {
         assert_eq!(
             EventEntry {
                 time: Reverse(Duration::from_secs(1)),
-                entity_key: Key::new(2)
+                entity_key: Key::new(2),
+                priority: 0,
             },
             EventEntry {
                 time: Reverse(Duration::from_secs(1)),
-                entity_key: Key::new(2)
+                entity_key: Key::new(2),
+                priority: 0,
             }
         );
         assert_eq!(
             EventEntry {
                 time: Reverse(Duration::from_secs(0)),
-                entity_key: Key::new(2)
+                entity_key: Key::new(2),
+                priority: 0,
             }
             .cmp(&EventEntry {
                 time: Reverse(Duration::from_secs(1)),
-                entity_key: Key::new(2)
+                entity_key: Key::new(2),
+                priority: 0,
             }),
             Ordering::Greater
         );
         assert_eq!(
             EventEntry {
                 time: Reverse(Duration::from_secs(2)),
-                entity_key: Key::new(2)
+                entity_key: Key::new(2),
+                priority: 0,
             }
             .cmp(&EventEntry {
                 time: Reverse(Duration::from_secs(1)),
-                entity_key: Key::new(2)
+                entity_key: Key::new(2),
+                priority: 0,
             }),
             Ordering::Less
         );
@@ -260,6 +473,7 @@ mod test {
             EventEntry {
                 time: Reverse(Duration::from_secs(x) + clock_ref.time()),
                 entity_key: Key::new(key_id),
+                priority: 0,
             }
         };
         let event_1 = make_event_entry(4); 
@@ -283,4 +497,158 @@ mod test {
         assert_eq!(None, r_event); 
         assert_eq!(Duration::from_secs(4), scheduler.time()); 
     }
+
+    #[test]
+    fn peek_leaves_the_next_event_scheduled() {
+        let mut scheduler = Scheduler::default();
+        assert_eq!(None, scheduler.peek());
+        scheduler.schedule(Duration::from_secs(4), Key::new(1));
+        scheduler.schedule(Duration::from_secs(1), Key::new(2));
+
+        assert_eq!(Some((Duration::from_secs(1), Key::new(2))), scheduler.peek());
+        assert_eq!(Some(Duration::from_secs(1)), scheduler.next_event_time());
+        assert_eq!(Duration::ZERO, scheduler.time());
+
+        assert_eq!(Some(Key::new(2)), scheduler.pop().map(|event| event.key()));
+        assert_eq!(Some((Duration::from_secs(4), Key::new(1))), scheduler.peek());
+        scheduler.pop();
+        assert_eq!(None, scheduler.next_event_time());
+    }
+
+    #[test]
+    fn calendar_queue_pops_in_time_order() {
+        let mut heap = Scheduler::default();
+        let mut calendar = Scheduler::with_event_list(Box::<CalendarQueue>::default());
+        // Pseudo-random delays spread over several bucket widths, with repeated times.
+        let mut delay = 7;
+        for id in 0..1000 {
+            delay = (delay * 7919 + 13) % 5003;
+            heap.schedule(Duration::from_millis(delay), Key::new(id));
+            calendar.schedule(Duration::from_millis(delay), Key::new(id));
+        }
+        assert!(calendar.remove(Key::new(10)));
+        assert!(!calendar.remove(Key::new(10)));
+        heap.remove(Key::new(10));
+
+        let mut last = Duration::ZERO;
+        while let Some(event) = calendar.pop() {
+            assert!(event.time() >= last);
+            last = event.time();
+            // Events at the same time may come out in another order, only their times are compared.
+            let other = heap.pop().unwrap();
+            assert_eq!(last, other.time());
+            // Rescheduled entities keep the queue moving forward.
+            if last.as_millis().is_multiple_of(3) && last < Duration::from_secs(20) {
+                calendar.schedule(Duration::from_millis(1500), event.key());
+                heap.schedule(Duration::from_millis(1500), other.key());
+            }
+        }
+        assert_eq!(None, heap.pop());
+    }
+
+    #[test]
+    fn calendar_queue_keeps_the_order_of_simultaneous_events() {
+        let mut calendar = Scheduler::with_event_list(Box::<CalendarQueue>::default());
+        // Enough events to resize the buckets a few times, every tenth one at the same time.
+        for id in 0..500 {
+            let delay = if id % 10 == 0 { 1000 } else { id * 7 % 2000 };
+            calendar.schedule(Duration::from_millis(delay), Key::new(id as usize));
+        }
+
+        let simultaneous: Vec<usize> = std::iter::from_fn(|| calendar.pop())
+            .filter(|event| event.time() == Duration::from_secs(1))
+            .map(|event| event.key().id())
+            .collect();
+        assert_eq!((0..500).step_by(10).collect::<Vec<_>>(), simultaneous);
+    }
+
+    #[test]
+    fn priorities_order_events_at_the_same_time() {
+        let events_lists: [Box<dyn FutureEventList>; 2] =
+            [Box::<BinaryHeap<EventEntry>>::default(), Box::<CalendarQueue>::default()];
+        for events in events_lists {
+            let mut scheduler = Scheduler::with_event_list(events);
+            scheduler.schedule(Duration::from_secs(1), Key::new(0));
+            scheduler.schedule_with_priority(Duration::from_secs(1), Key::new(1), -1);
+            scheduler.schedule_with_priority(Duration::from_secs(1), Key::new(2), 10);
+            scheduler.schedule_with_priority(Duration::from_secs(2), Key::new(3), 20);
+
+            let popped: Vec<Key> = std::iter::from_fn(|| scheduler.pop().map(|event| event.key())).collect();
+            assert_eq!(vec![Key::new(2), Key::new(0), Key::new(1), Key::new(3)], popped);
+        }
+    }
+
+    #[test]
+    fn time_until_next_event_of_holding_entities() {
+        use crate::{process, Action, Simulation};
+
+        let mut simulation = Simulation::default();
+        let holder = simulation.add_generator(process(|_| Some(Action::Hold(Duration::from_secs(5)))));
+        let passive = simulation.add_generator(process(|_| Some(Action::Passivate)));
+        simulation.schedule_now(holder);
+        simulation.schedule(Duration::from_secs(2), passive);
+        simulation.step().unwrap();
+        simulation.step().unwrap();
+
+        assert_eq!(Duration::from_secs(2), simulation.time());
+        assert_eq!(Some(Duration::from_secs(3)), simulation.time_until_next_event(holder));
+        assert_eq!(None, simulation.time_until_next_event(passive));
+    }
+
+    #[test]
+    fn cancel_matches_the_event_only() {
+        let mut scheduler = Scheduler::default();
+        let first = scheduler.schedule(Duration::from_secs(1), Key::new(0)).unwrap();
+        assert_eq!(None, scheduler.schedule(Duration::from_secs(2), Key::new(0)));
+        scheduler.pop();
+        let second = scheduler.schedule(Duration::from_secs(1), Key::new(0)).unwrap();
+
+        assert_ne!(first, second);
+        assert!(!scheduler.cancel(first));
+        assert!(scheduler.cancel(second));
+        assert!(!scheduler.cancel(second));
+        assert_eq!(None, scheduler.pop());
+    }
+
+    #[test]
+    fn schedule_all_skips_scheduled_keys() {
+        let mut scheduler = Scheduler::default();
+        scheduler.schedule(Duration::from_secs(1), Key::new(0));
+        let inserted = scheduler.schedule_all([
+            (Duration::from_secs(2), Key::new(0)),
+            (Duration::from_secs(3), Key::new(1)),
+            (Duration::from_secs(4), Key::new(1)),
+            (Duration::from_secs(5), Key::new(2)),
+        ]);
+
+        let inserted: Vec<(Duration, Key)> = inserted.iter().map(|id| (id.time(), id.key())).collect();
+        assert_eq!(
+            vec![(Duration::from_secs(3), Key::new(1)), (Duration::from_secs(5), Key::new(2))],
+            inserted
+        );
+        let popped: Vec<Key> = std::iter::from_fn(|| scheduler.pop().map(|event| event.key())).collect();
+        assert_eq!(vec![Key::new(0), Key::new(1), Key::new(2)], popped);
+    }
+
+    #[test]
+    fn calendar_queue_schedules_batches_in_order() {
+        let mut calendar = Scheduler::with_event_list(Box::<CalendarQueue>::default());
+        calendar.schedule(Duration::from_millis(2500), Key::new(1000));
+        // Enough events to grow the buckets several times in one go, every tenth one at the same time.
+        let inserted = calendar.schedule_all((0..1000).map(|id| {
+            let delay = if id % 10 == 0 { 1000 } else { id * 7919 % 5003 };
+            (Duration::from_millis(delay), Key::new(id as usize))
+        }));
+        assert_eq!(1000, inserted.len());
+
+        let popped: Vec<EventEntry> = std::iter::from_fn(|| calendar.pop()).collect();
+        assert_eq!(1001, popped.len());
+        assert!(popped.windows(2).all(|pair| pair[0].time() <= pair[1].time()));
+        let simultaneous: Vec<usize> = popped
+            .iter()
+            .filter(|event| event.time() == Duration::from_secs(1))
+            .map(|event| event.key().id())
+            .collect();
+        assert_eq!((0..1000).step_by(10).collect::<Vec<_>>(), simultaneous);
+    }
 }
@@ -0,0 +1,195 @@
+use std::cmp::Reverse;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use super::{EventEntry, FutureEventList};
+use crate::keys::Key;
+
+const MIN_BUCKETS: usize = 2;
+
+/// A calendar queue (R. Brown, 1988): events are hashed by time into buckets, each covering `width` of time
+/// and sorted by time, like the days of a year.
+///
+/// Scheduling and popping take amortized constant time when the bucket width suits the spread of the pending
+/// events, against the logarithmic time of the [`BinaryHeap`](std::collections::BinaryHeap). The number of
+/// buckets is doubled or halved as the queue grows or shrinks, which is also when the width is estimated
+/// again, or sooner once a badly off width has cost as much extra work as there are pending events. Whether
+/// that pays off depends on the model and the machine, `cargo bench --bench event_list` compares both. Events
+/// at the same time and priority are popped in the order they were scheduled.
+#[derive(Debug, Clone)]
+pub struct CalendarQueue {
+    buckets: Vec<VecDeque<EventEntry>>,
+    // Nanoseconds covered by each bucket.
+    width: u64,
+    len: usize,
+    // Time of the last popped event, no pending event is earlier.
+    last: u64,
+    // Extra work spent since the width was last estimated, on crowded buckets and on searches for events
+    // more than a year ahead.
+    waste: usize,
+}
+
+impl Default for CalendarQueue {
+    fn default() -> Self {
+        Self {
+            buckets: vec![VecDeque::new(); MIN_BUCKETS],
+            width: 1_000_000_000,
+            len: 0,
+            last: 0,
+            waste: 0,
+        }
+    }
+}
+
+impl CalendarQueue {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn index(&self, time: u64) -> usize {
+        ((time / self.width) % self.buckets.len() as u64) as usize
+    }
+
+    // Returns the index of the bucket the event was inserted in.
+    fn insert(&mut self, event: EventEntry) -> usize {
+        let index = self.index(nanos(&event));
+        let bucket = &mut self.buckets[index];
+        let position = bucket.partition_point(|other| order(other) <= order(&event));
+        bucket.insert(position, event);
+        index
+    }
+
+    // Whether the bucket holds far more events than the average, at different times, so narrower buckets
+    // would split them. Events at the same time always share a bucket.
+    fn is_crowded(&self, index: usize) -> bool {
+        let bucket = &self.buckets[index];
+        bucket.len() > 64
+            && bucket.len() > 8 * self.len / self.buckets.len()
+            && matches!((bucket.front(), bucket.back()), (Some(first), Some(last)) if nanos(first) != nanos(last))
+    }
+
+    // Index of the bucket holding the next event and whether it was found by scanning a year of buckets.
+    fn locate(&self) -> Option<(usize, bool)> {
+        if self.len == 0 {
+            return None;
+        }
+        // Every pending event is at or after `last`, so the first bucket, starting from the one of `last`,
+        // whose earliest event falls in the year being scanned holds the next event.
+        let count = self.buckets.len() as u64;
+        let first_day = self.last / self.width;
+        for day in first_day..first_day + count {
+            let index = (day % count) as usize;
+            if matches!(self.buckets[index].front(), Some(first) if nanos(first) / self.width <= day) {
+                return Some((index, true));
+            }
+        }
+        // The next event is more than a year ahead, search for it directly.
+        self.buckets
+            .iter()
+            .enumerate()
+            .filter_map(|(index, bucket)| bucket.front().map(|first| (nanos(first), index)))
+            .min()
+            .map(|(_, index)| (index, false))
+    }
+
+    // Takes linear time: events at the same time and priority share a bucket, so draining the buckets in
+    // turn keeps their order without sorting. The `added` events are inserted after the pending ones.
+    fn resize(&mut self, count: usize, added: Vec<EventEntry>) {
+        let mut events: Vec<EventEntry> = self.buckets.iter_mut().flat_map(|bucket| bucket.drain(..)).collect();
+        events.extend(added);
+        // Three times the average separation of the pending events, so each bucket holds a few.
+        let (min, max) = events.iter().fold((u64::MAX, 0), |(min, max), event| {
+            (min.min(nanos(event)), max.max(nanos(event)))
+        });
+        if max > min {
+            self.width = (3 * (max - min) / events.len() as u64).max(1);
+        }
+        // The buckets are drained, keeping their allocations.
+        self.buckets.resize_with(count, VecDeque::new);
+        self.waste = 0;
+        for event in events {
+            let _ = self.insert(event);
+        }
+    }
+}
+
+impl FutureEventList for CalendarQueue {
+    fn push(&mut self, event: EventEntry) {
+        let index = self.insert(event);
+        self.len += 1;
+        if self.is_crowded(index) {
+            self.waste += self.buckets[index].len();
+        }
+        if self.len > 2 * self.buckets.len() {
+            self.resize(2 * self.buckets.len(), Vec::new());
+        } else if self.waste > self.len {
+            // Estimating the width again takes linear time, as much as was wasted.
+            self.resize(self.buckets.len(), Vec::new());
+        }
+    }
+
+    fn extend(&mut self, events: Vec<EventEntry>) {
+        let mut count = self.buckets.len();
+        while self.len + events.len() > 2 * count {
+            count *= 2;
+        }
+        if count == self.buckets.len() {
+            for event in events {
+                self.push(event);
+            }
+        } else {
+            // A single resize to the final size, estimating the width over every event.
+            self.len += events.len();
+            self.resize(count, events);
+        }
+    }
+
+    fn pop(&mut self) -> Option<EventEntry> {
+        let (index, scanned) = self.locate()?;
+        let event = self.buckets[index].pop_front()?;
+        self.len -= 1;
+        self.last = nanos(&event);
+        if !scanned {
+            self.waste += self.buckets.len();
+        }
+        if self.len < self.buckets.len() / 2 && self.buckets.len() > MIN_BUCKETS {
+            self.resize(self.buckets.len() / 2, Vec::new());
+        } else if self.waste > self.len {
+            self.resize(self.buckets.len(), Vec::new());
+        }
+        Some(event)
+    }
+
+    fn peek(&self) -> Option<&EventEntry> {
+        self.locate().and_then(|(index, _)| self.buckets[index].front())
+    }
+
+    fn remove(&mut self, key: Key, time: Duration) -> bool {
+        let index = self.index(to_nanos(time));
+        let bucket = &mut self.buckets[index];
+        let Some(position) = bucket.iter().position(|event| event.key() == key) else {
+            return false;
+        };
+        bucket.remove(position);
+        self.len -= 1;
+        true
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+// Events are popped in increasing order.
+fn order(event: &EventEntry) -> (u64, Reverse<i32>) {
+    (nanos(event), Reverse(event.priority()))
+}
+
+fn nanos(event: &EventEntry) -> u64 {
+    to_nanos(event.time())
+}
+
+fn to_nanos(time: Duration) -> u64 {
+    u64::try_from(time.as_nanos()).expect("event times fit in 584 years")
+}
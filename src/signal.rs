@@ -0,0 +1,194 @@
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
+use std::rc::Rc;
+
+use crate::Key;
+
+/// A payload published on a topic with [`Action::Broadcast`](crate::Action::Broadcast).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signal {
+    pub topic: String,
+    pub payload: String,
+    /// The entity that broadcast it.
+    pub from: Key,
+}
+
+#[derive(Default)]
+struct Inner {
+    // Subscribers of every topic, in subscription order.
+    topics: HashMap<String, Vec<Key>>,
+    // Signals received and not read yet by every subscriber.
+    inboxes: HashMap<Key, VecDeque<Signal>>,
+}
+
+/// Topics entities subscribe to, to receive the signals other entities broadcast without knowing their keys.
+///
+/// Obtained from [`Simulation::signals`](crate::Simulation::signals), clones share the subscriptions. Every
+/// subscriber of a topic receives each [`Action::Broadcast`](crate::Action::Broadcast) on it, passive ones are
+/// activated:
+///
+/// ```ignore
+/// // Subscriber
+/// signals.subscribe("shift");
+/// loop {
+///     let Some(signal) = signals.receive() else {
+///         yield Action::Passivate;
+///         continue;
+///     };
+///     ...
+/// }
+///
+/// // Publisher
+/// yield Action::Broadcast("shift".to_owned(), "night".to_owned());
+/// ```
+///
+/// Subscriptions end with [`Signals::unsubscribe`] or when the entity completes.
+#[derive(Clone)]
+pub struct Signals {
+    inner: Rc<RefCell<Inner>>,
+    current: Rc<Cell<Option<Key>>>,
+}
+
+impl Signals {
+    pub(crate) fn new(current: Rc<Cell<Option<Key>>>) -> Self {
+        Self {
+            inner: Rc::default(),
+            current,
+        }
+    }
+
+    fn current(&self) -> Key {
+        self.current
+            .get()
+            .expect("signals can only be used from inside an entity")
+    }
+
+    /// Subscribe the entity currently being executed to `topic`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of an entity.
+    pub fn subscribe(&self, topic: impl Into<String>) {
+        let key = self.current();
+        let mut inner = self.inner.borrow_mut();
+        let subscribers = inner.topics.entry(topic.into()).or_default();
+        if !subscribers.contains(&key) {
+            subscribers.push(key);
+        }
+    }
+
+    /// Unsubscribe the entity currently being executed from `topic`, keeping the signals it already received.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of an entity.
+    pub fn unsubscribe(&self, topic: &str) {
+        let key = self.current();
+        if let Some(subscribers) = self.inner.borrow_mut().topics.get_mut(topic) {
+            subscribers.retain(|&subscriber| subscriber != key);
+        }
+    }
+
+    /// Take the oldest signal received by the entity currently being executed.
+    ///
+    /// When `None` is returned the entity can yield `Action::Passivate` to wait for the next broadcast.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of an entity.
+    pub fn receive(&self) -> Option<Signal> {
+        let key = self.current();
+        self.inner.borrow_mut().inboxes.get_mut(&key)?.pop_front()
+    }
+
+    /// Returns the number of entities subscribed to `topic`.
+    #[must_use]
+    pub fn subscribers(&self, topic: &str) -> usize {
+        self.inner.borrow().topics.get(topic).map_or(0, Vec::len)
+    }
+
+    // Deliver the signal to every subscriber of its topic, returning them.
+    pub(crate) fn broadcast(&self, signal: Signal) -> Vec<Key> {
+        let mut inner = self.inner.borrow_mut();
+        let subscribers = inner.topics.get(&signal.topic).cloned().unwrap_or_default();
+        for &subscriber in &subscribers {
+            inner.inboxes.entry(subscriber).or_default().push_back(signal.clone());
+        }
+        subscribers
+    }
+
+    pub(crate) fn clear(&self, key: Key) {
+        let mut inner = self.inner.borrow_mut();
+        for subscribers in inner.topics.values_mut() {
+            subscribers.retain(|&subscriber| subscriber != key);
+        }
+        inner.inboxes.remove(&key);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{process, Action, ClockRef, GenBoxed, Simulation};
+
+    type Log = Rc<RefCell<Vec<(usize, String, Duration)>>>;
+
+    // Subscribes to "shift", holds for `busy` and then reads the signals as they come.
+    fn worker(signals: Signals, id: usize, busy: u64, clock: ClockRef, log: Log) -> GenBoxed<()> {
+        let mut subscribed = false;
+        process(move |_| {
+            if !subscribed {
+                subscribed = true;
+                signals.subscribe("shift");
+                if busy > 0 {
+                    return Some(Action::Hold(Duration::from_secs(busy)));
+                }
+            }
+            while let Some(signal) = signals.receive() {
+                log.borrow_mut().push((id, signal.payload.clone(), clock.time()));
+                if signal.payload == "off" {
+                    signals.unsubscribe("shift");
+                    return None;
+                }
+            }
+            Some(Action::Passivate)
+        })
+    }
+
+    #[test]
+    fn broadcasts_reach_every_subscriber() {
+        let mut simulation = Simulation::default();
+        let signals = simulation.signals();
+        let log = Log::default();
+        for (id, busy) in [(0, 0), (1, 5)] {
+            let worker = simulation.add_generator(worker(signals.clone(), id, busy, simulation.clock(), log.clone()));
+            simulation.schedule_now(worker);
+        }
+        let mut actions = vec![
+            Action::Hold(Duration::from_secs(1)),
+            Action::Broadcast("shift".to_owned(), "night".to_owned()),
+            Action::Broadcast("other".to_owned(), "ignored".to_owned()),
+            Action::Hold(Duration::from_secs(9)),
+            Action::Broadcast("shift".to_owned(), "off".to_owned()),
+        ]
+        .into_iter();
+        let publisher = simulation.add_generator(process(move |_| actions.next()));
+        simulation.schedule_now(publisher);
+        simulation.run_until_empty();
+
+        let secs = Duration::from_secs;
+        let expected = vec![
+            (0, "night".to_owned(), secs(1)),
+            // Busy when the first signal was broadcast, it reads it afterwards.
+            (1, "night".to_owned(), secs(5)),
+            (0, "off".to_owned(), secs(10)),
+            (1, "off".to_owned(), secs(10)),
+        ];
+        let mut log = log.borrow().clone();
+        log.sort_by_key(|&(id, _, time)| (time, id));
+        assert_eq!(expected, log);
+        assert_eq!(0, signals.subscribers("shift"));
+    }
+}
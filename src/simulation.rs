@@ -1,33 +1,270 @@
-use std::cell::Cell;
-use std::ops::GeneratorState;
+use std::cell::{Cell, Ref, RefCell, RefMut};
+use std::collections::{HashMap, VecDeque};
 use std::rc::Rc;
 use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
 
+mod builder;
+
+pub use builder::SimulationBuilder;
+
+use crate::attributes::{Attributes, EntityAttributes};
+use crate::breakpoint::Breakpoint;
+use crate::bulk::{BulkServer, BulkService};
+use crate::checkpoint::{Checkpoint, CheckpointError, CheckpointRecorder, Decision};
+use crate::components::{Component, ComponentKind};
 use crate::container::{Container, EntityState};
-use crate::scheduler::Scheduler;
-use crate::state::State;
-use crate::{Action, GenBoxed, Key};
+use crate::continuous::Continuous;
+use crate::deadlock::{Deadlock, PassiveEntity};
+use crate::entity::{Entity, EntityContext, Process};
+use crate::error::SimulationError;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::handle::Progress;
+use crate::handle::{RunHandle, RunStatus};
+use crate::hooks::Hooks;
+use crate::instrumentation;
+use crate::level::LevelContainer;
+use crate::local::LocalStore;
+use crate::logger::SimLogger;
+use crate::metadata::RunMetadata;
+use crate::names::EntityNames;
+use crate::net::Network;
+use crate::cancel::{CancelOutcome, Cancellations};
+use crate::preempt::Preemptions;
+use crate::process::{process, GeneratorState};
+use crate::queue::{Consumers, SimQueue};
+use crate::resource::Resource;
+use crate::rng::{RngStreams, SimRng};
+use crate::scheduler::{EventId, FutureEventList, Scheduler};
+use crate::source::{Source, SourceHandle};
+use crate::spawner::Spawner;
+use crate::state::{SharedState, StateKey};
+use crate::stats::Statistic;
+use crate::steps::Steps;
+use crate::rendezvous::{Rendezvous, Wake, Wakes};
+use crate::replay::Divergence;
+use crate::signal::{Signal, Signals};
+use crate::sampler::Sampler;
+use crate::sink::Sink;
+use crate::module::Module;
+use crate::nested::NestedSimulation;
+use crate::resume::{Interrupt, Resume};
+use crate::time::SimTime;
+use crate::timeline::Timeline;
+use crate::trace::{TraceEvent, TraceEventKind, TraceRecorder};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::RealTimeRunner;
+use crate::{Action, GenBoxed, Key, WeakKey};
+
+type ResumeProvider<R> = Box<dyn FnMut(Key, Option<&Action>, Duration) -> R>;
 
 pub struct Simulation<R> {
     scheduler: Scheduler,
     entities: Container<R>,
-    state: Rc<Cell<State>>,
+    state: SharedState,
+    // The state of every module, by path.
+    module_states: HashMap<String, SharedState>,
+    spawner: Spawner<R>,
+    // The entity being resumed, used by the `Spawner` to link children to their parent.
+    current: Rc<Cell<Option<Key>>>,
+    // Entities declared for the initialization phase, in declaration order.
+    init_queue: VecDeque<Key>,
+    components: Vec<Component>,
+    run_handle: RunHandle,
+    // Shared with the exporters so every output embeds it.
+    metadata: Rc<RefCell<RunMetadata>>,
+    hooks: Hooks,
+    rng: SimRng,
+    streams: RngStreams,
+    statistics: Vec<Box<dyn Statistic>>,
+    // Released on behalf of the entities removed while holding units.
+    resources: Vec<Resource>,
+    // Hand the items reserved for removed entities to other waiting entities.
+    queues: Vec<Box<dyn Consumers>>,
+    // Resume the entities they serve after every step.
+    rendezvous: Vec<Rc<dyn Wakes<R>>>,
+    // End of the warm-up period, `None` once it's over or if there is none.
+    warm_up: Option<Duration>,
+    strict: bool,
+    // Time at which each passive entity became passive and who cancelled it, for deadlock reports.
+    passive_since: HashMap<Key, (Duration, Option<Key>)>,
+    names: EntityNames,
+    attributes: EntityAttributes,
+    local: LocalStore,
+    preemptions: Preemptions,
+    cancellations: Cancellations,
+    signals: Signals,
+    // Why each scheduled entity will be resumed, when it's not a timer.
+    wake_causes: HashMap<Key, Resume>,
+    // Values sent to passive entities with `send_message`, resumed with instead of the provider's.
+    messages: HashMap<Key, R>,
+    // Used by the run methods without a provider, with the last action of every entity while it's set.
+    resume_provider: Rc<RefCell<Option<ResumeProvider<R>>>>,
+    last_actions: Rc<RefCell<HashMap<Key, Action>>>,
+    breakpoints: Vec<Breakpoint>,
+    // Set when a run stopped before an event, so the next run executes it.
+    past_breakpoint: bool,
+    // Set by the `SimulationBuilder`, used by `run`.
+    time_limit: Option<Duration>,
+    #[cfg(not(target_arch = "wasm32"))]
+    real_time: Option<RealTimeRunner>,
+    trace: Option<TraceRecorder>,
+    sampler: Option<Sampler>,
+    #[cfg(feature = "chrono")]
+    calendar: Option<crate::Calendar>,
 }
 
-pub enum ShouldContinue {
+/// What happened in a step of the simulation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepOutcome {
+    /// An event was executed.
     Advance,
+    /// No more events are left.
     Break,
 }
 
+/// What happened in a step of the simulation, returned by [`Simulation::step_with`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepResult {
+    time: Duration,
+    // `None` if no more events were left.
+    key: Option<Key>,
+    action: Option<Action>,
+    entity_finished: bool,
+}
+
+impl StepResult {
+    fn exhausted(time: Duration) -> Self {
+        Self {
+            time,
+            key: None,
+            action: None,
+            entity_finished: false,
+        }
+    }
+
+    #[must_use]
+    pub fn outcome(&self) -> StepOutcome {
+        match self.key {
+            Some(_) => StepOutcome::Advance,
+            None => StepOutcome::Break,
+        }
+    }
+
+    /// Returns `true` if an event was executed, so more may be left.
+    #[must_use]
+    pub fn should_continue(&self) -> bool {
+        self.key.is_some()
+    }
+
+    /// Returns the simulation time of the step.
+    #[must_use]
+    pub fn time(&self) -> Duration {
+        self.time
+    }
+
+    /// Returns the entity that was resumed, `None` if no more events were left.
+    #[must_use]
+    pub fn key(&self) -> Option<Key> {
+        self.key
+    }
+
+    /// Returns the action yielded by the entity, `None` if it completed or no more events were left.
+    #[must_use]
+    pub fn action(&self) -> Option<&Action> {
+        self.action.as_ref()
+    }
+
+    /// Returns `true` if the entity completed in this step.
+    #[must_use]
+    pub fn entity_finished(&self) -> bool {
+        self.entity_finished
+    }
+}
+
+/// Information about the event about to be executed.
+///
+/// Handed to resume-value providers so they can compute the value each generator is resumed with.
+#[derive(Debug, Clone, Copy)]
+pub struct StepContext {
+    time: Duration,
+    key: Key,
+    resume: Resume,
+}
+
+impl StepContext {
+    /// Returns the simulation time at which the event is executed.
+    #[must_use]
+    pub fn time(&self) -> Duration {
+        self.time
+    }
+
+    /// Returns the [`Key`] of the entity about to be resumed.
+    #[must_use]
+    pub fn key(&self) -> Key {
+        self.key
+    }
+
+    /// Returns why the entity is about to be resumed.
+    #[must_use]
+    pub fn resume(&self) -> Resume {
+        self.resume
+    }
+}
+
 impl<R> Default for Simulation<R>
 where
     R: 'static,
 {
     fn default() -> Self {
+        let entities = Container::default();
+        let current = Rc::default();
+        let spawner = Spawner::new(entities.slots(), Rc::clone(&current));
+        let local = LocalStore::new(Rc::clone(&current));
+        let preemptions = Preemptions::new(Rc::clone(&current));
+        let cancellations = Cancellations::new(Rc::clone(&current));
+        let signals = Signals::new(Rc::clone(&current));
         Self {
             scheduler: Scheduler::default(),
-            entities: Container::default(),
-            state: Rc::new(Cell::new(State::default()))
+            entities,
+            state: SharedState::default(),
+            module_states: HashMap::default(),
+            spawner,
+            current,
+            init_queue: VecDeque::default(),
+            components: Vec::default(),
+            run_handle: RunHandle::default(),
+            metadata: Rc::default(),
+            hooks: Hooks::default(),
+            rng: SimRng::default(),
+            streams: RngStreams::new(0),
+            statistics: Vec::new(),
+            resources: Vec::new(),
+            queues: Vec::new(),
+            rendezvous: Vec::new(),
+            warm_up: None,
+            strict: false,
+            passive_since: HashMap::new(),
+            names: EntityNames::default(),
+            attributes: EntityAttributes::default(),
+            local,
+            preemptions,
+            cancellations,
+            signals,
+            wake_causes: HashMap::new(),
+            messages: HashMap::new(),
+            resume_provider: Rc::default(),
+            last_actions: Rc::default(),
+            breakpoints: Vec::new(),
+            past_breakpoint: false,
+            time_limit: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            real_time: None,
+            trace: None,
+            sampler: None,
+            #[cfg(feature = "chrono")]
+            calendar: None,
         }
     }
 }
@@ -36,20 +273,533 @@ impl<R> Simulation<R>
 where
     R: 'static,
 {
+    /// Returns a [`SimulationBuilder`] to configure a simulation in one place.
+    #[must_use]
+    pub fn builder() -> SimulationBuilder<R> {
+        SimulationBuilder::default()
+    }
+
+    /// Create a simulation with the named random number streams of `streams`, seeded with their seed.
+    #[must_use]
+    pub fn with_streams(streams: RngStreams) -> Self {
+        let mut simulation = Self {
+            streams,
+            ..Self::default()
+        };
+        simulation.set_seed(simulation.streams.seed());
+        simulation
+    }
+
+    /// Create a simulation whose pending events are kept in `events`, e.g. a
+    /// [`CalendarQueue`](crate::CalendarQueue) for models with huge numbers of pending events.
+    #[must_use]
+    pub fn with_event_list(events: impl FutureEventList + 'static) -> Self {
+        Self {
+            scheduler: Scheduler::with_event_list(Box::new(events)),
+            ..Self::default()
+        }
+    }
+
     /// Add an already constructed Generator into the simulation.
     #[inline]
     pub fn add_generator(&mut self, gen: GenBoxed<R>) -> Key {
         self.entities.add_generator(gen)
     }
 
+    /// Add an already constructed Generator into the simulation under `name`.
+    ///
+    /// The name can be looked up with [`Simulation::key_of`] and is shown by traces and strict mode panics.
+    ///
+    /// # Panics
+    ///
+    /// Panics if another entity still running has the same name.
+    pub fn add_generator_named(&mut self, name: impl Into<String>, gen: GenBoxed<R>) -> Key {
+        let name = name.into();
+        if let Some(existing) = self.names.key_of(&name) {
+            assert!(
+                self.entities.get_state(existing).is_none(),
+                "An entity named {:?} already exists. ID = {}",
+                name,
+                existing.id
+            );
+        }
+        let key = self.entities.add_generator(gen);
+        self.names.insert(name, key);
+        key
+    }
+
+    /// Add an already constructed Generator into the simulation with `attributes`, queryable through [`Simulation::attributes`].
+    pub fn add_generator_with(&mut self, gen: GenBoxed<R>, attributes: Attributes) -> Key {
+        let key = self.entities.add_generator(gen);
+        self.attributes.insert(key, attributes);
+        key
+    }
+
+    /// Add an entity defined as a struct, with the attributes given by [`Entity::attributes`].
+    pub fn add_entity<E: Entity + Process<R>>(&mut self, entity: E) -> Key {
+        let key = self.entities.reserve();
+        let attributes = entity.attributes();
+        let context = EntityContext::new(key, self.state.clone(), self.local.clone(), self.rng.clone());
+        self.entities.insert(key, entity.run(context));
+        self.attributes.insert(key, attributes);
+        key
+    }
+
+    /// Add every entity of `entities`, e.g. read from configuration data, returning their keys in order.
+    pub fn add_entities<E: Entity + Process<R>>(&mut self, entities: impl IntoIterator<Item = E>) -> Vec<Key> {
+        entities.into_iter().map(|entity| self.add_entity(entity)).collect()
+    }
+
+    /// Returns the registry of entity attributes, shared with the simulation.
+    #[must_use]
+    pub fn attributes(&self) -> EntityAttributes {
+        self.attributes.clone()
+    }
+
+    /// Returns the store of entity-private data, see [`LocalStore`].
+    #[must_use]
+    pub fn local_store(&self) -> LocalStore {
+        self.local.clone()
+    }
+
+    /// Store `value` as private data of the entity, returning its previous value of the same type.
+    pub fn insert_entity_data<T: 'static>(&mut self, key: Key, value: T) -> Option<T> {
+        self.local.insert(key, value)
+    }
+
+    /// Returns the private data of type `T` of the entity.
+    #[must_use]
+    pub fn entity_data<T: 'static>(&self, key: Key) -> Option<Ref<'_, T>> {
+        self.local.borrow(key)
+    }
+
+    /// Returns the private data of type `T` of the entity, mutably.
+    #[must_use]
+    pub fn entity_data_mut<T: 'static>(&mut self, key: Key) -> Option<RefMut<'_, T>> {
+        self.local.borrow_mut(key)
+    }
+
+    /// Returns the key of the entity named `name`, see [`Simulation::add_generator_named`].
+    #[must_use]
+    pub fn key_of(&self, name: &str) -> Option<Key> {
+        self.names.key_of(name)
+    }
+
+    /// Returns the name of the entity, `None` if it was added without one.
+    #[must_use]
+    pub fn name_of(&self, key: Key) -> Option<String> {
+        self.names.name_of(key)
+    }
+
+    /// Returns the registry of entity names, shared with the simulation so hooks can label entities.
+    #[must_use]
+    pub fn names(&self) -> EntityNames {
+        self.names.clone()
+    }
+
+    /// Returns a [`SimLogger`] writing records prefixed with the simulation time and entity name to stderr.
+    #[must_use]
+    pub fn logger(&self) -> SimLogger {
+        self.logger_to(std::io::stderr())
+    }
+
+    /// Returns a [`SimLogger`] writing to `output`, see [`Simulation::logger`].
+    #[must_use]
+    pub fn logger_to(&self, output: impl std::io::Write + 'static) -> SimLogger {
+        SimLogger::new(Box::new(output), self.clock(), Rc::clone(&self.current), self.names.clone())
+    }
+
+    /// Add an already constructed Generator into the simulation as a child of `parent`.
+    ///
+    /// When `parent` completes the child and its own descendants are terminated,
+    /// their scheduled events discarded and the units of [`Resource`]s they hold released.
+    pub fn add_child(&mut self, parent: Key, gen: GenBoxed<R>) -> Key {
+        let key = self.entities.add_generator(gen);
+        self.entities.set_parent(key, parent);
+        key
+    }
+
+    /// Register a callback invoked with the time, key and action every time an entity yields.
+    ///
+    /// The callback runs before the action is applied.
+    pub fn on_step(&mut self, hook: impl FnMut(Duration, Key, &Action) + 'static) {
+        self.hooks.add_on_step(hook);
+    }
+
+    /// Register a callback invoked with the key of every entity that completes its execution.
+    pub fn on_complete(&mut self, hook: impl FnMut(Key) + 'static) {
+        self.hooks.add_on_complete(hook);
+    }
+
+    /// Register a callback invoked with the time and key of every event inserted in the scheduler.
+    pub fn on_schedule(&mut self, hook: impl FnMut(Duration, Key) + 'static) {
+        self.hooks.add_on_schedule(hook);
+    }
+
+    /// Returns a [`Spawner`] that generators can use to add entities while the simulation runs.
+    #[must_use]
+    pub fn spawner(&self) -> Spawner<R> {
+        self.spawner.clone()
+    }
+
+    /// Register a model component of type `T` under `name` so it's listed by [`Simulation::components`].
+    ///
+    /// Entities don't need to be registered, every entity in the simulation is listed automatically.
+    pub fn register_component<T: ?Sized + 'static>(&mut self, name: impl Into<String>, kind: ComponentKind) {
+        let type_name = std::any::type_name::<T>();
+        self.components
+            .push(Component::new(name.into(), kind, type_name, None));
+    }
+
+    /// Add a [`Resource`] with `capacity` units, registered as a component under `name`. Its
+    /// [statistics](Resource::stats) are attached automatically.
+    pub fn add_resource(&mut self, name: impl Into<String>, capacity: usize) -> Resource {
+        let name = name.into();
+        self.register_component::<Resource>(name.clone(), ComponentKind::Resource);
+        let resource = Resource::new(name, capacity, self.clock(), Rc::clone(&self.current));
+        self.statistics.push(Box::new(resource.clone()));
+        self.resources.push(resource.clone());
+        resource
+    }
+
+    /// Add a [`LevelContainer`] holding up to `capacity` and starting at `initial`, registered as a component under
+    /// `name`. Its level statistics are attached automatically.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `initial` isn't between 0 and `capacity`.
+    pub fn add_level_container(&mut self, name: impl Into<String>, capacity: f64, initial: f64) -> LevelContainer {
+        let name = name.into();
+        self.register_component::<LevelContainer>(name.clone(), ComponentKind::Resource);
+        let container = LevelContainer::new(name, capacity, initial, self.clock(), Rc::clone(&self.current));
+        self.statistics.push(Box::new(container.clone()));
+        container
+    }
+
+    /// Call `f` with the shared state and the current time now and then every `interval`, e.g. to sample statistics,
+    /// from an entity scheduled to start now. Returns its key, to cancel it.
+    ///
+    /// The entity never completes, so runs have to be stopped with a time limit or a condition.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `interval` is zero.
+    pub fn every(&mut self, interval: impl SimTime, f: impl FnMut(&SharedState, Duration) + 'static) -> Key {
+        let interval = interval.to_duration();
+        assert!(!interval.is_zero(), "The interval of a periodic process must be positive");
+        let state = self.state();
+        let clock = self.clock();
+        let mut f = Box::new(f);
+        let key = self.add_generator(process(move |_| {
+            f(&state, clock.time());
+            Some(Action::Hold(interval))
+        }));
+        self.schedule_now(key);
+        key
+    }
+
+    /// Add a system of [`Continuous`] variables integrated in steps of at most `max_step`, stepped by an entity
+    /// named `name` scheduled to start now.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_step` is zero or if another entity still running is named `name`.
+    pub fn add_continuous(&mut self, name: impl Into<String>, max_step: Duration) -> Continuous {
+        let continuous = Continuous::new(max_step, self.clock(), Rc::clone(&self.current));
+        let key = self.add_generator_named(name, continuous.clone().into_generator());
+        self.schedule_now(key);
+        continuous
+    }
+
+    /// Add an empty [`Network`] carrying payloads of type `M`, registered as a component under `name`, with its
+    /// packets delivered by an entity named `name` scheduled to start now.
+    ///
+    /// The delay of the packets is attached as a statistic named `"<name> delay"`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if another entity still running is named `name`.
+    pub fn add_network<M: 'static>(&mut self, name: impl Into<String>) -> Network<M> {
+        let name = name.into();
+        self.register_component::<Network<M>>(name.clone(), ComponentKind::Channel);
+        let network = Network::new(&name, self.clock(), self.rng(), Rc::clone(&self.current));
+        self.add_statistic(format!("{} delay", name), network.delay());
+        let key = self.add_generator_named(name, network.clone().into_generator());
+        network.set_key(key);
+        self.schedule_now(key);
+        network
+    }
+
+    /// Add an empty [`SimQueue`], registered as a component under `name`.
+    pub fn add_queue<T: 'static>(&mut self, name: impl Into<String>) -> SimQueue<T> {
+        let name = name.into();
+        self.register_component::<SimQueue<T>>(name.clone(), ComponentKind::Channel);
+        let queue = SimQueue::new(&name, self.clock(), Rc::clone(&self.current));
+        self.statistics.push(Box::new(queue.clone()));
+        self.queues.push(Box::new(queue.clone()));
+        queue
+    }
+
+    /// Attach a statistics collector, registered as a component under `name`, so it's reset at the end of the warm-up period.
+    ///
+    /// Collectors share their state with their clones, so `statistic` is usually a clone of the one fed by the model.
+    /// Queues created with [`Simulation::add_queue`] are attached automatically.
+    pub fn add_statistic<S: Statistic + 'static>(&mut self, name: impl Into<String>, statistic: S) {
+        self.register_component::<S>(name, ComponentKind::Collector);
+        self.statistics.push(Box::new(statistic));
+    }
+
+    /// Stop the run methods at `breakpoint`, returning [`RunStatus::Breakpoint`].
+    pub fn set_breakpoint(&mut self, breakpoint: Breakpoint) {
+        if !self.breakpoints.contains(&breakpoint) {
+            self.breakpoints.push(breakpoint);
+        }
+    }
+
+    /// Remove `breakpoint`, returning whether it was set.
+    pub fn remove_breakpoint(&mut self, breakpoint: Breakpoint) -> bool {
+        let before = self.breakpoints.len();
+        self.breakpoints.retain(|&other| other != breakpoint);
+        self.breakpoints.len() != before
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    /// Returns the breakpoints still set, time breakpoints are removed once hit.
+    #[must_use]
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    // Returns the breakpoint stopping the run before the next event, if any.
+    pub(crate) fn breakpoint_before(&mut self) -> Option<Breakpoint> {
+        if self.breakpoints.is_empty() || self.past_breakpoint {
+            return None;
+        }
+        let (time, key) = self.peek()?;
+        let index = self.breakpoints.iter().position(|breakpoint| breakpoint.hit_before(time, key))?;
+        let breakpoint = self.breakpoints[index];
+        if let Breakpoint::Time(_) = breakpoint {
+            self.breakpoints.remove(index);
+        } else {
+            self.past_breakpoint = true;
+        }
+        Some(breakpoint)
+    }
+
+    // Returns the breakpoint stopping the run after `step`, if any.
+    pub(crate) fn breakpoint_after(&self, step: &StepResult) -> Option<Breakpoint> {
+        let action = step.action()?;
+        self.breakpoints.iter().find(|breakpoint| breakpoint.hit_after(action)).copied()
+    }
+
+    /// Panic on invalid actions instead of returning a [`SimulationError`], as the simulation used to do.
+    ///
+    /// Useful while developing a model, the panic points at the step that went wrong.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Reset every attached statistics collector when the clock reaches `time`, while the model keeps running.
+    ///
+    /// Events scheduled exactly at `time` are executed after the reset.
+    pub fn set_warm_up(&mut self, time: impl SimTime) {
+        self.warm_up = Some(time.to_duration());
+    }
+
+    /// Reset every attached statistics collector now.
+    pub fn reset_statistics(&self) {
+        for statistic in &self.statistics {
+            statistic.reset();
+        }
+    }
+
+    // Move the clock forward to `time`, which must not be past the next event, without executing any event.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn advance_to(&mut self, time: Duration) {
+        self.scheduler.advance_to(time);
+    }
+
+    // Reset the statistics at the end of the warm-up period if the next event is past it.
+    fn finish_warm_up(&mut self) {
+        let Some(warm_up) = self.warm_up else {
+            return;
+        };
+        if matches!(self.scheduler.next_event_time(), Some(next) if next >= warm_up) {
+            self.warm_up = None;
+            self.scheduler.advance_to(warm_up);
+            self.reset_statistics();
+        }
+    }
+
+    /// Install `source` as an entity, registered as a component under `name`.
+    ///
+    /// The first batch is spawned after the first interarrival time.
+    pub fn add_source(&mut self, name: impl Into<String>, source: Source<R>) -> SourceHandle {
+        self.register_component::<Source<R>>(name, ComponentKind::Source);
+        let stats = Rc::default();
+        let generator = source.into_generator(self.spawner(), Rc::clone(&stats), self.rng(), self.clock());
+        let key = self.add_generator(generator);
+        self.schedule_now(key);
+        SourceHandle::new(key, stats)
+    }
+
+    /// Add a [`Sampler`] reading values every `interval` from now on, registered as a component under `name`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `interval` is zero.
+    pub fn add_sampler(&mut self, name: impl Into<String>, interval: impl SimTime) -> Sampler {
+        let interval = interval.to_duration();
+        assert!(!interval.is_zero(), "The sampling interval must be positive");
+        self.register_component::<Sampler>(name, ComponentKind::Collector);
+        let sampler = Sampler::new(interval, self.state());
+        let key = self.add_generator(sampler.generator(self.clock()));
+        self.schedule_now(key);
+        sampler
+    }
+
+    /// Embed `child` as an entity advancing it in lockstep with this simulation, registered as a component under
+    /// `name`. The entity is scheduled now, which is time 0 of the child.
+    pub fn add_simulation(&mut self, name: impl Into<String>, child: Simulation<()>) -> NestedSimulation {
+        self.register_component::<NestedSimulation>(name, ComponentKind::Entity);
+        let key = self.entities.reserve();
+        let nested = NestedSimulation::new(key, child);
+        self.entities.insert(key, nested.generator());
+        self.schedule_now(key);
+        nested
+    }
+
+    /// Add a [`Sink`] where entities check out, registered as a component under `name`. Its statistics are
+    /// attached automatically.
+    pub fn add_sink(&mut self, name: impl Into<String>) -> Sink {
+        let name = name.into();
+        self.register_component::<Sink>(name.clone(), ComponentKind::Collector);
+        let sink = Sink::new(&name, self.clock(), Rc::clone(&self.current));
+        self.statistics.push(Box::new(sink.clone()));
+        sink
+    }
+
+    /// Install a server processing entities in batches, registered as a component under `name`.
+    ///
+    /// The server entity is declared for the initialization phase so it's ready before any entity joins it.
+    pub fn add_bulk_server(&mut self, name: impl Into<String>, service: BulkService) -> BulkServer {
+        self.register_component::<BulkServer>(name, ComponentKind::Resource);
+        // The server generator needs its own key, so it's reserved before creating it.
+        let key = self.entities.reserve();
+        let (server, gen) = BulkServer::new(service, key, Rc::clone(&self.current));
+        self.entities.insert(key, gen);
+        self.schedule_init(key);
+        server
+    }
+
+    /// Returns every registered component followed by the entities currently in the simulation, listed by name.
+    #[must_use]
+    pub fn components(&self) -> Vec<Component> {
+        let entity_type = std::any::type_name::<GenBoxed<R>>();
+        let entities = self.entities.keys().map(|key| {
+            Component::new(
+                self.names.label(key),
+                ComponentKind::Entity,
+                entity_type,
+                Some(key),
+            )
+        });
+        self.components.iter().cloned().chain(entities).collect()
+    }
+
+    /// Returns the parent of the entity associated with `key` if it has one.
+    #[must_use]
+    #[inline]
+    pub fn parent(&self, key: Key) -> Option<Key> {
+        self.entities.parent(key)
+    }
+
+    /// Returns the children of the entity associated with `key`.
+    #[must_use]
+    #[inline]
+    pub fn children(&self, key: Key) -> &[Key] {
+        self.entities.children(key)
+    }
+
     /// Schedules `entity_key` at `self.time() + time`.
     /// 
     /// `entity_key` is a [Key] corresponding to the entity to be scheduled.
     /// 
-    /// If `entity_key` was already scheduled it will ignore the following calls
+    /// If `entity_key` was already scheduled it will ignore the following calls.
+    /// Stale keys, whose entity no longer exists, are ignored too.
+    ///
+    /// Returns a handle to [cancel](Simulation::cancel) the event, `None` if it was ignored.
     #[inline]
-    pub fn schedule(&mut self, time: Duration, entity_key: Key) {
-        self.scheduler.schedule(time, entity_key)
+    pub fn schedule(&mut self, time: impl SimTime, entity_key: Key) -> Option<EventId> {
+        if self.entities.is_stale(entity_key) {
+            return None;
+        }
+        self.schedule_with_priority(time, entity_key, 0)
+    }
+
+    /// Schedules `entity_key` like [`Simulation::schedule`], to be executed before the entities scheduled at
+    /// the same time with a lower `priority`, e.g. resource releases before new arrivals.
+    pub fn schedule_with_priority(&mut self, time: impl SimTime, entity_key: Key, priority: i32) -> Option<EventId> {
+        if self.entities.is_stale(entity_key) || self.entity_state(entity_key) == Some(EntityState::Failed) {
+            return None;
+        }
+        let id = self
+            .scheduler
+            .schedule_with_priority(time.to_duration(), entity_key, priority)?;
+        self.scheduled(id);
+        Some(id)
+    }
+
+    // Bookkeeping of an event inserted in the scheduler.
+    fn scheduled(&mut self, id: EventId) {
+        self.wake_causes.remove(&id.key());
+        instrumentation::scheduled(id.time(), id.key());
+        self.hooks.schedule(id.time(), id.key());
+        if self.entities.get_state(id.key()) == Some(&EntityState::Passive) {
+            self.passive_since.remove(&id.key());
+        }
+        self.set_entity_state(id.key(), EntityState::Scheduled);
+    }
+
+    /// Cancel the event `id`, leaving its entity passive until it's activated or scheduled again.
+    ///
+    /// Returns whether the event was still pending: once it was executed, or the entity was scheduled again,
+    /// the handle no longer matches and nothing is cancelled.
+    pub fn cancel(&mut self, id: EventId) -> bool {
+        if !self.scheduler.cancel(id) {
+            return false;
+        }
+        self.wake_causes.remove(&id.key());
+        self.set_entity_state(id.key(), EntityState::Passive);
+        self.passive_since.insert(id.key(), (self.time(), None));
+        true
+    }
+
+    /// Schedules every `(time, entity_key)` pair like [`Simulation::schedule`], in a single pass over the
+    /// pending events instead of one per entity.
+    ///
+    /// Returns the handles of the events that weren't ignored.
+    pub fn schedule_all<T: SimTime>(&mut self, events: impl IntoIterator<Item = (T, Key)>) -> Vec<EventId> {
+        let entities = &self.entities;
+        let events = events
+            .into_iter()
+            .filter(|&(_, entity_key)| {
+                !entities.is_stale(entity_key) && entities.get_state(entity_key) != Some(&EntityState::Failed)
+            })
+            .map(|(time, entity_key)| (time.to_duration(), entity_key));
+        let ids = self.scheduler.schedule_all(events);
+        for &id in &ids {
+            self.scheduled(id);
+        }
+        ids
+    }
+
+    /// Schedules every entity of `keys` at `self.time()`, see [`Simulation::schedule_all`].
+    pub fn schedule_now_all(&mut self, keys: impl IntoIterator<Item = Key>) -> Vec<EventId> {
+        self.schedule_all(keys.into_iter().map(|key| (Duration::ZERO, key)))
     }
 
     /// Schedules `entity_key` to be executed for at `self.time()`.
@@ -58,8 +808,62 @@ where
     /// 
     /// If `entity_key` was already scheduled it will ignore the following calls
     #[inline]
-    pub fn schedule_now(&mut self, entity_key: Key) {
-        self.scheduler.schedule_now(entity_key)
+    pub fn schedule_now(&mut self, entity_key: Key) -> Option<EventId> {
+        self.schedule(Duration::ZERO, entity_key)
+    }
+
+    /// Schedules `entity_key` at `datetime` of the [calendar](Simulation::set_calendar), see [`Simulation::schedule`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if no calendar was set or if `datetime` is before the current date.
+    #[cfg(feature = "chrono")]
+    pub fn schedule_at_datetime<Tz: chrono::TimeZone>(
+        &mut self,
+        datetime: chrono::DateTime<Tz>,
+        entity_key: Key,
+    ) -> Option<EventId> {
+        let calendar = self
+            .calendar
+            .as_ref()
+            .expect("Simulation::schedule_at_datetime called without a calendar");
+        let time = calendar
+            .time_of(&datetime)
+            .and_then(|time| time.checked_sub(self.time()))
+            .unwrap_or_else(|| panic!("{} is in the past", datetime.fixed_offset()));
+        self.schedule(time, entity_key)
+    }
+
+    /// Anchor the clock to dates with `calendar`, see [`Calendar`](crate::Calendar).
+    #[cfg(feature = "chrono")]
+    pub fn set_calendar(&mut self, calendar: crate::Calendar) {
+        self.calendar = Some(calendar);
+    }
+
+    #[cfg(feature = "chrono")]
+    #[must_use]
+    pub fn calendar(&self) -> Option<&crate::Calendar> {
+        self.calendar.as_ref()
+    }
+
+    /// Returns the current date, `None` if no calendar was set.
+    #[cfg(feature = "chrono")]
+    #[must_use]
+    pub fn datetime(&self) -> Option<chrono::DateTime<chrono::FixedOffset>> {
+        self.calendar.as_ref().map(|calendar| calendar.datetime(self.time()))
+    }
+
+    /// Declares `entity_key` for the initialization phase.
+    ///
+    /// Entities declared this way are resumed once, in the order they were declared,
+    /// before any event in the scheduler is executed. The action they yield is handled as usual,
+    /// so an entity that has to keep running at time zero can yield `Action::Hold(Duration::ZERO)`.
+    ///
+    /// An entity declared for initialization shouldn't also be scheduled with [`Simulation::schedule`].
+    pub fn schedule_init(&mut self, entity_key: Key) {
+        if !self.init_queue.contains(&entity_key) {
+            self.init_queue.push_back(entity_key);
+        }
     }
 
     /// Returns the current simulation time.
@@ -69,6 +873,12 @@ where
         self.scheduler.time()
     }
 
+    /// Returns the current simulation time as a `T`, e.g. [`Ticks`](crate::Ticks).
+    #[must_use]
+    pub fn time_as<T: SimTime>(&self) -> T {
+        T::from_duration(self.time())
+    }
+
     #[must_use]
     #[inline]
     pub fn clock(&self) -> crate::scheduler::ClockRef {
@@ -76,156 +886,1348 @@ where
     }
 
     /// Retrieve a copy of the current [EntityState] of the generator asociated with `key`
+    ///
+    /// Entities that completed keep reporting [`EntityState::Completed`] until their slot is reused by another
+    /// entity that completes too.
     #[must_use]
     pub fn entity_state(&self, key: Key) -> Option<EntityState> {
-        self.entities.get_state(key).copied()
+        self.entities.state_of(key)
     }
 
-    /// Advance the simulation one event.
-    pub fn step_with(&mut self, resume_with: R) -> ShouldContinue {
-        if let Some(event_entry) = self.scheduler.pop() {
-            let key = event_entry.key();
-
-            let state = self.entities.step_with(key, resume_with);
-            match state {
-                GeneratorState::Yielded(action) => {
-                    let entity_state = self.entities.get_state_mut(key).unwrap();
-                    match action {
-                        Action::Hold(duration) => {
-                            // TODO: Maybe remove this check. It shouldn't happen.
-                            if let EntityState::Passive = *entity_state {
-                                panic!(
-                                    "A passive entity received a hold command. ID = {}",
-                                    key.id
-                                );
-                            }
-                            self.schedule(duration, key);
-                        }
-                        Action::Passivate => {
-                            // TODO: This check shouldn't happen, a passive generator
-                            // shouldn't be able to send another passivate
-                            match *entity_state {
-                                EntityState::Active => {
-                                    *entity_state = EntityState::Passive;
-                                }
-                                EntityState::Passive => {
-                                    panic!(
-                                        "A passive entity received a passivate command. ID = {}",
-                                        key.id
-                                    );
-                                }
-                            }
-                        }
-                        Action::ActivateOne(other_key) => {
-                            // TODO: This check shouldn't be necessary a passive generator
-                            // shouldn't be able to send an activate.
-                            if let EntityState::Passive = *entity_state {
-                                panic!("A passive entity sended an activate. ID = {}", key.id);
-                            }
-                            self.schedule_now(key);
-
-                            let other_state = self.entities.get_state_mut(other_key).unwrap();
-                            match *other_state {
-                                EntityState::Passive => {
-                                    *other_state = EntityState::Active;
-                                }
-                                EntityState::Active => {
-                                    panic!(
-                                        "Entity ID = {} tried to Activate Entity ID = {} but it was already active",
-                                        key.id,
-                                        other_key.id
-                                    )
-                                }
-                            }
-
-                            self.schedule_now(other_key);
-                        }
-                        Action::ActivateMany(other_keys) => {
-                            if let EntityState::Passive = *entity_state {
-                                panic!("A passive entity sended an activate. ID = {}", key.id);
-                            }
-                            self.schedule_now(key);
-                            for other_key in other_keys {
-                                let other_state = self.entities.get_state_mut(other_key).unwrap();
-                                match *other_state {
-                                    EntityState::Passive => {
-                                        *other_state = EntityState::Active;
-                                    }
-                                    EntityState::Active => {
-                                        panic!(
-                                            "Entity ID = {} tried to Activate Entity ID = {} but it was already active",
-                                            key.id,
-                                            other_key.id
-                                        )
-                                    }
-                                }
-                                self.schedule_now(other_key);
-                            }
-                        }
-                        Action::Cancel(other_key) => {
-                            if let EntityState::Passive = *entity_state {
-                                panic!(
-                                    "A passive entity did a Cancel. ID = {} to ID = {}",
-                                    key.id, other_key.id
-                                );
-                            }
-                            self.schedule_now(key);
-                            
-                            // -----------------------------------
-                            let other_state = self.entities.get_state_mut(other_key).unwrap();
-                            match *other_state {
-                                EntityState::Active => {
-                                    *other_state = EntityState::Passive;
-                                }
-                                EntityState::Passive => {
-                                    panic!(
-                                        "Entity ID = {} sent Cancel to Entity ID = {} but is was in a passive state",
-                                        key.id,
-                                        other_key.id
-                                    )
-                                }
-                            }
-                            // TODO: PROFILE AND OPTIMIZE THIS ENTIRE CHUNK
-
-                            // TODO: Maybe remove this check because if it passed the previous check then an event is guaranteed to exist in the scheduler
-                            // ---------------
-                            if !self.scheduler.remove(other_key) {
-                                panic!("Entity ID = {} send Cancel to ID = {} and it wasn't scheduled", key.id, other_key.id);
-                            };
-                            // ---------------
-                        }
-                    }
-                }
-                GeneratorState::Complete(_) => {
-                    self.entities.remove(key);
-                }
-            }
-            ShouldContinue::Advance
-        } else {
-            ShouldContinue::Break
-        }
+    /// Returns the keys of every entity in the simulation, in slot order.
+    #[must_use]
+    pub fn entity_keys(&self) -> Vec<Key> {
+        let mut keys: Vec<Key> = self.entities.keys().collect();
+        keys.sort_by_key(|key| key.id());
+        keys
     }
 
-    pub fn state(&self) -> Rc<Cell<State>> {
-        Rc::clone(&self.state)
+    /// Returns the pending events ordered by time, events at the same time in the order they were scheduled.
+    #[must_use]
+    pub fn pending_events(&self) -> Vec<EventId> {
+        let mut events = self.scheduler.pending();
+        events.sort_by_key(EventId::time);
+        events
     }
-}
 
-impl Simulation<()> {
+    /// Returns a [`Key`] for the entity referenced by `weak` or `None` if the entity no longer exists.
+    #[must_use]
     #[inline]
-    pub fn step(&mut self) -> ShouldContinue {
-        self.step_with(())
+    pub fn upgrade(&self, weak: WeakKey) -> Option<Key> {
+        self.entities.upgrade(weak)
+    }
+
+    /// Advance the simulation one event.
+    ///
+    /// Returns an error if the entity yielded an invalid action, see [`SimulationError`].
+    ///
+    /// # Panics
+    ///
+    /// Panics instead of returning an error in [strict mode](Simulation::set_strict).
+    #[inline]
+    pub fn step_with(&mut self, resume_with: R) -> Result<StepResult, SimulationError> {
+        self.step_with_provider(|_| resume_with)
     }
 
-    pub fn run_until_empty(&mut self) {
-        while let ShouldContinue::Advance = self.step() {}
+    /// Returns an iterator over the steps of the simulation, resuming each entity with the value returned by
+    /// `provider`, see [`Steps`].
+    pub fn steps_with<F>(&mut self, provider: F) -> Steps<'_, R, F>
+    where
+        F: FnMut(&StepContext) -> R,
+    {
+        Steps::new(self, provider)
     }
 
-    pub fn run_with_limit(&mut self, limit: Duration) {
-        while let ShouldContinue::Advance = self.step() {
-            if self.time() >= limit {
-                break;
+    /// Advance the simulation one event, resuming the entity with the value returned by `provider`.
+    ///
+    /// Entities pending in the initialization phase are resumed before any scheduled event.
+    ///
+    /// # Panics
+    ///
+    /// Panics instead of returning an error in [strict mode](Simulation::set_strict).
+    pub fn step_with_provider<F>(&mut self, provider: F) -> Result<StepResult, SimulationError>
+    where
+        F: FnOnce(&StepContext) -> R,
+    {
+        self.past_breakpoint = false;
+        self.insert_spawned();
+        let next = match self.init_queue.pop_front() {
+            Some(key) => Some(key),
+            None => {
+                self.finish_warm_up();
+                self.scheduler.pop().map(|event_entry| event_entry.key())
             }
-        }
+        };
+        let Some(key) = next else {
+            return Ok(StepResult::exhausted(self.time()));
+        };
+        let resume = self.wake_causes.remove(&key).unwrap_or(Resume::Timer);
+        let resume_with = match self.messages.remove(&key) {
+            Some(message) => message,
+            None => provider(&StepContext {
+                time: self.time(),
+                key,
+                resume,
+            }),
+        };
+
+        let _span = instrumentation::enter_step(self.time(), key);
+        self.current.set(Some(key));
+        let state = self.entities.step_with(key, resume_with);
+        self.current.set(None);
+        self.preemptions.clear(key);
+        self.cancellations.clear(key);
+        debug_assert!(
+            !self.state.is_locked() && !self.module_states.values().any(SharedState::is_locked),
+            "Entity ID = {} yielded while holding a StateGuard",
+            key.id
+        );
+        self.insert_spawned();
+        let mut step = StepResult {
+            time: self.time(),
+            key: Some(key),
+            action: None,
+            entity_finished: false,
+        };
+        let result = match state {
+            GeneratorState::Yielded(action) => {
+                instrumentation::yielded(&action);
+                self.hooks.step(self.scheduler.time(), key, &action);
+                step.action = Some(action.clone());
+                if self.resume_provider.borrow().is_some() {
+                    self.last_actions.borrow_mut().insert(key, action.clone());
+                }
+                self.apply(key, action)
+            }
+            GeneratorState::Complete(_) => {
+                step.entity_finished = true;
+                instrumentation::completed(key);
+                self.hooks.complete(key);
+                self.remove_tree(key);
+                Ok(())
+            }
+        };
+        if result.is_err() {
+            self.set_entity_state(key, EntityState::Failed);
+        }
+        self.wake_rendezvous();
+        match result {
+            Ok(()) => Ok(step),
+            Err(error) if self.strict => match self.names.name_of(error.key()) {
+                Some(name) => panic!("{} ({})", error, name),
+                None => panic!("{}", error),
+            },
+            Err(error) => Err(error),
+        }
+    }
+
+    // Resume the callees called and the callers replied to by the rendezvous since the last step, once passive.
+    fn wake_rendezvous(&mut self) {
+        for rendezvous in self.rendezvous.clone() {
+            let entities = &self.entities;
+            let wakes = rendezvous.take_wakes(&|key| matches!(entities.get_state(key), Some(EntityState::Passive)));
+            for wake in wakes {
+                match wake {
+                    Wake::Callee { key, by } => {
+                        self.schedule_now(key);
+                        self.wake_causes.insert(key, Resume::Activated { by });
+                    }
+                    Wake::Caller { key, reply } => {
+                        self.schedule_now(key);
+                        self.messages.insert(key, reply);
+                    }
+                }
+            }
+        }
+    }
+
+    // Remove `key` and its descendants with everything kept about them, returning the removed entities.
+    fn remove_tree(&mut self, key: Key) -> Vec<Key> {
+        let removed = self.entities.remove_tree(key);
+        for &removed in &removed {
+            self.entities.record_exit(removed, EntityState::Completed);
+            self.scheduler.remove(removed);
+            self.init_queue.retain(|&pending| pending != removed);
+            self.passive_since.remove(&removed);
+            self.local.clear(removed);
+            self.preemptions.clear(removed);
+            self.cancellations.clear(removed);
+            self.signals.clear(removed);
+            self.wake_causes.remove(&removed);
+            self.messages.remove(&removed);
+            self.last_actions.borrow_mut().remove(&removed);
+        }
+        for rendezvous in &self.rendezvous {
+            rendezvous.clear(&removed);
+        }
+        self.release_resources(&removed);
+        self.withdraw_consumers(&removed);
+        removed
+    }
+
+    // Release the units held by the removed entities, activating the requests granted them, after withdrawing the
+    // requests of the removed entities so none of them is granted a unit.
+    fn release_resources(&mut self, removed: &[Key]) {
+        let resources = self.resources.clone();
+        for resource in &resources {
+            resource.withdraw(removed);
+        }
+        for resource in &resources {
+            for &holder in removed {
+                for granted in resource.release_all(holder) {
+                    // Interrupts the hold of a request waiting with a timeout.
+                    self.scheduler.remove(granted);
+                    self.schedule_now(granted);
+                    self.wake_causes.insert(granted, Resume::Activated { by: holder });
+                }
+            }
+        }
+    }
+
+    // Withdraw the removed entities waiting on queues, activating the waiting entities handed the items reserved
+    // for them.
+    fn withdraw_consumers(&mut self, removed: &[Key]) {
+        let handed: Vec<(Key, Key)> = self.queues.iter().flat_map(|queue| queue.withdraw(removed)).collect();
+        for (consumer, previous) in handed {
+            // Entities that didn't passivate yet find the item when they take again.
+            if matches!(self.entities.get_state(consumer), Some(EntityState::Passive)) {
+                self.schedule_now(consumer);
+                self.wake_causes.insert(consumer, Resume::Activated { by: previous });
+            }
+        }
+    }
+
+    /// Kill the entity `key` whatever it's doing: it's removed with its descendants, as if it completed, their
+    /// pending events are discarded and the units of [`Resource`]s they hold released. The [`on_complete`](Simulation::on_complete) hooks are invoked for it.
+    ///
+    /// Returns the keys of every entity removed, empty if `key` no longer exists.
+    pub fn kill(&mut self, key: Key) -> Vec<Key> {
+        if self.entities.get_state(key).is_none() {
+            return Vec::new();
+        }
+        instrumentation::completed(key);
+        self.hooks.complete(key);
+        self.remove_tree(key)
+    }
+
+    // Carry out the action yielded by `key`.
+    // Every check is done before changing anything, so an invalid action leaves the simulation untouched.
+    fn apply(&mut self, key: Key, action: Action) -> Result<(), SimulationError> {
+        let passive = matches!(self.entities.get_state(key), Some(EntityState::Passive));
+        match action {
+            Action::Hold(duration) => {
+                if passive {
+                    return Err(SimulationError::HoldWhilePassive { key });
+                }
+                self.schedule(duration, key);
+                self.set_entity_state(key, EntityState::Holding);
+            }
+            Action::HoldWithPriority(duration, priority) => {
+                if passive {
+                    return Err(SimulationError::HoldWhilePassive { key });
+                }
+                self.schedule_with_priority(duration, key, priority);
+                self.set_entity_state(key, EntityState::Holding);
+            }
+            Action::Passivate => {
+                if passive {
+                    return Err(SimulationError::PassivateWhilePassive { key });
+                }
+                self.set_entity_state(key, EntityState::Passive);
+                self.passive_since.insert(key, (self.time(), None));
+            }
+            Action::ActivateOne(other) => self.activate(key, passive, &[other])?,
+            Action::ActivateMany(others) => self.activate(key, passive, &others)?,
+            Action::Cancel(other) => {
+                if passive {
+                    return Err(SimulationError::CancelWhilePassive { key, other });
+                }
+                // Cancelling an entity that isn't scheduled is a normal race, e.g. with its completion,
+                // so the canceller is told instead of failing.
+                // TODO: PROFILE AND OPTIMIZE THIS
+                let outcome = match self.entities.get_state(other) {
+                    Some(EntityState::Passive | EntityState::Failed) | None => CancelOutcome::NotScheduled,
+                    Some(_) if self.scheduler.remove(other) => CancelOutcome::Cancelled,
+                    Some(_) => CancelOutcome::NotScheduled,
+                };
+                if outcome == CancelOutcome::Cancelled {
+                    self.wake_causes.remove(&other);
+                    self.set_entity_state(other, EntityState::Passive);
+                    self.passive_since.insert(other, (self.time(), Some(key)));
+                }
+                self.cancellations.insert(key, outcome);
+                self.schedule_now(key);
+            }
+            Action::Preempt(other) => {
+                if passive {
+                    return Err(SimulationError::PreemptWhilePassive { key, other });
+                }
+                match self.entities.get_state(other) {
+                    None | Some(EntityState::Failed) => return Err(SimulationError::StaleKey { key, other }),
+                    Some(EntityState::Holding) => {}
+                    Some(_) => return Err(SimulationError::NotHolding { key, other }),
+                }
+                let Some(event) = self.scheduler.event_of(other) else {
+                    return Err(SimulationError::NotHolding { key, other });
+                };
+                self.scheduler.cancel(event);
+                let remaining = event.time().saturating_sub(self.time());
+                self.preemptions.insert(other, remaining);
+                self.schedule_now(key);
+                self.schedule_now(other);
+                let reason = Interrupt::Preempted { by: key, remaining };
+                self.wake_causes.insert(other, Resume::Interrupted { reason });
+            }
+            Action::Broadcast(topic, payload) => {
+                if passive {
+                    return Err(SimulationError::BroadcastWhilePassive { key });
+                }
+                let subscribers = self.signals.broadcast(Signal {
+                    topic,
+                    payload,
+                    from: key,
+                });
+                self.schedule_now(key);
+                for other in subscribers {
+                    if matches!(self.entities.get_state(other), Some(EntityState::Passive)) {
+                        self.schedule_now(other);
+                        self.wake_causes.insert(other, Resume::Activated { by: key });
+                    }
+                }
+            }
+            Action::Terminate(other) => {
+                if passive {
+                    return Err(SimulationError::TerminateWhilePassive { key, other });
+                }
+                // Like a cancel, terminating an entity that already completed isn't an error.
+                self.kill(other);
+                self.schedule_now(key);
+            }
+        }
+        Ok(())
+    }
+
+    fn activate(&mut self, key: Key, passive: bool, others: &[Key]) -> Result<(), SimulationError> {
+        if passive {
+            return Err(SimulationError::ActivateWhilePassive { key });
+        }
+        for (index, &other) in others.iter().enumerate() {
+            match self.entities.get_state(other) {
+                None | Some(EntityState::Failed) => return Err(SimulationError::StaleKey { key, other }),
+                // Activating the same entity twice in one action is a double activation too.
+                Some(EntityState::Passive) if others[..index].contains(&other) => {
+                    return Err(SimulationError::AlreadyActive { key, other })
+                }
+                Some(EntityState::Passive) => {}
+                Some(_) => return Err(SimulationError::AlreadyActive { key, other }),
+            }
+        }
+        self.schedule_now(key);
+        for &other in others {
+            self.schedule_now(other);
+            self.wake_causes.insert(other, Resume::Activated { by: key });
+        }
+        Ok(())
+    }
+
+    fn set_entity_state(&mut self, key: Key, state: EntityState) {
+        if let Some(entity_state) = self.entities.get_state_mut(key) {
+            *entity_state = state;
+        }
+    }
+
+    /// Returns the time and the entity of the next step without executing it, `None` if no more events are left.
+    ///
+    /// Entities spawned since the last step are scheduled first, as the step would do.
+    pub fn peek(&mut self) -> Option<(Duration, Key)> {
+        self.insert_spawned();
+        match self.init_queue.front() {
+            Some(&key) => Some((self.time(), key)),
+            None => self.scheduler.peek(),
+        }
+    }
+
+    /// Returns how long until `key` is resumed, `None` if it isn't scheduled, e.g. because it's passive.
+    #[must_use]
+    pub fn time_until_next_event(&self, key: Key) -> Option<Duration> {
+        if self.init_queue.contains(&key) {
+            return Some(Duration::ZERO);
+        }
+        self.scheduler
+            .event_of(key)
+            .map(|event| event.time().saturating_sub(self.time()))
+    }
+
+    /// Returns the time at which the next step will be executed, `None` if no more events are left.
+    #[must_use]
+    pub fn next_event_time(&self) -> Option<Duration> {
+        if !self.init_queue.is_empty() || self.spawner.has_pending() {
+            Some(self.time())
+        } else {
+            self.scheduler.next_event_time()
+        }
+    }
+
+    // Insert and schedule the entities created through the `Spawner` since the last call.
+    fn insert_spawned(&mut self) {
+        for spawned in self.spawner.take_pending() {
+            self.entities.insert(spawned.key, spawned.gen);
+            if let Some(parent) = spawned.parent {
+                self.entities.set_parent(spawned.key, parent);
+            }
+            self.schedule_now(spawned.key);
+        }
+    }
+
+    /// Returns the outcomes of the [`Action::Cancel`] yielded by the entities.
+    #[must_use]
+    pub fn cancellations(&self) -> Cancellations {
+        self.cancellations.clone()
+    }
+
+    /// Returns the topics entities subscribe to, to receive [`Action::Broadcast`] signals.
+    #[must_use]
+    pub fn signals(&self) -> Signals {
+        self.signals.clone()
+    }
+
+    /// Returns the record of the holds interrupted by [`Action::Preempt`].
+    #[must_use]
+    pub fn preemptions(&self) -> Preemptions {
+        self.preemptions.clone()
+    }
+
+    /// Returns the state shared by the simulation and its entities.
+    #[must_use]
+    pub fn state(&self) -> SharedState {
+        self.state.clone()
+    }
+
+    /// Returns the [`Module`] named `name`, a scope namespacing the entities, components and state added through it.
+    pub fn module(&mut self, name: &str) -> Module<'_, R> {
+        Module::new(self, name.to_owned())
+    }
+
+    pub(crate) fn module_state(&mut self, path: &str) -> SharedState {
+        self.module_states.entry(path.to_owned()).or_default().clone()
+    }
+
+    /// Returns the metadata describing this run.
+    #[must_use]
+    pub fn metadata(&self) -> Ref<'_, RunMetadata> {
+        self.metadata.borrow()
+    }
+
+    #[must_use]
+    pub fn metadata_mut(&self) -> RefMut<'_, RunMetadata> {
+        self.metadata.borrow_mut()
+    }
+
+    /// Returns the random number generator of the simulation, seeded with zero unless [`Simulation::set_seed`] is called.
+    #[must_use]
+    pub fn rng(&self) -> SimRng {
+        self.rng.clone()
+    }
+
+    /// Returns the random number stream named `name`, declared in the [`RngStreams`] of the simulation.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the stream wasn't declared, so a misspelled name can't silently break common random numbers.
+    #[must_use]
+    pub fn stream(&self, name: &str) -> SimRng {
+        self.streams
+            .get(name)
+            .unwrap_or_else(|| panic!("Unknown random number stream `{}`", name))
+    }
+
+    /// Restart the random number generator and every stream from `seed` and record it in the [`RunMetadata`].
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng.reseed(seed);
+        self.streams.reseed(seed);
+        self.metadata.borrow_mut().set_seed(seed);
+    }
+
+    /// Start recording the decisions of the run into a [`CheckpointRecorder`].
+    pub fn record_checkpoints(&mut self) -> CheckpointRecorder {
+        let recorder = CheckpointRecorder::new(Rc::clone(&self.metadata));
+        let on_step = recorder.clone();
+        self.on_step(move |time, key, action| {
+            on_step.record(Decision::yielded(time, key, action));
+        });
+        let on_complete = recorder.clone();
+        let clock = self.clock();
+        self.on_complete(move |key| {
+            on_complete.record(Decision::completed(clock.time(), key));
+        });
+        recorder
+    }
+
+    /// Bring a freshly built model to the point of `checkpoint` by replaying its decisions,
+    /// resuming each entity with the value returned by `provider`.
+    ///
+    /// The model has to be built from [`Checkpoint::metadata`] exactly as the original run, the replay stops
+    /// with an error at the first decision that doesn't match. Returns a recorder of the restored run
+    /// that already holds the replayed decisions.
+    pub fn restore_with<F>(&mut self, checkpoint: &Checkpoint, mut provider: F) -> Result<CheckpointRecorder, CheckpointError>
+    where
+        F: FnMut(&StepContext) -> R,
+    {
+        let recorder = self.record_checkpoints();
+        for (step, expected) in checkpoint.decisions().iter().enumerate() {
+            // Invalid actions are decisions too, replaying them gives the same error.
+            if matches!(self.step_with_provider(&mut provider), Ok(step) if !step.should_continue()) {
+                return Err(CheckpointError::Ended { step });
+            }
+            let found = recorder.last().expect("Recorded by the step");
+            if found != *expected {
+                return Err(CheckpointError::Diverged {
+                    step,
+                    expected: expected.clone(),
+                    found,
+                });
+            }
+        }
+        Ok(recorder)
+    }
+
+    /// Drive the model through the steps of `trace`, checking that every step has the same time, entity and action,
+    /// resuming each entity with the value returned by `provider`.
+    ///
+    /// Meant to catch nondeterminism and regressions: build the model again as it was when `trace` was recorded
+    /// and replay it. Stops at the first [`Divergence`], otherwise returns the number of replayed steps.
+    pub fn replay_with<F>(&mut self, trace: &[TraceEvent], mut provider: F) -> Result<usize, Box<Divergence>>
+    where
+        F: FnMut(&StepContext) -> R,
+    {
+        let recorder = self.record_trace();
+        for (step, expected) in trace.iter().enumerate() {
+            let found = match self.step_with_provider(&mut provider) {
+                Ok(step) if !step.should_continue() => None,
+                _ => recorder.last(),
+            };
+            Divergence::check(step, expected, found)?;
+        }
+        Ok(trace.len())
+    }
+
+    /// Start recording every action yielded and every completion into a [`TraceRecorder`].
+    pub fn record_trace(&mut self) -> TraceRecorder {
+        let recorder = TraceRecorder::new(Rc::clone(&self.metadata), self.names.clone());
+        let on_step = recorder.clone();
+        self.on_step(move |time, key, action| {
+            on_step.record(time, key, TraceEventKind::Yielded(action.clone()));
+        });
+        let on_complete = recorder.clone();
+        let clock = self.clock();
+        self.on_complete(move |key| {
+            on_complete.record(clock.time(), key, TraceEventKind::Completed);
+        });
+        recorder
+    }
+
+    /// Record the values of the [`State`](crate::State) variable `key`, converted by `sample`, over simulated time.
+    ///
+    /// The variable is sampled now and after every step, the [`Timeline`] keeps a point each time it changes.
+    /// Steps where the variable was removed from the state are skipped.
+    pub fn record_timeline<V: 'static>(
+        &mut self,
+        name: impl Into<String>,
+        key: StateKey<V>,
+        sample: impl Fn(&V) -> f64 + 'static,
+    ) -> Timeline {
+        let timeline = Timeline::new(&name.into());
+        let state = self.state();
+        let clock = self.clock();
+        let record = {
+            let timeline = timeline.clone();
+            move || {
+                if let Some(value) = state.with(|state| state.get(key).map(&sample)) {
+                    timeline.sample(clock.time(), value);
+                }
+            }
+        };
+        record();
+        let on_complete = Rc::new(record);
+        let on_step = Rc::clone(&on_complete);
+        self.on_step(move |_, _, _| on_step());
+        self.on_complete(move |_| on_complete());
+        timeline
+    }
+
+    /// Returns a [`RunHandle`] that can pause the ongoing run from callbacks or generators.
+    #[must_use]
+    pub fn run_handle(&self) -> RunHandle {
+        self.run_handle.clone()
+    }
+
+    /// Advance the simulation with the time limit and real-time mode set through the [`SimulationBuilder`],
+    /// until no more events are left if there is no limit.
+    ///
+    /// Each entity is resumed with the value returned by `provider`.
+    pub fn run_with<F>(&mut self, provider: F) -> RunStatus
+    where
+        F: FnMut(&StepContext) -> R,
+    {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(runner) = self.real_time {
+            let limit = self.time_limit.unwrap_or(Duration::MAX);
+            return runner.run_with_limit_with(self, limit, provider);
+        }
+        match self.time_limit {
+            Some(limit) => self.run_with_limit_with(limit, provider),
+            None => self.run_until_empty_with(provider),
+        }
+    }
+
+    /// Returns the trace recorded since the simulation was built with
+    /// [`SimulationBuilder::record_trace`](crate::SimulationBuilder::record_trace).
+    #[must_use]
+    pub fn trace(&self) -> Option<TraceRecorder> {
+        self.trace.clone()
+    }
+
+    /// Returns the sampler installed when the simulation was built with
+    /// [`SimulationBuilder::sample_every`](crate::SimulationBuilder::sample_every).
+    #[must_use]
+    pub fn sampler(&self) -> Option<Sampler> {
+        self.sampler.clone()
+    }
+
+    /// Advance the simulation until no more events are left.
+    ///
+    /// Each entity is resumed with the value returned by `provider`.
+    pub fn run_until_empty_with<F>(&mut self, provider: F) -> RunStatus
+    where
+        F: FnMut(&StepContext) -> R,
+    {
+        self.drive(provider, |_| false)
+    }
+
+    /// Advance the simulation until `limit` is reached or no more events are left.
+    ///
+    /// Each entity is resumed with the value returned by `provider`.
+    pub fn run_with_limit_with<F>(&mut self, limit: impl SimTime, provider: F) -> RunStatus
+    where
+        F: FnMut(&StepContext) -> R,
+    {
+        let limit = limit.to_duration();
+        self.drive(provider, |simulation| simulation.time() >= limit)
+    }
+
+    /// Advance the simulation until `budget` of wall-clock time has elapsed or no more events are left, returning
+    /// [`RunStatus::LimitReached`] if the budget ran out. How far the run got is given by [`Simulation::time`].
+    ///
+    /// The budget is checked after every event, so a single step running longer than the budget isn't interrupted.
+    /// Each entity is resumed with the value returned by `provider`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn run_with_wallclock_budget_with<F>(&mut self, budget: Duration, provider: F) -> RunStatus
+    where
+        F: FnMut(&StepContext) -> R,
+    {
+        let start = Instant::now();
+        self.drive(provider, |_| start.elapsed() >= budget)
+    }
+
+    /// Advance the simulation like [`Simulation::run_with_limit_with`], calling `report` with the [`Progress`] of
+    /// the run every `every` of wall-clock time, and once more when the run ends.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn run_with_limit_and_progress_with<F>(
+        &mut self,
+        limit: impl SimTime,
+        every: Duration,
+        provider: F,
+        mut report: impl FnMut(&Progress),
+    ) -> RunStatus
+    where
+        F: FnMut(&StepContext) -> R,
+    {
+        let limit = limit.to_duration();
+        let start = Instant::now();
+        let mut last_report = start;
+        let mut events = 0;
+        let status = self.drive(provider, |simulation| {
+            events += 1;
+            let now = Instant::now();
+            if now.duration_since(last_report) >= every {
+                last_report = now;
+                report(&Progress {
+                    time: simulation.time(),
+                    events,
+                    elapsed: now.duration_since(start),
+                });
+            }
+            simulation.time() >= limit
+        });
+        report(&Progress {
+            time: self.time(),
+            events,
+            elapsed: start.elapsed(),
+        });
+        status
+    }
+
+    /// Advance the simulation at most `count` events.
+    ///
+    /// Returns the number of events executed, which is lower than `count` only if the scheduler ran out of events,
+    /// the run was paused, it stopped at a [breakpoint](Simulation::set_breakpoint) or an entity yielded an invalid
+    /// action. The event where the invalid action was yielded isn't counted, even though it was consumed, and a
+    /// breakpoint hit before an event leaves it pending.
+    /// Each entity is resumed with the value returned by `provider`.
+    pub fn step_n_with<F>(&mut self, count: usize, provider: F) -> usize
+    where
+        F: FnMut(&StepContext) -> R,
+    {
+        let mut executed = 0;
+        if count > 0 {
+            self.drive(provider, |_| {
+                executed += 1;
+                executed >= count
+            });
+        }
+        executed
+    }
+
+    /// Advance the simulation for `delta` of simulated time from the current time
+    /// or until no more events are left.
+    ///
+    /// Events later than the horizon are left pending and the clock is moved to the horizon, so consecutive runs
+    /// advance it by exactly their `delta`.
+    /// Each entity is resumed with the value returned by `provider`.
+    pub fn run_for_with<F>(&mut self, delta: impl SimTime, provider: F) -> RunStatus
+    where
+        F: FnMut(&StepContext) -> R,
+    {
+        let horizon = self.time() + delta.to_duration();
+        let past_horizon = |simulation: &Self| matches!(simulation.next_event_time(), Some(next) if next > horizon);
+        let status = if past_horizon(self) {
+            RunStatus::LimitReached
+        } else {
+            self.drive(provider, past_horizon)
+        };
+        if status == RunStatus::LimitReached {
+            self.advance_to(horizon);
+        }
+        status
+    }
+
+    /// Advance the simulation until `stop` returns `true` or no more events are left.
+    ///
+    /// `stop` is evaluated after each step with access to the whole simulation (time, state, entity states).
+    /// Each entity is resumed with the value returned by `provider`.
+    pub fn run_until_with<F, P>(&mut self, provider: F, stop: P) -> RunStatus
+    where
+        F: FnMut(&StepContext) -> R,
+        P: FnMut(&Self) -> bool,
+    {
+        self.drive(provider, stop)
+    }
+
+    /// Returns the entities that are passive with no events left to activate them,
+    /// `None` if the scheduler isn't empty or no entity is passive.
+    ///
+    /// A run ending this way is usually a modeling bug: every entity is waiting on another one.
+    #[must_use]
+    pub fn deadlock(&self) -> Option<Deadlock> {
+        if self.next_event_time().is_some() || self.passive_since.is_empty() {
+            return None;
+        }
+        let entities = self
+            .passive_since
+            .iter()
+            .map(|(&key, &(since, cancelled_by))| PassiveEntity {
+                key,
+                since,
+                cancelled_by,
+            })
+            .collect();
+        Some(Deadlock::new(self.time(), entities))
+    }
+
+    // Status of a run that ran out of events.
+    pub(crate) fn exhausted_status(&self) -> RunStatus {
+        match self.deadlock() {
+            Some(deadlock) => {
+                instrumentation::deadlocked(&deadlock);
+                RunStatus::Deadlocked
+            }
+            None => RunStatus::Exhausted,
+        }
+    }
+
+    pub(crate) fn take_pause_request(&self) -> bool {
+        self.run_handle.take_pause_request()
+    }
+
+    /// Register the closure computing the value each entity is resumed with from its key, the action it yielded last
+    /// (`None` the first time it's resumed) and the current time, used through [`Simulation::resume_provider`].
+    pub fn set_resume_provider(&mut self, provider: impl FnMut(Key, Option<&Action>, Duration) -> R + 'static) {
+        *self.resume_provider.borrow_mut() = Some(Box::new(provider));
+    }
+
+    pub fn clear_resume_provider(&mut self) {
+        self.resume_provider.borrow_mut().take();
+        self.last_actions.borrow_mut().clear();
+    }
+
+    /// Returns a provider for the run methods calling the closure registered with
+    /// [`Simulation::set_resume_provider`], so a model sets it up once:
+    ///
+    /// ```ignore
+    /// simulation.run_until_empty_with(simulation.resume_provider());
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// The provider panics if no closure is registered when an entity is resumed.
+    pub fn resume_provider(&self) -> impl FnMut(&StepContext) -> R + 'static {
+        let provider = Rc::clone(&self.resume_provider);
+        let last_actions = Rc::clone(&self.last_actions);
+        move |context| {
+            let mut provider = provider.borrow_mut();
+            let provider = provider.as_mut().expect("no resume provider is registered");
+            provider(context.key(), last_actions.borrow().get(&context.key()), context.time())
+        }
+    }
+
+    /// End the run by resuming every live entity one last time, with the value returned by `provider` for
+    /// [`Resume::SimulationEnding`], so entities abandoned mid-hold or passive can flush their partial work.
+    ///
+    /// Entities are resumed in the order they were added and their actions are ignored. Afterwards they are
+    /// removed like [killed](Simulation::kill) entities, along with their pending events; entities that were never
+    /// scheduled are removed without being resumed.
+    ///
+    /// Returns the number of entities resumed.
+    pub fn finalize_with<F>(&mut self, mut provider: F) -> usize
+    where
+        F: FnMut(&StepContext) -> R,
+    {
+        self.insert_spawned();
+        let mut keys: Vec<Key> = self.entities.keys().collect();
+        keys.sort_by_key(|key| key.id());
+        let mut finalized = 0;
+        for &key in &keys {
+            if !matches!(
+                self.entities.get_state(key),
+                Some(EntityState::Scheduled | EntityState::Holding | EntityState::Passive)
+            ) {
+                continue;
+            }
+            let resume_with = provider(&StepContext {
+                time: self.time(),
+                key,
+                resume: Resume::SimulationEnding,
+            });
+            let _span = instrumentation::enter_step(self.time(), key);
+            self.current.set(Some(key));
+            let _ = self.entities.step_with(key, resume_with);
+            self.current.set(None);
+            finalized += 1;
+        }
+        // Entities spawned while finalizing are never started.
+        self.spawner.take_pending();
+        for key in keys {
+            self.kill(key);
+        }
+        finalized
+    }
+
+    // Loop shared by every run method.
+    // Steps until no more events are left, `stop` returns `true` or a pause is requested through the `RunHandle`.
+    fn drive<F, P>(&mut self, mut provider: F, mut stop: P) -> RunStatus
+    where
+        F: FnMut(&StepContext) -> R,
+        P: FnMut(&Self) -> bool,
+    {
+        loop {
+            if let Some(breakpoint) = self.breakpoint_before() {
+                return RunStatus::Breakpoint(breakpoint);
+            }
+            let advanced = self.step_with_provider(&mut provider);
+            let paused = self.take_pause_request();
+            let hit = match advanced {
+                Ok(step) if !step.should_continue() => return self.exhausted_status(),
+                Err(error) => return RunStatus::Failed(error),
+                Ok(step) => self.breakpoint_after(&step),
+            };
+            // A pause requested on the step that meets the stop condition is still reported.
+            let stopped = stop(self);
+            if paused {
+                return RunStatus::Paused;
+            }
+            if stopped {
+                return RunStatus::LimitReached;
+            }
+            if let Some(breakpoint) = hit {
+                return RunStatus::Breakpoint(breakpoint);
+            }
+        }
+    }
+}
+
+impl Simulation<()> {
+    #[inline]
+    pub fn step(&mut self) -> Result<StepResult, SimulationError> {
+        self.step_with(())
+    }
+
+    /// Returns an iterator over the steps of the simulation, see [`Steps`].
+    pub fn steps(&mut self) -> Steps<'_, (), fn(&StepContext)> {
+        self.steps_with(|_| ())
+    }
+
+    /// Drive the model through the steps of `trace`, see [`Simulation::replay_with`].
+    pub fn replay(&mut self, trace: &[TraceEvent]) -> Result<usize, Box<Divergence>> {
+        self.replay_with(trace, |_| ())
+    }
+
+    /// Bring a freshly built model to the point of `checkpoint`, see [`Simulation::restore_with`].
+    pub fn restore(&mut self, checkpoint: &Checkpoint) -> Result<CheckpointRecorder, CheckpointError> {
+        self.restore_with(checkpoint, |_| ())
+    }
+
+    /// Advance the simulation as configured by the [`SimulationBuilder`], see [`Simulation::run_with`].
+    pub fn run(&mut self) -> RunStatus {
+        self.run_with(|_| ())
+    }
+
+    pub fn run_until_empty(&mut self) -> RunStatus {
+        self.run_until_empty_with(|_| ())
+    }
+
+    pub fn run_with_limit(&mut self, limit: impl SimTime) -> RunStatus {
+        self.run_with_limit_with(limit, |_| ())
+    }
+
+    /// Advance the simulation until `budget` of wall-clock time has elapsed, see
+    /// [`Simulation::run_with_wallclock_budget_with`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn run_with_wallclock_budget(&mut self, budget: Duration) -> RunStatus {
+        self.run_with_wallclock_budget_with(budget, |_| ())
+    }
+
+    /// Advance the simulation until `limit` is reached, reporting its progress, see
+    /// [`Simulation::run_with_limit_and_progress_with`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn run_with_limit_and_progress(
+        &mut self,
+        limit: impl SimTime,
+        every: Duration,
+        report: impl FnMut(&Progress),
+    ) -> RunStatus {
+        self.run_with_limit_and_progress_with(limit, every, |_| (), report)
+    }
+
+    /// Advance the simulation at most `count` events, returning the number executed, see
+    /// [`Simulation::step_n_with`].
+    pub fn step_n(&mut self, count: usize) -> usize {
+        self.step_n_with(count, |_| ())
+    }
+
+    /// Advance the simulation for `delta` of simulated time from the current time
+    /// or until no more events are left, see [`Simulation::run_for_with`].
+    pub fn run_for(&mut self, delta: impl SimTime) -> RunStatus {
+        self.run_for_with(delta, |_| ())
+    }
+
+    /// Advance the simulation until `stop` returns `true` or no more events are left.
+    ///
+    /// `stop` is evaluated after each step with access to the whole simulation (time, state, entity states).
+    pub fn run_until<P>(&mut self, stop: P) -> RunStatus
+    where
+        P: FnMut(&Self) -> bool,
+    {
+        self.run_until_with(|_| (), stop)
+    }
+}
+
+/// Simulations whose entities are resumed with the [`Resume`] cause of every step, run with
+/// [`Resume::from_context`] as the provider.
+impl<T: 'static> Simulation<Resume<T>> {
+    /// Add a [`Rendezvous`] for calls with requests of type `Q` answered with replies of type `T`, registered as a
+    /// component under `name`. Its response time is attached as a statistic named `"<name> response time"`.
+    pub fn add_rendezvous<Q: 'static>(&mut self, name: impl Into<String>) -> Rendezvous<Q, T> {
+        let name = name.into();
+        self.register_component::<Rendezvous<Q, T>>(name.clone(), ComponentKind::Channel);
+        let rendezvous = Rendezvous::new(&name, self.clock(), Rc::clone(&self.current));
+        self.add_statistic(format!("{} response time", name), rendezvous.response_time());
+        self.rendezvous.push(Rc::new(rendezvous.clone()));
+        rendezvous
+    }
+
+    /// Resume the passive entity `key` now with `Resume::Message(message)`, instead of the value of the provider.
+    ///
+    /// Returns the message back if the entity isn't passive.
+    pub fn send_message(&mut self, key: Key, message: T) -> Result<(), T> {
+        if self.entity_state(key) != Some(EntityState::Passive) {
+            return Err(message);
+        }
+        self.schedule_now(key);
+        self.messages.insert(key, Resume::Message(message));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+
+    // Records `name` every time it's resumed, then completes.
+    fn recorder(name: &'static str, order: &Rc<RefCell<Vec<&'static str>>>) -> GenBoxed<()> {
+        let order = Rc::clone(order);
+        process(move |_| {
+            order.borrow_mut().push(name);
+            None
+        })
+    }
+
+    #[test]
+    fn init_entities_run_first_in_declaration_order() {
+        let mut simulation = Simulation::default();
+        let order = Rc::new(RefCell::new(Vec::new()));
+        let scheduled = simulation.add_generator(recorder("scheduled", &order));
+        let second = simulation.add_generator(recorder("second", &order));
+        let first = simulation.add_generator(recorder("first", &order));
+        simulation.schedule_now(scheduled);
+        simulation.schedule_init(first);
+        simulation.schedule_init(second);
+        // Declaring an entity twice keeps its first place.
+        simulation.schedule_init(first);
+        simulation.run_until_empty();
+
+        assert_eq!(vec!["first", "second", "scheduled"], *order.borrow());
+    }
+
+    // Ticks every second until time 5, pausing the run when it resumes at time 2 if `pause` is set.
+    fn ticking(pause: bool) -> (Simulation<()>, StateKey<Vec<u64>>) {
+        let mut simulation = Simulation::default();
+        let ticks = simulation.state().with_mut(|state| state.insert(Vec::new()));
+        let state = simulation.state();
+        let clock = simulation.clock();
+        let handle = simulation.run_handle();
+        let key = simulation.add_generator(process(move |_| {
+            let now = clock.time().as_secs();
+            state.with_mut(|state| state.get_mut(ticks).unwrap().push(now));
+            if pause && now == 2 {
+                handle.pause();
+            }
+            (now < 5).then_some(Action::Hold(Duration::from_secs(1)))
+        }));
+        simulation.schedule_now(key);
+        (simulation, ticks)
+    }
+
+    #[test]
+    fn pauses_are_reported_when_the_limit_is_reached_on_the_same_step() {
+        let (mut simulation, ticks) = ticking(true);
+        assert_eq!(RunStatus::Paused, simulation.run_with_limit(Duration::from_secs(2)));
+        assert_eq!(Duration::from_secs(2), simulation.time());
+        assert!(!simulation.run_handle().is_pause_requested());
+
+        assert_eq!(RunStatus::Exhausted, simulation.run());
+        let ticks = simulation.state().with(|state| state.get(ticks).unwrap().clone());
+        assert_eq!(vec![0, 1, 2, 3, 4, 5], ticks);
+    }
+
+    #[test]
+    fn paused_runs_resume_to_the_same_end() {
+        let (mut reference, reference_ticks) = ticking(false);
+        assert_eq!(RunStatus::Exhausted, reference.run());
+
+        let (mut simulation, ticks) = ticking(true);
+        assert_eq!(RunStatus::Paused, simulation.run());
+        assert_eq!(Duration::from_secs(2), simulation.time());
+        assert!(!simulation.run_handle().is_pause_requested());
+        assert_eq!(RunStatus::Exhausted, simulation.run());
+
+        assert_eq!(reference.time(), simulation.time());
+        let ticks = simulation.state().with(|state| state.get(ticks).unwrap().clone());
+        assert_eq!(reference.state().with(|state| state.get(reference_ticks).unwrap().clone()), ticks);
+        assert_eq!(vec![0, 1, 2, 3, 4, 5], ticks);
+    }
+
+    #[test]
+    fn run_methods_resume_entities_with_the_values_of_the_provider() {
+        let received = Rc::new(RefCell::new(Vec::new()));
+        let build = || {
+            let mut simulation = Simulation::<u64>::default();
+            // Never scheduled, so the entity below has the key 1.
+            simulation.add_generator(process(|_| None));
+            let recorded = Rc::clone(&received);
+            let mut holds = 0;
+            let key = simulation.add_generator(process(move |value: u64| {
+                recorded.borrow_mut().push(value);
+                holds += 1;
+                (holds <= 3).then_some(Action::Hold(Duration::from_secs(2)))
+            }));
+            simulation.schedule_now(key);
+            simulation
+        };
+        // The provider sees the time and the entity of every step.
+        let provider = |context: &StepContext| context.time().as_secs() * 100 + context.key().id() as u64;
+
+        let mut simulation = build();
+        assert_eq!(RunStatus::LimitReached, simulation.run_with_limit_with(Duration::from_secs(3), provider));
+        assert_eq!(vec![1, 201, 401], *received.borrow());
+        received.borrow_mut().clear();
+
+        let mut simulation = build();
+        assert_eq!(RunStatus::Exhausted, simulation.run_until_empty_with(provider));
+        assert_eq!(vec![1, 201, 401, 601], *received.borrow());
+    }
+
+    // Holds 3 seconds, four times.
+    fn holding() -> GenBoxed<()> {
+        let mut holds = 0;
+        process(move |_| {
+            holds += 1;
+            (holds <= 4).then_some(Action::Hold(Duration::from_secs(3)))
+        })
+    }
+
+    #[test]
+    fn step_n_counts_the_executed_events() {
+        let mut simulation = Simulation::default();
+        let key = simulation.add_generator(holding());
+        simulation.schedule_now(key);
+        assert_eq!(0, simulation.step_n(0));
+        assert_eq!(2, simulation.step_n(2));
+        assert_eq!(Duration::from_secs(3), simulation.time());
+
+        // A breakpoint before an event leaves it pending.
+        simulation.set_breakpoint(Breakpoint::time(Duration::from_secs(9)));
+        assert_eq!(1, simulation.step_n(10));
+        assert_eq!(Some(Duration::from_secs(9)), simulation.next_event_time());
+        assert_eq!(2, simulation.step_n(10));
+        assert_eq!(Duration::from_secs(12), simulation.time());
+
+        // The event of an invalid action isn't counted.
+        let mut simulation = Simulation::default();
+        let key = simulation.add_generator(process(|_| Some(Action::ActivateOne(Key::with_generation(0, 1)))));
+        simulation.schedule_now(key);
+        assert_eq!(0, simulation.step_n(3));
+        assert_eq!(Some(EntityState::Failed), simulation.entity_state(key));
+        assert_eq!(None, simulation.next_event_time());
+    }
+
+    #[test]
+    fn run_for_stops_at_the_horizon() {
+        let mut simulation = Simulation::default();
+        let key = simulation.add_generator(holding());
+        simulation.schedule(Duration::from_secs(1), key);
+        assert_eq!(RunStatus::LimitReached, simulation.run_for(Duration::from_secs(2)));
+        // Events at 1, 4, 7, 10 and 13, the one at 4 is past the horizon.
+        assert_eq!(Duration::from_secs(2), simulation.time());
+        assert_eq!(Some(Duration::from_secs(4)), simulation.next_event_time());
+        assert_eq!(RunStatus::LimitReached, simulation.run_for(Duration::from_secs(1)));
+        assert_eq!(Duration::from_secs(3), simulation.time());
+        assert_eq!(RunStatus::LimitReached, simulation.run_for(Duration::from_secs(4)));
+        assert_eq!(Duration::from_secs(7), simulation.time());
+        assert_eq!(Some(Duration::from_secs(10)), simulation.next_event_time());
+        assert_eq!(RunStatus::Exhausted, simulation.run_for(Duration::from_secs(100)));
+        assert_eq!(Duration::from_secs(13), simulation.time());
+    }
+
+    #[test]
+    fn run_until_stops_after_the_step_meeting_the_condition() {
+        let build = || {
+            let mut simulation = Simulation::default();
+            let served = simulation.state().with_mut(|state| state.insert(0u32));
+            let state = simulation.state();
+            let key = simulation.add_generator(process(move |_| {
+                let served = state.with_mut(|state| {
+                    let served = state.get_mut(served).unwrap();
+                    *served += 1;
+                    *served
+                });
+                (served < 5).then_some(Action::Hold(Duration::from_secs(2)))
+            }));
+            simulation.schedule(Duration::from_secs(1), key);
+            (simulation, served)
+        };
+        let served_at_least = |served, count| {
+            move |simulation: &Simulation<()>| simulation.state().with(|state| *state.get(served).unwrap() >= count)
+        };
+
+        let (mut simulation, served) = build();
+        assert_eq!(RunStatus::LimitReached, simulation.run_until(served_at_least(served, 3)));
+        // Stopped right after the third customer, the next one is still pending.
+        assert_eq!(Duration::from_secs(5), simulation.time());
+        assert_eq!(Some(Duration::from_secs(7)), simulation.next_event_time());
+        // The condition is only checked after a step, so it stops again one step later.
+        assert_eq!(RunStatus::LimitReached, simulation.run_until(served_at_least(served, 3)));
+        assert_eq!(Duration::from_secs(7), simulation.time());
+
+        let (mut simulation, served) = build();
+        assert_eq!(RunStatus::Exhausted, simulation.run_until(served_at_least(served, 10)));
+        assert_eq!(Duration::from_secs(9), simulation.time());
+        assert_eq!(5, simulation.state().with(|state| *state.get(served).unwrap()));
+    }
+
+    #[test]
+    fn removed_entities_release_their_resources() {
+        let mut simulation = Simulation::default();
+        let machine = simulation.add_resource("machine", 1);
+        let granted = Rc::new(RefCell::new(Vec::new()));
+
+        let parent = simulation.add_generator(process(|_| Some(Action::Passivate)));
+        let holder = {
+            let machine = machine.clone();
+            let mut requested = false;
+            process(move |_| {
+                if requested {
+                    return Some(Action::Hold(Duration::from_secs(1)));
+                }
+                requested = true;
+                assert!(machine.request());
+                Some(Action::Hold(Duration::from_secs(10)))
+            })
+        };
+        let holder = simulation.add_child(parent, holder);
+        // Queued first, but removed with its parent before getting the unit.
+        let queued = {
+            let machine = machine.clone();
+            process(move |_| (!machine.request()).then_some(Action::Passivate))
+        };
+        let queued = simulation.add_child(parent, queued);
+        let waiter = {
+            let (machine, granted, clock) = (machine.clone(), Rc::clone(&granted), simulation.clock());
+            let mut requested = false;
+            process(move |_| {
+                if !requested {
+                    requested = true;
+                    return (!machine.request()).then_some(Action::Passivate);
+                }
+                granted.borrow_mut().push(clock.time().as_secs());
+                assert_eq!(None, machine.release());
+                None
+            })
+        };
+        let waiter = simulation.add_generator(waiter);
+        let mut terminated = false;
+        let killer = simulation.add_generator(process(move |_| {
+            if terminated {
+                return None;
+            }
+            terminated = true;
+            Some(Action::Terminate(parent))
+        }));
+        simulation.schedule_now_all([parent, holder]);
+        simulation.schedule(Duration::from_secs(1), queued);
+        simulation.schedule(Duration::from_secs(2), waiter);
+        simulation.schedule(Duration::from_secs(3), killer);
+
+        assert_eq!(RunStatus::Exhausted, simulation.run_until_empty());
+        assert_eq!(vec![3], *granted.borrow());
+        assert_eq!(Duration::from_secs(3), simulation.time());
+        assert_eq!((0, 0), (machine.in_use(), machine.queue_len()));
+        assert_eq!(Some(EntityState::Completed), simulation.entity_state(waiter));
+    }
+
+    #[test]
+    fn live_entities_are_finalized_at_the_end_of_the_run() {
+        let mut simulation: Simulation<Resume<u32>> = Simulation::default();
+        let flushed = Rc::new(RefCell::new(Vec::new()));
+        let record = Rc::clone(&flushed);
+        let (mut started, mut parts) = (false, 0);
+        let machine = simulation.add_generator(process(move |resume| {
+            if started {
+                if let Resume::SimulationEnding = resume {
+                    record.borrow_mut().push(parts);
+                    return None;
+                }
+                parts += 1;
+            }
+            started = true;
+            Some(Action::Hold(Duration::from_secs(3)))
+        }));
+        let resumes = Rc::new(RefCell::new(Vec::new()));
+        let log = Rc::clone(&resumes);
+        let mut actions = vec![Action::Hold(Duration::from_secs(1)), Action::Passivate].into_iter();
+        let worker = simulation.add_generator(process(move |resume| {
+            log.borrow_mut().push(resume);
+            actions.next()
+        }));
+        let never_started = simulation.add_generator(process(|_| Some(Action::Passivate)));
+        simulation.schedule_now(machine);
+        simulation.schedule_now(worker);
+        simulation.run_with_limit_with(Duration::from_secs(9), Resume::from_context);
+
+        assert_eq!(2, simulation.finalize_with(Resume::from_context));
+        assert_eq!(vec![3], *flushed.borrow());
+        // The worker was passive waiting for an activation.
+        assert_eq!(vec![Resume::Timer, Resume::Timer, Resume::SimulationEnding], *resumes.borrow());
+        for key in [machine, worker, never_started] {
+            assert_eq!(Some(EntityState::Completed), simulation.entity_state(key));
+        }
+        assert_eq!(None, simulation.next_event_time());
+    }
+
+    #[test]
+    fn schedule_hooks_see_every_event() {
+        let mut simulation = Simulation::default();
+        let scheduled = Rc::new(RefCell::new(Vec::new()));
+        let recorded = Rc::clone(&scheduled);
+        simulation.on_schedule(move |time, key| recorded.borrow_mut().push((time.as_secs(), key)));
+        let mut holds = 0;
+        let worker = simulation.add_generator(process(move |_| {
+            holds += 1;
+            (holds < 3).then_some(Action::Hold(Duration::from_secs(3)))
+        }));
+        simulation.schedule(Duration::from_secs(2), worker);
+        simulation.run_until_empty();
+
+        // Scheduled by the model at 2, then rescheduled by its holds.
+        assert_eq!(vec![(2, worker), (5, worker), (8, worker)], *scheduled.borrow());
+    }
+
+    #[test]
+    fn periodic_processes_run_at_every_interval() {
+        let mut simulation = Simulation::default();
+        let counter = simulation.state().with_mut(|state| state.insert(0u32));
+        let samples = Rc::new(RefCell::new(Vec::new()));
+        let recorded = Rc::clone(&samples);
+        simulation.every(Duration::from_secs(10), move |state, time| {
+            let count = state.with_mut(|state| {
+                let count = state.get_mut(counter).unwrap();
+                *count += 1;
+                *count
+            });
+            recorded.borrow_mut().push((count, time.as_secs()));
+        });
+        simulation.run_with_limit(Duration::from_secs(25));
+
+        assert_eq!(vec![(1, 0), (2, 10), (3, 20), (4, 30)], *samples.borrow());
+    }
+
+    #[test]
+    fn long_runs_report_their_progress() {
+        let mut simulation = Simulation::default();
+        simulation.every(Duration::from_secs(10), |_, _| {});
+        let mut reports = Vec::new();
+        let status = simulation.run_with_limit_and_progress(Duration::from_secs(25), Duration::ZERO, |progress| {
+            reports.push((progress.time.as_secs(), progress.events));
+        });
+
+        assert_eq!(RunStatus::LimitReached, status);
+        // One report per event, and the last one when the run ends.
+        assert_eq!(vec![(0, 1), (10, 2), (20, 3), (30, 4), (30, 4)], reports);
+    }
+
+    #[test]
+    fn runs_stop_when_the_wallclock_budget_runs_out() {
+        let mut simulation = Simulation::default();
+        simulation.every(Duration::from_secs(1), |_, _| {});
+        assert_eq!(RunStatus::LimitReached, simulation.run_with_wallclock_budget(Duration::ZERO));
+        assert_eq!(Duration::ZERO, simulation.time());
+
+        let mut simulation = Simulation::default();
+        let key = simulation.add_generator(process(|_| None));
+        simulation.schedule(Duration::from_secs(3), key);
+        assert_eq!(RunStatus::Exhausted, simulation.run_with_wallclock_budget(Duration::from_secs(60)));
+        assert_eq!(Duration::from_secs(3), simulation.time());
     }
 }
@@ -0,0 +1,230 @@
+use std::marker::PhantomData;
+use std::time::Duration;
+
+use super::Simulation;
+use crate::scheduler::{FutureEventList, Scheduler};
+use crate::state::State;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::RealTimeRunner;
+use crate::{RngStreams, SimTime};
+
+/// Configuration of a [`Simulation`] gathered in one place, created with [`Simulation::builder`].
+///
+/// ```ignore
+/// let mut simulation: Simulation<()> = Simulation::builder()
+///     .seed(42)
+///     .time_limit(Duration::from_secs(3600))
+///     .record_trace()
+///     .strict(true)
+///     .state(state)
+///     .build();
+/// // Add and schedule the entities, then:
+/// simulation.run();
+/// let trace = simulation.trace().unwrap();
+/// ```
+pub struct SimulationBuilder<R> {
+    seed: Option<u64>,
+    streams: Option<RngStreams>,
+    time_limit: Option<Duration>,
+    warm_up: Option<Duration>,
+    record_trace: bool,
+    sample_every: Option<Duration>,
+    strict: bool,
+    #[cfg(not(target_arch = "wasm32"))]
+    real_time: Option<RealTimeRunner>,
+    state: Option<State>,
+    events: Option<Box<dyn FutureEventList>>,
+    resume: PhantomData<fn() -> R>,
+}
+
+impl<R> Default for SimulationBuilder<R> {
+    fn default() -> Self {
+        Self {
+            seed: None,
+            streams: None,
+            time_limit: None,
+            warm_up: None,
+            record_trace: false,
+            sample_every: None,
+            strict: false,
+            #[cfg(not(target_arch = "wasm32"))]
+            real_time: None,
+            state: None,
+            events: None,
+            resume: PhantomData,
+        }
+    }
+}
+
+impl<R> SimulationBuilder<R>
+where
+    R: 'static,
+{
+    /// Seed the random number generator and the streams, see [`Simulation::set_seed`].
+    #[must_use]
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Declare the named random number streams of `streams`, see [`Simulation::with_streams`]. A seed set with
+    /// [`SimulationBuilder::seed`] takes precedence over the seed of `streams`.
+    #[must_use]
+    pub fn streams(mut self, streams: RngStreams) -> Self {
+        self.streams = Some(streams);
+        self
+    }
+
+    /// Stop [`Simulation::run`] once the clock reaches `limit`.
+    #[must_use]
+    pub fn time_limit(mut self, limit: impl SimTime) -> Self {
+        self.time_limit = Some(limit.to_duration());
+        self
+    }
+
+    /// See [`Simulation::set_warm_up`].
+    #[must_use]
+    pub fn warm_up(mut self, time: impl SimTime) -> Self {
+        self.warm_up = Some(time.to_duration());
+        self
+    }
+
+    /// Record a trace from the start, available through [`Simulation::trace`].
+    #[must_use]
+    pub fn record_trace(mut self) -> Self {
+        self.record_trace = true;
+        self
+    }
+
+    /// Install a sampler taking a sample every `interval`, available through [`Simulation::sampler`] to choose the
+    /// values it reads. It stops at the time limit, which must be set with [`SimulationBuilder::time_limit`] since
+    /// [`Simulation::run`] would never return otherwise.
+    #[must_use]
+    pub fn sample_every(mut self, interval: impl SimTime) -> Self {
+        self.sample_every = Some(interval.to_duration());
+        self
+    }
+
+    /// Panic on invalid actions instead of reporting them, see [`Simulation::set_strict`].
+    #[must_use]
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Make [`Simulation::run`] track wall-clock time with `runner`.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[must_use]
+    pub fn real_time(mut self, runner: RealTimeRunner) -> Self {
+        self.real_time = Some(runner);
+        self
+    }
+
+    /// Start with `state` as the shared state.
+    #[must_use]
+    pub fn state(mut self, state: State) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    /// Keep the pending events in `events`, see [`Simulation::with_event_list`].
+    #[must_use]
+    pub fn event_list(mut self, events: impl FutureEventList + 'static) -> Self {
+        self.events = Some(Box::new(events));
+        self
+    }
+
+    /// # Panics
+    ///
+    /// Panics if a sampler is requested without a time limit.
+    #[must_use]
+    pub fn build(self) -> Simulation<R> {
+        assert!(
+            self.sample_every.is_none() || self.time_limit.is_some(),
+            "A sampler needs a time limit, the run would never end"
+        );
+        let mut simulation = match self.events {
+            Some(events) => Simulation {
+                scheduler: Scheduler::with_event_list(events),
+                ..Simulation::default()
+            },
+            None => Simulation::default(),
+        };
+        if let Some(streams) = self.streams {
+            simulation.streams = streams;
+            simulation.set_seed(simulation.streams.seed());
+        }
+        if let Some(seed) = self.seed {
+            simulation.set_seed(seed);
+        }
+        if let Some(warm_up) = self.warm_up {
+            simulation.set_warm_up(warm_up);
+        }
+        if let Some(state) = self.state {
+            simulation.state.set(state);
+        }
+        if self.record_trace {
+            simulation.trace = Some(simulation.record_trace());
+        }
+        if let Some(interval) = self.sample_every {
+            let sampler = simulation.add_sampler("sampler", interval);
+            sampler.until(self.time_limit.expect("checked above"));
+            simulation.sampler = Some(sampler);
+        }
+        simulation.set_strict(self.strict);
+        simulation.time_limit = self.time_limit;
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            simulation.real_time = self.real_time;
+        }
+        simulation
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{process, Action, RunStatus};
+
+    #[test]
+    fn builder_configures_the_simulation() {
+        let mut state = State::default();
+        let limit = state.insert(3u32);
+        let mut simulation = Simulation::builder()
+            .seed(7)
+            .time_limit(Duration::from_secs(10))
+            .record_trace()
+            .state(state)
+            .build();
+        let shared = simulation.state();
+        let key = simulation.add_generator(process(move |_| {
+            let seconds = shared.with(|state| *state.get(limit).unwrap());
+            Some(Action::Hold(Duration::from_secs(seconds.into())))
+        }));
+        simulation.schedule_now(key);
+
+        assert_eq!(RunStatus::LimitReached, simulation.run());
+        assert_eq!(Duration::from_secs(12), simulation.time());
+        assert_eq!(Some(7), simulation.metadata().seed());
+        assert_eq!(5, simulation.trace().unwrap().events().len());
+    }
+
+    #[test]
+    fn builder_declares_the_streams() {
+        let streams = || RngStreams::new(3).with_stream("arrivals");
+        let simulation = Simulation::<()>::builder().streams(streams()).build();
+        let reference = Simulation::<()>::with_streams(streams());
+        assert_eq!(reference.stream("arrivals").next_u64(), simulation.stream("arrivals").next_u64());
+        assert_eq!(Some(3), simulation.metadata().seed());
+
+        let reseeded = Simulation::<()>::builder().streams(streams()).seed(4).build();
+        let reference = Simulation::<()>::with_streams(RngStreams::new(4).with_stream("arrivals"));
+        assert_eq!(reference.stream("arrivals").next_u64(), reseeded.stream("arrivals").next_u64());
+    }
+
+    #[test]
+    #[should_panic(expected = "A sampler needs a time limit")]
+    fn samplers_need_a_time_limit() {
+        let _ = Simulation::<()>::builder().sample_every(Duration::from_secs(1)).build();
+    }
+}
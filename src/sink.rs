@@ -0,0 +1,195 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::scheduler::ClockRef;
+use crate::stats::{Statistic, Tally};
+use crate::Key;
+
+#[derive(Debug)]
+struct Inner {
+    // Time at which every entity still in the system entered it.
+    entered: HashMap<Key, Duration>,
+    count: u64,
+    // Start of the observation period for the throughput.
+    start: Duration,
+}
+
+/// The exit of the system, recording the time every entity spent in it and the throughput.
+///
+/// Created with [`Simulation::add_sink`](crate::Simulation::add_sink). Entities enter the system when spawned by a
+/// [`Source`](crate::Source) connected with [`Source::sink`](crate::Source::sink), or with [`Sink::enter`], and check
+/// out at the end of their lifecycle:
+///
+/// ```ignore
+/// let sink = simulation.add_sink("exit");
+/// simulation.add_source("arrivals", Source::distributed(interarrival, customer).sink(&sink));
+///
+/// // At the end of the customer
+/// sink.check_out();
+/// ```
+#[derive(Clone)]
+pub struct Sink {
+    inner: Rc<RefCell<Inner>>,
+    time_in_system: Tally,
+    clock: ClockRef,
+    current: Rc<Cell<Option<Key>>>,
+}
+
+impl Sink {
+    pub(crate) fn new(name: &str, clock: ClockRef, current: Rc<Cell<Option<Key>>>) -> Self {
+        let inner = Inner {
+            entered: HashMap::new(),
+            count: 0,
+            start: clock.time(),
+        };
+        Self {
+            inner: Rc::new(RefCell::new(inner)),
+            time_in_system: Tally::new(format!("{} time in system", name)),
+            clock,
+            current,
+        }
+    }
+
+    fn current(&self) -> Key {
+        self.current
+            .get()
+            .expect("sinks can only be used from inside an entity")
+    }
+
+    /// Record that the entity currently being executed enters the system now.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of an entity.
+    pub fn enter(&self) {
+        self.enter_key(self.current());
+    }
+
+    pub(crate) fn enter_key(&self, key: Key) {
+        self.inner.borrow_mut().entered.insert(key, self.clock.time());
+    }
+
+    /// Check the entity currently being executed out of the system, recording the time it spent in it if it
+    /// entered through this sink.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of an entity.
+    pub fn check_out(&self) {
+        let key = self.current();
+        let mut inner = self.inner.borrow_mut();
+        inner.count += 1;
+        if let Some(entered) = inner.entered.remove(&key) {
+            self.time_in_system
+                .record_duration(self.clock.time().saturating_sub(entered));
+        }
+    }
+
+    /// Returns the number of entities that checked out.
+    #[must_use]
+    pub fn count(&self) -> u64 {
+        self.inner.borrow().count
+    }
+
+    /// Returns the number of entities in the system, those that entered and didn't check out yet.
+    #[must_use]
+    pub fn in_system(&self) -> usize {
+        self.inner.borrow().entered.len()
+    }
+
+    /// Returns the number of check-outs per second since the start, or the end of the warm-up period.
+    ///
+    /// Returns zero if no time has elapsed.
+    #[must_use]
+    pub fn throughput(&self) -> f64 {
+        let inner = self.inner.borrow();
+        let elapsed = self.clock.time().saturating_sub(inner.start).as_secs_f64();
+        if elapsed > 0.0 {
+            inner.count as f64 / elapsed
+        } else {
+            0.0
+        }
+    }
+
+    /// Returns the statistics of the time entities spent in the system, in seconds.
+    #[must_use]
+    pub fn time_in_system(&self) -> Tally {
+        self.time_in_system.clone()
+    }
+}
+
+impl Statistic for Sink {
+    /// Reset the time in system and the throughput, entities in the system keep their entry time.
+    fn reset(&self) {
+        self.time_in_system.reset();
+        let mut inner = self.inner.borrow_mut();
+        inner.count = 0;
+        inner.start = self.clock.time();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{process, Action, GenBoxed, Simulation, Source};
+
+    fn customer(sink: Sink, stay: u64) -> GenBoxed<()> {
+        let mut stayed = false;
+        process(move |_| {
+            if stayed {
+                sink.check_out();
+                return None;
+            }
+            stayed = true;
+            Some(Action::Hold(Duration::from_secs(stay)))
+        })
+    }
+
+    #[test]
+    fn sinks_record_the_time_in_system_of_source_entities() {
+        let mut simulation = Simulation::default();
+        let sink = simulation.add_sink("exit");
+        let exit = sink.clone();
+        let source = Source::distributed(Duration::from_secs(2), move || customer(exit.clone(), 3))
+            .max_arrivals(4)
+            .sink(&sink);
+        simulation.add_source("arrivals", source);
+        simulation.run_until_empty();
+
+        assert_eq!(4, sink.count());
+        assert_eq!(0, sink.in_system());
+        assert_eq!(4, sink.time_in_system().count());
+        assert_eq!(Some(3.0), sink.time_in_system().mean());
+        assert_eq!(4.0 / simulation.time().as_secs_f64(), sink.throughput());
+    }
+
+    #[test]
+    fn entities_can_enter_by_themselves() {
+        let mut simulation = Simulation::default();
+        let sink = simulation.add_sink("exit");
+        let exit = sink.clone();
+        let mut step = 0;
+        let key = simulation.add_generator(process(move |_| {
+            step += 1;
+            match step {
+                1 => Some(Action::Hold(Duration::from_secs(1))),
+                2 => {
+                    exit.enter();
+                    Some(Action::Hold(Duration::from_secs(5)))
+                }
+                _ => {
+                    exit.check_out();
+                    None
+                }
+            }
+        }));
+        simulation.schedule_now(key);
+        simulation.run_until_empty();
+
+        assert_eq!(1, sink.count());
+        assert_eq!(Some(5.0), sink.time_in_system().mean());
+        assert_eq!(1.0 / 6.0, sink.throughput());
+    }
+}
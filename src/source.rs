@@ -0,0 +1,216 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::distributions::Distribution;
+use crate::scheduler::ClockRef;
+use crate::{process, Action, GenBoxed, Key, SimRng, Sink, Spawner};
+
+enum Interarrival {
+    Sampler(Box<dyn FnMut() -> Duration>),
+    // Sampled with the random number generator of the simulation.
+    Distribution(Box<dyn Distribution>),
+}
+
+/// Description of an arrival process that spawns new entities into the simulation.
+///
+/// Installed with [`Simulation::add_source`](crate::Simulation::add_source).
+/// Every `interarrival` time a batch of `batch_size` entities (one by default) created by `factory` is spawned,
+/// until the optional limits on the number of arrivals and on time are reached:
+///
+/// ```ignore
+/// let customers = Source::distributed(Exponential::new(Duration::from_secs(4)), customer)
+///     .max_arrivals(100)
+///     .until(Duration::from_secs(3600));
+/// simulation.add_source("customers", customers);
+/// ```
+pub struct Source<R> {
+    interarrival: Interarrival,
+    batch_size: Box<dyn FnMut() -> usize>,
+    factory: Box<dyn FnMut() -> GenBoxed<R>>,
+    max_arrivals: Option<usize>,
+    until: Option<Duration>,
+    sink: Option<Sink>,
+}
+
+impl<R> Source<R>
+where
+    R: 'static,
+{
+    pub fn new(
+        interarrival: impl FnMut() -> Duration + 'static,
+        factory: impl FnMut() -> GenBoxed<R> + 'static,
+    ) -> Self {
+        Self::with_interarrival(Interarrival::Sampler(Box::new(interarrival)), factory)
+    }
+
+    /// Draw the interarrival times from `distribution` with the random number generator of the simulation.
+    pub fn distributed(
+        distribution: impl Distribution + 'static,
+        factory: impl FnMut() -> GenBoxed<R> + 'static,
+    ) -> Self {
+        Self::with_interarrival(Interarrival::Distribution(Box::new(distribution)), factory)
+    }
+
+    fn with_interarrival(interarrival: Interarrival, factory: impl FnMut() -> GenBoxed<R> + 'static) -> Self {
+        Self {
+            interarrival,
+            batch_size: Box::new(|| 1),
+            factory: Box::new(factory),
+            max_arrivals: None,
+            until: None,
+            sink: None,
+        }
+    }
+
+    /// Generate batch arrivals, the size of each group is drawn from `batch_size`.
+    #[must_use]
+    pub fn batch_size(mut self, batch_size: impl FnMut() -> usize + 'static) -> Self {
+        self.batch_size = Box::new(batch_size);
+        self
+    }
+
+    /// Stop after spawning `count` entities, the last batch is cut short if needed.
+    #[must_use]
+    pub fn max_arrivals(mut self, count: usize) -> Self {
+        self.max_arrivals = Some(count);
+        self
+    }
+
+    /// Stop spawning after simulation time `time`.
+    #[must_use]
+    pub fn until(mut self, time: Duration) -> Self {
+        self.until = Some(time);
+        self
+    }
+
+    /// Make the spawned entities enter the system of `sink`, where they check out.
+    #[must_use]
+    pub fn sink(mut self, sink: &Sink) -> Self {
+        self.sink = Some(sink.clone());
+        self
+    }
+
+    pub(crate) fn into_generator(
+        mut self,
+        spawner: Spawner<R>,
+        stats: Rc<RefCell<SourceStats>>,
+        rng: SimRng,
+        clock: ClockRef,
+    ) -> GenBoxed<R> {
+        // Every resume after the first one ends an interarrival time.
+        let mut started = false;
+        let mut arrivals = 0;
+        process(move |_| {
+            if started {
+                let mut size = (self.batch_size)();
+                if let Some(max) = self.max_arrivals {
+                    size = size.min(max - arrivals);
+                }
+                for _ in 0..size {
+                    let key = spawner.spawn_detached((self.factory)());
+                    if let Some(sink) = &self.sink {
+                        sink.enter_key(key);
+                    }
+                }
+                arrivals += size;
+                stats.borrow_mut().record(size);
+            }
+            started = true;
+            if self.max_arrivals.is_some_and(|max| arrivals >= max) {
+                return None;
+            }
+            let interarrival = match &mut self.interarrival {
+                Interarrival::Sampler(sampler) => sampler(),
+                Interarrival::Distribution(distribution) => distribution.sample(&rng),
+            };
+            if self.until.is_some_and(|until| clock.time() + interarrival > until) {
+                return None;
+            }
+            Some(Action::Hold(interarrival))
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct SourceStats {
+    batch_sizes: Vec<usize>,
+}
+
+impl SourceStats {
+    fn record(&mut self, size: usize) {
+        self.batch_sizes.push(size);
+    }
+}
+
+/// Handle to a [`Source`] installed in a simulation, used to inspect its arrivals.
+#[derive(Clone)]
+pub struct SourceHandle {
+    key: Key,
+    stats: Rc<RefCell<SourceStats>>,
+}
+
+impl SourceHandle {
+    pub(crate) fn new(key: Key, stats: Rc<RefCell<SourceStats>>) -> Self {
+        Self { key, stats }
+    }
+
+    /// Returns the key of the entity generating the arrivals.
+    #[must_use]
+    pub fn key(&self) -> Key {
+        self.key
+    }
+
+    /// Returns the number of entities spawned so far.
+    #[must_use]
+    pub fn arrivals(&self) -> usize {
+        self.stats.borrow().batch_sizes.iter().sum()
+    }
+
+    /// Returns the number of batches spawned so far.
+    #[must_use]
+    pub fn batches(&self) -> usize {
+        self.stats.borrow().batch_sizes.len()
+    }
+
+    /// Returns the size of every batch spawned so far, in arrival order.
+    #[must_use]
+    pub fn batch_sizes(&self) -> Vec<usize> {
+        self.stats.borrow().batch_sizes.clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Simulation;
+
+    fn run(source: Source<()>) -> (SourceHandle, Duration) {
+        let mut simulation = Simulation::default();
+        let handle = simulation.add_source("arrivals", source);
+        simulation.run_until_empty();
+        (handle, simulation.time())
+    }
+
+    fn customer() -> GenBoxed<()> {
+        process(|_| None)
+    }
+
+    #[test]
+    fn sources_stop_at_their_limits() {
+        let every_two_seconds = || Source::distributed(Duration::from_secs(2), customer);
+
+        let (arrivals, end) = run(every_two_seconds().max_arrivals(3));
+        assert_eq!(vec![1, 1, 1], arrivals.batch_sizes());
+        assert_eq!(Duration::from_secs(6), end);
+
+        // The last batch is cut short by the count, the next arrival would be after the time limit.
+        let (arrivals, end) = run(every_two_seconds().batch_size(|| 2).max_arrivals(3).until(Duration::from_secs(5)));
+        assert_eq!(vec![2, 1], arrivals.batch_sizes());
+        assert_eq!(Duration::from_secs(4), end);
+
+        let (arrivals, end) = run(every_two_seconds().batch_size(|| 2).until(Duration::from_secs(5)));
+        assert_eq!(4, arrivals.arrivals());
+        assert_eq!(Duration::from_secs(4), end);
+    }
+}
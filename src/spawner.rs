@@ -0,0 +1,68 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use crate::container::Slots;
+use crate::{GenBoxed, Key};
+
+pub(crate) struct Spawned<R> {
+    pub(crate) key: Key,
+    pub(crate) gen: GenBoxed<R>,
+    pub(crate) parent: Option<Key>,
+}
+
+/// A handle that lets generators add new entities to the simulation while it is running.
+///
+/// Obtained from [`Simulation::spawner`](crate::Simulation::spawner). Spawned entities are
+/// inserted and scheduled at the current simulation time right after the spawning entity yields.
+pub struct Spawner<R> {
+    pending: Rc<RefCell<Vec<Spawned<R>>>>,
+    slots: Rc<RefCell<Slots>>,
+    current: Rc<Cell<Option<Key>>>,
+}
+
+impl<R> Clone for Spawner<R> {
+    fn clone(&self) -> Self {
+        Self {
+            pending: Rc::clone(&self.pending),
+            slots: Rc::clone(&self.slots),
+            current: Rc::clone(&self.current),
+        }
+    }
+}
+
+impl<R> Spawner<R> {
+    pub(crate) fn new(slots: Rc<RefCell<Slots>>, current: Rc<Cell<Option<Key>>>) -> Self {
+        Self {
+            pending: Rc::default(),
+            slots,
+            current,
+        }
+    }
+
+    /// Spawn `gen` as a child of the entity currently being executed.
+    ///
+    /// When the parent completes its children are terminated too.
+    /// If called outside of an entity the spawned entity has no parent.
+    pub fn spawn(&self, gen: GenBoxed<R>) -> Key {
+        self.push(gen, self.current.get())
+    }
+
+    /// Spawn `gen` without linking it to the entity currently being executed.
+    pub fn spawn_detached(&self, gen: GenBoxed<R>) -> Key {
+        self.push(gen, None)
+    }
+
+    fn push(&self, gen: GenBoxed<R>, parent: Option<Key>) -> Key {
+        let key = self.slots.borrow_mut().reserve();
+        self.pending.borrow_mut().push(Spawned { key, gen, parent });
+        key
+    }
+
+    pub(crate) fn has_pending(&self) -> bool {
+        !self.pending.borrow().is_empty()
+    }
+
+    pub(crate) fn take_pending(&self) -> Vec<Spawned<R>> {
+        std::mem::take(&mut *self.pending.borrow_mut())
+    }
+}
@@ -0,0 +1,471 @@
+//! Stochastic simulation of chemical kinetics (Gillespie's algorithm).
+//!
+//! A [`ReactionSystem`] holds species, counted in molecules, and reactions with mass-action kinetics: the
+//! propensity of a reaction is its stochastic rate constant, per second, times the number of distinct
+//! combinations of its reactant molecules. It runs on the clock of a [`Simulation`] with either method of
+//! [`Method`], drawing from the given random number stream:
+//!
+//! ```ignore
+//! let mut system = ReactionSystem::new();
+//! let prey = system.add_species("prey", 100);
+//! let predator = system.add_species("predator", 50);
+//! system.add_reaction("birth", 1.0, &[(prey, 1)], &[(prey, 2)]);
+//! system.add_reaction("predation", 0.005, &[(prey, 1), (predator, 1)], &[(predator, 2)]);
+//! system.add_reaction("death", 0.6, &[(predator, 1)], &[]);
+//!
+//! let rng = simulation.stream("reactions");
+//! let model = system.build(&mut simulation, Method::NextReaction, rng);
+//! model.watch(&simulation.record_trace());
+//! simulation.run_with_limit(Duration::from_secs(100));
+//! ```
+//!
+//! The count of every species is an [`Accumulate`] registered as a collector of the simulation, and
+//! [`SsaModel::watch`] adds the counts to the columns of a [`TraceRecorder`].
+
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::distributions::to_duration;
+use crate::scheduler::ClockRef;
+use crate::{process, Accumulate, Action, GenBoxed, Key, SimRng, Simulation, TraceRecorder};
+
+/// How the next reaction and its time are drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    /// Gillespie's direct method: a single entity draws the time to the next reaction from the total propensity,
+    /// then which reaction it is. Simple and fast for small systems.
+    Direct,
+    /// Gibson and Bruck's next-reaction method: every reaction is an entity holding until its own firing time,
+    /// and only the reactions depending on the species changed by a firing have their time updated.
+    /// Suited to large systems where each reaction affects a few others.
+    NextReaction,
+}
+
+/// A species of a [`ReactionSystem`], returned by [`ReactionSystem::add_species`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SpeciesId(usize);
+
+/// A reaction of a [`ReactionSystem`], returned by [`ReactionSystem::add_reaction`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ReactionId(usize);
+
+#[derive(Debug, Clone)]
+struct Reaction {
+    name: String,
+    rate: f64,
+    reactants: Vec<(usize, u32)>,
+    // Net change of each species when the reaction fires.
+    changes: Vec<(usize, i64)>,
+}
+
+/// Description of a system of reactions, see the [module documentation](self).
+#[derive(Debug, Clone, Default)]
+pub struct ReactionSystem {
+    species: Vec<(String, u64)>,
+    reactions: Vec<Reaction>,
+}
+
+impl ReactionSystem {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a species with `count` molecules at the start.
+    ///
+    /// # Panics
+    ///
+    /// Panics if another species is named `name`.
+    pub fn add_species(&mut self, name: impl Into<String>, count: u64) -> SpeciesId {
+        let name = name.into();
+        assert!(
+            self.species.iter().all(|(other, _)| *other != name),
+            "A species named {:?} already exists",
+            name
+        );
+        self.species.push((name, count));
+        SpeciesId(self.species.len() - 1)
+    }
+
+    /// Add a reaction consuming `reactants` and producing `products`, given as species and their coefficients,
+    /// with the stochastic rate constant `rate`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if another reaction is named `name` or if `rate` is negative or not finite.
+    pub fn add_reaction(
+        &mut self,
+        name: impl Into<String>,
+        rate: f64,
+        reactants: &[(SpeciesId, u32)],
+        products: &[(SpeciesId, u32)],
+    ) -> ReactionId {
+        let name = name.into();
+        assert!(
+            self.reactions.iter().all(|other| other.name != name),
+            "A reaction named {:?} already exists",
+            name
+        );
+        assert!(rate.is_finite() && rate >= 0.0, "Invalid rate {} of reaction {:?}", rate, name);
+        let mut changes: Vec<(usize, i64)> = Vec::new();
+        let terms = reactants
+            .iter()
+            .map(|&(species, coefficient)| (species, -i64::from(coefficient)))
+            .chain(products.iter().map(|&(species, coefficient)| (species, i64::from(coefficient))));
+        for (SpeciesId(species), change) in terms {
+            match changes.iter_mut().find(|(other, _)| *other == species) {
+                Some((_, total)) => *total += change,
+                None => changes.push((species, change)),
+            }
+        }
+        changes.retain(|&(_, change)| change != 0);
+        self.reactions.push(Reaction {
+            name,
+            rate,
+            reactants: reactants.iter().map(|&(SpeciesId(species), coefficient)| (species, coefficient)).collect(),
+            changes,
+        });
+        ReactionId(self.reactions.len() - 1)
+    }
+
+    /// Add the reactions to `simulation`, drawing from `rng`, and schedule them to start now.
+    ///
+    /// The count of every species is registered as a collector named `<species> count`. The reactions stop when
+    /// none of them can fire anymore.
+    pub fn build(self, simulation: &mut Simulation<()>, method: Method, rng: SimRng) -> SsaModel {
+        let clock = simulation.clock();
+        let species = self
+            .species
+            .into_iter()
+            .map(|(name, count)| {
+                let statistic = Accumulate::new(format!("{} count", name), clock.clone(), count as f64);
+                simulation.add_statistic(statistic.name(), statistic.clone());
+                Species { name, count, statistic }
+            })
+            .collect();
+        let count = self.reactions.len();
+        let runtime = Rc::new(RefCell::new(Runtime {
+            species,
+            reactions: self.reactions,
+            firings: vec![0; count],
+            keys: Vec::new(),
+            status: vec![Status::Start; count],
+            clock,
+            rng,
+        }));
+        match method {
+            Method::Direct => {
+                let key = simulation.add_generator_named("ssa", direct(Rc::clone(&runtime)));
+                simulation.schedule_now(key);
+            }
+            Method::NextReaction => {
+                for index in 0..count {
+                    let name = runtime.borrow().reactions[index].name.clone();
+                    let key = simulation.add_generator_named(name, next_reaction(Rc::clone(&runtime), index));
+                    runtime.borrow_mut().keys.push(key);
+                    simulation.schedule_now(key);
+                }
+            }
+        }
+        SsaModel { runtime }
+    }
+}
+
+struct Species {
+    name: String,
+    count: u64,
+    statistic: Accumulate,
+}
+
+// Where a reaction of the next-reaction method stands.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Status {
+    // Its firing time has to be drawn anew when resumed.
+    Start,
+    // Holding until `due`, drawn with `propensity`.
+    Holding { due: Duration, propensity: f64 },
+    // Preempted by a firing that changed its propensity.
+    Interrupted { due: Duration, propensity: f64 },
+    Passive,
+    // Yielding the actions of its last firing.
+    Busy,
+}
+
+struct Runtime {
+    species: Vec<Species>,
+    reactions: Vec<Reaction>,
+    firings: Vec<u64>,
+    keys: Vec<Key>,
+    status: Vec<Status>,
+    clock: ClockRef,
+    rng: SimRng,
+}
+
+impl Runtime {
+    fn propensity(&self, reaction: usize) -> f64 {
+        let reaction = &self.reactions[reaction];
+        reaction
+            .reactants
+            .iter()
+            .fold(reaction.rate, |propensity, &(species, coefficient)| {
+                propensity * combinations(self.species[species].count, coefficient)
+            })
+    }
+
+    fn fire(&mut self, reaction: usize) {
+        self.firings[reaction] += 1;
+        for &(species, change) in &self.reactions[reaction].changes {
+            let species = &mut self.species[species];
+            species.count = species
+                .count
+                .checked_add_signed(change)
+                .expect("a reaction only fires while its reactants are available");
+            species.statistic.set(species.count as f64);
+        }
+    }
+
+    // Whether firing `reaction` changes the propensity of `other`.
+    fn affects(&self, reaction: usize, other: usize) -> bool {
+        self.reactions[reaction].changes.iter().any(|&(species, _)| {
+            self.reactions[other]
+                .reactants
+                .iter()
+                .any(|&(reactant, _)| reactant == species)
+        })
+    }
+
+    // Draw an exponential waiting time with rate `propensity`.
+    fn waiting_time(&self, propensity: f64) -> Duration {
+        to_duration(-(1.0 - self.rng.next_f64()).ln() / propensity)
+    }
+}
+
+// Number of ways to choose `k` molecules among `n`.
+fn combinations(n: u64, k: u32) -> f64 {
+    (0..u64::from(k)).fold(1.0, |total, i| {
+        if i >= n {
+            0.0
+        } else {
+            total * (n - i) as f64 / (i + 1) as f64
+        }
+    })
+}
+
+/// A [`ReactionSystem`] built into a simulation, used to read the counts of its species.
+pub struct SsaModel {
+    runtime: Rc<RefCell<Runtime>>,
+}
+
+impl SsaModel {
+    /// Returns the number of molecules of the species named `name`.
+    #[must_use]
+    pub fn count(&self, name: &str) -> Option<u64> {
+        self.species(name, |species| species.count)
+    }
+
+    /// Returns the number of molecules of every species, by name.
+    #[must_use]
+    pub fn counts(&self) -> HashMap<String, u64> {
+        self.runtime
+            .borrow()
+            .species
+            .iter()
+            .map(|species| (species.name.clone(), species.count))
+            .collect()
+    }
+
+    /// Returns the time-weighted statistics of the count of the species named `name`.
+    #[must_use]
+    pub fn species_statistic(&self, name: &str) -> Option<Accumulate> {
+        self.species(name, |species| species.statistic.clone())
+    }
+
+    /// Returns how many times the reaction named `name` fired.
+    #[must_use]
+    pub fn firings(&self, name: &str) -> Option<u64> {
+        let runtime = self.runtime.borrow();
+        let index = runtime.reactions.iter().position(|reaction| reaction.name == name)?;
+        Some(runtime.firings[index])
+    }
+
+    /// Add the count of every species to the values sampled by `recorder`, in the order they were added.
+    pub fn watch(&self, recorder: &TraceRecorder) {
+        let names: Vec<String> = self.runtime.borrow().species.iter().map(|species| species.name.clone()).collect();
+        for (index, name) in names.into_iter().enumerate() {
+            let runtime = Rc::clone(&self.runtime);
+            recorder.watch(name, move || runtime.borrow().species[index].count as f64);
+        }
+    }
+
+    fn species<T>(&self, name: &str, f: impl FnOnce(&Species) -> T) -> Option<T> {
+        self.runtime
+            .borrow()
+            .species
+            .iter()
+            .find(|species| species.name == name)
+            .map(f)
+    }
+}
+
+fn direct(runtime: Rc<RefCell<Runtime>>) -> GenBoxed<()> {
+    // The reaction drawn to fire at the end of the current hold.
+    let mut next: Option<usize> = None;
+    process(move |_| {
+        let mut runtime = runtime.borrow_mut();
+        if let Some(reaction) = next.take() {
+            runtime.fire(reaction);
+        }
+        let propensities: Vec<f64> = (0..runtime.reactions.len())
+            .map(|reaction| runtime.propensity(reaction))
+            .collect();
+        let total: f64 = propensities.iter().sum();
+        if total <= 0.0 {
+            return None;
+        }
+        let hold = runtime.waiting_time(total);
+        let mut draw = runtime.rng.next_f64() * total;
+        // Rounding may leave a remainder after the last reaction, which then fires.
+        let mut reaction = propensities.iter().rposition(|&propensity| propensity > 0.0);
+        for (index, &propensity) in propensities.iter().enumerate() {
+            if propensity > 0.0 && draw < propensity {
+                reaction = Some(index);
+                break;
+            }
+            draw -= propensity;
+        }
+        next = reaction;
+        Some(Action::Hold(hold))
+    })
+}
+
+fn next_reaction(runtime: Rc<RefCell<Runtime>>, index: usize) -> GenBoxed<()> {
+    let mut pending = VecDeque::new();
+    // The status taking effect once the last pending action is yielded.
+    let mut after = Status::Busy;
+    process(move |_| {
+        let mut runtime = runtime.borrow_mut();
+        if pending.is_empty() {
+            let now = runtime.clock.time();
+            let propensity = runtime.propensity(index);
+            let due = match runtime.status[index] {
+                Status::Holding { .. } => {
+                    runtime.fire(index);
+                    for other in (0..runtime.reactions.len()).filter(|&other| other != index) {
+                        if !runtime.affects(index, other) {
+                            continue;
+                        }
+                        match runtime.status[other] {
+                            Status::Holding { due, propensity } => {
+                                runtime.status[other] = Status::Interrupted { due, propensity };
+                                pending.push_back(Action::Preempt(runtime.keys[other]));
+                            }
+                            Status::Passive if runtime.propensity(other) > 0.0 => {
+                                runtime.status[other] = Status::Start;
+                                pending.push_back(Action::ActivateOne(runtime.keys[other]));
+                            }
+                            _ => {}
+                        }
+                    }
+                    let propensity = runtime.propensity(index);
+                    (propensity > 0.0).then(|| now + runtime.waiting_time(propensity))
+                }
+                // Gibson and Bruck reuse the time left, rescaled to the new propensity.
+                Status::Interrupted { due, propensity: old } if propensity > 0.0 => {
+                    Some(now + (due - now).mul_f64(old / propensity))
+                }
+                _ if propensity > 0.0 => Some(now + runtime.waiting_time(propensity)),
+                _ => None,
+            };
+            after = match due {
+                Some(due) => {
+                    pending.push_back(Action::Hold(due - now));
+                    Status::Holding {
+                        due,
+                        propensity: runtime.propensity(index),
+                    }
+                }
+                None => {
+                    pending.push_back(Action::Passivate);
+                    Status::Passive
+                }
+            };
+            runtime.status[index] = Status::Busy;
+        }
+        let action = pending.pop_front();
+        if pending.is_empty() {
+            runtime.status[index] = after;
+        }
+        action
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // A + B -> C with 20 A and 30 B ends with 20 C, and A + A -> D halves the molecules of A.
+    fn system() -> ReactionSystem {
+        let mut system = ReactionSystem::new();
+        let a = system.add_species("A", 20);
+        let b = system.add_species("B", 30);
+        let c = system.add_species("C", 0);
+        let d = system.add_species("D", 0);
+        system.add_reaction("bind", 0.01, &[(a, 1), (b, 1)], &[(c, 1)]);
+        system.add_reaction("dimerize", 0.02, &[(a, 2)], &[(d, 1)]);
+        system
+    }
+
+    #[test]
+    fn reactions_run_until_exhausted() {
+        for method in [Method::Direct, Method::NextReaction] {
+            let mut simulation = Simulation::default();
+            let rng = simulation.rng();
+            let model = system().build(&mut simulation, method, rng);
+            simulation.run_until_empty();
+
+            let counts = model.counts();
+            assert_eq!(0, counts["A"], "{:?}", method);
+            assert_eq!(20, counts["C"] + 2 * counts["D"], "{:?}", method);
+            assert_eq!(30 - counts["C"], counts["B"], "{:?}", method);
+            assert_eq!(model.firings("bind"), model.count("C"));
+            assert_eq!(model.firings("dimerize"), model.count("D"));
+        }
+    }
+
+    #[test]
+    fn methods_agree_on_the_mean_of_a_birth_death_process() {
+        // Immigration at 10 per second and death at 1 per molecule per second, a Poisson stationary distribution
+        // with mean 10.
+        for method in [Method::Direct, Method::NextReaction] {
+            let mut system = ReactionSystem::new();
+            let x = system.add_species("X", 0);
+            system.add_reaction("immigration", 10.0, &[], &[(x, 1)]);
+            system.add_reaction("death", 1.0, &[(x, 1)], &[]);
+            let mut simulation = Simulation::default();
+            simulation.set_seed(3);
+            let rng = simulation.rng();
+            let model = system.build(&mut simulation, method, rng);
+            simulation.run_with_limit(Duration::from_secs(2_000));
+
+            let mean = model.species_statistic("X").unwrap().time_average();
+            assert!((mean - 10.0).abs() < 0.5, "{:?}: {}", method, mean);
+        }
+    }
+
+    #[test]
+    fn species_counts_are_watched_by_traces() {
+        let mut simulation = Simulation::default();
+        let recorder = simulation.record_trace();
+        let rng = simulation.rng();
+        let model = system().build(&mut simulation, Method::Direct, rng);
+        model.watch(&recorder);
+        simulation.run_until_empty();
+
+        let last = recorder.events().last().unwrap().values.clone();
+        assert_eq!(vec![0.0, 30.0 - last[2], last[2], last[3]], last);
+        assert_eq!(combinations(5, 2), 10.0);
+        assert_eq!(combinations(1, 2), 0.0);
+    }
+}
@@ -32,10 +32,74 @@ impl<V> StateKey<V> {
 }
 
 use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
+
+use crate::scheduler::ClockRef;
+use crate::timeline::Timeline;
+
+/// Error returned by the name-based methods of [`State`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StateError {
+    /// A value was already inserted under the name.
+    NameTaken(String),
+    /// No value exists under the name.
+    NotFound(String),
+    /// The value under the name has another type.
+    TypeMismatch {
+        name: String,
+        expected: &'static str,
+        found: &'static str,
+    },
+    /// The value under the name couldn't be converted from or to JSON, only with the `serde` feature.
+    Serialization { name: String, message: String },
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NameTaken(name) => write!(f, "a state value named {:?} already exists", name),
+            Self::NotFound(name) => write!(f, "no state value named {:?}", name),
+            Self::TypeMismatch { name, expected, found } => write!(
+                f,
+                "the state value named {:?} is a {} but a {} was requested",
+                name, found, expected
+            ),
+            Self::Serialization { name, message } => {
+                write!(f, "the state value named {:?} couldn't be converted to or from JSON: {}", name, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+#[cfg(feature = "serde")]
+mod snapshot;
+
+type Record = dyn Fn(&dyn Any);
+
+// Records the new value of an observed variable, see `State::observe_with`.
+struct Observer(Box<Record>);
+
+impl fmt::Debug for Observer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Observer")
+    }
+}
 
 #[derive(Debug, Default)]
 pub struct State {
     store: Vec<Option<Box<dyn Any>>>,
+    // Values inserted by name: their index in `store` and the name of their type.
+    names: HashMap<String, (usize, &'static str)>,
+    // Observers of the values, by index in `store`.
+    observers: HashMap<usize, Vec<Observer>>,
+    #[cfg(feature = "serde")]
+    serializable: Vec<snapshot::Serializable>,
 }
 
 impl State {
@@ -102,6 +166,122 @@ impl State {
             .map(|value| value.downcast_mut::<V>().expect("Ensured by the key type."))
     }
 
+    /// Replace the value of `key` with `value`, notifying its observers. Returns the previous value, `None` if the
+    /// key was removed, in which case nothing is stored.
+    pub fn set<V: 'static>(&mut self, key: StateKey<V>, value: V) -> Option<V> {
+        self.update(key, |current| std::mem::replace(current, value))
+    }
+
+    /// Call `f` with the value of `key`, mutably, then notify its observers. Returns `None` if the key was removed.
+    ///
+    /// Unlike [`State::get_mut`], changes made through this method are seen by [`State::observe`].
+    pub fn update<V: 'static, T>(&mut self, key: StateKey<V>, f: impl FnOnce(&mut V) -> T) -> Option<T> {
+        let value = self.get_mut(key)?;
+        let result = f(value);
+        if let Some(observers) = self.observers.get(&key.id) {
+            let value = self.get(key).expect("Ensured by get_mut.");
+            for observer in observers {
+                (observer.0)(value);
+            }
+        }
+        Some(result)
+    }
+
+    /// Returns a [`Timeline`] of the value of `key`, with a point now and at every change made through
+    /// [`State::set`] and [`State::update`], timed by `clock`.
+    ///
+    /// Every change is recorded, even those undone at the same time, so excursions missed by
+    /// [`Simulation::record_timeline`](crate::Simulation::record_timeline), which samples after every step, show up:
+    ///
+    /// ```ignore
+    /// let clock = simulation.clock();
+    /// let queue_length = simulation.state().with_mut(|state| state.observe(length, &clock));
+    /// // In the entities:
+    /// state.with_mut(|state| state.update(length, |length| *length += 1));
+    /// ```
+    ///
+    /// The timeline is named after the value if it was inserted by name, `"state <id>"` otherwise.
+    pub fn observe<V: Copy + Into<f64> + 'static>(&mut self, key: StateKey<V>, clock: &ClockRef) -> Timeline {
+        self.observe_with(key, clock, |&value| value.into())
+    }
+
+    /// Same as [`State::observe`], converting the value with `sample`.
+    pub fn observe_with<V: 'static>(
+        &mut self,
+        key: StateKey<V>,
+        clock: &ClockRef,
+        sample: impl Fn(&V) -> f64 + 'static,
+    ) -> Timeline {
+        let name = self
+            .names
+            .iter()
+            .find(|&(_, &(id, _))| id == key.id)
+            .map_or_else(|| format!("state {}", key.id), |(name, _)| name.clone());
+        let timeline = Timeline::new(&name);
+        if let Some(value) = self.get(key) {
+            timeline.record(clock.time(), sample(value));
+        }
+        let observer = {
+            let (timeline, clock) = (timeline.clone(), clock.clone());
+            move |value: &dyn Any| {
+                let value = value.downcast_ref::<V>().expect("Ensured by the key type.");
+                timeline.record(clock.time(), sample(value));
+            }
+        };
+        self.observers.entry(key.id).or_default().push(Observer(Box::new(observer)));
+        timeline
+    }
+
+    /// Insert `value` under `name`, for models where [`StateKey`]s can't be threaded through,
+    /// e.g. models assembled from configuration files.
+    ///
+    /// The returned key can be used as any other, the value remains reachable by name until it's removed.
+    pub fn insert_named<V: 'static>(&mut self, name: impl Into<String>, value: V) -> Result<StateKey<V>, StateError> {
+        let name = name.into();
+        if let Some(&(id, _)) = self.names.get(&name) {
+            if matches!(self.store.get(id), Some(Some(_))) {
+                return Err(StateError::NameTaken(name));
+            }
+        }
+        let key = self.insert(value);
+        self.names.insert(name, (key.id, std::any::type_name::<V>()));
+        Ok(key)
+    }
+
+    /// Returns the key of the value named `name`, checking that it's a `V`.
+    pub fn key_of<V: 'static>(&self, name: &str) -> Result<StateKey<V>, StateError> {
+        let &(id, found) = self
+            .names
+            .get(name)
+            .filter(|&&(id, _)| matches!(self.store.get(id), Some(Some(_))))
+            .ok_or_else(|| StateError::NotFound(name.to_owned()))?;
+        if !self.store[id].as_ref().is_some_and(|value| value.is::<V>()) {
+            return Err(StateError::TypeMismatch {
+                name: name.to_owned(),
+                expected: std::any::type_name::<V>(),
+                found,
+            });
+        }
+        Ok(StateKey::new(id))
+    }
+
+    pub fn get_named<V: 'static>(&self, name: &str) -> Result<&V, StateError> {
+        let key = self.key_of::<V>(name)?;
+        Ok(self.get(key).expect("Ensured by key_of."))
+    }
+
+    pub fn get_named_mut<V: 'static>(&mut self, name: &str) -> Result<&mut V, StateError> {
+        let key = self.key_of::<V>(name)?;
+        Ok(self.get_mut(key).expect("Ensured by key_of."))
+    }
+
+    /// Remove the value named `name`, freeing the name.
+    pub fn remove_named<V: 'static>(&mut self, name: &str) -> Result<V, StateError> {
+        let key = self.key_of::<V>(name)?;
+        self.names.remove(name);
+        Ok(self.remove(key).expect("Ensured by key_of."))
+    }
+
     pub fn len(&self) -> usize {
         self.store.len()
     }
@@ -110,3 +290,196 @@ impl State {
         self.store.is_empty()
     }
 }
+
+/// The [`State`] shared by the simulation and its entities, obtained from [`Simulation::state`](crate::Simulation::state).
+///
+/// Access is scoped to a closure so the state can't be left out of place across a yield:
+///
+/// ```ignore
+/// let len = shared_state.with(|state| *state.get(queue_len).unwrap());
+/// shared_state.with_mut(|state| *state.get_mut(queue_len).unwrap() += 1);
+/// ```
+///
+/// Calling [`SharedState::with_mut`] from inside another access to the same state panics.
+/// [`SharedState::lock`] is a lighter alternative returning a guard.
+#[derive(Debug, Clone, Default)]
+pub struct SharedState {
+    inner: Rc<RefCell<State>>,
+    locked: Rc<Cell<bool>>,
+}
+
+impl SharedState {
+    /// Call `f` with the state.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the state is locked.
+    pub fn with<T>(&self, f: impl FnOnce(&State) -> T) -> T {
+        self.assert_unlocked();
+        f(&self.inner.borrow())
+    }
+
+    /// Call `f` with the state, mutably.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the state is locked.
+    pub fn with_mut<T>(&self, f: impl FnOnce(&mut State) -> T) -> T {
+        self.assert_unlocked();
+        f(&mut self.inner.borrow_mut())
+    }
+
+    /// Returns a guard giving access to the state until it's dropped, when the state is put back.
+    ///
+    /// The guard must be dropped before the entity yields, in debug builds the simulation panics otherwise.
+    ///
+    /// ```ignore
+    /// {
+    ///     let mut state = shared_state.lock();
+    ///     *state.get_mut(queue_len).unwrap() += 1;
+    /// }
+    /// yield Action::Passivate;
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the state is already locked.
+    #[must_use]
+    pub fn lock(&self) -> StateGuard {
+        self.assert_unlocked();
+        self.locked.set(true);
+        StateGuard {
+            state: self.inner.take(),
+            shared: self.clone(),
+        }
+    }
+
+    /// Returns `true` while a [`StateGuard`] is alive.
+    #[must_use]
+    pub fn is_locked(&self) -> bool {
+        self.locked.get()
+    }
+
+    fn assert_unlocked(&self) {
+        assert!(!self.is_locked(), "The shared state is locked by a StateGuard");
+    }
+
+    /// Extract the state, leaving an empty one in place until [`SharedState::set`] is called.
+    ///
+    /// Prefer [`SharedState::with_mut`], which can't forget to put the state back.
+    #[must_use]
+    pub fn take(&self) -> State {
+        self.inner.take()
+    }
+
+    /// Put back a state extracted with [`SharedState::take`], replacing the current one.
+    pub fn set(&self, state: State) {
+        *self.inner.borrow_mut() = state;
+    }
+}
+
+/// Access to the [`State`] returned by [`SharedState::lock`], putting it back when dropped.
+#[derive(Debug)]
+pub struct StateGuard {
+    state: State,
+    shared: SharedState,
+}
+
+impl Deref for StateGuard {
+    type Target = State;
+
+    fn deref(&self) -> &State {
+        &self.state
+    }
+}
+
+impl DerefMut for StateGuard {
+    fn deref_mut(&mut self) -> &mut State {
+        &mut self.state
+    }
+}
+
+impl Drop for StateGuard {
+    fn drop(&mut self) {
+        *self.shared.inner.borrow_mut() = std::mem::take(&mut self.state);
+        self.shared.locked.set(false);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn named_values_are_typed_and_unique() {
+        let mut state = State::default();
+        let key = state.insert_named("queue_len", 0usize).unwrap();
+        *state.get_named_mut::<usize>("queue_len").unwrap() += 2;
+        assert_eq!(Some(&2), state.get(key));
+        assert_eq!(Ok(&2), state.get_named::<usize>("queue_len"));
+
+        assert_eq!(
+            Err(StateError::NameTaken("queue_len".to_owned())),
+            state.insert_named("queue_len", 1usize).map(StateKey::id)
+        );
+        assert_eq!(Err(StateError::NotFound("busy".to_owned())), state.get_named::<bool>("busy"));
+        assert_eq!(
+            Err(StateError::TypeMismatch {
+                name: "queue_len".to_owned(),
+                expected: "f64",
+                found: "usize",
+            }),
+            state.get_named::<f64>("queue_len")
+        );
+
+        assert_eq!(Ok(2), state.remove_named::<usize>("queue_len"));
+        assert!(state.insert_named("queue_len", 0.5).is_ok());
+    }
+
+    #[test]
+    fn shared_state_access_is_scoped() {
+        let shared_state = SharedState::default();
+        let counter = shared_state.with_mut(|state| state.insert(0u32));
+        let clone = shared_state.clone();
+        clone.with_mut(|state| *state.get_mut(counter).unwrap() += 1);
+        assert_eq!(1, shared_state.with(|state| *state.get(counter).unwrap()));
+        assert_eq!(1, shared_state.with(State::len));
+    }
+
+    #[test]
+    fn guards_put_the_state_back() {
+        let shared_state = SharedState::default();
+        let counter = {
+            let mut state = shared_state.lock();
+            assert!(shared_state.is_locked());
+            state.insert(5u32)
+        };
+        assert!(!shared_state.is_locked());
+        *shared_state.lock().get_mut(counter).unwrap() += 1;
+        assert_eq!(Some(6), shared_state.with(|state| state.get(counter).copied()));
+    }
+
+    #[test]
+    fn observed_values_record_every_change() {
+        let clock = Rc::new(Cell::new(Duration::ZERO));
+        let clock_ref = ClockRef::from(Rc::clone(&clock));
+        let mut state = State::default();
+        let length = state.insert_named("queue length", 2u32).unwrap();
+        let timeline = state.observe(length, &clock_ref);
+
+        clock.set(Duration::from_secs(1));
+        state.update(length, |length| *length += 1);
+        // A peak undone at the same time is kept.
+        state.update(length, |length| *length -= 1);
+        *state.get_mut(length).unwrap() = 9;
+        clock.set(Duration::from_secs(3));
+        assert_eq!(Some(9), state.set(length, 0));
+
+        let points: Vec<(u64, f64)> = timeline.points().iter().map(|&(time, value)| (time.as_secs(), value)).collect();
+        assert_eq!(vec![(0, 2.0), (1, 3.0), (1, 2.0), (3, 0.0)], points);
+        assert_eq!(Some(3.0), timeline.max());
+        assert_eq!("queue length", timeline.name());
+    }
+}
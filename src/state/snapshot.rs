@@ -0,0 +1,147 @@
+//! JSON snapshots of the values of a [`State`] registered as serializable.
+
+use std::any::Any;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use super::{State, StateError, StateKey};
+
+type SerializeFn = fn(&dyn Any) -> serde_json::Result<Value>;
+type DeserializeFn = fn(Value) -> serde_json::Result<Box<dyn Any>>;
+
+/// A value included in snapshots, with the functions converting it from and to JSON.
+#[derive(Debug)]
+pub(super) struct Serializable {
+    name: String,
+    id: usize,
+    serialize: SerializeFn,
+    deserialize: DeserializeFn,
+}
+
+fn serialize<V: Serialize + 'static>(value: &dyn Any) -> serde_json::Result<Value> {
+    serde_json::to_value(value.downcast_ref::<V>().expect("Ensured by the registration."))
+}
+
+fn deserialize<V: DeserializeOwned + 'static>(value: Value) -> serde_json::Result<Box<dyn Any>> {
+    serde_json::from_value::<V>(value).map(|value| Box::new(value) as Box<dyn Any>)
+}
+
+impl State {
+    /// Insert `value` under `name`, see [`State::insert_named`], and include it in the snapshots
+    /// returned by [`State::to_json`].
+    pub fn insert_serializable<V>(&mut self, name: impl Into<String>, value: V) -> Result<StateKey<V>, StateError>
+    where
+        V: Serialize + DeserializeOwned + 'static,
+    {
+        let name = name.into();
+        let key = self.insert_named(name.clone(), value)?;
+        self.register_serializable::<V>(&name)?;
+        Ok(key)
+    }
+
+    /// Include the value named `name` in the snapshots returned by [`State::to_json`].
+    pub fn register_serializable<V>(&mut self, name: &str) -> Result<(), StateError>
+    where
+        V: Serialize + DeserializeOwned + 'static,
+    {
+        let key = self.key_of::<V>(name)?;
+        self.serializable.retain(|entry| entry.name != name);
+        self.serializable.push(Serializable {
+            name: name.to_owned(),
+            id: key.id,
+            serialize: serialize::<V>,
+            deserialize: deserialize::<V>,
+        });
+        Ok(())
+    }
+
+    /// Returns a JSON object with every serializable value still in the state, by name.
+    pub fn to_json(&self) -> Result<Value, StateError> {
+        let mut object = Map::new();
+        for entry in &self.serializable {
+            if let Some(Some(value)) = self.store.get(entry.id) {
+                let json = (entry.serialize)(value.as_ref()).map_err(|error| StateError::Serialization {
+                    name: entry.name.clone(),
+                    message: error.to_string(),
+                })?;
+                object.insert(entry.name.clone(), json);
+            }
+        }
+        Ok(Value::Object(object))
+    }
+
+    /// Write the snapshot returned by [`State::to_json`] to `path`, pretty printed.
+    pub fn write_json(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let json = self.to_json().map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        fs::write(path, format!("{:#}", json))
+    }
+
+    /// Replace the serializable values with the ones in `json`, a snapshot returned by [`State::to_json`].
+    ///
+    /// Values missing from the snapshot are left untouched. Nothing is replaced if any value fails to deserialize.
+    pub fn restore_json(&mut self, json: &Value) -> Result<(), StateError> {
+        let mut restored = Vec::new();
+        for entry in &self.serializable {
+            if let Some(value) = json.get(&entry.name) {
+                let value = (entry.deserialize)(value.clone()).map_err(|error| StateError::Serialization {
+                    name: entry.name.clone(),
+                    message: error.to_string(),
+                })?;
+                restored.push((entry.id, value));
+            }
+        }
+        for (id, value) in restored {
+            self.store[id] = Some(value);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Counters {
+        arrivals: u32,
+        departures: u32,
+    }
+
+    #[test]
+    fn snapshots_round_trip() {
+        let mut state = State::default();
+        let counters = state
+            .insert_serializable("counters", Counters { arrivals: 3, departures: 1 })
+            .unwrap();
+        state.insert_named("queue_len", 2usize).unwrap();
+        state.register_serializable::<usize>("queue_len").unwrap();
+        state.insert_named("private", 'x').unwrap();
+
+        let json = state.to_json().unwrap();
+        assert_eq!(
+            r#"{"counters":{"arrivals":3,"departures":1},"queue_len":2}"#,
+            json.to_string()
+        );
+
+        state.get_mut(counters).unwrap().arrivals = 10;
+        state.restore_json(&json).unwrap();
+        assert_eq!(3, state.get(counters).unwrap().arrivals);
+
+        let invalid = serde_json::json!({ "queue_len": "two" });
+        assert!(matches!(
+            state.restore_json(&invalid),
+            Err(StateError::Serialization { name, .. }) if name == "queue_len"
+        ));
+        assert!(matches!(
+            state.register_serializable::<u32>("queue_len"),
+            Err(StateError::TypeMismatch { .. })
+        ));
+    }
+}
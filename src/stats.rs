@@ -0,0 +1,1000 @@
+use std::cell::RefCell;
+use std::fmt::{self, Write as _};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::csv;
+use crate::scheduler::ClockRef;
+
+/// A statistics collector that can discard what it collected, see [`Simulation::set_warm_up`](crate::Simulation::set_warm_up).
+pub trait Statistic {
+    fn reset(&self);
+}
+
+// Quantile of the standard normal distribution, Acklam's rational approximation (relative error below 1.2e-9).
+fn normal_quantile(p: f64) -> f64 {
+    const A: [f64; 6] = [
+        -3.969_683_028_665_376e1,
+        2.209_460_984_245_205e2,
+        -2.759_285_104_469_687e2,
+        1.383_577_518_672_69e2,
+        -3.066_479_806_614_716e1,
+        2.506_628_277_459_239,
+    ];
+    const B: [f64; 5] = [
+        -5.447_609_879_822_406e1,
+        1.615_858_368_580_409e2,
+        -1.556_989_798_598_866e2,
+        6.680_131_188_771_972e1,
+        -1.328_068_155_288_572e1,
+    ];
+    const C: [f64; 6] = [
+        -7.784_894_002_430_293e-3,
+        -3.223_964_580_411_365e-1,
+        -2.400_758_277_161_838,
+        -2.549_732_539_343_734,
+        4.374_664_141_464_968,
+        2.938_163_982_698_783,
+    ];
+    const D: [f64; 4] = [
+        7.784_695_709_041_462e-3,
+        3.224_671_290_700_398e-1,
+        2.445_134_137_142_996,
+        3.754_408_661_907_416,
+    ];
+    let tail = |q: f64| {
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5])
+            / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0)
+    };
+    if p < 0.024_25 {
+        tail((-2.0 * p.ln()).sqrt())
+    } else if p > 1.0 - 0.024_25 {
+        -tail((-2.0 * (1.0 - p).ln()).sqrt())
+    } else {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q
+            / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+    }
+}
+
+/// Returns the critical value of Student's t distribution for a two-sided interval at `confidence`
+/// (e.g. 0.95) with the given degrees of freedom, NaN with zero degrees of freedom.
+///
+/// Uses Hill's approximation (Algorithm 396), accurate to at least four significant digits.
+///
+/// # Panics
+///
+/// Panics if `confidence` isn't in `(0, 1)`.
+#[must_use]
+pub fn t_critical(confidence: f64, degrees_of_freedom: usize) -> f64 {
+    assert!(
+        confidence > 0.0 && confidence < 1.0,
+        "Invalid confidence level: {}",
+        confidence
+    );
+    if degrees_of_freedom == 0 {
+        return f64::NAN;
+    }
+    // Probability of both tails.
+    let p = 1.0 - confidence;
+    let n = degrees_of_freedom as f64;
+    if degrees_of_freedom == 1 {
+        let angle = p * std::f64::consts::FRAC_PI_2;
+        return angle.cos() / angle.sin();
+    }
+    if degrees_of_freedom == 2 {
+        return (2.0 / (p * (2.0 - p)) - 2.0).sqrt();
+    }
+    let a = 1.0 / (n - 0.5);
+    let b = 48.0 / (a * a);
+    let mut c = ((20_700.0 * a / b - 98.0) * a - 16.0) * a + 96.36;
+    let d = ((94.5 / (b + c) - 3.0) / b + 1.0) * (a * std::f64::consts::FRAC_PI_2).sqrt() * n;
+    let x = d * p;
+    let mut y = x.powf(2.0 / n);
+    if y > 0.05 + a {
+        // Asymptotic inverse expansion about the normal quantile.
+        let x = normal_quantile(0.5 * p);
+        y = x * x;
+        if degrees_of_freedom < 5 {
+            c += 0.3 * (n - 4.5) * (x + 0.6);
+        }
+        c += (((0.05 * d * x - 5.0) * x - 7.0) * x - 2.0) * x + b;
+        y = (((((0.4 * y + 6.3) * y + 36.0) * y + 94.5) / c - y - 3.0) / b + 1.0) * x;
+        y = a * y * y;
+        y = if y > 0.002 { y.exp() - 1.0 } else { 0.5 * y * y + y };
+    } else {
+        y = ((1.0 / (((n + 6.0) / (n * y) - 0.089 * d - 0.822) * (n + 2.0) * 3.0) + 0.5 / (n + 4.0)) * y - 1.0)
+            * (n + 1.0)
+            / (n + 2.0)
+            + 1.0 / y;
+    }
+    (n * y).sqrt()
+}
+
+/// Summary statistics of a series of values with a confidence interval of their mean.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Summary {
+    pub count: usize,
+    pub mean: f64,
+    /// Sample standard deviation, NaN with less than two values.
+    pub std_dev: f64,
+    /// Half width of the confidence interval of the mean, NaN with less than two values.
+    pub half_width: f64,
+    /// Confidence level of the interval, e.g. 0.95.
+    pub confidence: f64,
+}
+
+impl Summary {
+    /// Summarize `values`, `None` if there are none.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `confidence` isn't in `(0, 1)`.
+    #[must_use]
+    pub fn from_values(values: &[f64], confidence: f64) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        let count = values.len();
+        let mean = values.iter().sum::<f64>() / count as f64;
+        let squares: f64 = values.iter().map(|value| (value - mean).powi(2)).sum();
+        let variance = if count > 1 { squares / (count - 1) as f64 } else { f64::NAN };
+        Some(Self::new(count, mean, variance.sqrt(), confidence))
+    }
+
+    fn new(count: usize, mean: f64, std_dev: f64, confidence: f64) -> Self {
+        Self {
+            count,
+            mean,
+            std_dev,
+            half_width: t_critical(confidence, count.saturating_sub(1)) * std_dev / (count as f64).sqrt(),
+            confidence,
+        }
+    }
+
+    /// Returns the bounds of the confidence interval.
+    #[must_use]
+    pub fn interval(&self) -> (f64, f64) {
+        (self.mean - self.half_width, self.mean + self.half_width)
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ± {} ({}% confidence, n = {})",
+            self.mean,
+            self.half_width,
+            self.confidence * 100.0,
+            self.count
+        )
+    }
+}
+
+fn optional(value: Option<f64>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+#[derive(Debug, Default)]
+struct TallyInner {
+    name: String,
+    count: usize,
+    mean: f64,
+    // Sum of squared differences from the mean (Welford's algorithm).
+    m2: f64,
+    min: Option<f64>,
+    max: Option<f64>,
+    batch_means: Option<BatchMeans>,
+}
+
+/// Statistics of a series of observations, such as waiting times.
+///
+/// Clones share the same observations so it can be moved into generators and read after the run.
+#[derive(Debug, Clone)]
+pub struct Tally {
+    inner: Rc<RefCell<TallyInner>>,
+}
+
+impl Tally {
+    #[must_use]
+    pub fn new(name: impl Into<String>) -> Self {
+        let inner = TallyInner {
+            name: name.into(),
+            ..TallyInner::default()
+        };
+        Self {
+            inner: Rc::new(RefCell::new(inner)),
+        }
+    }
+
+    pub fn record(&self, value: f64) {
+        let mut inner = self.inner.borrow_mut();
+        inner.count += 1;
+        let delta = value - inner.mean;
+        inner.mean += delta / inner.count as f64;
+        inner.m2 += delta * (value - inner.mean);
+        inner.min = Some(inner.min.map_or(value, |min| min.min(value)));
+        inner.max = Some(inner.max.map_or(value, |max| max.max(value)));
+        if let Some(batch_means) = &inner.batch_means {
+            batch_means.record(value);
+        }
+    }
+
+    /// Keep every observation from now on in a [`BatchMeans`] analyzer, returning it.
+    ///
+    /// Calling it again returns the same analyzer.
+    pub fn batch_means(&self) -> BatchMeans {
+        let mut inner = self.inner.borrow_mut();
+        inner.batch_means.get_or_insert_with(BatchMeans::default).clone()
+    }
+
+    /// Record a duration in seconds.
+    pub fn record_duration(&self, duration: Duration) {
+        self.record(duration.as_secs_f64());
+    }
+
+    #[must_use]
+    pub fn name(&self) -> String {
+        self.inner.borrow().name.clone()
+    }
+
+    #[must_use]
+    pub fn count(&self) -> usize {
+        self.inner.borrow().count
+    }
+
+    /// Returns the mean of the observations, `None` if there are none.
+    #[must_use]
+    pub fn mean(&self) -> Option<f64> {
+        let inner = self.inner.borrow();
+        (inner.count > 0).then(|| inner.mean)
+    }
+
+    /// Returns the sample variance of the observations, `None` if there are less than two.
+    #[must_use]
+    pub fn variance(&self) -> Option<f64> {
+        let inner = self.inner.borrow();
+        (inner.count > 1).then(|| inner.m2 / (inner.count - 1) as f64)
+    }
+
+    /// Returns the sample standard deviation of the observations, `None` if there are less than two.
+    #[must_use]
+    pub fn std_dev(&self) -> Option<f64> {
+        self.variance().map(f64::sqrt)
+    }
+
+    /// Returns the summary of the observations with a 95% confidence interval, `None` if there are none.
+    ///
+    /// The interval assumes independent observations, use [`Tally::batch_means`] for autocorrelated ones.
+    #[must_use]
+    pub fn summary(&self) -> Option<Summary> {
+        self.summary_at(0.95)
+    }
+
+    /// Same as [`Tally::summary`] with a confidence level in `(0, 1)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `confidence` isn't in `(0, 1)`.
+    #[must_use]
+    pub fn summary_at(&self, confidence: f64) -> Option<Summary> {
+        let mean = self.mean()?;
+        let std_dev = self.std_dev().unwrap_or(f64::NAN);
+        Some(Summary::new(self.count(), mean, std_dev, confidence))
+    }
+
+    #[must_use]
+    pub fn min(&self) -> Option<f64> {
+        self.inner.borrow().min
+    }
+
+    #[must_use]
+    pub fn max(&self) -> Option<f64> {
+        self.inner.borrow().max
+    }
+
+    /// Discard every observation, e.g. at the end of a warm-up period.
+    pub fn reset(&self) {
+        let mut inner = self.inner.borrow_mut();
+        let name = std::mem::take(&mut inner.name);
+        let batch_means = inner.batch_means.take();
+        if let Some(batch_means) = &batch_means {
+            batch_means.reset();
+        }
+        *inner = TallyInner {
+            name,
+            batch_means,
+            ..TallyInner::default()
+        };
+    }
+
+    /// Returns the statistics as CSV with a header and a single row.
+    #[must_use]
+    pub fn to_csv(&self) -> String {
+        let mut output = String::from("name,count,mean,variance,std_dev,min,max\n");
+        let _ = writeln!(
+            output,
+            "{},{},{},{},{},{},{}",
+            csv::field(&self.name()),
+            self.count(),
+            optional(self.mean()),
+            optional(self.variance()),
+            optional(self.std_dev()),
+            optional(self.min()),
+            optional(self.max())
+        );
+        output
+    }
+
+    /// Write the statistics as CSV to `path`, see [`Tally::to_csv`].
+    pub fn write_csv(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_csv())
+    }
+}
+
+impl Statistic for Tally {
+    fn reset(&self) {
+        Tally::reset(self);
+    }
+}
+
+#[derive(Debug)]
+struct AccumulateInner {
+    name: String,
+    level: f64,
+    start: Duration,
+    last_change: Duration,
+    // Integral of the level from `start` to `last_change`.
+    area: f64,
+    min: f64,
+    max: f64,
+}
+
+/// Time-weighted statistics of a level that changes over time, such as the length of a queue.
+///
+/// Clones share the same level so it can be moved into generators and read after the run.
+#[derive(Debug, Clone)]
+pub struct Accumulate {
+    inner: Rc<RefCell<AccumulateInner>>,
+    clock: ClockRef,
+}
+
+impl Accumulate {
+    /// Start tracking a level with value `initial` from the current simulation time.
+    #[must_use]
+    pub fn new(name: impl Into<String>, clock: ClockRef, initial: f64) -> Self {
+        let now = clock.time();
+        let inner = AccumulateInner {
+            name: name.into(),
+            level: initial,
+            start: now,
+            last_change: now,
+            area: 0.0,
+            min: initial,
+            max: initial,
+        };
+        Self {
+            inner: Rc::new(RefCell::new(inner)),
+            clock,
+        }
+    }
+
+    /// Change the level at the current simulation time.
+    pub fn set(&self, level: f64) {
+        let now = self.clock.time();
+        let mut inner = self.inner.borrow_mut();
+        inner.area += inner.level * now.saturating_sub(inner.last_change).as_secs_f64();
+        inner.last_change = now;
+        inner.level = level;
+        inner.min = inner.min.min(level);
+        inner.max = inner.max.max(level);
+    }
+
+    /// Change the level by `delta` at the current simulation time.
+    pub fn add(&self, delta: f64) {
+        self.set(self.level() + delta);
+    }
+
+    #[must_use]
+    pub fn name(&self) -> String {
+        self.inner.borrow().name.clone()
+    }
+
+    /// Returns the current level.
+    #[must_use]
+    pub fn level(&self) -> f64 {
+        self.inner.borrow().level
+    }
+
+    /// Returns the time-weighted average of the level up to the current simulation time.
+    ///
+    /// Returns the current level if no time has elapsed.
+    #[must_use]
+    pub fn time_average(&self) -> f64 {
+        let now = self.clock.time();
+        let inner = self.inner.borrow();
+        let elapsed = now.saturating_sub(inner.start).as_secs_f64();
+        if elapsed == 0.0 {
+            return inner.level;
+        }
+        let area = inner.area + inner.level * now.saturating_sub(inner.last_change).as_secs_f64();
+        area / elapsed
+    }
+
+    #[must_use]
+    pub fn min(&self) -> f64 {
+        self.inner.borrow().min
+    }
+
+    #[must_use]
+    pub fn max(&self) -> f64 {
+        self.inner.borrow().max
+    }
+
+    /// Returns the time elapsed since the level started being tracked.
+    #[must_use]
+    pub fn elapsed(&self) -> Duration {
+        self.clock.time().saturating_sub(self.inner.borrow().start)
+    }
+
+    /// Start tracking again from the current simulation time keeping the current level,
+    /// e.g. at the end of a warm-up period.
+    pub fn reset(&self) {
+        let now = self.clock.time();
+        let mut inner = self.inner.borrow_mut();
+        inner.start = now;
+        inner.last_change = now;
+        inner.area = 0.0;
+        inner.min = inner.level;
+        inner.max = inner.level;
+    }
+
+    /// Returns the statistics as CSV with a header and a single row, `elapsed` is in seconds.
+    #[must_use]
+    pub fn to_csv(&self) -> String {
+        let mut output = String::from("name,elapsed,time_average,min,max,level\n");
+        let _ = writeln!(
+            output,
+            "{},{},{},{},{},{}",
+            csv::field(&self.name()),
+            self.elapsed().as_secs_f64(),
+            self.time_average(),
+            self.min(),
+            self.max(),
+            self.level()
+        );
+        output
+    }
+
+    /// Write the statistics as CSV to `path`, see [`Accumulate::to_csv`].
+    pub fn write_csv(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_csv())
+    }
+}
+
+impl Statistic for Accumulate {
+    fn reset(&self) {
+        Accumulate::reset(self);
+    }
+}
+
+#[derive(Debug)]
+struct HistogramInner {
+    name: String,
+    // Bin `i` counts the values in `[edges[i], edges[i + 1])`.
+    edges: Vec<f64>,
+    counts: Vec<usize>,
+    underflow: usize,
+    overflow: usize,
+}
+
+/// Frequency distribution of a series of observations, such as waiting times.
+///
+/// Clones share the same observations so it can be moved into generators and read after the run.
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    inner: Rc<RefCell<HistogramInner>>,
+}
+
+impl Histogram {
+    /// `bins` bins of the same width covering `[low, high)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bins` is zero or `low` isn't less than `high`.
+    #[must_use]
+    pub fn fixed(name: impl Into<String>, low: f64, high: f64, bins: usize) -> Self {
+        assert!(bins > 0, "A histogram needs at least one bin");
+        let width = (high - low) / bins as f64;
+        let mut edges: Vec<f64> = (0..bins).map(|bin| low + width * bin as f64).collect();
+        edges.push(high);
+        Self::with_edges(name, edges)
+    }
+
+    /// Bins between consecutive `edges`, each bin includes its lower edge.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are less than two edges or they aren't strictly increasing.
+    #[must_use]
+    pub fn with_edges(name: impl Into<String>, edges: Vec<f64>) -> Self {
+        assert!(edges.len() >= 2, "A histogram needs at least two edges");
+        assert!(
+            edges.windows(2).all(|pair| pair[0] < pair[1]),
+            "Histogram edges must be strictly increasing: {:?}",
+            edges
+        );
+        let inner = HistogramInner {
+            name: name.into(),
+            counts: vec![0; edges.len() - 1],
+            edges,
+            underflow: 0,
+            overflow: 0,
+        };
+        Self {
+            inner: Rc::new(RefCell::new(inner)),
+        }
+    }
+
+    /// Record a value, values outside of the edges are counted as underflow or overflow.
+    pub fn record(&self, value: f64) {
+        let mut inner = self.inner.borrow_mut();
+        let position = inner.edges.partition_point(|&edge| edge <= value);
+        if position == 0 {
+            inner.underflow += 1;
+        } else if position == inner.edges.len() {
+            inner.overflow += 1;
+        } else {
+            inner.counts[position - 1] += 1;
+        }
+    }
+
+    /// Record a duration in seconds.
+    pub fn record_duration(&self, duration: Duration) {
+        self.record(duration.as_secs_f64());
+    }
+
+    #[must_use]
+    pub fn name(&self) -> String {
+        self.inner.borrow().name.clone()
+    }
+
+    /// Returns every bin as `(low, high, count)`.
+    #[must_use]
+    pub fn bins(&self) -> Vec<(f64, f64, usize)> {
+        let inner = self.inner.borrow();
+        inner
+            .edges
+            .windows(2)
+            .zip(&inner.counts)
+            .map(|(pair, &count)| (pair[0], pair[1], count))
+            .collect()
+    }
+
+    /// Returns the number of values below the first edge.
+    #[must_use]
+    pub fn underflow(&self) -> usize {
+        self.inner.borrow().underflow
+    }
+
+    /// Returns the number of values at or above the last edge.
+    #[must_use]
+    pub fn overflow(&self) -> usize {
+        self.inner.borrow().overflow
+    }
+
+    /// Returns the number of values recorded, including underflow and overflow.
+    #[must_use]
+    pub fn count(&self) -> usize {
+        let inner = self.inner.borrow();
+        inner.counts.iter().sum::<usize>() + inner.underflow + inner.overflow
+    }
+
+    /// Discard every observation, e.g. at the end of a warm-up period.
+    pub fn reset(&self) {
+        let mut inner = self.inner.borrow_mut();
+        inner.counts.iter_mut().for_each(|count| *count = 0);
+        inner.underflow = 0;
+        inner.overflow = 0;
+    }
+
+    /// Returns a text rendering of the histogram, one line per bin with a bar at most `width` characters long.
+    #[must_use]
+    pub fn render(&self, width: usize) -> String {
+        let bins = self.bins();
+        let highest = bins.iter().map(|&(_, _, count)| count).max().unwrap_or(0).max(1);
+        let mut output = format!("{}\n", self.name());
+        for (low, high, count) in bins {
+            let _ = writeln!(
+                output,
+                "[{}, {}) {:>6} {}",
+                low,
+                high,
+                count,
+                "#".repeat(count * width / highest)
+            );
+        }
+        let _ = writeln!(output, "underflow {}, overflow {}", self.underflow(), self.overflow());
+        output
+    }
+
+    /// Returns the bins as CSV, one row per bin.
+    ///
+    /// Underflow and overflow are written as bins with an infinite edge.
+    #[must_use]
+    pub fn to_csv(&self) -> String {
+        let mut output = String::from("low,high,count\n");
+        let inner = self.inner.borrow();
+        let first = inner.edges[0];
+        let last = inner.edges[inner.edges.len() - 1];
+        let _ = writeln!(output, "-inf,{},{}", first, inner.underflow);
+        for (pair, count) in inner.edges.windows(2).zip(&inner.counts) {
+            let _ = writeln!(output, "{},{},{}", pair[0], pair[1], count);
+        }
+        let _ = writeln!(output, "{},inf,{}", last, inner.overflow);
+        output
+    }
+
+    /// Write the bins as CSV to `path`, see [`Histogram::to_csv`].
+    pub fn write_csv(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_csv())
+    }
+}
+
+impl Statistic for Histogram {
+    fn reset(&self) {
+        Histogram::reset(self);
+    }
+}
+
+/// Steady-state estimate produced by [`BatchMeans::analyze`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BatchMeansResult {
+    pub batches: usize,
+    pub batch_size: usize,
+    /// Mean of the observations used, the ones that didn't fill a batch are left out.
+    pub mean: f64,
+    /// Standard deviation of the batch means.
+    pub std_dev: f64,
+    /// Half width of the 95% confidence interval of the mean.
+    pub half_width: f64,
+    /// Lag-1 autocorrelation of the batch means, close to zero when batches are nearly independent.
+    pub lag1_autocorrelation: f64,
+}
+
+impl BatchMeansResult {
+    /// Returns the summary of the batch means with a confidence interval at `confidence`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `confidence` isn't in `(0, 1)`.
+    #[must_use]
+    pub fn summary_at(&self, confidence: f64) -> Summary {
+        Summary::new(self.batches, self.mean, self.std_dev, confidence)
+    }
+}
+
+/// Batch means analysis of the observations of a single long run, created with [`Tally::batch_means`].
+///
+/// The observations are split into consecutive batches whose means are treated as independent samples.
+/// Batches start small and are doubled until the lag-1 autocorrelation of their means is low enough.
+#[derive(Debug, Clone, Default)]
+pub struct BatchMeans {
+    observations: Rc<RefCell<Vec<f64>>>,
+}
+
+impl BatchMeans {
+    // Batches are doubled while there are more than `MIN_BATCHES * 2`.
+    const MIN_BATCHES: usize = 10;
+    const INITIAL_BATCHES: usize = 64;
+    const MAX_AUTOCORRELATION: f64 = 0.2;
+
+    pub fn record(&self, value: f64) {
+        self.observations.borrow_mut().push(value);
+    }
+
+    /// Returns the number of observations kept.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.observations.borrow().len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.observations.borrow().is_empty()
+    }
+
+    /// Discard every observation.
+    pub fn reset(&self) {
+        self.observations.borrow_mut().clear();
+    }
+
+    /// Returns the estimate with automatically sized batches, `None` with less than ten observations.
+    ///
+    /// If the autocorrelation is still high once only ten batches are left they are used anyway,
+    /// check `lag1_autocorrelation` to see whether the run was long enough.
+    #[must_use]
+    pub fn analyze(&self) -> Option<BatchMeansResult> {
+        let observations = self.observations.borrow();
+        let mut batch_size = (observations.len() / Self::INITIAL_BATCHES).max(1);
+        loop {
+            let result = Self::with_batch_size(&observations, batch_size)?;
+            if result.lag1_autocorrelation.abs() <= Self::MAX_AUTOCORRELATION || result.batches < Self::MIN_BATCHES * 2 {
+                return Some(result);
+            }
+            batch_size *= 2;
+        }
+    }
+
+    /// Returns the MSER-5 estimate of the warm-up in the observations kept, see [`mser`].
+    #[must_use]
+    pub fn mser5(&self) -> Option<Truncation> {
+        mser5(&self.observations.borrow())
+    }
+
+    /// Returns the estimate with batches of `batch_size` observations, `None` if there are less than ten batches.
+    #[must_use]
+    pub fn analyze_with_batch_size(&self, batch_size: usize) -> Option<BatchMeansResult> {
+        Self::with_batch_size(&self.observations.borrow(), batch_size.max(1))
+    }
+
+    fn with_batch_size(observations: &[f64], batch_size: usize) -> Option<BatchMeansResult> {
+        let batches = observations.len() / batch_size;
+        if batches < Self::MIN_BATCHES {
+            return None;
+        }
+        // The leftover observations are the earliest ones, the most affected by the initial conditions.
+        let used = &observations[observations.len() - batches * batch_size..];
+        let means: Vec<f64> = used
+            .chunks(batch_size)
+            .map(|batch| batch.iter().sum::<f64>() / batch_size as f64)
+            .collect();
+        let count = batches as f64;
+        let mean = means.iter().sum::<f64>() / count;
+        let squares: f64 = means.iter().map(|batch_mean| (batch_mean - mean).powi(2)).sum();
+        let std_dev = (squares / (count - 1.0)).sqrt();
+        let lag1_autocorrelation = if squares == 0.0 {
+            0.0
+        } else {
+            means
+                .windows(2)
+                .map(|pair| (pair[0] - mean) * (pair[1] - mean))
+                .sum::<f64>()
+                / squares
+        };
+        Some(BatchMeansResult {
+            batches,
+            batch_size,
+            mean,
+            std_dev,
+            half_width: t_critical(0.95, batches - 1) * std_dev / count.sqrt(),
+            lag1_autocorrelation,
+        })
+    }
+}
+
+impl Statistic for BatchMeans {
+    fn reset(&self) {
+        BatchMeans::reset(self);
+    }
+}
+
+/// Warm-up estimate produced by [`mser`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Truncation {
+    /// Number of leading observations to delete, a multiple of `batch_size`.
+    pub observations: usize,
+    pub batch_size: usize,
+    /// Mean of the observations left after the truncation.
+    pub mean: f64,
+    /// The minimized MSER statistic, the squared standard error of the truncated mean.
+    pub mser: f64,
+    /// `false` if the minimum is in the last batches of the first half of the series, meaning the series doesn't
+    /// reach a steady state and the run should be longer.
+    pub reliable: bool,
+}
+
+/// Estimate how many leading observations of `values` are biased by the initial conditions with the Marginal
+/// Standard Error Rule on batches of `batch_size` observations, `None` with less than four batches.
+///
+/// The truncation minimizes the standard error of the mean of the remaining batches, only truncations in the
+/// first half of the series are considered. Use it to set the warm-up from a pilot run, e.g. with
+/// [`BatchMeans::mser5`] or [`Timeline::mser5`](crate::Timeline::mser5).
+#[must_use]
+pub fn mser(values: &[f64], batch_size: usize) -> Option<Truncation> {
+    let batch_size = batch_size.max(1);
+    let means: Vec<f64> = values
+        .chunks_exact(batch_size)
+        .map(|batch| batch.iter().sum::<f64>() / batch_size as f64)
+        .collect();
+    if means.len() < 4 {
+        return None;
+    }
+    // Sums of the batch means and their squares from every batch to the end, to evaluate each truncation in
+    // constant time.
+    let mut sums = vec![(0.0, 0.0); means.len() + 1];
+    for (index, mean) in means.iter().enumerate().rev() {
+        let (sum, squares) = sums[index + 1];
+        sums[index] = (sum + mean, squares + mean * mean);
+    }
+    let candidates = means.len() / 2;
+    let (truncated, mser, mean) = (0..=candidates)
+        .map(|truncated| {
+            let (sum, squares) = sums[truncated];
+            let count = (means.len() - truncated) as f64;
+            let mean = sum / count;
+            let deviations = (squares - sum * mean).max(0.0);
+            (truncated, deviations / (count * count), mean)
+        })
+        .fold((0, f64::INFINITY, 0.0), |best, candidate| if candidate.1 < best.1 { candidate } else { best });
+    Some(Truncation {
+        observations: truncated * batch_size,
+        batch_size,
+        mean,
+        mser,
+        reliable: truncated + 1 < candidates,
+    })
+}
+
+/// [`mser`] with batches of five observations, the usual MSER-5 rule.
+#[must_use]
+pub fn mser5(values: &[f64]) -> Option<Truncation> {
+    mser(values, 5)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{process, Action, GenBoxed, Simulation};
+
+    #[test]
+    fn t_critical_values() {
+        // (confidence, degrees of freedom, value from the usual tables)
+        let table = [
+            (0.95, 1, 12.706),
+            (0.95, 2, 4.303),
+            (0.95, 4, 2.776),
+            (0.95, 30, 2.042),
+            (0.90, 5, 2.015),
+            (0.99, 10, 3.169),
+            (0.99, 120, 2.617),
+        ];
+        for (confidence, degrees_of_freedom, expected) in table {
+            let value = t_critical(confidence, degrees_of_freedom);
+            assert!((value - expected).abs() < 1e-3, "{} vs {}", value, expected);
+        }
+        assert!((normal_quantile(0.975) - 1.959_964).abs() < 1e-6);
+    }
+
+    #[test]
+    fn tally_mean_and_variance() {
+        let tally = Tally::new("waiting");
+        assert_eq!(None, tally.mean());
+        for value in [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0] {
+            tally.record(value);
+        }
+        assert_eq!(8, tally.count());
+        assert_eq!(Some(5.0), tally.mean());
+        assert!((tally.variance().unwrap() - 32.0 / 7.0).abs() < 1e-12);
+        assert_eq!((Some(2.0), Some(9.0)), (tally.min(), tally.max()));
+        let summary = tally.summary_at(0.99).unwrap();
+        assert_eq!(Some(summary), Summary::from_values(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0], 0.99));
+        assert!((summary.half_width - 3.499 * (32.0f64 / 7.0).sqrt() / 8.0f64.sqrt()).abs() < 1e-3);
+        assert_eq!("name,count,mean,variance,std_dev,min,max", tally.to_csv().lines().next().unwrap());
+    }
+
+    fn arrival(queue: Accumulate, at: u64, stay: u64) -> GenBoxed<()> {
+        let mut step = 0;
+        process(move |_| {
+            step += 1;
+            match step {
+                1 => Some(Action::Hold(Duration::from_secs(at))),
+                2 => {
+                    queue.add(1.0);
+                    Some(Action::Hold(Duration::from_secs(stay)))
+                }
+                _ => {
+                    queue.add(-1.0);
+                    None
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn accumulate_is_weighted_by_time() {
+        let mut simulation = Simulation::default();
+        let queue = Accumulate::new("queue", simulation.clock(), 0.0);
+        // Level 1 during [1, 3), 2 during [3, 5), 1 during [5, 6) and 0 up to 8.
+        for (at, stay) in [(1, 4), (3, 3)] {
+            let key = simulation.add_generator(arrival(queue.clone(), at, stay));
+            simulation.schedule_now(key);
+        }
+        simulation.run_until_empty();
+        assert_eq!(Duration::from_secs(6), simulation.time());
+        assert_eq!(7.0 / 6.0, queue.time_average());
+        assert_eq!(2.0, queue.max());
+        assert_eq!(0.0, queue.level());
+    }
+
+    #[test]
+    fn statistics_are_reset_after_the_warm_up() {
+        let mut simulation = Simulation::default();
+        let queue = Accumulate::new("queue", simulation.clock(), 0.0);
+        simulation.add_statistic("queue", queue.clone());
+        simulation.set_warm_up(Duration::from_secs(2));
+        // Level 1 during [1, 5) and 2 during [3, 6), only [2, 6) is measured.
+        for (at, stay) in [(1, 4), (3, 3)] {
+            let key = simulation.add_generator(arrival(queue.clone(), at, stay));
+            simulation.schedule_now(key);
+        }
+        simulation.run_until_empty();
+        assert_eq!(Duration::from_secs(4), queue.elapsed());
+        assert_eq!(6.0 / 4.0, queue.time_average());
+    }
+
+    #[test]
+    fn batch_means_grow_until_batches_are_independent() {
+        let tally = Tally::new("waiting");
+        let batch_means = tally.batch_means();
+        assert_eq!(None, batch_means.analyze());
+
+        // An autocorrelated series alternating between 0 and 2 every 400 observations,
+        // the means of batches of 100 are strongly correlated while those of batches of 200 aren't.
+        for index in 0..6400 {
+            tally.record(if (index / 400) % 2 == 0 { 0.0 } else { 2.0 });
+        }
+        assert_eq!(6400, batch_means.len());
+        assert!(batch_means.analyze_with_batch_size(100).unwrap().lag1_autocorrelation > 0.4);
+        let result = batch_means.analyze().unwrap();
+        assert_eq!((200, 32), (result.batch_size, result.batches));
+        assert_eq!(1.0, result.mean);
+        assert!(result.lag1_autocorrelation.abs() <= 0.2);
+
+        tally.reset();
+        assert!(batch_means.is_empty());
+    }
+
+    #[test]
+    fn histogram_bins() {
+        let waiting = Histogram::fixed("waiting", 0.0, 10.0, 5);
+        for value in [-1.0, 0.0, 1.9, 2.0, 5.5, 9.99, 10.0, 12.0] {
+            waiting.record(value);
+        }
+        let counts: Vec<usize> = waiting.bins().iter().map(|&(_, _, count)| count).collect();
+        assert_eq!(vec![2, 1, 1, 0, 1], counts);
+        assert_eq!((1, 2, 8), (waiting.underflow(), waiting.overflow(), waiting.count()));
+        assert!(waiting.render(4).contains("[0, 2)      2 ####"));
+
+        let custom = Histogram::with_edges("custom", vec![0.0, 1.0, 10.0]);
+        custom.record_duration(Duration::from_millis(1500));
+        assert_eq!("low,high,count\n-inf,0,0\n0,1,0\n1,10,1\n10,inf,0\n", custom.to_csv());
+    }
+
+    #[test]
+    fn mser_truncates_the_initial_transient() {
+        // A transient decaying over the first 100 observations, then noise around 10.
+        let values: Vec<f64> = (0..1000)
+            .map(|index| {
+                let noise = if index % 2 == 0 { 0.5 } else { -0.5 };
+                let transient = if index < 100 { 50.0 * (1.0 - index as f64 / 100.0) } else { 0.0 };
+                10.0 + transient + noise
+            })
+            .collect();
+        let truncation = mser5(&values).unwrap();
+        assert!((90..=110).contains(&truncation.observations), "{:?}", truncation);
+        assert_eq!(0, truncation.observations % 5);
+        assert!((truncation.mean - 10.0).abs() < 0.01);
+        assert!(truncation.reliable);
+
+        // A transient lasting more than half of the series: the minimum is at the last truncation considered.
+        let shifted: Vec<f64> = (0..200).map(|index| if index < 110 { 100.0 } else { 10.0 } + (index % 3) as f64).collect();
+        assert!(!mser5(&shifted).unwrap().reliable);
+        assert_eq!(None, mser5(&values[..15]));
+    }
+}
@@ -0,0 +1,92 @@
+use std::time::Duration;
+
+use crate::{SimTime, Simulation, SimulationError, StepContext, StepResult};
+
+/// Iterator over the steps of a simulation, created with [`Simulation::steps`] or [`Simulation::steps_with`].
+///
+/// Every item is the result of one step, the iterator ends when no more events are left:
+///
+/// ```ignore
+/// for step in simulation.steps().until(Duration::from_secs(60)) {
+///     let step = step?;
+///     println!("{:?}: {:?} {:?}", step.time(), step.key(), step.action());
+/// }
+/// ```
+pub struct Steps<'a, R, F> {
+    simulation: &'a mut Simulation<R>,
+    provider: F,
+    limit: Option<Duration>,
+}
+
+impl<'a, R, F> Steps<'a, R, F>
+where
+    R: 'static,
+    F: FnMut(&StepContext) -> R,
+{
+    pub(crate) fn new(simulation: &'a mut Simulation<R>, provider: F) -> Self {
+        Self {
+            simulation,
+            provider,
+            limit: None,
+        }
+    }
+
+    /// Stop before the first event scheduled after `limit`, leaving it pending.
+    #[must_use]
+    pub fn until(mut self, limit: impl SimTime) -> Self {
+        self.limit = Some(limit.to_duration());
+        self
+    }
+}
+
+impl<R, F> Iterator for Steps<'_, R, F>
+where
+    R: 'static,
+    F: FnMut(&StepContext) -> R,
+{
+    type Item = Result<StepResult, SimulationError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(limit) = self.limit {
+            if matches!(self.simulation.next_event_time(), Some(next) if next > limit) {
+                return None;
+            }
+        }
+        match self.simulation.step_with_provider(&mut self.provider) {
+            Ok(step) if !step.should_continue() => None,
+            result => Some(result),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{process, Action};
+
+    #[test]
+    fn steps_are_iterable() {
+        let mut simulation = Simulation::default();
+        let mut holds = 0;
+        let key = simulation.add_generator(process(move |_| {
+            holds += 1;
+            (holds <= 5).then_some(Action::Hold(Duration::from_secs(1)))
+        }));
+        simulation.schedule_now(key);
+
+        let times: Vec<Duration> = simulation
+            .steps()
+            .until(Duration::from_secs(2))
+            .map(|step| step.unwrap().time())
+            .collect();
+        assert_eq!(
+            vec![Duration::ZERO, Duration::from_secs(1), Duration::from_secs(2)],
+            times
+        );
+        assert_eq!(Some(Duration::from_secs(3)), simulation.next_event_time());
+
+        let finished = simulation.steps().filter_map(Result::ok).filter(StepResult::entity_finished);
+        assert_eq!(1, finished.count());
+        assert_eq!(Duration::from_secs(5), simulation.time());
+    }
+}
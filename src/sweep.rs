@@ -0,0 +1,329 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::csv;
+use crate::stats::Summary;
+use crate::{Replication, Replications, Replicator};
+
+/// A combination of parameter values of a [`Sweep`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SweepPoint {
+    values: Vec<(String, f64)>,
+}
+
+impl SweepPoint {
+    /// Returns the value of the parameter `name`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the sweep has no parameter `name`.
+    #[must_use]
+    pub fn get(&self, name: &str) -> f64 {
+        self.values
+            .iter()
+            .find(|(parameter, _)| parameter == name)
+            .map(|&(_, value)| value)
+            .unwrap_or_else(|| panic!("the sweep has no parameter `{}`", name))
+    }
+
+    /// Returns the value of every parameter, in the order they were added to the sweep.
+    #[must_use]
+    pub fn values(&self) -> &[(String, f64)] {
+        &self.values
+    }
+}
+
+/// Runs a model for every combination of parameter values, replicating each one.
+///
+/// Every point uses the same replication seeds, so points are compared under common random numbers:
+///
+/// ```ignore
+/// let results = Sweep::new()
+///     .over("arrival rate", [0.5, 0.8, 0.95])
+///     .over("servers", 1..=5)
+///     .replications(30)
+///     .run(|point, replication| {
+///         let mut simulation = Simulation::default();
+///         simulation.set_seed(replication.seed());
+///         // Build the model from `point.get("arrival rate")` and `point.get("servers")`, and run it.
+///         replication.record("mean wait", wait.mean().unwrap_or(0.0));
+///     });
+/// results.write_csv("sweep.csv")?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct Sweep {
+    parameters: Vec<(String, Vec<f64>)>,
+    replications: usize,
+    base_seed: u64,
+}
+
+impl Default for Sweep {
+    fn default() -> Self {
+        Self {
+            parameters: Vec::new(),
+            replications: 1,
+            base_seed: 0,
+        }
+    }
+}
+
+impl Sweep {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the parameter `name` swept over `values`, every combination with the previous parameters is run.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the sweep already has a parameter `name`.
+    #[must_use]
+    pub fn over<V: Into<f64>>(mut self, name: impl Into<String>, values: impl IntoIterator<Item = V>) -> Self {
+        let name = name.into();
+        assert!(
+            self.parameters.iter().all(|(parameter, _)| *parameter != name),
+            "the sweep already has a parameter `{}`",
+            name
+        );
+        self.parameters.push((name, values.into_iter().map(Into::into).collect()));
+        self
+    }
+
+    /// Run every point `replications` times, once by default.
+    #[must_use]
+    pub fn replications(mut self, replications: usize) -> Self {
+        self.replications = replications;
+        self
+    }
+
+    /// Derive the seeds of the replications from `seed`, see [`Replicator::base_seed`].
+    #[must_use]
+    pub fn base_seed(mut self, seed: u64) -> Self {
+        self.base_seed = seed;
+        self
+    }
+
+    fn replicator(&self) -> Replicator {
+        Replicator::new(self.replications).base_seed(self.base_seed)
+    }
+
+    /// Returns every combination of parameter values, the last parameter varying the fastest.
+    #[must_use]
+    pub fn points(&self) -> Vec<SweepPoint> {
+        let mut points = vec![SweepPoint { values: Vec::new() }];
+        for (name, values) in &self.parameters {
+            points = points
+                .into_iter()
+                .flat_map(|point| {
+                    values.iter().map(move |&value| {
+                        let mut point = point.clone();
+                        point.values.push((name.clone(), value));
+                        point
+                    })
+                })
+                .collect();
+        }
+        points
+    }
+
+    /// Call `model` for every replication of every point, in order, and collect the metrics it records.
+    pub fn run(&self, mut model: impl FnMut(&SweepPoint, &mut Replication)) -> SweepResults {
+        let replicator = self.replicator();
+        let runs = self
+            .points()
+            .into_iter()
+            .map(|point| {
+                let replications = replicator.run(|replication| model(&point, replication));
+                (point, replications)
+            })
+            .collect();
+        SweepResults {
+            parameters: self.parameters.iter().map(|(name, _)| name.clone()).collect(),
+            runs,
+        }
+    }
+
+    /// Same as [`Sweep::run`], running the points on `threads` threads, see [`Replicator::run_parallel`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the thread pool can't be created.
+    #[cfg(feature = "rayon")]
+    pub fn run_parallel(&self, threads: usize, model: impl Fn(&SweepPoint, &mut Replication) + Sync) -> SweepResults {
+        use rayon::prelude::*;
+
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .expect("failed to create the thread pool");
+        let replicator = self.replicator();
+        let runs = pool.install(|| {
+            self.points()
+                .into_par_iter()
+                .map(|point| {
+                    let replications = replicator.run(|replication| model(&point, replication));
+                    (point, replications)
+                })
+                .collect()
+        });
+        SweepResults {
+            parameters: self.parameters.iter().map(|(name, _)| name.clone()).collect(),
+            runs,
+        }
+    }
+}
+
+/// The results of [`Sweep::run`], the replications of every point.
+#[derive(Debug, Clone)]
+pub struct SweepResults {
+    parameters: Vec<String>,
+    runs: Vec<(SweepPoint, Replications)>,
+}
+
+impl SweepResults {
+    /// Returns every point with its replications, in the order of [`Sweep::points`].
+    #[must_use]
+    pub fn runs(&self) -> &[(SweepPoint, Replications)] {
+        &self.runs
+    }
+
+    /// Returns the name of every metric recorded by any replication, sorted.
+    #[must_use]
+    pub fn metrics(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.runs.iter().flat_map(|(_, runs)| runs.metrics()).collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Returns the summary of `metric` at every point with a 95% confidence interval, `None` where no replication
+    /// recorded it.
+    #[must_use]
+    pub fn summary(&self, metric: &str) -> Vec<(SweepPoint, Option<Summary>)> {
+        self.runs
+            .iter()
+            .map(|(point, runs)| (point.clone(), runs.summary(metric)))
+            .collect()
+    }
+
+    /// Returns one row per replication of every point, with a column per parameter, the replication and its seed,
+    /// and a column per metric.
+    #[must_use]
+    pub fn to_csv(&self) -> String {
+        let metrics = self.metrics();
+        let mut output = self.header(["replication", "seed"], &metrics, "");
+        for (point, runs) in &self.runs {
+            for run in runs.runs() {
+                Self::write_point(&mut output, point);
+                let _ = write!(output, "{},{}", run.index(), run.seed());
+                for name in &metrics {
+                    output.push(',');
+                    if let Some(value) = run.get(name) {
+                        let _ = write!(output, "{}", value);
+                    }
+                }
+                output.push('\n');
+            }
+        }
+        output
+    }
+
+    /// Returns one row per point, with a column per parameter, the number of replications, and the mean and the
+    /// half width of the 95% confidence interval of every metric.
+    #[must_use]
+    pub fn summary_csv(&self) -> String {
+        let metrics = self.metrics();
+        let mut output = self.header(["replications"], &metrics, " mean");
+        output.pop();
+        for name in &metrics {
+            output.push(',');
+            output.push_str(&csv::field(&format!("{} half width", name)));
+        }
+        output.push('\n');
+        for (point, runs) in &self.runs {
+            Self::write_point(&mut output, point);
+            let _ = write!(output, "{}", runs.runs().len());
+            let summaries: Vec<Option<Summary>> = metrics.iter().map(|name| runs.summary(name)).collect();
+            for summary in &summaries {
+                output.push(',');
+                if let Some(summary) = summary {
+                    let _ = write!(output, "{}", summary.mean);
+                }
+            }
+            for summary in &summaries {
+                output.push(',');
+                if let Some(summary) = summary.filter(|summary| !summary.half_width.is_nan()) {
+                    let _ = write!(output, "{}", summary.half_width);
+                }
+            }
+            output.push('\n');
+        }
+        output
+    }
+
+    /// Write the results as CSV to `path`, see [`SweepResults::to_csv`].
+    pub fn write_csv(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_csv())
+    }
+
+    fn header<const N: usize>(&self, columns: [&str; N], metrics: &[String], suffix: &str) -> String {
+        let mut header: Vec<String> = self.parameters.iter().map(|name| csv::field(name).into_owned()).collect();
+        header.extend(columns.iter().map(|&column| column.to_owned()));
+        header.extend(metrics.iter().map(|name| csv::field(&format!("{}{}", name, suffix)).into_owned()));
+        header.join(",") + "\n"
+    }
+
+    fn write_point(output: &mut String, point: &SweepPoint) {
+        for (_, value) in point.values() {
+            let _ = write!(output, "{},", value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{process, Action, Simulation};
+
+    #[test]
+    fn sweeps_run_the_cartesian_product() {
+        let sweep = Sweep::new().over("period", [1.0, 2.0]).over("workers", 1..=3).replications(2);
+        assert_eq!(6, sweep.points().len());
+        assert_eq!(vec![("period".to_owned(), 1.0), ("workers".to_owned(), 2.0)], sweep.points()[1].values());
+
+        let results = sweep.run(|point, replication| {
+            let mut simulation = Simulation::default();
+            simulation.set_seed(replication.seed());
+            let period = Duration::from_secs_f64(point.get("period"));
+            for _ in 0..point.get("workers") as usize {
+                let mut holds = 0;
+                let key = simulation.add_generator(process(move |_| {
+                    holds += 1;
+                    (holds <= 3).then_some(Action::Hold(period))
+                }));
+                simulation.schedule_now(key);
+            }
+            let steps = simulation.step_n(usize::MAX);
+            replication.record("steps", steps as f64);
+            replication.record("end", simulation.time().as_secs_f64());
+        });
+
+        let csv = results.to_csv();
+        let mut lines = csv.lines();
+        assert_eq!(Some("period,workers,replication,seed,end,steps"), lines.next());
+        let seed = Replicator::new(2).seed(1);
+        assert_eq!(Some(format!("1,1,1,{},3,4", seed).as_str()), lines.nth(1));
+        assert_eq!(13, csv.lines().count());
+
+        let summary = results.summary_csv();
+        assert_eq!(Some("period,workers,replications,end mean,steps mean,end half width,steps half width"), summary.lines().next());
+        assert_eq!(Some("2,3,2,6,12,0,0"), summary.lines().last());
+        let steps: Vec<f64> = results.summary("steps").iter().map(|(_, summary)| summary.unwrap().mean).collect();
+        assert_eq!(vec![4.0, 8.0, 12.0, 4.0, 8.0, 12.0], steps);
+    }
+}
@@ -0,0 +1,167 @@
+//! Representations of simulation time other than [`Duration`].
+//!
+//! The clock, the event list and [`Action::Hold`](crate::Action::Hold) stay in [`Duration`] with nanosecond
+//! resolution, and the scheduling and run-limit methods convert whatever [`SimTime`] they're given.
+//! Dimensionless models can count in [`Ticks`] of one second, in ticks of their own length defined with
+//! [`ticks!`](crate::ticks), or in `f64` seconds:
+//!
+//! ```ignore
+//! rustsim::ticks! {
+//!     /// Steps of 10 ms.
+//!     pub struct Steps = Duration::from_millis(10);
+//! }
+//!
+//! simulation.schedule(Steps(3), key);
+//! yield Action::hold(2.5);
+//! simulation.run_with_limit(Steps(100));
+//! let now: Steps = simulation.time_as();
+//! ```
+//!
+//! Ticks convert exactly as long as every hold is a whole number of ticks. `f64` times are rounded to the
+//! nearest nanosecond, with times too large for a [`Duration`] saturating to [`Duration::MAX`].
+
+use std::time::Duration;
+
+use crate::distributions::to_duration;
+
+/// A representation of simulation time, converted to and from the [`Duration`] of the clock.
+pub trait SimTime: Copy {
+    fn to_duration(self) -> Duration;
+
+    fn from_duration(duration: Duration) -> Self;
+}
+
+impl SimTime for Duration {
+    fn to_duration(self) -> Duration {
+        self
+    }
+
+    fn from_duration(duration: Duration) -> Self {
+        duration
+    }
+}
+
+/// Time units as `f64`, negative values are clamped to zero.
+impl SimTime for f64 {
+    /// # Panics
+    ///
+    /// Panics if `self` is NaN.
+    fn to_duration(self) -> Duration {
+        to_duration(self)
+    }
+
+    fn from_duration(duration: Duration) -> Self {
+        duration.as_secs_f64()
+    }
+}
+
+/// Define a type counting whole ticks of a given length, converted to the clock like [`Ticks`]:
+///
+/// ```ignore
+/// rustsim::ticks! {
+///     /// Days of the model.
+///     pub struct Days = Duration::from_secs(24 * 3600);
+/// }
+///
+/// simulation.run_with_limit(Days(30));
+/// ```
+///
+/// The length is evaluated at every conversion. Converting a time that isn't a whole number of ticks rounds it
+/// down, and ticks too long for a [`Duration`] saturate to [`Duration::MAX`].
+///
+/// # Panics
+///
+/// The conversions panic if the length of a tick is zero.
+#[macro_export]
+macro_rules! ticks {
+    ($(#[$meta:meta])* $vis:vis struct $name:ident = $length:expr;) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+        $vis struct $name(pub u64);
+
+        impl $crate::SimTime for $name {
+            fn to_duration(self) -> ::std::time::Duration {
+                $crate::__ticks_to_duration(self.0, $length)
+            }
+
+            fn from_duration(duration: ::std::time::Duration) -> Self {
+                Self($crate::__ticks_from_duration(duration, $length))
+            }
+        }
+
+        impl ::std::fmt::Display for $name {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                ::std::write!(f, "{}", self.0)
+            }
+        }
+    };
+}
+
+ticks! {
+    /// Whole seconds, for models where time is a step count. Ticks of another length are defined with
+    /// [`ticks!`](crate::ticks).
+    ///
+    /// Converting a time that isn't a whole number of ticks rounds it down.
+    pub struct Ticks = Duration::from_secs(1);
+}
+
+#[doc(hidden)]
+#[must_use]
+pub fn ticks_to_duration(ticks: u64, length: Duration) -> Duration {
+    assert!(!length.is_zero(), "ticks must be longer than zero");
+    let nanos = u128::from(ticks) * length.as_nanos();
+    match u64::try_from(nanos / 1_000_000_000) {
+        Ok(secs) => Duration::new(secs, (nanos % 1_000_000_000) as u32),
+        Err(_) => Duration::MAX,
+    }
+}
+
+#[doc(hidden)]
+#[must_use]
+pub fn ticks_from_duration(duration: Duration, length: Duration) -> u64 {
+    assert!(!length.is_zero(), "ticks must be longer than zero");
+    u64::try_from(duration.as_nanos() / length.as_nanos()).unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{process, Action, Simulation};
+
+    #[test]
+    fn models_can_use_ticks_and_units() {
+        let mut simulation = Simulation::default();
+        let mut holds = 0;
+        let key = simulation.add_generator(process(move |_| {
+            holds += 1;
+            Some(Action::hold(if holds % 2 == 0 { 1.5 } else { 0.5 }))
+        }));
+        simulation.schedule(Ticks(1), key);
+        simulation.run_with_limit(Ticks(5));
+
+        assert_eq!(Ticks(5), simulation.time_as::<Ticks>());
+        assert_eq!(5.0, simulation.time_as::<f64>());
+        assert_eq!(Ticks(3), Ticks::from_duration(Duration::from_millis(3999)));
+        assert_eq!(Duration::ZERO, (-1.0).to_duration());
+    }
+
+    ticks! {
+        /// Steps of 250 ms.
+        struct Steps = Duration::from_millis(250);
+    }
+
+    #[test]
+    fn ticks_can_have_any_length() {
+        let mut simulation = Simulation::default();
+        let key = simulation.add_generator(process(|_| Some(Action::hold(Steps(3)))));
+        simulation.schedule(Steps(2), key);
+        simulation.run_with_limit(Steps(10));
+
+        // Resumed at steps 2, 5, 8 and 11.
+        assert_eq!(Duration::from_millis(2750), simulation.time());
+        assert_eq!(Steps(11), simulation.time_as());
+        assert_eq!(Steps(3), Steps::from_duration(Duration::from_millis(999)));
+        assert_eq!(Duration::MAX, ticks_to_duration(u64::MAX, Duration::from_secs(2)));
+        assert_eq!("11", simulation.time_as::<Steps>().to_string());
+    }
+}
@@ -0,0 +1,199 @@
+use std::cell::RefCell;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::csv;
+use crate::stats::{mser5, Truncation};
+use crate::trace::json_string;
+
+/// The values taken by a [`State`](crate::State) variable over simulated time.
+///
+/// Created with [`Simulation::record_timeline`](crate::Simulation::record_timeline), which samples the variable
+/// after every step and keeps a point each time it changes, so the timeline is a step function:
+///
+/// ```ignore
+/// let queue_length = simulation.record_timeline("queue length", waiting, |queue: &Vec<Key>| queue.len() as f64);
+/// simulation.run_until_empty();
+/// queue_length.write_vega_lite("queue_length.vl.json")?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct Timeline {
+    name: Rc<str>,
+    points: Rc<RefCell<Vec<(Duration, f64)>>>,
+}
+
+impl Timeline {
+    pub(crate) fn new(name: &str) -> Self {
+        Self {
+            name: Rc::from(name),
+            points: Rc::default(),
+        }
+    }
+
+    // Add a point if the value changed, replacing the last one if it's at the same time.
+    pub(crate) fn sample(&self, time: Duration, value: f64) {
+        let mut points = self.points.borrow_mut();
+        match points.last() {
+            Some(&(_, last)) if last == value => {}
+            Some(&(last_time, _)) if last_time == time => {
+                points.pop();
+                if points.last().map(|&(_, before)| before) != Some(value) {
+                    points.push((time, value));
+                }
+            }
+            _ => points.push((time, value)),
+        }
+    }
+
+    // Add a point for every change, even several at the same time, so excursions lasting no time are kept.
+    pub(crate) fn record(&self, time: Duration, value: f64) {
+        let mut points = self.points.borrow_mut();
+        if points.last().map(|&(_, last)| last) != Some(value) {
+            points.push((time, value));
+        }
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the time and the new value of every change, ready to be plotted as a step function.
+    #[must_use]
+    pub fn points(&self) -> Vec<(Duration, f64)> {
+        self.points.borrow().clone()
+    }
+
+    /// Returns the largest value taken, `None` if the timeline is empty.
+    #[must_use]
+    pub fn max(&self) -> Option<f64> {
+        self.points.borrow().iter().map(|&(_, value)| value).reduce(f64::max)
+    }
+
+    /// Returns the time average of the value over consecutive intervals of `interval` from time zero, up to the last
+    /// complete interval before `end`. The value is taken as zero before the first point.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `interval` is zero.
+    #[must_use]
+    pub fn interval_means(&self, interval: Duration, end: Duration) -> Vec<f64> {
+        assert!(!interval.is_zero(), "the interval must be positive");
+        let points = self.points.borrow();
+        let width = interval.as_secs_f64();
+        let intervals = (end.as_secs_f64() / width) as usize;
+        let mut means = vec![0.0; intervals];
+        for (index, &(time, value)) in points.iter().enumerate() {
+            let start = time.as_secs_f64();
+            let stop = points.get(index + 1).map_or(end.as_secs_f64(), |&(next, _)| next.as_secs_f64());
+            // Spread the value over the intervals overlapping `[start, stop)`.
+            let first = (start / width) as usize;
+            for (slot, mean) in means.iter_mut().enumerate().skip(first) {
+                let (low, high) = (slot as f64 * width, (slot + 1) as f64 * width);
+                if low >= stop {
+                    break;
+                }
+                *mean += value * (high.min(stop) - low.max(start)) / width;
+            }
+        }
+        means
+    }
+
+    /// Returns the warm-up estimated by MSER-5 on the [interval means](Timeline::interval_means) of a pilot run
+    /// ending at `end`, with the truncation, see [`mser`](crate::mser). `None` with less than twenty intervals.
+    ///
+    /// ```ignore
+    /// let (warm_up, truncation) = queue_length.mser5(Duration::from_secs(60), simulation.time()).unwrap();
+    /// assert!(truncation.reliable, "the pilot run is too short");
+    /// ```
+    #[must_use]
+    pub fn mser5(&self, interval: Duration, end: Duration) -> Option<(Duration, Truncation)> {
+        let truncation = mser5(&self.interval_means(interval, end))?;
+        Some((interval * truncation.observations as u32, truncation))
+    }
+
+    /// Returns the timeline as CSV with the columns `time`, in seconds, and `value`.
+    #[must_use]
+    pub fn to_csv(&self) -> String {
+        let mut output = format!("time,{}\n", csv::field(&self.name));
+        for &(time, value) in self.points.borrow().iter() {
+            let _ = writeln!(output, "{},{}", time.as_secs_f64(), value);
+        }
+        output
+    }
+
+    /// Returns a Vega-Lite specification plotting the timeline as a step line over simulated time, with the
+    /// points inlined as its data.
+    #[must_use]
+    pub fn to_vega_lite(&self) -> String {
+        let values: Vec<String> = self
+            .points
+            .borrow()
+            .iter()
+            .map(|&(time, value)| format!(r#"{{"time":{},"value":{}}}"#, time.as_secs_f64(), json_number(value)))
+            .collect();
+        format!(
+            concat!(
+                r#"{{"$schema":"https://vega.github.io/schema/vega-lite/v5.json","title":{},"#,
+                r#""data":{{"values":[{}]}},"mark":{{"type":"line","interpolate":"step-after"}},"#,
+                r#""encoding":{{"x":{{"field":"time","type":"quantitative","title":"time (s)"}},"#,
+                r#""y":{{"field":"value","type":"quantitative","title":{}}}}}}}"#
+            ),
+            json_string(&self.name),
+            values.join(","),
+            json_string(&self.name)
+        )
+    }
+
+    /// Write the Vega-Lite specification to `path`, see [`Timeline::to_vega_lite`].
+    pub fn write_vega_lite(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_vega_lite())
+    }
+}
+
+// JSON has no representation for infinities and NaN.
+fn json_number(value: f64) -> String {
+    if value.is_finite() {
+        value.to_string()
+    } else {
+        "null".to_owned()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{process, Action, Simulation};
+
+    #[test]
+    fn timelines_keep_the_changes_of_state_variables() {
+        let mut simulation = Simulation::default();
+        let length = simulation.state().with_mut(|state| state.insert(0usize));
+        let timeline = simulation.record_timeline("queue length", length, |&length| length as f64);
+        let state = simulation.state();
+        let mut steps = [2, 3, 3, 0].into_iter();
+        let key = simulation.add_generator(process(move |_| {
+            let next = steps.next()?;
+            state.with_mut(|state| *state.get_mut(length).unwrap() = next);
+            Some(Action::Hold(Duration::from_secs(1)))
+        }));
+        simulation.schedule(Duration::from_secs(1), key);
+        simulation.run_until_empty();
+
+        let seconds = |points: Vec<(Duration, f64)>| -> Vec<(u64, f64)> {
+            points.into_iter().map(|(time, value)| (time.as_secs(), value)).collect()
+        };
+        assert_eq!(vec![(0, 0.0), (1, 2.0), (2, 3.0), (4, 0.0)], seconds(timeline.points()));
+        assert_eq!("time,queue length\n0,0\n1,2\n2,3\n4,0\n", timeline.to_csv());
+        let means = timeline.interval_means(Duration::from_secs(2), Duration::from_secs(9));
+        assert_eq!(vec![1.0, 3.0, 0.0, 0.0], means);
+        assert_eq!(None, timeline.mser5(Duration::from_secs(1), Duration::from_secs(10)));
+        let spec = timeline.to_vega_lite();
+        assert!(spec.contains(r#""title":"queue length""#));
+        assert!(spec.contains(r#""values":[{"time":0,"value":0},{"time":1,"value":2},"#));
+    }
+}
@@ -0,0 +1,589 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::csv;
+use crate::metadata::RunMetadata;
+use crate::names::EntityNames;
+use crate::{Action, Key};
+
+/// What happened to an entity in a [`TraceEvent`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TraceEventKind {
+    Yielded(Action),
+    Completed,
+}
+
+/// A single entry of a recorded trace.
+#[derive(Debug, Clone)]
+pub struct TraceEvent {
+    pub time: Duration,
+    pub key: Key,
+    pub kind: TraceEventKind,
+    /// Values of the [watched](TraceRecorder::watch) variables right after the event, in the order they were added.
+    pub values: Vec<f64>,
+}
+
+/// An interval during which an entity was holding or passive, see [`TraceRecorder::gantt`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GanttBar {
+    pub key: Key,
+    /// `"Hold"` or `"Passive"`.
+    pub state: &'static str,
+    pub start: Duration,
+    pub end: Duration,
+}
+
+type Watched = (String, Box<dyn Fn() -> f64>);
+
+/// Records every action yielded and every completion during a run.
+///
+/// Created with [`Simulation::record_trace`](crate::Simulation::record_trace), it keeps recording
+/// for as long as the simulation lives. Every export embeds the [`RunMetadata`] of the simulation.
+#[derive(Clone)]
+pub struct TraceRecorder {
+    events: Rc<RefCell<Vec<TraceEvent>>>,
+    watched: Rc<RefCell<Vec<Watched>>>,
+    metadata: Rc<RefCell<RunMetadata>>,
+    names: EntityNames,
+}
+
+impl TraceRecorder {
+    pub(crate) fn new(metadata: Rc<RefCell<RunMetadata>>, names: EntityNames) -> Self {
+        Self {
+            events: Rc::default(),
+            watched: Rc::default(),
+            metadata,
+            names,
+        }
+    }
+
+    pub(crate) fn record(&self, time: Duration, key: Key, kind: TraceEventKind) {
+        let values = self.watched.borrow().iter().map(|(_, sample)| sample()).collect();
+        self.events.borrow_mut().push(TraceEvent {
+            time,
+            key,
+            kind,
+            values,
+        });
+    }
+
+    /// Sample `value` after every event, e.g. the length of a queue, adding a column named `name` to the CSV export.
+    pub fn watch(&self, name: impl Into<String>, value: impl Fn() -> f64 + 'static) {
+        self.watched.borrow_mut().push((name.into(), Box::new(value)));
+    }
+
+    pub(crate) fn last(&self) -> Option<TraceEvent> {
+        self.events.borrow().last().cloned()
+    }
+
+    /// Returns a copy of the events recorded so far.
+    #[must_use]
+    pub fn events(&self) -> Vec<TraceEvent> {
+        self.events.borrow().clone()
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.events.borrow().len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.events.borrow().is_empty()
+    }
+
+    /// Discard every event recorded so far.
+    pub fn clear(&self) {
+        self.events.borrow_mut().clear();
+    }
+
+    /// Returns the trace in the Chrome trace-event JSON format, readable by `about:tracing` and Perfetto.
+    ///
+    /// Each entity is a track, labeled with its name if it has one, where Hold and Passive intervals are duration events,
+    /// while activations, cancellations and completions are instant events.
+    #[must_use]
+    pub fn to_chrome_json(&self) -> String {
+        let events = self.events.borrow();
+        let mut entries = Vec::new();
+        let mut tracks = Vec::new();
+        // Interval currently open for each entity: (name, start).
+        let mut open: HashMap<Key, (&'static str, Duration)> = HashMap::new();
+        let close = |entries: &mut Vec<String>, open: &mut HashMap<Key, (&'static str, Duration)>, key: Key, end: Duration| {
+            if let Some((name, start)) = open.remove(&key) {
+                entries.push(format!(
+                    r#"{{"name":"{}","ph":"X","pid":1,"tid":{},"ts":{},"dur":{}}}"#,
+                    name,
+                    key.id(),
+                    start.as_micros(),
+                    end.saturating_sub(start).as_micros()
+                ));
+            }
+        };
+        let instant = |name: String, key: Key, time: Duration| {
+            format!(
+                r#"{{"name":{},"ph":"i","s":"t","pid":1,"tid":{},"ts":{}}}"#,
+                json_string(&name),
+                key.id(),
+                time.as_micros()
+            )
+        };
+
+        for event in events.iter() {
+            if !tracks.contains(&event.key) {
+                tracks.push(event.key);
+            }
+            close(&mut entries, &mut open, event.key, event.time);
+            match &event.kind {
+                TraceEventKind::Yielded(Action::Hold(_) | Action::HoldWithPriority(..)) => {
+                    open.insert(event.key, ("Hold", event.time));
+                }
+                TraceEventKind::Yielded(Action::Passivate) => {
+                    open.insert(event.key, ("Passive", event.time));
+                }
+                TraceEventKind::Yielded(Action::ActivateOne(other)) => {
+                    entries.push(instant(format!("Activate {}", other.id()), event.key, event.time));
+                }
+                TraceEventKind::Yielded(Action::ActivateMany(others)) => {
+                    let others: Vec<String> = others.iter().map(|other| other.id().to_string()).collect();
+                    entries.push(instant(format!("Activate {}", others.join(", ")), event.key, event.time));
+                }
+                TraceEventKind::Yielded(Action::Cancel(other)) => {
+                    entries.push(instant(format!("Cancel {}", other.id()), event.key, event.time));
+                    // The cancelled entity stops holding and becomes passive.
+                    close(&mut entries, &mut open, *other, event.time);
+                    open.insert(*other, ("Passive", event.time));
+                }
+                TraceEventKind::Yielded(Action::Preempt(other)) => {
+                    entries.push(instant(format!("Preempt {}", other.id()), event.key, event.time));
+                    // The preempted entity stops holding and is resumed right away.
+                    close(&mut entries, &mut open, *other, event.time);
+                }
+                TraceEventKind::Yielded(Action::Broadcast(topic, _)) => {
+                    entries.push(instant(format!("Broadcast {}", topic), event.key, event.time));
+                }
+                TraceEventKind::Yielded(Action::Terminate(other)) => {
+                    entries.push(instant(format!("Terminate {}", other.id()), event.key, event.time));
+                    close(&mut entries, &mut open, *other, event.time);
+                }
+                TraceEventKind::Completed => {
+                    entries.push(instant("Completed".to_owned(), event.key, event.time));
+                }
+            }
+        }
+        let end = events.last().map_or(Duration::ZERO, |event| event.time);
+        let mut still_open: Vec<Key> = open.keys().copied().collect();
+        still_open.sort_by_key(|key| key.id());
+        for key in still_open {
+            close(&mut entries, &mut open, key, end);
+        }
+
+        let mut json = String::from(r#"{"traceEvents":["#);
+        let names = tracks.iter().map(|key| {
+            format!(
+                r#"{{"name":"thread_name","ph":"M","pid":1,"tid":{},"args":{{"name":{}}}}}"#,
+                key.id(),
+                json_string(&self.names.label(*key))
+            )
+        });
+        for (index, entry) in names.chain(entries).enumerate() {
+            if index > 0 {
+                json.push(',');
+            }
+            json.push_str(&entry);
+        }
+        json.push_str(r#"],"displayTimeUnit":"ms","otherData":{"#);
+        for (index, (name, value)) in self.metadata.borrow().entries().iter().enumerate() {
+            if index > 0 {
+                json.push(',');
+            }
+            let _ = write!(json, "{}:{}", json_string(name), json_string(value));
+        }
+        json.push_str("}}");
+        json
+    }
+
+    /// Write the trace in the Chrome trace-event JSON format to `path`.
+    pub fn write_chrome_json(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_chrome_json())
+    }
+
+    /// Returns the trace as CSV, one row per event.
+    ///
+    /// The columns are `time` (in seconds), `entity`, `action`, `argument` (the duration of a hold
+    /// in seconds or the activated and cancelled entities) and one per [watched](TraceRecorder::watch) variable.
+    /// The run metadata is written first as `# name: value` lines.
+    #[must_use]
+    pub fn to_csv(&self) -> String {
+        self.csv(|time| time.as_secs_f64().to_string())
+    }
+
+    /// Returns the trace as CSV like [`TraceRecorder::to_csv`], with the `time` column written as an
+    /// RFC 3339 date of `calendar`.
+    #[cfg(feature = "chrono")]
+    #[must_use]
+    pub fn to_csv_with_calendar(&self, calendar: &crate::Calendar) -> String {
+        self.csv(|time| calendar.datetime(time).to_rfc3339())
+    }
+
+    fn csv(&self, format_time: impl Fn(Duration) -> String) -> String {
+        let mut output = csv::metadata_comments(&self.metadata.borrow());
+        output.push_str("time,entity,action,argument");
+        let watched = self.watched.borrow();
+        for (name, _) in watched.iter() {
+            output.push(',');
+            output.push_str(&csv::field(name));
+        }
+        output.push('\n');
+
+        for event in self.events.borrow().iter() {
+            let (action, argument) = action_fields(&event.kind);
+            let _ = write!(
+                output,
+                "{},{},{},{}",
+                format_time(event.time),
+                event.key.id(),
+                action,
+                argument
+            );
+            // Variables watched after the event was recorded are left empty.
+            for index in 0..watched.len() {
+                output.push(',');
+                if let Some(value) = event.values.get(index) {
+                    let _ = write!(output, "{}", value);
+                }
+            }
+            output.push('\n');
+        }
+        output
+    }
+
+    /// Returns the Hold and Passive intervals of every entity, ordered by entity and start time.
+    ///
+    /// Intervals still open when the trace ends are closed at the time of its last event, empty intervals are left
+    /// out, so the gaps of an entity's row are the periods it wasn't waiting for anything.
+    #[must_use]
+    pub fn gantt(&self) -> Vec<GanttBar> {
+        let events = self.events.borrow();
+        let mut bars = Vec::new();
+        let mut open: HashMap<Key, (&'static str, Duration)> = HashMap::new();
+        let close = |bars: &mut Vec<GanttBar>, open: &mut HashMap<Key, (&'static str, Duration)>, key: Key, end: Duration| {
+            if let Some((state, start)) = open.remove(&key) {
+                if end > start {
+                    bars.push(GanttBar { key, state, start, end });
+                }
+            }
+        };
+        for event in events.iter() {
+            close(&mut bars, &mut open, event.key, event.time);
+            match &event.kind {
+                TraceEventKind::Yielded(Action::Hold(_) | Action::HoldWithPriority(..)) => {
+                    open.insert(event.key, ("Hold", event.time));
+                }
+                TraceEventKind::Yielded(Action::Passivate) => {
+                    open.insert(event.key, ("Passive", event.time));
+                }
+                TraceEventKind::Yielded(Action::Cancel(other)) => {
+                    close(&mut bars, &mut open, *other, event.time);
+                    open.insert(*other, ("Passive", event.time));
+                }
+                TraceEventKind::Yielded(Action::Preempt(other) | Action::Terminate(other)) => {
+                    close(&mut bars, &mut open, *other, event.time);
+                }
+                _ => {}
+            }
+        }
+        let end = events.last().map_or(Duration::ZERO, |event| event.time);
+        let still_open: Vec<Key> = open.keys().copied().collect();
+        for key in still_open {
+            close(&mut bars, &mut open, key, end);
+        }
+        bars.sort_by_key(|bar| (bar.key.id(), bar.start));
+        bars
+    }
+
+    /// Returns the [Gantt intervals](TraceRecorder::gantt) as CSV with the columns `entity`, `state`, `start` and
+    /// `end`, times in seconds, after the run metadata written as `# name: value` lines.
+    #[must_use]
+    pub fn gantt_csv(&self) -> String {
+        let mut output = csv::metadata_comments(&self.metadata.borrow());
+        output.push_str("entity,state,start,end\n");
+        for bar in self.gantt() {
+            let _ = writeln!(
+                output,
+                "{},{},{},{}",
+                csv::field(&self.names.label(bar.key)),
+                bar.state,
+                bar.start.as_secs_f64(),
+                bar.end.as_secs_f64()
+            );
+        }
+        output
+    }
+
+    /// Returns the [Gantt intervals](TraceRecorder::gantt) as an SVG chart, a row per entity labelled with its name
+    /// and simulated time along the horizontal axis. Hovering a bar shows its state and interval.
+    #[must_use]
+    pub fn to_gantt_svg(&self) -> String {
+        const LABELS: f64 = 120.0;
+        const WIDTH: f64 = 800.0;
+        const ROW: f64 = 20.0;
+        let bars = self.gantt();
+        let mut rows: Vec<Key> = bars.iter().map(|bar| bar.key).collect();
+        rows.dedup();
+        let end = self.events.borrow().last().map_or(0.0, |event| event.time.as_secs_f64());
+        let scale = if end > 0.0 { (WIDTH - LABELS) / end } else { 0.0 };
+        let height = ROW * (rows.len() as f64 + 1.0);
+
+        let mut svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" font-family="sans-serif" font-size="12">"#,
+            WIDTH, height
+        );
+        for (row, key) in rows.iter().enumerate() {
+            let _ = write!(
+                svg,
+                r#"<text x="4" y="{}">{}</text>"#,
+                ROW * row as f64 + 14.0,
+                xml_escape(&self.names.label(*key))
+            );
+        }
+        for bar in &bars {
+            let row = rows.iter().position(|&key| key == bar.key).unwrap_or_default();
+            let color = if bar.state == "Hold" { "#4e79a7" } else { "#e15759" };
+            let _ = write!(
+                svg,
+                r#"<rect x="{:.2}" y="{}" width="{:.2}" height="{}" fill="{}"><title>{} {:?}-{:?}</title></rect>"#,
+                LABELS + bar.start.as_secs_f64() * scale,
+                ROW * row as f64 + 3.0,
+                (bar.end - bar.start).as_secs_f64() * scale,
+                ROW - 6.0,
+                color,
+                bar.state,
+                bar.start,
+                bar.end
+            );
+        }
+        let _ = write!(
+            svg,
+            r#"<text x="{}" y="{}">0</text><text x="{}" y="{}" text-anchor="end">{}s</text></svg>"#,
+            LABELS,
+            height - 4.0,
+            WIDTH,
+            height - 4.0,
+            end
+        );
+        svg
+    }
+
+    /// Write the Gantt chart as SVG to `path`, see [`TraceRecorder::to_gantt_svg`].
+    pub fn write_gantt_svg(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_gantt_svg())
+    }
+
+    /// Write the trace as CSV to `path`, see [`TraceRecorder::to_csv`].
+    pub fn write_csv(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_csv())
+    }
+
+    /// Write the trace as CSV with dates to `path`, see [`TraceRecorder::to_csv_with_calendar`].
+    #[cfg(feature = "chrono")]
+    pub fn write_csv_with_calendar(&self, path: impl AsRef<Path>, calendar: &crate::Calendar) -> io::Result<()> {
+        fs::write(path, self.to_csv_with_calendar(calendar))
+    }
+}
+
+// Returns the action name and argument of an event, as written to CSV.
+pub(crate) fn action_fields(kind: &TraceEventKind) -> (&'static str, String) {
+    match kind {
+        TraceEventKind::Yielded(Action::Hold(duration)) => ("Hold", duration.as_secs_f64().to_string()),
+        TraceEventKind::Yielded(Action::HoldWithPriority(duration, priority)) => {
+            ("HoldWithPriority", format!("{} {}", duration.as_secs_f64(), priority))
+        }
+        TraceEventKind::Yielded(Action::Passivate) => ("Passivate", String::new()),
+        TraceEventKind::Yielded(Action::ActivateOne(other)) => ("ActivateOne", other.id().to_string()),
+        TraceEventKind::Yielded(Action::ActivateMany(others)) => {
+            let others: Vec<String> = others.iter().map(|other| other.id().to_string()).collect();
+            ("ActivateMany", others.join(" "))
+        }
+        TraceEventKind::Yielded(Action::Cancel(other)) => ("Cancel", other.id().to_string()),
+        TraceEventKind::Yielded(Action::Preempt(other)) => ("Preempt", other.id().to_string()),
+        TraceEventKind::Yielded(Action::Broadcast(topic, payload)) => ("Broadcast", format!("{} {}", topic, payload)),
+        TraceEventKind::Yielded(Action::Terminate(other)) => ("Terminate", other.id().to_string()),
+        TraceEventKind::Completed => ("Completed", String::new()),
+    }
+}
+
+pub(crate) fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+pub(crate) fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::process::script;
+    use crate::{GenBoxed, Simulation};
+
+    fn sleeper() -> GenBoxed<()> {
+        script(vec![Action::Hold(Duration::from_millis(5)), Action::Passivate])
+    }
+
+    fn waker(other: Key) -> GenBoxed<()> {
+        script(vec![Action::Hold(Duration::from_millis(8)), Action::ActivateOne(other)])
+    }
+
+    #[test]
+    fn chrome_json_has_intervals_and_metadata() {
+        let mut simulation = Simulation::default();
+        simulation.metadata_mut().set_scenario("two \"entities\"");
+        let trace = simulation.record_trace();
+        let sleeper = simulation.add_generator(sleeper());
+        let waker = simulation.add_generator(waker(sleeper));
+        simulation.schedule_now(sleeper);
+        simulation.schedule_now(waker);
+        simulation.run_until_empty();
+
+        assert_eq!(6, trace.len());
+        let json = trace.to_chrome_json();
+        assert!(json.contains(r#"{"name":"Hold","ph":"X","pid":1,"tid":0,"ts":0,"dur":5000}"#));
+        assert!(json.contains(r#"{"name":"Passive","ph":"X","pid":1,"tid":0,"ts":5000,"dur":3000}"#));
+        assert!(json.contains(r#"{"name":"Activate 0","ph":"i","s":"t","pid":1,"tid":1,"ts":8000}"#));
+        assert!(json.ends_with(r#""otherData":{"scenario":"two \"entities\""}}"#));
+    }
+
+    #[test]
+    fn csv_has_a_row_per_event_and_watched_columns() {
+        let mut simulation = Simulation::default();
+        simulation.metadata_mut().set_seed(7);
+        let trace = simulation.record_trace();
+        let clock = simulation.clock();
+        trace.watch("clock, ms", move || clock.time().as_millis() as f64);
+        let sleeper = simulation.add_generator(sleeper());
+        let waker = simulation.add_generator(waker(sleeper));
+        simulation.schedule_now(sleeper);
+        simulation.schedule_now(waker);
+        simulation.run_until_empty();
+
+        let csv = trace.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(
+            vec![
+                "# seed: 7",
+                "time,entity,action,argument,\"clock, ms\"",
+                "0,0,Hold,0.005,0",
+                "0,1,Hold,0.008,0",
+                "0.005,0,Passivate,,5",
+                "0.008,1,ActivateOne,0,8",
+                "0.008,1,Completed,,8",
+                "0.008,0,Completed,,8",
+            ],
+            lines
+        );
+    }
+
+    #[test]
+    fn named_entities_label_their_tracks() {
+        let mut simulation = Simulation::default();
+        let trace = simulation.record_trace();
+        let machine = simulation.add_generator_named("machine-3", sleeper());
+        let other = simulation.add_generator(waker(machine));
+        simulation.schedule_now(machine);
+        simulation.schedule_now(other);
+        simulation.run_until_empty();
+
+        assert_eq!(Some(machine), simulation.key_of("machine-3"));
+        assert_eq!(Some("machine-3".to_owned()), simulation.name_of(machine));
+        assert_eq!("entity 1", simulation.names().label(other));
+        let json = trace.to_chrome_json();
+        assert!(json.contains(r#"{"name":"thread_name","ph":"M","pid":1,"tid":0,"args":{"name":"machine-3"}}"#));
+        assert!(json.contains(r#"{"name":"thread_name","ph":"M","pid":1,"tid":1,"args":{"name":"entity 1"}}"#));
+
+        // The name is free again once its entity completed.
+        let replacement = simulation.add_generator_named("machine-3", sleeper());
+        assert_eq!(Some(replacement), simulation.key_of("machine-3"));
+        assert_eq!(Some("machine-3".to_owned()), simulation.name_of(machine));
+    }
+
+    // The model of the other tests with the waker holding for `wake_after` milliseconds.
+    fn build(wake_after: u64) -> Simulation<()> {
+        let mut simulation = Simulation::default();
+        let sleeper = simulation.add_generator(sleeper());
+        let waker = simulation.add_generator(script(vec![
+            Action::Hold(Duration::from_millis(wake_after)),
+            Action::ActivateOne(sleeper),
+        ]));
+        simulation.schedule_now(sleeper);
+        simulation.schedule_now(waker);
+        simulation
+    }
+
+    #[test]
+    fn replays_flag_the_first_divergence() {
+        let mut original = build(8);
+        let trace = original.record_trace();
+        original.run_until_empty();
+        let events = trace.events();
+
+        assert_eq!(Ok(6), build(8).replay(&events).map_err(|divergence| divergence.step));
+        let divergence = build(9).replay(&events).unwrap_err();
+        assert_eq!(1, divergence.step);
+        assert_eq!(
+            Some(TraceEventKind::Yielded(Action::Hold(Duration::from_millis(9)))),
+            divergence.found.as_ref().map(|event| event.kind.clone())
+        );
+        assert!(divergence.to_string().starts_with("step 1 diverged, expected entity 1 Hold(8ms) at 0ns"));
+    }
+
+    #[test]
+    fn gantt_bars_cover_holds_and_passive_periods() {
+        let mut simulation = Simulation::default();
+        let trace = simulation.record_trace();
+        let sleeper = simulation.add_generator_named("sleeper <1>", sleeper());
+        let waker = simulation.add_generator(waker(sleeper));
+        simulation.schedule_now(sleeper);
+        simulation.schedule_now(waker);
+        simulation.run_until_empty();
+
+        let bar = |key, state, start, end| GanttBar {
+            key,
+            state,
+            start: Duration::from_millis(start),
+            end: Duration::from_millis(end),
+        };
+        let expected = vec![bar(sleeper, "Hold", 0, 5), bar(sleeper, "Passive", 5, 8), bar(waker, "Hold", 0, 8)];
+        assert_eq!(expected, trace.gantt());
+        assert!(trace
+            .gantt_csv()
+            .ends_with("entity,state,start,end\nsleeper <1>,Hold,0,0.005\nsleeper <1>,Passive,0.005,0.008\nentity 1,Hold,0,0.008\n"));
+        let svg = trace.to_gantt_svg();
+        assert!(svg.starts_with("<svg") && svg.ends_with("</svg>"));
+        assert!(svg.contains("sleeper &lt;1&gt;"));
+        assert_eq!(3, svg.matches("<rect").count());
+    }
+}
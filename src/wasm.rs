@@ -0,0 +1,152 @@
+//! A stepping API with JavaScript-friendly types, enabled by the `wasm` feature.
+//!
+//! The core compiles to `wasm32-unknown-unknown`: wall-clock features ([`RealTimeRunner`](crate::RealTimeRunner),
+//! wall-clock budgets and progress reports) and threads ([`parallel`](crate::parallel)) are left out on that target.
+//! [`WebSimulation`] is exported with `wasm-bindgen` and only takes and returns numbers, booleans and strings, so
+//! the crate building the demo only exports a function creating the model:
+//!
+//! ```ignore
+//! #[wasm_bindgen]
+//! pub fn bank() -> WebSimulation {
+//!     let mut simulation = Simulation::default();
+//!     // Add and schedule the entities.
+//!     WebSimulation::new(simulation)
+//! }
+//! ```
+//!
+//! ```js
+//! const simulation = bank();
+//! while (simulation.advance_to(simulation.time() + 60)) {
+//!     document.getElementById("gantt").innerHTML = simulation.gantt_svg();
+//! }
+//! ```
+
+use std::time::Duration;
+
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use crate::{Breakpoint, RunStatus, Simulation, TraceRecorder};
+
+/// A [`Simulation`] driven one step at a time from JavaScript, recording its trace.
+#[wasm_bindgen]
+pub struct WebSimulation {
+    simulation: Simulation<()>,
+    trace: TraceRecorder,
+    last_error: Option<String>,
+}
+
+impl WebSimulation {
+    /// Wrap a model whose entities are already added and scheduled.
+    pub fn new(mut simulation: Simulation<()>) -> Self {
+        let trace = simulation.trace().unwrap_or_else(|| simulation.record_trace());
+        Self {
+            simulation,
+            trace,
+            last_error: None,
+        }
+    }
+
+    /// Returns the wrapped simulation, e.g. to read its state between steps.
+    pub fn simulation_mut(&mut self) -> &mut Simulation<()> {
+        &mut self.simulation
+    }
+}
+
+#[wasm_bindgen]
+impl WebSimulation {
+    /// Execute the next event, returning `false` once no more events are left.
+    ///
+    /// An invalid action doesn't stop the simulation, it's reported by [`WebSimulation::last_error`].
+    pub fn step(&mut self) -> bool {
+        match self.simulation.step() {
+            Ok(step) => step.should_continue(),
+            Err(error) => {
+                self.last_error = Some(error.to_string());
+                true
+            }
+        }
+    }
+
+    /// Execute every event before `time`, in seconds, e.g. to follow an animation frame. Returns `false` once no
+    /// more events are left.
+    pub fn advance_to(&mut self, time: f64) -> bool {
+        let breakpoint = Breakpoint::time(Duration::from_secs_f64(time.max(0.0)));
+        self.simulation.set_breakpoint(breakpoint);
+        let status = self.simulation.run_until_empty();
+        self.simulation.remove_breakpoint(breakpoint);
+        match status {
+            RunStatus::Exhausted | RunStatus::Deadlocked => false,
+            RunStatus::Failed(error) => {
+                self.last_error = Some(error.to_string());
+                true
+            }
+            _ => true,
+        }
+    }
+
+    /// Returns the simulation time in seconds.
+    #[must_use]
+    pub fn time(&self) -> f64 {
+        self.simulation.time().as_secs_f64()
+    }
+
+    /// Returns the time of the next event in seconds, `None` (`undefined` in JavaScript) if no more events are
+    /// left.
+    #[must_use]
+    pub fn next_event_time(&self) -> Option<f64> {
+        self.simulation.next_event_time().map(|time| time.as_secs_f64())
+    }
+
+    /// Returns the message of the last invalid action yielded by an entity.
+    #[must_use]
+    pub fn last_error(&self) -> Option<String> {
+        self.last_error.clone()
+    }
+
+    /// Returns the trace recorded so far as CSV, see [`TraceRecorder::to_csv`].
+    #[must_use]
+    pub fn trace_csv(&self) -> String {
+        self.trace.to_csv()
+    }
+
+    /// Returns the trace recorded so far in the Chrome trace-event JSON format, see
+    /// [`TraceRecorder::to_chrome_json`].
+    #[must_use]
+    pub fn trace_json(&self) -> String {
+        self.trace.to_chrome_json()
+    }
+
+    /// Returns the Gantt chart of the run so far as SVG, see [`TraceRecorder::to_gantt_svg`].
+    #[must_use]
+    pub fn gantt_svg(&self) -> String {
+        self.trace.to_gantt_svg()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{process, Action};
+
+    #[test]
+    fn simulations_are_stepped_with_plain_values() {
+        let mut simulation = Simulation::default();
+        let mut holds = 0;
+        let key = simulation.add_generator(process(move |_| {
+            holds += 1;
+            (holds <= 3).then_some(Action::Hold(Duration::from_millis(500)))
+        }));
+        simulation.schedule_now(key);
+        let mut web = WebSimulation::new(simulation);
+
+        assert!(web.step());
+        assert_eq!(Some(0.5), web.next_event_time());
+        assert!(web.advance_to(1.2));
+        assert_eq!(1.0, web.time());
+        assert!(!web.advance_to(10.0));
+        assert_eq!(1.5, web.time());
+        assert!(!web.step());
+        assert_eq!(None, web.last_error());
+        assert_eq!(5, web.trace_csv().lines().count());
+    }
+}